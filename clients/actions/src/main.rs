@@ -0,0 +1,352 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tiny_http::{Header, Method, Request, Response, Server};
+use trade_p2p_sdk::{
+  allowlist::PartnerAllowlist,
+  claim::ClaimCode,
+  instruction::PartnerAuth,
+  order::{mint_label, stage_name},
+  parse_pubkey,
+  pda::parse_order_address,
+  rpc::RpcClient,
+  transaction::{Transaction, TransactionEncoding},
+  ClientError, EscrowAccount, Pubkey, Stage, PROGRAM_ID,
+};
+
+// order links are `/orders/<order>`, wallets resolve them to `/api/actions/orders/<order>`
+const ACTION_PATH: &str = "/api/actions/orders/";
+const ACTION_VERSION: &str = "2.1.3";
+const MAX_BODY_LEN: u64 = 4096;
+const ICON: &str = include_str!("icon.svg");
+
+#[derive(Parser)]
+#[command(
+  name = "trade-p2p-actions",
+  about = "Serve trade-p2p orders as Solana Actions and Solana Pay transaction requests"
+)]
+struct Cli {
+  /// rpc url or cluster moniker: mainnet-beta, devnet, testnet, localhost
+  #[arg(long, short = 'u', default_value = "devnet")]
+  url: String,
+  #[arg(long)]
+  program_id: Option<String>,
+  /// address to listen on
+  #[arg(long, default_value = "127.0.0.1:8081")]
+  listen: String,
+  /// public url of this service, used for the icon. Defaults to http://<Host header>
+  #[arg(long)]
+  base_url: Option<String>,
+  /// partner allowlist file of orders served here, may be repeated
+  #[arg(long = "allowlist")]
+  allowlists: Vec<PathBuf>,
+  /// most the price of an oracle-pegged order may rise above the quoted price
+  /// before the exchange lands, in basis points
+  #[arg(long, default_value_t = 100)]
+  max_slippage_bps: u16,
+}
+
+struct Service {
+  rpc: RpcClient,
+  program_id: Pubkey,
+  base_url: Option<String>,
+  // proofs for allowlisted orders are built from the list matching their root
+  allowlists: Vec<PartnerAllowlist>,
+  max_slippage_bps: u16,
+}
+
+// failed request, rendered as `{"message": ...}` as the actions spec expects
+struct ActionError {
+  status: u16,
+  message: String,
+}
+
+impl ActionError {
+  fn bad_request(message: String) -> ActionError {
+    ActionError { status: 400, message }
+  }
+}
+
+impl From<ClientError> for ActionError {
+  fn from(err: ClientError) -> ActionError {
+    let status = match err {
+      ClientError::AccountNotFound(_) => 404,
+      ClientError::Rpc(_) | ClientError::Io(_) => 502,
+      _ => 400,
+    };
+    ActionError {
+      status,
+      message: err.to_string(),
+    }
+  }
+}
+
+type ActionResult = std::result::Result<Value, ActionError>;
+
+fn main() {
+  if let Err(err) = run(Cli::parse()) {
+    eprintln!("error: {:#}", err);
+    std::process::exit(1);
+  }
+}
+
+fn run(cli: Cli) -> Result<()> {
+  let service = Service {
+    rpc: RpcClient::new(&cli.url),
+    program_id: match &cli.program_id {
+      Some(program_id) => parse_pubkey(program_id)?,
+      None => PROGRAM_ID,
+    },
+    base_url: cli.base_url.map(|url| url.trim_end_matches('/').to_string()),
+    allowlists: cli
+      .allowlists
+      .iter()
+      .map(PartnerAllowlist::read_from_file)
+      .collect::<std::result::Result<_, _>>()?,
+    max_slippage_bps: cli.max_slippage_bps,
+  };
+  let server = Server::http(&cli.listen).map_err(|err| anyhow!("listening on {}: {}", cli.listen, err))?;
+  println!("serving orders from {} on http://{}", service.rpc.url(), cli.listen);
+  for request in server.incoming_requests() {
+    service.respond(request);
+  }
+  Ok(())
+}
+
+impl Service {
+  fn respond(&self, mut request: Request) {
+    let (path, query) = match request.url().split_once('?') {
+      Some((path, query)) => (path.to_string(), query.to_string()),
+      None => (request.url().to_string(), String::new()),
+    };
+    let response = match (request.method(), path.as_str()) {
+      // cors preflight
+      (Method::Options, _) => Response::from_string(""),
+      (Method::Get, "/icon.svg") => {
+        Response::from_string(ICON).with_header(header("Content-Type", "image/svg+xml"))
+      }
+      (method, path) => {
+        let result = match (method, path) {
+          (Method::Get, "/actions.json") => Ok(json!({
+            "rules": [{ "pathPattern": "/orders/**", "apiPath": format!("{}**", ACTION_PATH) }]
+          })),
+          (Method::Get, path) if path.starts_with(ACTION_PATH) => {
+            self.describe(&path[ACTION_PATH.len()..], &self.base_url(&request))
+          }
+          (Method::Post, path) if path.starts_with(ACTION_PATH) => {
+            match (read_account(&mut request), claim_code(&query)) {
+              (Ok(account), Ok(claim_code)) => {
+                self.transaction(&path[ACTION_PATH.len()..], &account, claim_code.as_ref())
+              }
+              (Err(err), _) | (_, Err(err)) => Err(err),
+            }
+          }
+          _ => Err(ActionError {
+            status: 404,
+            message: format!("no route for {} {}", method, path),
+          }),
+        };
+        let (status, body) = match result {
+          Ok(body) => (200, body),
+          Err(err) => (err.status, json!({ "message": err.message })),
+        };
+        Response::from_string(body.to_string())
+          .with_status_code(status)
+          .with_header(header("Content-Type", "application/json"))
+      }
+    };
+    let response = response
+      .with_header(header("Access-Control-Allow-Origin", "*"))
+      .with_header(header("Access-Control-Allow-Methods", "GET,POST,PUT,OPTIONS"))
+      .with_header(header(
+        "Access-Control-Allow-Headers",
+        "Content-Type, Authorization, Content-Encoding, Accept-Encoding",
+      ))
+      .with_header(header("X-Action-Version", ACTION_VERSION));
+    if let Err(err) = request.respond(response) {
+      eprintln!("error: writing response: {}", err);
+    }
+  }
+
+  fn base_url(&self, request: &Request) -> String {
+    if let Some(base_url) = &self.base_url {
+      return base_url.clone();
+    }
+    let host = request
+      .headers()
+      .iter()
+      .find(|header| header.field.equiv("Host"))
+      .map(|header| header.value.to_string())
+      .unwrap_or_default();
+    format!("http://{}", host)
+  }
+
+  fn fetch_order(&self, order: &str) -> std::result::Result<EscrowAccount, ActionError> {
+    let address = parse_order_address(&self.program_id, order)?;
+    Ok(self.rpc.get_order(&address)?)
+  }
+
+  // action metadata, also a valid solana pay transaction request `GET` response
+  fn describe(&self, order: &str, base_url: &str) -> ActionResult {
+    let escrow = self.fetch_order(order)?;
+    // a dutch auction is described at its current price
+    let mut response = json!({
+      "type": "action",
+      "icon": format!("{}/icon.svg", base_url),
+      "title": format!("Trade order {}/{}", escrow.creator, escrow.order_id),
+      "description": format!(
+        "Send {} {} to receive {} {}",
+        // an oracle-pegged order the feed cannot price shows its last price
+        self.rpc.quote_receive_value(&escrow, now()).unwrap_or(escrow.receive_value),
+        mint_label(escrow.creator_receive_token_mint.as_ref()),
+        escrow.trade_value,
+        mint_label(escrow.creator_send_token_mint.as_ref()),
+      ),
+      "label": "Exchange",
+    });
+    if escrow.stage != Stage::ReadyExchange.to_code() {
+      response["disabled"] = json!(true);
+      response["error"] = json!({ "message": format!("order is {}", stage_name(escrow.stage)) });
+    } else if escrow.claim_hash.is_some() {
+      response["description"] = json!(format!(
+        "{}, reserved for holders of its claim code",
+        response["description"].as_str().unwrap_or_default()
+      ));
+    } else if escrow.partner_root.is_some() {
+      response["description"] = json!(format!(
+        "{}, reserved for allowlisted wallets",
+        response["description"].as_str().unwrap_or_default()
+      ));
+    } else if let Some(partner) = escrow.specify_partner {
+      response["description"] = json!(format!(
+        "{}, reserved for {}",
+        response["description"].as_str().unwrap_or_default(),
+        partner
+      ));
+    }
+    Ok(response)
+  }
+
+  // `exchange` transaction paid and signed by the taker, already signed by the
+  // claim key when the link carries the order's claim code. An order priced at
+  // fill time costs at most the price it is quoted at now, which only falls for
+  // a dutch auction, plus `max_slippage_bps` for an oracle-pegged order
+  fn transaction(&self, order: &str, account: &Pubkey, claim_code: Option<&ClaimCode>) -> ActionResult {
+    let escrow = self.fetch_order(order)?;
+    let proof = escrow
+      .partner_root
+      .and_then(|root| self.allowlists.iter().find(|allowlist| allowlist.root() == root))
+      .and_then(|allowlist| allowlist.proof(account))
+      .unwrap_or_default();
+    let auth = PartnerAuth {
+      proof,
+      claim_key: claim_code.map(ClaimCode::pubkey),
+    };
+    let max_receive_value = match (&escrow.oracle_peg, &escrow.dutch_auction) {
+      (None, None) => None,
+      (peg, _) => {
+        let quote = self.rpc.quote_receive_value(&escrow, now())?;
+        Some(with_slippage(quote, peg.map_or(0, |_| self.max_slippage_bps)))
+      }
+    };
+    let instructions = self
+      .rpc
+      .exchange_instructions(&self.program_id, &escrow, account, &auth, max_receive_value)?;
+    let mut transaction =
+      Transaction::new_with_blockhash(&instructions, account, self.rpc.get_latest_blockhash()?);
+    if let Some(claim_code) = claim_code {
+      transaction.sign(&claim_code.keypair())?;
+    }
+    Ok(json!({
+      "type": "transaction",
+      "transaction": transaction.encode(TransactionEncoding::Base64),
+      "message": format!("Exchange order {}/{}", escrow.creator, escrow.order_id),
+    }))
+  }
+}
+
+// most paid for `quote` allowing `slippage_bps` above it
+fn with_slippage(quote: u64, slippage_bps: u16) -> u64 {
+  u64::try_from(quote as u128 * (10_000 + slippage_bps as u128) / 10_000).unwrap_or(u64::MAX)
+}
+
+fn now() -> i64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+// claim code carried by a private link as `?code=<claim code>`
+fn claim_code(query: &str) -> std::result::Result<Option<ClaimCode>, ActionError> {
+  query
+    .split('&')
+    .filter_map(|param| param.strip_prefix("code="))
+    .next()
+    .map(|code| code.parse().map_err(ActionError::from))
+    .transpose()
+}
+
+// the taker wallet from a `{"account": "<pubkey>"}` body
+fn read_account(request: &mut Request) -> std::result::Result<Pubkey, ActionError> {
+  let mut body = String::new();
+  request
+    .as_reader()
+    .take(MAX_BODY_LEN)
+    .read_to_string(&mut body)
+    .map_err(|err| ActionError::bad_request(format!("reading body: {}", err)))?;
+  parse_account(&body)
+}
+
+fn parse_account(body: &str) -> std::result::Result<Pubkey, ActionError> {
+  let body: Value = serde_json::from_str(body)
+    .map_err(|err| ActionError::bad_request(format!("invalid json body: {}", err)))?;
+  let account = body["account"]
+    .as_str()
+    .ok_or_else(|| ActionError::bad_request("missing account".to_string()))?;
+  Ok(parse_pubkey(account)?)
+}
+
+fn header(name: &str, value: &str) -> Header {
+  Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use clap::CommandFactory;
+
+  #[test]
+  fn arguments_are_consistent() {
+    Cli::command().debug_assert();
+  }
+
+  #[test]
+  fn the_taker_is_read_from_the_body() {
+    let account = Pubkey::new_unique();
+    let body = json!({ "account": account.to_string() }).to_string();
+    assert_eq!(parse_account(&body).ok(), Some(account));
+    for body in ["", "{}", r#"{"account": 1}"#, r#"{"account": "taker"}"#] {
+      assert_eq!(parse_account(body).err().map(|err| err.status), Some(400));
+    }
+  }
+
+  #[test]
+  fn slippage_caps_the_quote() {
+    assert_eq!(with_slippage(1_000, 0), 1_000);
+    assert_eq!(with_slippage(1_000, 50), 1_005);
+    assert_eq!(with_slippage(999, 50), 1_003);
+    assert_eq!(with_slippage(u64::MAX, 1), u64::MAX);
+  }
+
+  #[test]
+  fn the_claim_code_is_read_from_the_query() {
+    let code = ClaimCode::generate();
+    let query = format!("cluster=devnet&code={}", code);
+    assert_eq!(claim_code(&query).ok().flatten().map(|code| code.pubkey()), Some(code.pubkey()));
+    assert!(matches!(claim_code("cluster=devnet"), Ok(None)));
+    assert_eq!(claim_code("code=secret").err().map(|err| err.status), Some(400));
+  }
+}
@@ -0,0 +1,238 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use serde::Serialize;
+use serde_json::json;
+use std::path::PathBuf;
+use tiny_http::{Header, Method, Request, Response, Server};
+use trade_p2p_indexer::{query, OrderFilter, Store};
+use trade_p2p_sdk::order::{parse_stage, NATIVE_MINT_LABEL};
+use trade_p2p_sdk::parse_pubkey;
+
+#[derive(Parser)]
+#[command(name = "trade-p2p-api", about = "Serve the indexed trade-p2p order history over HTTP")]
+struct Cli {
+  /// sqlite database written by trade-p2p-indexer
+  #[arg(long, default_value = "trade-p2p.sqlite")]
+  db: PathBuf,
+  /// address to listen on
+  #[arg(long, default_value = "127.0.0.1:8080")]
+  listen: String,
+}
+
+// failed request, rendered as `{"error": ...}`
+struct ApiError {
+  status: u16,
+  message: String,
+}
+
+impl ApiError {
+  fn bad_request(message: String) -> ApiError {
+    ApiError { status: 400, message }
+  }
+
+  fn not_found(message: String) -> ApiError {
+    ApiError { status: 404, message }
+  }
+}
+
+impl From<anyhow::Error> for ApiError {
+  fn from(err: anyhow::Error) -> ApiError {
+    ApiError {
+      status: 500,
+      message: format!("{:#}", err),
+    }
+  }
+}
+
+type ApiResult = std::result::Result<serde_json::Value, ApiError>;
+
+fn main() {
+  if let Err(err) = run(Cli::parse()) {
+    eprintln!("error: {:#}", err);
+    std::process::exit(1);
+  }
+}
+
+fn run(cli: Cli) -> Result<()> {
+  let store = Store::open(&cli.db)?;
+  let server = Server::http(&cli.listen).map_err(|err| anyhow!("listening on {}: {}", cli.listen, err))?;
+  println!("serving {} on http://{}", cli.db.display(), cli.listen);
+  for request in server.incoming_requests() {
+    respond(&store, request);
+  }
+  Ok(())
+}
+
+fn respond(store: &Store, request: Request) {
+  let result = if *request.method() == Method::Get {
+    route(store, request.url())
+  } else {
+    Err(ApiError {
+      status: 405,
+      message: format!("method {} not allowed", request.method()),
+    })
+  };
+  let (status, body) = match result {
+    Ok(body) => (200, body),
+    Err(err) => (err.status, json!({ "error": err.message })),
+  };
+  let response = Response::from_string(body.to_string())
+    .with_status_code(status)
+    .with_header(header("Content-Type", "application/json"))
+    .with_header(header("Access-Control-Allow-Origin", "*"));
+  if let Err(err) = request.respond(response) {
+    eprintln!("error: writing response: {}", err);
+  }
+}
+
+fn header(name: &str, value: &str) -> Header {
+  Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn route(store: &Store, url: &str) -> ApiResult {
+  let (path, query) = url.split_once('?').unwrap_or((url, ""));
+  let params = form_urlencoded::parse(query.as_bytes()).into_owned().collect::<Vec<_>>();
+  let segments = path.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<_>>();
+  let conn = store.connection();
+  match segments.as_slice() {
+    ["orders"] => to_json(query::find_orders(conn, &order_filter(&params)?)?),
+    ["orders", creator, order_id] => {
+      let creator = pubkey(creator)?;
+      let order_id = order_id
+        .parse::<u64>()
+        .map_err(|_| ApiError::bad_request(format!("invalid order id {}", order_id)))?;
+      match query::find_order(conn, &creator, order_id)? {
+        Some(order) => to_json(order),
+        None => Err(ApiError::not_found(format!("order {}/{} not found", creator, order_id))),
+      }
+    }
+    ["pairs"] => to_json(query::pair_stats(conn)?),
+    ["pairs", send_mint, receive_mint] => {
+      let (send_mint, receive_mint) = (mint(send_mint)?, mint(receive_mint)?);
+      query::pair_stats(conn)?
+        .into_iter()
+        .find(|pair| pair.send_mint == send_mint && pair.receive_mint == receive_mint)
+        .map_or_else(
+          || Err(ApiError::not_found(format!("no orders for {}/{}", send_mint, receive_mint))),
+          to_json,
+        )
+    }
+    _ => Err(ApiError::not_found(format!("no route for {}", path))),
+  }
+}
+
+fn to_json<T: Serialize>(value: T) -> ApiResult {
+  Ok(serde_json::to_value(value).map_err(anyhow::Error::from)?)
+}
+
+fn order_filter(params: &[(String, String)]) -> std::result::Result<OrderFilter, ApiError> {
+  let mut filter = OrderFilter::default();
+  for (name, value) in params {
+    match name.as_str() {
+      "mint" => filter.mint = Some(mint(value)?),
+      "creator" => filter.creator = Some(pubkey(value)?),
+      "partner" => filter.partner = Some(pubkey(value)?),
+      "stage" => {
+        filter.stage = Some(
+          parse_stage(value).ok_or_else(|| ApiError::bad_request(format!("invalid stage {}", value)))?,
+        )
+      }
+      "limit" => filter.limit = Some(number(name, value)?),
+      "offset" => filter.offset = Some(number(name, value)?),
+      _ => return Err(ApiError::bad_request(format!("unknown parameter {}", name))),
+    }
+  }
+  Ok(filter)
+}
+
+fn pubkey(value: &str) -> std::result::Result<String, ApiError> {
+  parse_pubkey(value)
+    .map(|pubkey| pubkey.to_string())
+    .map_err(|err| ApiError::bad_request(err.to_string()))
+}
+
+// a mint address or `SOL` for the native side
+fn mint(value: &str) -> std::result::Result<String, ApiError> {
+  if value.eq_ignore_ascii_case(NATIVE_MINT_LABEL) {
+    return Ok(NATIVE_MINT_LABEL.to_string());
+  }
+  pubkey(value)
+}
+
+fn number(name: &str, value: &str) -> std::result::Result<u32, ApiError> {
+  value
+    .parse()
+    .map_err(|_| ApiError::bad_request(format!("invalid {} {}", name, value)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use trade_p2p_indexer::{OrderEvent, TransactionRecord};
+  use trade_p2p_sdk::Pubkey;
+
+  // one open order selling a token for SOL
+  fn store(creator: Pubkey, send_mint: Pubkey) -> Store {
+    let mut store = Store::open_in_memory().unwrap();
+    let created = OrderEvent::Created {
+      order: Pubkey::new_unique(),
+      creator,
+      order_id: 7,
+      trade_type: 1,
+      send_mint: Some(send_mint),
+      receive_mint: None,
+      trade_value: 100,
+      receive_value: 250,
+      specify_partner: None,
+      fee_account: creator,
+      timestamp: 0,
+    };
+    let record = TransactionRecord {
+      signature: "signature".to_string(),
+      slot: 1,
+      block_time: None,
+      fee_payer: creator,
+      fee: 5_000,
+      events: vec![created],
+    };
+    store.apply(&record).unwrap();
+    store
+  }
+
+  fn status(result: ApiResult) -> u16 {
+    result.map_or_else(|err| err.status, |_| 200)
+  }
+
+  #[test]
+  fn orders_are_found_by_creator_and_id() {
+    let (creator, send_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let store = store(creator, send_mint);
+    let order = route(&store, &format!("/orders/{}/7", creator)).ok().unwrap();
+    assert_eq!((order["order_id"].as_str(), order["receive_mint"].as_str()), (Some("7"), Some("SOL")));
+    assert_eq!(status(route(&store, &format!("/orders/{}/8", creator))), 404);
+    assert_eq!(status(route(&store, &format!("/orders/{}/seven", creator))), 400);
+    assert_eq!(status(route(&store, "/orders/creator/7")), 400);
+  }
+
+  #[test]
+  fn orders_are_filtered_by_query() {
+    let (creator, send_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let store = store(creator, send_mint);
+    let orders = |query: &str| route(&store, &format!("/orders?{}", query)).ok().unwrap();
+    assert_eq!(orders("mint=sol&stage=readyexchange").as_array().unwrap().len(), 1);
+    assert_eq!(orders(&format!("creator={}", Pubkey::new_unique())).as_array().unwrap().len(), 0);
+    assert_eq!(status(route(&store, "/orders?stage=open")), 400);
+    assert_eq!(status(route(&store, "/orders?limit=-1")), 400);
+    assert_eq!(status(route(&store, "/orders?sort=price")), 400);
+  }
+
+  #[test]
+  fn pairs_are_found_by_mints() {
+    let (creator, send_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let store = store(creator, send_mint);
+    let pair = route(&store, &format!("/pairs/{}/SOL", send_mint)).ok().unwrap();
+    assert_eq!(pair["open_orders"], 1);
+    assert_eq!(status(route(&store, &format!("/pairs/SOL/{}", send_mint))), 404);
+    assert_eq!(status(route(&store, "/markets")), 404);
+  }
+}
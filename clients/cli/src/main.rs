@@ -0,0 +1,1274 @@
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use trade_p2p_sdk::{
+  allowlist::{encode_root, PartnerAllowlist},
+  claim::ClaimCode,
+  instruction::{self, CreateMany, CreateTrade, PartnerAuth, CREATE_MANY_MAX_ORDERS, EXCHANGE_MANY_MAX_ORDERS},
+  keypair::Keypair,
+  oracle::{mock_oracle_account, new_mock_oracle_address, OraclePrice},
+  order::{mint_label, stage_name, Order},
+  parse_pubkey,
+  pda::{find_state_address, parse_order_address},
+  rpc::RpcClient,
+  trade_p2p::processor::PriceCap,
+  transaction::{parse_signature, Transaction, TransactionEncoding},
+  sealed_bid::SealedBid,
+  DutchAuction, EnglishAuction, EscrowAccount, Instruction, PriceGuard, Pubkey, SealedAuction, Stage,
+  TriggerDirection, PROGRAM_ID,
+};
+
+#[derive(Parser)]
+#[command(name = "trade-p2p", about = "Create, take, cancel and inspect trade-p2p orders")]
+struct Cli {
+  /// rpc url or cluster moniker: mainnet-beta, devnet, testnet, localhost
+  #[arg(long, short = 'u', global = true, default_value = "devnet")]
+  url: String,
+  /// keypair that signs the transaction: creator for create/cancel, partner for exchange/decline, matcher for match/crank, bidder for bid, partner for offer
+  #[arg(long, short = 'k', global = true)]
+  keypair: Option<PathBuf>,
+  #[arg(long, global = true)]
+  program_id: Option<String>,
+  /// act as this wallet without its keypair, only together with --export
+  #[arg(long, global = true)]
+  wallet: Option<String>,
+  /// use the durable nonce stored in this account instead of a recent blockhash
+  #[arg(long, global = true)]
+  nonce: Option<String>,
+  /// authority of the nonce account, defaults to the wallet
+  #[arg(long, global = true, requires = "nonce")]
+  nonce_authority: Option<String>,
+  /// print the unsigned transaction in this encoding (base58 or base64) instead of sending it
+  #[arg(long, global = true)]
+  export: Option<TransactionEncoding>,
+  #[command(subcommand)]
+  command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// create a new order, the trade type is taken from the mints given
+  Create(Box<CreateArgs>),
+  /// create a ladder of orders on one pair, several per transaction
+  CreateMany {
+    /// mint sent by the creator, omit to trade SOL
+    #[arg(long)]
+    send_mint: Option<String>,
+    /// mint received by the creator, omit to receive SOL
+    #[arg(long)]
+    receive_mint: Option<String>,
+    /// `<TRADE_VALUE>:<RECEIVE_VALUE>` of one order, may be repeated
+    #[arg(long = "level", required = true, value_parser = parse_level)]
+    levels: Vec<(u64, u64)>,
+    /// list the orders on the on-chain market of their pair
+    #[arg(long)]
+    list: bool,
+    #[arg(long)]
+    fee_account: Option<String>,
+  },
+  /// fill an open order as partner
+  Exchange {
+    order: String,
+    /// partner allowlist the order was created with, used to prove the wallet is in it
+    #[arg(long)]
+    allowlist: Option<PathBuf>,
+    /// claim code received from the creator
+    #[arg(long)]
+    claim_code: Option<ClaimCode>,
+    /// pay in one of the order's payment options, SOL for SOL, at its price
+    #[arg(long)]
+    pay_mint: Option<String>,
+    /// most the wallet pays for a dutch auction or oracle-pegged order, whose
+    /// price is only known when the transaction lands
+    #[arg(long)]
+    max_receive_value: Option<u64>,
+  },
+  /// fill several orders of one pair in one transaction, in the order given
+  ExchangeMany {
+    #[arg(required = true)]
+    orders: Vec<String>,
+    /// most the wallet pays in total, in the mint the creators receive
+    #[arg(long)]
+    budget: u64,
+    /// `<RECEIVE_VALUE>:<TRADE_VALUE>`, highest average price paid per amount received
+    #[arg(long, value_parser = parse_price_cap)]
+    max_price: Option<PriceCap>,
+    /// skip orders that cannot be filled instead of failing the transaction
+    #[arg(long)]
+    best_effort: bool,
+  },
+  /// settle two crossing orders of opposite sides of a pair against each other,
+  /// the wallet keeps the price improvement
+  Match { order_a: String, order_b: String },
+  /// match every crossing pair of open orders of a pair, one transaction each
+  Crank {
+    /// mint sent by one side and received by the other, omit for SOL
+    #[arg(long)]
+    send_mint: Option<String>,
+    /// mint received by that side, omit for SOL
+    #[arg(long)]
+    receive_mint: Option<String>,
+  },
+  /// bid on an english auction order, the best bid is refunded
+  Bid {
+    order: String,
+    /// amount offered in the mint the creator receives, defaults to the lowest bid accepted
+    #[arg(long)]
+    value: Option<u64>,
+  },
+  /// commit a sealed bid on a sealed-bid auction order, print the sealed bid to reveal it later
+  CommitBid {
+    order: String,
+    /// amount offered in the mint the creator receives
+    #[arg(long)]
+    value: u64,
+    /// amount locked until the reveal, hides the bid, defaults to the value
+    #[arg(long)]
+    deposit: Option<u64>,
+  },
+  /// reveal a sealed bid printed by commit-bid, a bid that is not the best is refunded
+  RevealBid { order: String, sealed_bid: SealedBid },
+  /// once reveals are over, close every sealed bid never revealed
+  ReclaimBids { order: String },
+  /// once an auction has ended, swap the order with its best bid or
+  /// refund the creator when nobody bid
+  SettleAuction { order: String },
+  /// offer your own amount and mint for an open order, the creator may accept it
+  Offer {
+    order: String,
+    /// amount offered
+    #[arg(long)]
+    value: u64,
+    /// mint offered, omit to offer SOL
+    #[arg(long)]
+    mint: Option<String>,
+  },
+  /// list the open offers on an order, largest first
+  Offers { order: String },
+  /// swap your order with the offer of a partner
+  AcceptOffer { order: String, partner: String },
+  /// take back your offer on an order
+  WithdrawOffer { order: String },
+  /// once quoting is over, fill a best-price request for quote with its best quote
+  SettleRfq { order: String },
+  /// refund every offer left on an order that is no longer open
+  RefundOffers { order: String },
+  /// make stop-loss and take-profit orders whose trigger price was reached
+  /// ready for exchange, anyone can send it
+  Trigger {
+    orders: Vec<String>,
+    /// every order waiting for a trigger that has been reached
+    #[arg(long, conflicts_with = "orders")]
+    all: bool,
+  },
+  /// cancel an open order and create it again at new values in one transaction,
+  /// keeping its mints, partner restrictions, market page and auction
+  Replace {
+    order: String,
+    /// defaults to the trade value of the order replaced
+    #[arg(long)]
+    trade_value: Option<u64>,
+    /// defaults to the receive value of the order replaced
+    #[arg(long)]
+    receive_value: Option<u64>,
+    #[command(flatten)]
+    price_guard: PriceGuardArgs,
+  },
+  /// cancel an open order and refund the vault to the creator
+  Cancel { order: String },
+  /// cancel several orders in as few transactions as possible
+  CancelMany {
+    orders: Vec<String>,
+    /// every open order of the wallet
+    #[arg(long, conflicts_with = "orders")]
+    all: bool,
+    /// skip orders that can no longer be cancelled instead of failing their batch
+    #[arg(long)]
+    best_effort: bool,
+  },
+  /// refuse an order addressed to you, the vault is refunded to the creator
+  Decline { order: String },
+  /// grow orders created by the first release of the program to the current
+  /// layout, the wallet pays their extra rent
+  Migrate {
+    orders: Vec<String>,
+    /// every order of the first release
+    #[arg(long, conflicts_with = "orders")]
+    all: bool,
+  },
+  /// print the content of an order
+  Show { order: String },
+  /// write a mock Pyth-format price account for `solana-test-validator --account`,
+  /// to peg orders to a local feed
+  MockOracle {
+    /// json file written
+    out: PathBuf,
+    /// price of one send token in receive tokens, scaled by 10^--exponent
+    #[arg(long)]
+    price: i64,
+    #[arg(long, default_value_t = -8, allow_hyphen_values = true)]
+    exponent: i32,
+    /// confidence interval, scaled as the price
+    #[arg(long, default_value_t = 0)]
+    confidence: u64,
+    /// unix time the price was published at, defaults to now
+    #[arg(long)]
+    publish_time: Option<i64>,
+    /// address of the price account, defaults to a new one
+    #[arg(long)]
+    address: Option<String>,
+  },
+  /// list orders owned by the program
+  List {
+    #[arg(long)]
+    creator: Option<String>,
+    #[arg(long)]
+    stage: Option<String>,
+  },
+  /// list the open orders addressed to a wallet, by default the --keypair wallet
+  Inbox {
+    #[arg(long)]
+    partner: Option<String>,
+  },
+  /// print the on-chain market of a pair, best price first
+  Market {
+    /// mint sent by the order creators, omit for SOL
+    #[arg(long)]
+    send_mint: Option<String>,
+    /// mint received by the order creators, omit for SOL
+    #[arg(long)]
+    receive_mint: Option<String>,
+  },
+  /// sign an exported transaction with --keypair, works without network access
+  Sign {
+    transaction: String,
+    #[arg(long, default_value = "base64")]
+    encoding: TransactionEncoding,
+  },
+  /// add signatures collected from offline signers to an exported transaction
+  AddSignature {
+    transaction: String,
+    /// `<PUBKEY>=<SIGNATURE>`, may be repeated
+    #[arg(long = "signer", required = true)]
+    signers: Vec<String>,
+    #[arg(long, default_value = "base64")]
+    encoding: TransactionEncoding,
+  },
+  /// send a fully signed exported transaction
+  Submit {
+    transaction: String,
+    #[arg(long, default_value = "base64")]
+    encoding: TransactionEncoding,
+  },
+}
+
+#[derive(Args)]
+struct CreateArgs {
+  /// mint sent by the creator, omit to trade SOL
+  #[arg(long)]
+  send_mint: Option<String>,
+  /// mint received by the creator, omit to receive SOL
+  #[arg(long)]
+  receive_mint: Option<String>,
+  #[arg(long)]
+  trade_value: u64,
+  #[arg(long)]
+  receive_value: u64,
+  /// only this wallet may fill the order
+  #[arg(long)]
+  partner: Option<String>,
+  /// file of wallets allowed to fill the order, one public key per line
+  #[arg(long)]
+  allowlist: Option<PathBuf>,
+  /// generate a claim code, only whoever is given the code may fill the order
+  #[arg(long)]
+  claim_code: bool,
+  /// list the order on the on-chain market of its pair
+  #[arg(long)]
+  list: bool,
+  /// sell by dutch auction, the price falls from --receive-value to this floor
+  #[arg(long, requires = "auction_duration")]
+  floor_value: Option<u64>,
+  /// seconds the dutch auction price takes to reach --floor-value
+  #[arg(long, requires = "floor_value")]
+  auction_duration: Option<i64>,
+  /// unix time the dutch auction price starts falling, defaults to now
+  #[arg(long, requires = "floor_value")]
+  auction_start: Option<i64>,
+  /// sell by english auction to the best bid placed before this unix time,
+  /// --receive-value being the reserve price
+  #[arg(long, requires = "min_increment", conflicts_with_all = ["floor_value", "partner", "allowlist", "claim_code"])]
+  bidding_end: Option<i64>,
+  /// least each bid must raise the best one by
+  #[arg(long, requires = "bidding_end")]
+  min_increment: Option<u64>,
+  /// sell by sealed-bid auction: bids are committed before this unix time,
+  /// --receive-value being the reserve price
+  #[arg(long, requires = "reveal_end", conflicts_with_all = ["floor_value", "bidding_end", "partner", "allowlist", "claim_code"])]
+  commit_end: Option<i64>,
+  /// unix time sealed bids must be revealed before
+  #[arg(long, requires = "commit_end")]
+  reveal_end: Option<i64>,
+  /// give the deposits of bids never revealed to the creator instead of refunding them
+  #[arg(long, requires = "commit_end")]
+  forfeit_unrevealed: bool,
+  /// request quotes until this unix time, --receive-value being 0: takers
+  /// offer what they pay for the order and one of the quotes fills it
+  #[arg(long, conflicts_with_all = ["floor_value", "bidding_end", "commit_end", "partner", "allowlist", "claim_code", "list"])]
+  quote_deadline: Option<i64>,
+  /// mint quotes may also be made in, SOL for SOL, may be repeated
+  #[arg(long = "quote-mint", requires = "quote_deadline")]
+  quote_mints: Vec<String>,
+  /// the highest quote in the receive mint fills the order at the deadline,
+  /// settled by anyone with settle-rfq, instead of the creator picking one
+  #[arg(long, requires = "quote_deadline", conflicts_with = "quote_mints")]
+  best_price: bool,
+  /// peg the price to this Pyth-format price account, the price of one send
+  /// token in receive tokens, --receive-value only being indicative
+  #[arg(
+    long,
+    requires_all = ["min_value", "max_value"],
+    conflicts_with_all = ["floor_value", "bidding_end", "commit_end", "quote_deadline"]
+  )]
+  oracle: Option<String>,
+  /// share of the oracle price asked, in basis points
+  #[arg(long, requires = "oracle", default_value_t = 10_000)]
+  spread_bps: u16,
+  /// lowest receive value of a pegged order
+  #[arg(long, requires = "oracle")]
+  min_value: Option<u64>,
+  /// highest receive value of a pegged order
+  #[arg(long, requires = "oracle")]
+  max_value: Option<u64>,
+  /// seconds after which an oracle price is stale
+  #[arg(long, requires = "oracle", default_value_t = 60)]
+  max_oracle_age: u32,
+  /// widest oracle confidence interval accepted, in basis points of the price
+  #[arg(long, requires = "oracle", default_value_t = 100)]
+  max_confidence_bps: u16,
+  #[command(flatten)]
+  price_guard: PriceGuardArgs,
+  /// `<MINT>:<RECEIVE_VALUE>`, another mint the order may be paid in at its own
+  /// price, SOL for SOL, may be repeated up to 3 times
+  #[arg(
+    long = "payment-option",
+    value_parser = parse_payment_option,
+    conflicts_with_all = ["floor_value", "bidding_end", "commit_end", "quote_deadline", "oracle"]
+  )]
+  payment_options: Vec<(Option<Pubkey>, u64)>,
+  /// stop-loss or take-profit order: only exchanged once the price of this
+  /// Pyth-format price account has reached --trigger-price
+  #[arg(
+    long,
+    requires = "trigger_price",
+    conflicts_with_all = ["floor_value", "bidding_end", "commit_end", "quote_deadline", "list"]
+  )]
+  trigger_oracle: Option<String>,
+  /// price firing the trigger, in the units of the feed
+  #[arg(long, requires = "trigger_oracle")]
+  trigger_price: Option<i64>,
+  /// fire once the price rises to --trigger-price (take-profit), instead of
+  /// once it falls to it (stop-loss)
+  #[arg(long, requires = "trigger_oracle")]
+  trigger_above: bool,
+  /// seconds after which a price no longer fires the trigger
+  #[arg(long, requires = "trigger_oracle", default_value_t = 60)]
+  max_trigger_age: u32,
+  #[arg(long)]
+  fee_account: Option<String>,
+}
+
+// fat-finger check of a new order's price against a reference feed
+#[derive(Args)]
+struct PriceGuardArgs {
+  /// refuse a receive value more than --max-deviation-bps away from the trade
+  /// value at the price of this Pyth-format price account
+  #[arg(long)]
+  reference_oracle: Option<String>,
+  /// largest deviation from the reference price accepted, in basis points
+  #[arg(long, requires = "reference_oracle", default_value_t = 1_000)]
+  max_deviation_bps: u16,
+  /// seconds after which the reference price is stale
+  #[arg(long, requires = "reference_oracle", default_value_t = 60)]
+  max_reference_age: u32,
+  /// create the order whatever its deviation from the reference price
+  #[arg(long, requires = "reference_oracle")]
+  override_price_guard: bool,
+}
+
+impl PriceGuardArgs {
+  fn price_guard(&self) -> Result<Option<PriceGuard>> {
+    match &self.reference_oracle {
+      Some(oracle) => Ok(Some(PriceGuard {
+        oracle: parse_pubkey(oracle)?,
+        max_deviation_bps: self.max_deviation_bps,
+        max_age: self.max_reference_age,
+        override_deviation: self.override_price_guard,
+      })),
+      None => Ok(None),
+    }
+  }
+}
+
+fn main() {
+  if let Err(err) = run(Cli::parse()) {
+    eprintln!("error: {:#}", err);
+    std::process::exit(1);
+  }
+}
+
+fn run(cli: Cli) -> Result<()> {
+  let rpc = RpcClient::new(&cli.url);
+  let program_id = match &cli.program_id {
+    Some(program_id) => parse_pubkey(program_id)?,
+    None => PROGRAM_ID,
+  };
+  match &cli.command {
+    Command::Create(args) => {
+      let creator = wallet(&cli)?;
+      let claim_code = args.claim_code.then(ClaimCode::generate);
+      let order_id = rpc.next_order_id(&program_id, &creator)?;
+      eprintln!("order id: {}", order_id);
+      let send_mint = args.send_mint.as_deref().map(parse_pubkey).transpose()?;
+      let receive_mint = args.receive_mint.as_deref().map(parse_pubkey).transpose()?;
+      let market_page = match args.list {
+        true => {
+          let page = rpc.find_market_page(&program_id, send_mint.as_ref(), receive_mint.as_ref(), 1)?;
+          eprintln!("market page: {}", page);
+          Some(page)
+        }
+        false => None,
+      };
+      let dutch_auction = match (args.floor_value, args.auction_duration) {
+        (Some(floor_value), Some(duration)) => {
+          let start_time = match args.auction_start {
+            Some(start_time) => start_time,
+            None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64,
+          };
+          Some(DutchAuction {
+            floor_value,
+            start_time,
+            end_time: start_time + duration,
+          })
+        }
+        _ => None,
+      };
+      let english_auction = match (args.bidding_end, args.min_increment) {
+        (Some(end_time), Some(min_increment)) => Some(EnglishAuction { end_time, min_increment }),
+        _ => None,
+      };
+      let sealed_auction = match (args.commit_end, args.reveal_end) {
+        (Some(commit_end), Some(reveal_end)) => Some(SealedAuction {
+          commit_end,
+          reveal_end,
+          forfeit_unrevealed: args.forfeit_unrevealed,
+        }),
+        _ => None,
+      };
+      let rfq = match args.quote_deadline {
+        Some(deadline) => {
+          let quote_mints = args
+            .quote_mints
+            .iter()
+            .map(|mint| parse_quote_mint(mint))
+            .collect::<Result<Vec<_>>>()?;
+          Some(instruction::rfq_terms(deadline, &quote_mints, args.best_price)?)
+        }
+        None => None,
+      };
+      let oracle_peg = match (&args.oracle, args.min_value, args.max_value) {
+        (Some(oracle), Some(min_value), Some(max_value)) => Some(instruction::oracle_peg_terms(
+          &parse_pubkey(oracle)?,
+          args.spread_bps,
+          (min_value, max_value),
+          args.max_oracle_age,
+          args.max_confidence_bps,
+        )?),
+        _ => None,
+      };
+      let trigger = match (&args.trigger_oracle, args.trigger_price) {
+        (Some(oracle), Some(trigger_price)) => Some(instruction::trigger_terms(
+          &parse_pubkey(oracle)?,
+          trigger_price,
+          match args.trigger_above {
+            true => TriggerDirection::AtOrAbove,
+            false => TriggerDirection::AtOrBelow,
+          },
+          args.max_trigger_age,
+        )?),
+        _ => None,
+      };
+      let trade = CreateTrade {
+        creator,
+        order_id,
+        send_mint,
+        receive_mint,
+        trade_value: args.trade_value,
+        receive_value: args.receive_value,
+        specify_partner: args.partner.as_deref().map(parse_pubkey).transpose()?,
+        partner_root: match &args.allowlist {
+          Some(path) => {
+            let allowlist = PartnerAllowlist::read_from_file(path)?;
+            let root = allowlist.root();
+            eprintln!("allowlist: {} partners, root {}", allowlist.partners().len(), encode_root(&root));
+            Some(root)
+          }
+          None => None,
+        },
+        claim_hash: claim_code.as_ref().map(ClaimCode::hash),
+        market_page,
+        dutch_auction,
+        english_auction,
+        sealed_auction,
+        rfq,
+        oracle_peg,
+        price_guard: args.price_guard.price_guard()?,
+        trigger,
+        payment_options: match args.payment_options.is_empty() {
+          true => None,
+          false => Some(instruction::payment_options_terms(&creator, &args.payment_options)?),
+        },
+        fee_account: match &args.fee_account {
+          Some(fee_account) => parse_pubkey(fee_account)?,
+          None => trade_p2p_sdk::default_fee_account(),
+        },
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+      };
+      eprintln!("trade type: {:?}", trade.trade_type()?);
+      let ix = trade.instruction(&program_id)?;
+      let (state, _) = find_state_address(&program_id, &creator, order_id);
+      eprintln!("order: {}", state);
+      if let Some(claim_code) = &claim_code {
+        eprintln!("claim code (share it privately with the partner): {}", claim_code);
+      }
+      send(&cli, &rpc, &[ix], &creator, &[])?;
+    }
+    Command::CreateMany {
+      send_mint,
+      receive_mint,
+      levels,
+      list,
+      fee_account,
+    } => {
+      let creator = wallet(&cli)?;
+      let send_mint = send_mint.as_deref().map(parse_pubkey).transpose()?;
+      let receive_mint = receive_mint.as_deref().map(parse_pubkey).transpose()?;
+      let fee_account = match fee_account {
+        Some(fee_account) => parse_pubkey(fee_account)?,
+        None => trade_p2p_sdk::default_fee_account(),
+      };
+      let batches = levels.chunks(CREATE_MANY_MAX_ORDERS).collect::<Vec<_>>();
+      if batches.len() > 1 && cli.nonce.is_some() {
+        bail!("{} orders need {} transactions, a nonce account can only be used by one", levels.len(), batches.len());
+      }
+      eprintln!("creating {} orders in {} transactions", levels.len(), batches.len());
+      let mut next_order_id = rpc.next_order_id(&program_id, &creator)?;
+      for batch in batches {
+        let market_page = match list {
+          true => Some(rpc.find_market_page(&program_id, send_mint.as_ref(), receive_mint.as_ref(), batch.len())?),
+          false => None,
+        };
+        let ladder = CreateMany {
+          creator,
+          order_id: next_order_id,
+          send_mint,
+          receive_mint,
+          levels: batch.to_vec(),
+          market_page,
+          fee_account,
+          timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+        };
+        let ix = ladder.instruction(&program_id)?;
+        for (index, state) in ladder.order_addresses(&program_id).iter().enumerate() {
+          eprintln!("order {}: {}", next_order_id + index as u64, state);
+        }
+        send(&cli, &rpc, &[ix], &creator, &[])?;
+        next_order_id += batch.len() as u64;
+      }
+    }
+    Command::Exchange {
+      order,
+      allowlist,
+      claim_code,
+      pay_mint,
+      max_receive_value,
+    } => {
+      let partner = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      let proof = match allowlist {
+        Some(path) => {
+          let allowlist = PartnerAllowlist::read_from_file(path)?;
+          if escrow.partner_root != Some(allowlist.root()) {
+            bail!("{} is not the allowlist this order was created with", path.display());
+          }
+          allowlist
+            .proof(&partner)
+            .ok_or_else(|| anyhow!("{} is not in {}", partner, path.display()))?
+        }
+        None => Vec::new(),
+      };
+      let auth = PartnerAuth {
+        proof,
+        claim_key: claim_code.as_ref().map(ClaimCode::pubkey),
+      };
+      let instructions = match pay_mint {
+        Some(mint) => {
+          let mint = parse_quote_mint(mint)?;
+          rpc.exchange_paying_instructions(&program_id, &escrow, &partner, mint.as_ref(), &auth, *max_receive_value)?
+        }
+        None => rpc.exchange_instructions(&program_id, &escrow, &partner, &auth, *max_receive_value)?,
+      };
+      let claim_keypair = claim_code.as_ref().map(ClaimCode::keypair);
+      send(&cli, &rpc, &instructions, &partner, claim_keypair.as_slice())?;
+    }
+    Command::ExchangeMany {
+      orders,
+      budget,
+      max_price,
+      best_effort,
+    } => {
+      let partner = wallet(&cli)?;
+      if orders.len() > EXCHANGE_MANY_MAX_ORDERS {
+        bail!("at most {} orders fit in one transaction", EXCHANGE_MANY_MAX_ORDERS);
+      }
+      let escrows = orders
+        .iter()
+        .map(|order| Ok(fetch_order(&rpc, &program_id, order)?.1))
+        .collect::<Result<Vec<_>>>()?;
+      let instructions =
+        rpc.exchange_many_instructions(&program_id, &escrows, &partner, *budget, *max_price, !best_effort)?;
+      send(&cli, &rpc, &instructions, &partner, &[])?;
+    }
+    Command::Match { order_a, order_b } => {
+      let matcher = wallet(&cli)?;
+      let (_, a) = fetch_order(&rpc, &program_id, order_a)?;
+      let (_, b) = fetch_order(&rpc, &program_id, order_b)?;
+      let (fee_a, fee_b) = instruction::check_match(&a, &b)?;
+      eprintln!(
+        "fees: {} {}, {} {}",
+        fee_a,
+        mint_label(a.creator_send_token_mint.as_ref()),
+        fee_b,
+        mint_label(b.creator_send_token_mint.as_ref())
+      );
+      let instructions = rpc.match_orders_instructions(&program_id, &a, &b, &matcher)?;
+      send(&cli, &rpc, &instructions, &matcher, &[])?;
+    }
+    Command::Crank {
+      send_mint,
+      receive_mint,
+    } => {
+      let matcher = wallet(&cli)?;
+      let send_mint = send_mint.as_deref().map(parse_pubkey).transpose()?;
+      let receive_mint = receive_mint.as_deref().map(parse_pubkey).transpose()?;
+      let open = rpc
+        .get_orders(&program_id, None)?
+        .into_iter()
+        .map(|(_, escrow)| escrow)
+        .filter(|escrow| escrow.stage == Stage::ReadyExchange.to_code())
+        .collect::<Vec<_>>();
+      let side = |send: Option<Pubkey>, receive: Option<Pubkey>| {
+        open
+          .iter()
+          .filter(|escrow| escrow.creator_send_token_mint == send && escrow.creator_receive_token_mint == receive)
+          .cloned()
+          .collect::<Vec<_>>()
+      };
+      let (asks, bids) = (side(send_mint, receive_mint), side(receive_mint, send_mint));
+      let pairs = instruction::crossing_orders(&asks, &bids);
+      if pairs.is_empty() {
+        bail!("no crossing orders");
+      }
+      if pairs.len() > 1 && cli.nonce.is_some() {
+        bail!("{} matches need {} transactions, a nonce account can only be used by one", pairs.len(), pairs.len());
+      }
+      eprintln!("matching {} pairs of orders", pairs.len());
+      for (a, b) in pairs {
+        eprintln!("{}/{} with {}/{}", a.creator, a.order_id, b.creator, b.order_id);
+        let instructions = rpc.match_orders_instructions(&program_id, a, b, &matcher)?;
+        send(&cli, &rpc, &instructions, &matcher, &[])?;
+      }
+    }
+    Command::Bid { order, value } => {
+      let bidder = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      let value = match value {
+        Some(value) => *value,
+        None => {
+          let value = instruction::min_bid(&escrow, rpc.get_best_bid(&escrow)?.as_ref());
+          eprintln!("bid: {}", value);
+          value
+        }
+      };
+      let instructions = rpc.place_bid_instructions(&program_id, &escrow, &bidder, value)?;
+      send(&cli, &rpc, &instructions, &bidder, &[])?;
+    }
+    Command::CommitBid { order, value, deposit } => {
+      let bidder = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      let sealed = SealedBid::generate(*value);
+      let instructions =
+        rpc.commit_bid_instructions(&program_id, &escrow, &bidder, deposit.unwrap_or(*value), &sealed)?;
+      eprintln!("sealed bid (keep it to reveal the bid): {}", sealed);
+      send(&cli, &rpc, &instructions, &bidder, &[])?;
+    }
+    Command::RevealBid { order, sealed_bid } => {
+      let bidder = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      let instructions = rpc.reveal_bid_instructions(&program_id, &escrow, &bidder, sealed_bid)?;
+      send(&cli, &rpc, &instructions, &bidder, &[])?;
+    }
+    Command::ReclaimBids { order } => {
+      let payer = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      let instructions = rpc.reclaim_bids_instructions(&program_id, &escrow, &payer)?;
+      let bid_count = instructions.iter().filter(|ix| ix.program_id == program_id).count();
+      if bid_count == 0 {
+        bail!("no unrevealed bid to reclaim");
+      }
+      if bid_count > 1 && cli.nonce.is_some() {
+        bail!("{} bids need {} transactions, a nonce account can only be used by one", bid_count, bid_count);
+      }
+      // one transaction per bid, with the token account it may need
+      let mut batch = Vec::new();
+      for ix in instructions {
+        let reclaim = ix.program_id == program_id;
+        batch.push(ix);
+        if reclaim {
+          send(&cli, &rpc, &batch, &payer, &[])?;
+          batch.clear();
+        }
+      }
+    }
+    Command::SettleAuction { order } => {
+      let payer = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      let end_time = escrow
+        .auction_end()
+        .ok_or_else(|| anyhow!("order is not an auction"))?;
+      let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+      if now < end_time {
+        bail!("auction is open until {}", end_time);
+      }
+      let instructions = rpc.settle_auction_instructions(&program_id, &escrow, &payer)?;
+      send(&cli, &rpc, &instructions, &payer, &[])?;
+    }
+    Command::Offer { order, value, mint } => {
+      let partner = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      let mint = mint.as_deref().map(parse_pubkey).transpose()?;
+      let instructions = rpc.make_offer_instructions(&program_id, &escrow, &partner, mint.as_ref(), *value)?;
+      send(&cli, &rpc, &instructions, &partner, &[])?;
+    }
+    Command::Offers { order } => {
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      for (_, offer) in rpc.get_offers(&program_id, &escrow)? {
+        println!("{}  {} {}", offer.partner, offer.value, mint_label(offer.offer_mint.as_ref()));
+      }
+    }
+    Command::AcceptOffer { order, partner } => {
+      let creator = wallet(&cli)?;
+      let (address, escrow) = fetch_order(&rpc, &program_id, order)?;
+      if escrow.creator != creator {
+        bail!("order was created by {}, not {}", escrow.creator, creator);
+      }
+      let offer = rpc
+        .get_offer(&program_id, &address, &parse_pubkey(partner)?)?
+        .ok_or_else(|| anyhow!("{} has no open offer on the order", partner))?;
+      let instructions = rpc.accept_offer_instructions(&program_id, &escrow, &offer)?;
+      send(&cli, &rpc, &instructions, &creator, &[])?;
+    }
+    Command::WithdrawOffer { order } => {
+      let partner = wallet(&cli)?;
+      let address = parse_order_address(&program_id, order)?;
+      let offer = rpc
+        .get_offer(&program_id, &address, &partner)?
+        .ok_or_else(|| anyhow!("{} has no open offer on the order", partner))?;
+      send(&cli, &rpc, &[instruction::withdraw_offer(&program_id, &offer)], &partner, &[])?;
+    }
+    Command::SettleRfq { order } => {
+      let payer = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      let rfq = escrow.rfq.ok_or_else(|| anyhow!("order is not a request for quote"))?;
+      let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+      if now < rfq.deadline {
+        bail!("quotes are taken until {}", rfq.deadline);
+      }
+      let instructions = rpc.settle_rfq_instructions(&program_id, &escrow, &payer)?;
+      send(&cli, &rpc, &instructions, &payer, &[])?;
+    }
+    Command::RefundOffers { order } => {
+      let payer = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      let instructions = rpc.refund_offers_instructions(&program_id, &escrow, &payer)?;
+      if instructions.is_empty() {
+        bail!("order has no offers left");
+      }
+      send(&cli, &rpc, &instructions, &payer, &[])?;
+    }
+    Command::Trigger { orders, all } => {
+      let payer = wallet(&cli)?;
+      let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+      match all {
+        true => {
+          let armed = rpc
+            .get_orders(&program_id, None)?
+            .into_iter()
+            .filter(|(_, escrow)| escrow.stage == Stage::AwaitingTrigger.to_code())
+            .collect::<Vec<_>>();
+          let mut fired = 0;
+          for (address, escrow) in &armed {
+            match rpc.trigger_instructions(&program_id, escrow, now) {
+              Ok(instructions) => {
+                eprintln!("order: {}", address);
+                send(&cli, &rpc, &instructions, &payer, &[])?;
+                fired += 1;
+              }
+              Err(err) => eprintln!("skipped {}: {}", address, err),
+            }
+          }
+          eprintln!("{} of {} orders triggered", fired, armed.len());
+        }
+        false => {
+          if orders.is_empty() {
+            bail!("no order to trigger");
+          }
+          for order in orders {
+            let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+            let instructions = rpc.trigger_instructions(&program_id, &escrow, now)?;
+            send(&cli, &rpc, &instructions, &payer, &[])?;
+          }
+        }
+      }
+    }
+    Command::Replace {
+      order,
+      trade_value,
+      receive_value,
+      price_guard,
+    } => {
+      let creator = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      if escrow.creator != creator {
+        bail!("order was created by {}, not {}", escrow.creator, creator);
+      }
+      let order_id = rpc.next_order_id(&program_id, &creator)?;
+      let trade = CreateTrade {
+        creator,
+        order_id,
+        send_mint: escrow.creator_send_token_mint,
+        receive_mint: escrow.creator_receive_token_mint,
+        trade_value: trade_value.unwrap_or(escrow.trade_value),
+        receive_value: receive_value.unwrap_or(escrow.receive_value),
+        specify_partner: escrow.specify_partner,
+        partner_root: escrow.partner_root,
+        claim_hash: escrow.claim_hash,
+        // the old order leaves its page before the new one is listed
+        market_page: escrow.market_page,
+        dutch_auction: escrow.dutch_auction,
+        english_auction: escrow.english_auction,
+        sealed_auction: escrow.sealed_auction,
+        rfq: escrow.rfq,
+        oracle_peg: escrow.oracle_peg,
+        price_guard: price_guard.price_guard()?,
+        // a trigger that already fired is not armed again
+        trigger: escrow.trigger.filter(|_| escrow.stage == Stage::AwaitingTrigger.to_code()),
+        payment_options: escrow.payment_options,
+        fee_account: escrow.fee_account,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+      };
+      let ix = trade.replace_instruction(&program_id, &escrow)?;
+      let (state, _) = find_state_address(&program_id, &creator, order_id);
+      eprintln!("order: {}", state);
+      send(&cli, &rpc, &[ix], &creator, &[])?;
+    }
+    Command::Cancel { order } => {
+      let creator = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      if escrow.creator != creator {
+        bail!("order was created by {}, not {}", escrow.creator, creator);
+      }
+      let ix = instruction::cancel(&program_id, &escrow);
+      send(&cli, &rpc, &[ix], &creator, &[])?;
+    }
+    Command::CancelMany {
+      orders,
+      all,
+      best_effort,
+    } => {
+      let creator = wallet(&cli)?;
+      let escrows = match all {
+        true => rpc
+          .get_orders(&program_id, Some(&creator))?
+          .into_iter()
+          .map(|(_, escrow)| escrow)
+          .filter(EscrowAccount::is_open)
+          .collect::<Vec<_>>(),
+        false => orders
+          .iter()
+          .map(|order| Ok(fetch_order(&rpc, &program_id, order)?.1))
+          .collect::<Result<Vec<_>>>()?,
+      };
+      if escrows.is_empty() {
+        bail!("no order to cancel");
+      }
+      if let Some(escrow) = escrows.iter().find(|escrow| escrow.creator != creator) {
+        bail!("order {}/{} was not created by {}", escrow.creator, escrow.order_id, creator);
+      }
+      let instructions = instruction::cancel_many_batches(&program_id, &creator, &escrows, !best_effort);
+      if instructions.len() > 1 && cli.nonce.is_some() {
+        bail!("{} orders need {} transactions, a nonce account can only be used by one", escrows.len(), instructions.len());
+      }
+      eprintln!("cancelling {} orders in {} transactions", escrows.len(), instructions.len());
+      for ix in instructions {
+        send(&cli, &rpc, &[ix], &creator, &[])?;
+      }
+    }
+    Command::Decline { order } => {
+      let partner = wallet(&cli)?;
+      let (_, escrow) = fetch_order(&rpc, &program_id, order)?;
+      if escrow.specify_partner != Some(partner) {
+        bail!("order is not addressed to {}", partner);
+      }
+      let ix = instruction::decline(&program_id, &escrow, &partner);
+      send(&cli, &rpc, &[ix], &partner, &[])?;
+    }
+    Command::Migrate { orders, all } => {
+      let payer = wallet(&cli)?;
+      let addresses = match all {
+        true => rpc.get_unmigrated_orders(&program_id, None)?,
+        false => orders
+          .iter()
+          .map(|order| {
+            let address = parse_order_address(&program_id, order)?;
+            Ok((address, rpc.get_unmigrated_order(&address)?))
+          })
+          .collect::<Result<Vec<_>>>()?,
+      };
+      if addresses.is_empty() {
+        bail!("no order to migrate");
+      }
+      let batches = addresses.chunks(instruction::MIGRATE_MAX_ORDERS).collect::<Vec<_>>();
+      if batches.len() > 1 && cli.nonce.is_some() {
+        bail!("{} orders need {} transactions, a nonce account can only be used by one", addresses.len(), batches.len());
+      }
+      eprintln!("migrating {} orders in {} transactions", addresses.len(), batches.len());
+      for batch in batches {
+        let instructions = batch
+          .iter()
+          .map(|(address, creator)| instruction::migrate_escrow(&program_id, address, creator, &payer))
+          .collect::<Vec<_>>();
+        send(&cli, &rpc, &instructions, &payer, &[])?;
+      }
+    }
+    Command::Show { order } => {
+      let (address, escrow) = fetch_order(&rpc, &program_id, order)?;
+      let fields = Order::new(&address, &escrow).fields();
+      let width = fields.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+      for (label, value) in fields {
+        println!("{:width$}  {}", label, value, width = width);
+      }
+      if escrow.dutch_auction.is_some() || escrow.oracle_peg.is_some() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        match rpc.quote_receive_value(&escrow, now) {
+          Ok(value) => println!("{:width$}  {}", "current price", value, width = width),
+          Err(err) => println!("{:width$}  - ({})", "current price", err, width = width),
+        }
+      }
+      if let Some(best_bid) = rpc.get_best_bid(&escrow)? {
+        println!("{:width$}  {} by {}", "best bid value", best_bid.value, best_bid.bidder, width = width);
+      }
+    }
+    Command::MockOracle {
+      out,
+      price,
+      exponent,
+      confidence,
+      publish_time,
+      address,
+    } => {
+      let address = match address {
+        Some(address) => parse_pubkey(address)?,
+        None => new_mock_oracle_address(),
+      };
+      let price = OraclePrice {
+        price: *price,
+        confidence: *confidence,
+        exponent: *exponent,
+        publish_time: match publish_time {
+          Some(publish_time) => *publish_time,
+          None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64,
+        },
+      };
+      let account = mock_oracle_account(&address, &price);
+      std::fs::write(out, account.to_string()).with_context(|| format!("writing {}", out.display()))?;
+      println!("{}", address);
+    }
+    Command::List { creator, stage } => {
+      let creator = creator.as_deref().map(parse_pubkey).transpose()?;
+      let mut orders = rpc.get_orders(&program_id, creator.as_ref())?;
+      if let Some(stage) = stage {
+        orders.retain(|(_, escrow)| stage_name(escrow.stage).eq_ignore_ascii_case(stage));
+      }
+      orders.sort_by_key(|(_, escrow)| (escrow.creator, escrow.order_id));
+      print_orders(&orders);
+    }
+    Command::Inbox { partner } => {
+      let partner = match partner {
+        Some(partner) => parse_pubkey(partner)?,
+        None => load_keypair(&cli.keypair)?.pubkey(),
+      };
+      print_orders(&rpc.get_inbox_orders(&program_id, &partner)?);
+    }
+    Command::Market {
+      send_mint,
+      receive_mint,
+    } => {
+      let send_mint = send_mint.as_deref().map(parse_pubkey).transpose()?;
+      let receive_mint = receive_mint.as_deref().map(parse_pubkey).transpose()?;
+      let mut entries = rpc
+        .get_market(&program_id, send_mint.as_ref(), receive_mint.as_ref())?
+        .into_iter()
+        .flat_map(|page| page.orders)
+        .collect::<Vec<_>>();
+      // the creator sells `trade_value` for `receive_value`: lowest price per unit first
+      entries.sort_by(|a, b| {
+        (a.receive_value as u128 * b.trade_value as u128).cmp(&(b.receive_value as u128 * a.trade_value as u128))
+      });
+      println!("{:44}  {:>20}  {:>20}  {:>20}", "ORDER", "TRADE VALUE", "RECEIVE VALUE", "PRICE");
+      for entry in &entries {
+        println!(
+          "{:44}  {:>20}  {:>20}  {:>20.9}",
+          entry.order.to_string(),
+          entry.trade_value,
+          entry.receive_value,
+          entry.receive_value as f64 / entry.trade_value as f64
+        );
+      }
+    }
+    Command::Sign {
+      transaction,
+      encoding,
+    } => {
+      let mut transaction = Transaction::decode(transaction, *encoding)?;
+      let signer = load_keypair(&cli.keypair)?;
+      transaction.sign(&signer)?;
+      let position = transaction.signer_position(&signer.pubkey())?;
+      eprintln!(
+        "{}={}",
+        signer.pubkey(),
+        bs58::encode(transaction.signatures[position]).into_string()
+      );
+      print_missing_signers(&transaction);
+      println!("{}", transaction.encode(*encoding));
+    }
+    Command::AddSignature {
+      transaction,
+      signers,
+      encoding,
+    } => {
+      let mut transaction = Transaction::decode(transaction, *encoding)?;
+      for signer in signers {
+        let (pubkey, signature) = signer
+          .split_once('=')
+          .ok_or_else(|| anyhow!("expected <PUBKEY>=<SIGNATURE>, got {}", signer))?;
+        transaction.add_signature(&parse_pubkey(pubkey)?, parse_signature(signature)?)?;
+      }
+      print_missing_signers(&transaction);
+      println!("{}", transaction.encode(*encoding));
+    }
+    Command::Submit {
+      transaction,
+      encoding,
+    } => {
+      let transaction = Transaction::decode(transaction, *encoding)?;
+      if !transaction.is_signed() {
+        print_missing_signers(&transaction);
+        bail!("transaction is not fully signed");
+      }
+      println!("signature: {}", rpc.send_and_confirm_transaction(&transaction)?);
+    }
+  }
+  Ok(())
+}
+
+// `<TRADE_VALUE>:<RECEIVE_VALUE>` of a `create-many` order
+fn parse_level(value: &str) -> Result<(u64, u64)> {
+  let (trade_value, receive_value) = value
+    .split_once(':')
+    .ok_or_else(|| anyhow!("expected <TRADE_VALUE>:<RECEIVE_VALUE>, got {}", value))?;
+  Ok((trade_value.parse()?, receive_value.parse()?))
+}
+
+// `--quote-mint` of a request for quote, `SOL` standing for SOL
+fn parse_quote_mint(value: &str) -> Result<Option<Pubkey>> {
+  match value {
+    "SOL" => Ok(None),
+    _ => Ok(Some(parse_pubkey(value)?)),
+  }
+}
+
+// `<MINT>:<RECEIVE_VALUE>` of a payment option, SOL for SOL
+fn parse_payment_option(value: &str) -> Result<(Option<Pubkey>, u64)> {
+  let (mint, receive_value) = value
+    .split_once(':')
+    .ok_or_else(|| anyhow!("expected <MINT>:<RECEIVE_VALUE>, got {}", value))?;
+  Ok((parse_quote_mint(mint)?, receive_value.parse()?))
+}
+
+// `<RECEIVE_VALUE>:<TRADE_VALUE>` of an `exchange-many` price cap
+fn parse_price_cap(value: &str) -> Result<PriceCap> {
+  let (receive_value, trade_value) = value
+    .split_once(':')
+    .ok_or_else(|| anyhow!("expected <RECEIVE_VALUE>:<TRADE_VALUE>, got {}", value))?;
+  Ok(PriceCap {
+    receive_value: receive_value.parse()?,
+    trade_value: trade_value.parse()?,
+  })
+}
+
+// wallet acting in the transaction: `--wallet` when exporting, otherwise the keypair owner
+fn wallet(cli: &Cli) -> Result<Pubkey> {
+  match &cli.wallet {
+    Some(_) if cli.export.is_none() => bail!("--wallet can only be used with --export"),
+    Some(wallet) => Ok(parse_pubkey(wallet)?),
+    None => Ok(load_keypair(&cli.keypair)?.pubkey()),
+  }
+}
+
+fn load_keypair(path: &Option<PathBuf>) -> Result<Keypair> {
+  let path = match path {
+    Some(path) => path.clone(),
+    None => {
+      let home = std::env::var("HOME").context("HOME is not set, pass --keypair")?;
+      PathBuf::from(home).join(".config/solana/id.json")
+    }
+  };
+  Ok(Keypair::read_from_file(path)?)
+}
+
+// an order is either its state address or `<creator>/<order_id>`
+fn fetch_order(rpc: &RpcClient, program_id: &Pubkey, order: &str) -> Result<(Pubkey, EscrowAccount)> {
+  let address = parse_order_address(program_id, order)?;
+  Ok((address, rpc.get_order(&address)?))
+}
+
+// build the transaction paid by `wallet`, then either export it or sign and send it.
+// `co_signers` are keys held by this tool (e.g. claim keys), they sign in both cases.
+fn send(
+  cli: &Cli,
+  rpc: &RpcClient,
+  instructions: &[Instruction],
+  wallet: &Pubkey,
+  co_signers: &[Keypair],
+) -> Result<()> {
+  let mut transaction = match &cli.nonce {
+    Some(nonce_account) => {
+      let nonce_account = parse_pubkey(nonce_account)?;
+      let nonce_authority = match &cli.nonce_authority {
+        Some(nonce_authority) => parse_pubkey(nonce_authority)?,
+        None => *wallet,
+      };
+      let nonce = rpc.get_nonce(&nonce_account)?;
+      if nonce.authority != nonce_authority {
+        bail!("nonce account {} is controlled by {}", nonce_account, nonce.authority);
+      }
+      Transaction::new_with_nonce(instructions, wallet, &nonce_account, &nonce_authority, nonce.blockhash)
+    }
+    None => Transaction::new_with_blockhash(instructions, wallet, rpc.get_latest_blockhash()?),
+  };
+  for co_signer in co_signers {
+    transaction.sign(co_signer)?;
+  }
+  if let Some(encoding) = cli.export {
+    eprintln!("signers: {}", join_keys(transaction.signer_keys()));
+    println!("{}", transaction.encode(encoding));
+    return Ok(());
+  }
+  let signer = load_keypair(&cli.keypair)?;
+  transaction.sign(&signer)?;
+  if !transaction.is_signed() {
+    print_missing_signers(&transaction);
+    bail!("other signers are required, use --export and sign offline");
+  }
+  println!("signature: {}", rpc.send_and_confirm_transaction(&transaction)?);
+  Ok(())
+}
+
+fn print_missing_signers(transaction: &Transaction) {
+  let missing = transaction.missing_signers();
+  if !missing.is_empty() {
+    eprintln!("missing signatures: {}", join_keys(&missing));
+  }
+}
+
+fn join_keys(keys: &[Pubkey]) -> String {
+  keys.iter().map(Pubkey::to_string).collect::<Vec<_>>().join(", ")
+}
+
+fn print_orders(orders: &[(Pubkey, EscrowAccount)]) {
+  println!(
+    "{:44}  {:>20}  {:10}  {:13}  {:>20}  {:>20}",
+    "ORDER", "ID", "TYPE", "STAGE", "TRADE VALUE", "RECEIVE VALUE"
+  );
+  for (address, escrow) in orders {
+    let order = Order::new(address, escrow);
+    println!(
+      "{:44}  {:>20}  {:10}  {:13}  {:>20}  {:>20}",
+      order.address,
+      order.order_id,
+      order.trade_type,
+      order.stage,
+      order.trade_value,
+      order.receive_value
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use clap::CommandFactory;
+
+  #[test]
+  fn arguments_are_consistent() {
+    Cli::command().debug_assert();
+  }
+
+  #[test]
+  fn values_are_split_on_colons() {
+    assert_eq!(parse_level("100:250").unwrap(), (100, 250));
+    assert!(parse_level("100").is_err());
+    assert!(parse_level("100:-1").is_err());
+    let cap = parse_price_cap("3:2").unwrap();
+    assert_eq!((cap.receive_value, cap.trade_value), (3, 2));
+    let mint = Pubkey::new_unique();
+    assert_eq!(parse_payment_option(&format!("{}:5", mint)).unwrap(), (Some(mint), 5));
+    assert_eq!(parse_payment_option("SOL:5").unwrap(), (None, 5));
+    assert!(parse_payment_option("sol:5").is_err());
+  }
+
+  #[test]
+  fn orders_are_taken_by_address_or_creator_and_id() {
+    let creator = Pubkey::new_unique();
+    let cli = Cli::try_parse_from(["trade-p2p", "show", &format!("{}/7", creator)]).unwrap();
+    let Command::Show { order } = &cli.command else {
+      panic!("expected show");
+    };
+    let address = find_state_address(&PROGRAM_ID, &creator, 7).0;
+    assert_eq!(parse_order_address(&PROGRAM_ID, order).unwrap(), address);
+    assert_eq!(parse_order_address(&PROGRAM_ID, &address.to_string()).unwrap(), address);
+    assert!(parse_order_address(&PROGRAM_ID, &format!("{}/seven", creator)).is_err());
+  }
+
+  #[test]
+  fn wallet_without_keypair_only_exports() {
+    let wallet = Pubkey::new_unique().to_string();
+    let cli = Cli::try_parse_from(["trade-p2p", "--wallet", &wallet, "cancel", "order"]).unwrap();
+    assert!(super::wallet(&cli).is_err());
+    let cli = Cli::try_parse_from(["trade-p2p", "--wallet", &wallet, "--export", "base58", "cancel", "order"]).unwrap();
+    assert_eq!(super::wallet(&cli).unwrap().to_string(), wallet);
+    assert!(Cli::try_parse_from(["trade-p2p", "--nonce-authority", &wallet, "cancel", "order"]).is_err());
+  }
+}
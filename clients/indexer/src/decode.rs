@@ -0,0 +1,558 @@
+use anchor_lang::solana_program::{hash::hash, pubkey::Pubkey};
+use anchor_lang::prelude::borsh;
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::str::FromStr;
+use trade_p2p::event::{
+  BidCommitted, BidPlaced, BidReclaimed, BidRevealed, OfferAccepted, OfferMade, OfferWithdrawn, OrderTriggered,
+  OrdersMatched, PaymentOptionUsed, TradeCancelled, TradeCreated, TradeDeclined, TradeExchanged, TradeReplaced, TriggerArmed,
+};
+use trade_p2p::state::{TradeType, TriggerDirection};
+use trade_p2p_sdk::pda::{find_inbox_address, find_user_address};
+
+const PROGRAM_DATA_LOG: &str = "Program data: ";
+
+// order lifecycle change found in a transaction, either from an emitted event
+// or, for transactions recorded before the program emitted events, from the
+// instruction itself
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrderEvent {
+  Created {
+    order: Pubkey,
+    creator: Pubkey,
+    order_id: u64,
+    trade_type: u8,
+    send_mint: Option<Pubkey>,
+    receive_mint: Option<Pubkey>,
+    trade_value: u64,
+    receive_value: u64,
+    specify_partner: Option<Pubkey>,
+    fee_account: Pubkey,
+    timestamp: u64,
+  },
+  Exchanged {
+    order: Pubkey,
+    creator: Pubkey,
+    order_id: u64,
+    partner: Pubkey,
+    // only known when decoded from an event
+    trade_value: Option<u64>,
+    receive_value: Option<u64>,
+  },
+  Cancelled {
+    order: Pubkey,
+    creator: Pubkey,
+    order_id: u64,
+    refund_value: Option<u64>,
+  },
+  Declined {
+    order: Pubkey,
+    creator: Pubkey,
+    order_id: u64,
+    partner: Pubkey,
+    refund_value: Option<u64>,
+  },
+  // link from an order to the one `replace_trade` created in its place, the
+  // close and the creation themselves come as `Cancelled` and `Created`
+  Replaced {
+    order: Pubkey,
+    creator: Pubkey,
+    order_id: u64,
+    new_order: Pubkey,
+    new_order_id: u64,
+    moved_value: u64,
+  },
+  // fees a matcher kept for settling two orders with `match_orders`, each order
+  // comes as `Exchanged` with the other creator as partner
+  Matched {
+    order_a: Pubkey,
+    order_b: Pubkey,
+    matcher: Pubkey,
+    fee_a: u64,
+    fee_b: u64,
+  },
+  // bid on an english auction order, `outbid` being the bid it refunded. The
+  // settlement comes as `Exchanged` to the winner or `Cancelled`
+  BidPlaced {
+    order: Pubkey,
+    bid: Pubkey,
+    bidder: Pubkey,
+    bid_index: u32,
+    value: u64,
+    outbid: Option<Pubkey>,
+  },
+  // sealed bid committed on a sealed-bid auction order, its value still hidden
+  BidCommitted {
+    order: Pubkey,
+    bid: Pubkey,
+    bidder: Pubkey,
+    bid_index: u32,
+    deposit: u64,
+  },
+  // sealed bid revealed, either the new `best` bid refunding `outbid`, or refunded
+  BidRevealed {
+    order: Pubkey,
+    bid: Pubkey,
+    bidder: Pubkey,
+    value: u64,
+    best: bool,
+    outbid: Option<Pubkey>,
+  },
+  // sealed bid never revealed, its deposit `forfeited` to the creator or refunded
+  BidReclaimed {
+    order: Pubkey,
+    bid: Pubkey,
+    bidder: Pubkey,
+    deposit: u64,
+    forfeited: bool,
+  },
+  // offer of a partner on an order, `mint` being `None` for SOL
+  OfferMade {
+    order: Pubkey,
+    offer: Pubkey,
+    partner: Pubkey,
+    mint: Option<Pubkey>,
+    value: u64,
+  },
+  // offer the creator swapped the order with, which closes it as exchanged
+  OfferAccepted {
+    order: Pubkey,
+    offer: Pubkey,
+    creator: Pubkey,
+    order_id: u64,
+    partner: Pubkey,
+    mint: Option<Pubkey>,
+    value: u64,
+  },
+  // offer taken back by its partner
+  OfferWithdrawn {
+    order: Pubkey,
+    offer: Pubkey,
+    partner: Pubkey,
+    value: u64,
+  },
+  // order filled in one of its payment options, `mint` being `None` for SOL
+  PaidWithOption {
+    order: Pubkey,
+    mint: Option<Pubkey>,
+    receive_value: u64,
+  },
+  // conditional order created waiting for the price of `oracle` to reach `trigger_price`
+  TriggerArmed {
+    order: Pubkey,
+    oracle: Pubkey,
+    trigger_price: i64,
+    direction: TriggerDirection,
+  },
+  // conditional order made ready for exchange at oracle price `price`
+  Triggered {
+    order: Pubkey,
+    creator: Pubkey,
+    order_id: u64,
+    price: i64,
+    publish_time: i64,
+  },
+}
+
+#[derive(Clone, Debug)]
+pub struct TransactionRecord {
+  pub signature: String,
+  pub slot: u64,
+  pub block_time: Option<i64>,
+  pub fee_payer: Pubkey,
+  pub fee: u64,
+  pub events: Vec<OrderEvent>,
+}
+
+// decode a `getTransaction` result (json encoding). Returns `None` for failed
+// transactions and transactions that do not touch the program.
+pub fn decode_transaction(program_id: &Pubkey, value: &Value) -> Result<Option<TransactionRecord>> {
+  let meta = &value["meta"];
+  if !meta.is_null() && !meta["err"].is_null() {
+    return Ok(None);
+  }
+  let signature = value["transaction"]["signatures"][0]
+    .as_str()
+    .ok_or_else(|| anyhow!("transaction without signature"))?
+    .to_string();
+  let account_keys = account_keys(value)?;
+  let logs = meta["logMessages"]
+    .as_array()
+    .map(|logs| logs.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+    .unwrap_or_default();
+
+  let mut events = decode_events(program_id, &logs);
+  if events.is_empty() {
+    events = decode_instructions(program_id, value, &account_keys)?;
+  }
+  if events.is_empty() {
+    return Ok(None);
+  }
+  Ok(Some(TransactionRecord {
+    signature,
+    slot: value["slot"].as_u64().unwrap_or_default(),
+    block_time: value["blockTime"].as_i64(),
+    fee_payer: account_keys[0],
+    fee: meta["fee"].as_u64().unwrap_or_default(),
+    events,
+  }))
+}
+
+// static account keys followed by the keys loaded from address lookup tables
+fn account_keys(value: &Value) -> Result<Vec<Pubkey>> {
+  let loaded = &value["meta"]["loadedAddresses"];
+  let keys = value["transaction"]["message"]["accountKeys"]
+    .as_array()
+    .into_iter()
+    .chain(loaded["writable"].as_array())
+    .chain(loaded["readonly"].as_array())
+    .flatten()
+    .map(|key| {
+      key
+        .as_str()
+        .and_then(|key| Pubkey::from_str(key).ok())
+        .ok_or_else(|| anyhow!("invalid account key {}", key))
+    })
+    .collect::<Result<Vec<_>>>()?;
+  if keys.is_empty() {
+    return Err(anyhow!("transaction without account keys"));
+  }
+  Ok(keys)
+}
+
+// anchor events are logged as base64 `Program data:` lines, only those logged
+// while the program itself is executing are taken
+fn decode_events(program_id: &Pubkey, logs: &[&str]) -> Vec<OrderEvent> {
+  let program = program_id.to_string();
+  let mut invocations: Vec<&str> = Vec::new();
+  let mut events = Vec::new();
+  for log in logs {
+    let words = log.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+      ["Program", id, "invoke", ..] => invocations.push(id),
+      ["Program", _, "success"] | ["Program", _, "failed:", ..] => {
+        invocations.pop();
+      }
+      _ => {
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA_LOG) {
+          if invocations.last() == Some(&program.as_str()) {
+            if let Some(event) = base64::decode(data).ok().and_then(|data| decode_event(&data)) {
+              events.push(event);
+            }
+          }
+        }
+      }
+    }
+  }
+  events
+}
+
+fn decode_event(data: &[u8]) -> Option<OrderEvent> {
+  if data.len() < 8 {
+    return None;
+  }
+  let (discriminator, mut body) = data.split_at(8);
+  if discriminator == TradeCreated::discriminator() {
+    let event = TradeCreated::deserialize(&mut body).ok()?;
+    Some(OrderEvent::Created {
+      order: event.escrow_state,
+      creator: event.creator,
+      order_id: event.order_id,
+      trade_type: event.trade_type,
+      send_mint: event.creator_send_token_mint,
+      receive_mint: event.creator_receive_token_mint,
+      trade_value: event.trade_value,
+      receive_value: event.receive_value,
+      specify_partner: event.specify_partner,
+      fee_account: event.fee_account,
+      timestamp: event.timestamp,
+    })
+  } else if discriminator == TradeExchanged::discriminator() {
+    let event = TradeExchanged::deserialize(&mut body).ok()?;
+    Some(OrderEvent::Exchanged {
+      order: event.escrow_state,
+      creator: event.creator,
+      order_id: event.order_id,
+      partner: event.partner,
+      trade_value: Some(event.trade_value),
+      receive_value: Some(event.receive_value),
+    })
+  } else if discriminator == TradeCancelled::discriminator() {
+    let event = TradeCancelled::deserialize(&mut body).ok()?;
+    Some(OrderEvent::Cancelled {
+      order: event.escrow_state,
+      creator: event.creator,
+      order_id: event.order_id,
+      refund_value: Some(event.refund_value),
+    })
+  } else if discriminator == TradeDeclined::discriminator() {
+    let event = TradeDeclined::deserialize(&mut body).ok()?;
+    Some(OrderEvent::Declined {
+      order: event.escrow_state,
+      creator: event.creator,
+      order_id: event.order_id,
+      partner: event.partner,
+      refund_value: Some(event.refund_value),
+    })
+  } else if discriminator == TradeReplaced::discriminator() {
+    let event = TradeReplaced::deserialize(&mut body).ok()?;
+    Some(OrderEvent::Replaced {
+      order: event.escrow_state,
+      creator: event.creator,
+      order_id: event.order_id,
+      new_order: event.new_escrow_state,
+      new_order_id: event.new_order_id,
+      moved_value: event.moved_value,
+    })
+  } else if discriminator == OrdersMatched::discriminator() {
+    let event = OrdersMatched::deserialize(&mut body).ok()?;
+    Some(OrderEvent::Matched {
+      order_a: event.escrow_state_a,
+      order_b: event.escrow_state_b,
+      matcher: event.matcher,
+      fee_a: event.fee_a,
+      fee_b: event.fee_b,
+    })
+  } else if discriminator == BidPlaced::discriminator() {
+    let event = BidPlaced::deserialize(&mut body).ok()?;
+    Some(OrderEvent::BidPlaced {
+      order: event.escrow_state,
+      bid: event.bid,
+      bidder: event.bidder,
+      bid_index: event.bid_index,
+      value: event.value,
+      outbid: event.outbid,
+    })
+  } else if discriminator == BidCommitted::discriminator() {
+    let event = BidCommitted::deserialize(&mut body).ok()?;
+    Some(OrderEvent::BidCommitted {
+      order: event.escrow_state,
+      bid: event.bid,
+      bidder: event.bidder,
+      bid_index: event.bid_index,
+      deposit: event.deposit,
+    })
+  } else if discriminator == BidRevealed::discriminator() {
+    let event = BidRevealed::deserialize(&mut body).ok()?;
+    Some(OrderEvent::BidRevealed {
+      order: event.escrow_state,
+      bid: event.bid,
+      bidder: event.bidder,
+      value: event.value,
+      best: event.best,
+      outbid: event.outbid,
+    })
+  } else if discriminator == BidReclaimed::discriminator() {
+    let event = BidReclaimed::deserialize(&mut body).ok()?;
+    Some(OrderEvent::BidReclaimed {
+      order: event.escrow_state,
+      bid: event.bid,
+      bidder: event.bidder,
+      deposit: event.deposit,
+      forfeited: event.forfeited,
+    })
+  } else if discriminator == OfferMade::discriminator() {
+    let event = OfferMade::deserialize(&mut body).ok()?;
+    Some(OrderEvent::OfferMade {
+      order: event.escrow_state,
+      offer: event.offer,
+      partner: event.partner,
+      mint: event.mint,
+      value: event.value,
+    })
+  } else if discriminator == OfferAccepted::discriminator() {
+    let event = OfferAccepted::deserialize(&mut body).ok()?;
+    Some(OrderEvent::OfferAccepted {
+      order: event.escrow_state,
+      offer: event.offer,
+      creator: event.creator,
+      order_id: event.order_id,
+      partner: event.partner,
+      mint: event.mint,
+      value: event.value,
+    })
+  } else if discriminator == OfferWithdrawn::discriminator() {
+    let event = OfferWithdrawn::deserialize(&mut body).ok()?;
+    Some(OrderEvent::OfferWithdrawn {
+      order: event.escrow_state,
+      offer: event.offer,
+      partner: event.partner,
+      value: event.value,
+    })
+  } else if discriminator == PaymentOptionUsed::discriminator() {
+    let event = PaymentOptionUsed::deserialize(&mut body).ok()?;
+    Some(OrderEvent::PaidWithOption {
+      order: event.escrow_state,
+      mint: event.mint,
+      receive_value: event.receive_value,
+    })
+  } else if discriminator == TriggerArmed::discriminator() {
+    let event = TriggerArmed::deserialize(&mut body).ok()?;
+    Some(OrderEvent::TriggerArmed {
+      order: event.escrow_state,
+      oracle: event.oracle,
+      trigger_price: event.trigger_price,
+      direction: event.direction,
+    })
+  } else if discriminator == OrderTriggered::discriminator() {
+    let event = OrderTriggered::deserialize(&mut body).ok()?;
+    Some(OrderEvent::Triggered {
+      order: event.escrow_state,
+      creator: event.creator,
+      order_id: event.order_id,
+      price: event.price,
+      publish_time: event.publish_time,
+    })
+  } else {
+    None
+  }
+}
+
+// top level and inner (cpi) instructions addressed to the program
+fn decode_instructions(program_id: &Pubkey, value: &Value, keys: &[Pubkey]) -> Result<Vec<OrderEvent>> {
+  let inner = value["meta"]["innerInstructions"]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter_map(|inner| inner["instructions"].as_array())
+    .flatten();
+  let mut events = Vec::new();
+  for instruction in value["transaction"]["message"]["instructions"]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .chain(inner)
+  {
+    let program_index = instruction["programIdIndex"].as_u64().unwrap_or(u64::MAX) as usize;
+    if keys.get(program_index) != Some(program_id) {
+      continue;
+    }
+    let accounts = instruction["accounts"]
+      .as_array()
+      .into_iter()
+      .flatten()
+      .map(|index| {
+        index
+          .as_u64()
+          .and_then(|index| keys.get(index as usize).copied())
+          .ok_or_else(|| anyhow!("invalid account index {}", index))
+      })
+      .collect::<Result<Vec<_>>>()?;
+    let data = instruction["data"]
+      .as_str()
+      .and_then(|data| bs58::decode(data).into_vec().ok())
+      .ok_or_else(|| anyhow!("invalid instruction data"))?;
+    if let Some(event) = decode_instruction(program_id, &data, &accounts) {
+      events.push(event);
+    }
+  }
+  Ok(events)
+}
+
+// anchor sighash of a global instruction: first 8 bytes of sha256("global:<name>")
+pub fn instruction_discriminator(name: &str) -> [u8; 8] {
+  let mut discriminator = [0u8; 8];
+  discriminator.copy_from_slice(&hash(format!("global:{}", name).as_bytes()).to_bytes()[..8]);
+  discriminator
+}
+
+// leading fields of `CreateParams`. Fields appended by later program versions
+// are not read, so instructions of every version decode.
+#[derive(AnchorDeserialize)]
+struct CreateParamsPrefix {
+  order_id: u64,
+  specify_partner: Option<Pubkey>,
+  trade_value: u64,
+  receive_value: u64,
+  timestamp: u64,
+}
+
+fn decode_instruction(program_id: &Pubkey, data: &[u8], accounts: &[Pubkey]) -> Option<OrderEvent> {
+  use trade_p2p::instruction::{Cancel, Decline};
+  if data.len() < 8 {
+    return None;
+  }
+  let (discriminator, mut body) = data.split_at(8);
+  if discriminator == instruction_discriminator("create_trade") {
+    // escrow_state, escrow_vault, creator, creator_send_account,
+    // creator_receive_account, fee_account, system, token, rent, [user_account],
+    // mints..., then the partner inbox for orders with a specify partner and
+    // the market page for listed orders
+    let params = CreateParamsPrefix::deserialize(&mut body).ok()?;
+    let (creator, send_account, mut mints) = (*accounts.get(2)?, *accounts.get(3)?, accounts.get(9..)?);
+    if mints.first() == Some(&find_user_address(program_id, &creator).0) {
+      mints = &mints[1..];
+    }
+    // vault_bump, partner_root, claim_hash, market_page: only decodes for
+    // versions that list orders on a market
+    let market_page = <(u8, Option<[u8; 32]>, Option<[u8; 32]>, Option<u16>)>::deserialize(&mut body)
+      .ok()
+      .and_then(|(_, _, _, market_page)| market_page);
+    if market_page.is_some() {
+      mints = mints.split_last()?.1;
+    }
+    if let Some(partner) = &params.specify_partner {
+      let (inbox, _) = find_inbox_address(program_id, partner);
+      if let Some((last, rest)) = mints.split_last() {
+        if *last == inbox {
+          mints = rest;
+        }
+      }
+    }
+    // SOL sides use the creator wallet itself instead of a token account
+    let (trade_type, send_mint, receive_mint) = match mints {
+      [send_mint, receive_mint, ..] => (TradeType::TokenToken, Some(*send_mint), Some(*receive_mint)),
+      [receive_mint] if send_account == creator => (TradeType::SolToken, None, Some(*receive_mint)),
+      [send_mint] => (TradeType::TokenSol, Some(*send_mint), None),
+      [] => return None,
+    };
+    Some(OrderEvent::Created {
+      order: *accounts.first()?,
+      creator,
+      order_id: params.order_id,
+      trade_type: trade_type.to_code(),
+      send_mint,
+      receive_mint,
+      trade_value: params.trade_value,
+      receive_value: params.receive_value,
+      specify_partner: params.specify_partner,
+      fee_account: *accounts.get(5)?,
+      timestamp: params.timestamp,
+    })
+  } else if discriminator == instruction_discriminator("exchange") {
+    // escrow_state, escrow_vault, creator_receive_account, partner_send_account,
+    // partner_receive_account, creator, partner, ...
+    // only the order id, the allowlist proof that follows is not needed
+    let order_id = u64::deserialize(&mut body).ok()?;
+    Some(OrderEvent::Exchanged {
+      order: *accounts.first()?,
+      creator: *accounts.get(5)?,
+      order_id,
+      partner: *accounts.get(6)?,
+      trade_value: None,
+      receive_value: None,
+    })
+  } else if discriminator == instruction_discriminator("cancel") {
+    // escrow_state, escrow_vault, creator_send_account, creator, ...
+    let args = Cancel::deserialize(&mut body).ok()?;
+    Some(OrderEvent::Cancelled {
+      order: *accounts.first()?,
+      creator: *accounts.get(3)?,
+      order_id: args._order_id,
+      refund_value: None,
+    })
+  } else if discriminator == instruction_discriminator("decline") {
+    // escrow_state, escrow_vault, creator_send_account, creator, partner, ...
+    let args = Decline::deserialize(&mut body).ok()?;
+    Some(OrderEvent::Declined {
+      order: *accounts.first()?,
+      creator: *accounts.get(3)?,
+      order_id: args._order_id,
+      partner: *accounts.get(4)?,
+      refund_value: None,
+    })
+  } else {
+    None
+  }
+}
@@ -0,0 +1,10 @@
+// Order history for the trade-p2p program: decodes program transactions into
+// order events and keeps them in a SQLite store that can be queried.
+pub mod decode;
+pub mod query;
+pub mod source;
+pub mod store;
+
+pub use decode::{decode_transaction, OrderEvent, TransactionRecord};
+pub use query::{OrderFilter, OrderRow, PairStats};
+pub use store::Store;
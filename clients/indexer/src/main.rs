@@ -0,0 +1,101 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
+use trade_p2p_indexer::{decode_transaction, source, Store};
+use trade_p2p_sdk::{parse_pubkey, rpc::RpcClient, Pubkey, PROGRAM_ID};
+
+// newest signature indexed from rpc, the next poll only fetches newer ones
+const LAST_SIGNATURE: &str = "last_signature";
+
+#[derive(Parser)]
+#[command(name = "trade-p2p-indexer", about = "Index trade-p2p order history into SQLite")]
+struct Cli {
+  /// sqlite database file
+  #[arg(long, default_value = "trade-p2p.sqlite")]
+  db: PathBuf,
+  #[arg(long)]
+  program_id: Option<String>,
+  #[command(subcommand)]
+  command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// fetch program transactions from an rpc node
+  Rpc {
+    /// rpc url or cluster moniker: mainnet-beta, devnet, testnet, localhost
+    #[arg(long, short = 'u', default_value = "devnet")]
+    url: String,
+    /// keep polling for new transactions
+    #[arg(long)]
+    follow: bool,
+    /// seconds between polls with --follow
+    #[arg(long, default_value_t = 10)]
+    interval: u64,
+  },
+  /// index recorded `getTransaction` json files from a directory
+  Replay { dir: PathBuf },
+}
+
+fn main() {
+  if let Err(err) = run(Cli::parse()) {
+    eprintln!("error: {:#}", err);
+    std::process::exit(1);
+  }
+}
+
+fn run(cli: Cli) -> Result<()> {
+  let program_id = match &cli.program_id {
+    Some(program_id) => parse_pubkey(program_id)?,
+    None => PROGRAM_ID,
+  };
+  let mut store = Store::open(&cli.db)?;
+  match cli.command {
+    Command::Rpc {
+      url,
+      follow,
+      interval,
+    } => {
+      let rpc = RpcClient::new(&url);
+      loop {
+        sync_rpc(&rpc, &program_id, &mut store)?;
+        if !follow {
+          break;
+        }
+        std::thread::sleep(Duration::from_secs(interval));
+      }
+    }
+    Command::Replay { dir } => {
+      let (mut indexed, mut skipped) = (0, 0);
+      for transaction in source::read_directory(&dir)? {
+        match decode_transaction(&program_id, &transaction)? {
+          Some(record) if store.apply(&record)? => indexed += 1,
+          _ => skipped += 1,
+        }
+      }
+      println!("indexed {} transactions, skipped {}", indexed, skipped);
+    }
+  }
+  Ok(())
+}
+
+fn sync_rpc(rpc: &RpcClient, program_id: &Pubkey, store: &mut Store) -> Result<()> {
+  let until = store.sync_state(LAST_SIGNATURE)?;
+  let signatures = source::fetch_signatures(rpc, program_id, until.as_deref())?;
+  let mut indexed = 0;
+  for signature in &signatures {
+    let transaction = source::fetch_transaction(rpc, signature)?;
+    if let Some(record) = decode_transaction(program_id, &transaction)? {
+      if store.apply(&record)? {
+        indexed += 1;
+      }
+    }
+  }
+  // only move the cursor once the whole batch is stored, a crash replays it
+  if let Some(newest) = signatures.last() {
+    store.set_sync_state(LAST_SIGNATURE, newest)?;
+  }
+  println!("fetched {} transactions, indexed {}", signatures.len(), indexed);
+  Ok(())
+}
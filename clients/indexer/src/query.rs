@@ -0,0 +1,255 @@
+use crate::store::{from_sql_u64, to_sql_u64};
+
+use anyhow::Result;
+use rusqlite::{types::Value as SqlValue, Connection, OptionalExtension, Row};
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use trade_p2p::state::Stage;
+use trade_p2p_sdk::order::{stage_name, trade_type_name, NATIVE_MINT_LABEL};
+
+const ORDER_COLUMNS: &str = "address, creator, order_id, trade_type, send_mint, receive_mint,
+  trade_value, receive_value, specify_partner, partner, fee_account, timestamp, stage,
+  created_signature, created_slot, closed_signature, closed_slot";
+
+pub const DEFAULT_LIMIT: u32 = 100;
+pub const MAX_LIMIT: u32 = 1000;
+
+// u64 values are sent as strings, javascript numbers lose precision above 2^53
+fn as_string<S: Serializer>(value: &u64, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+  serializer.collect_str(value)
+}
+
+fn as_string_u128<S: Serializer>(value: &u128, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+  serializer.collect_str(value)
+}
+
+fn as_optional_string<S: Serializer>(
+  value: &Option<u64>,
+  serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+  match value {
+    Some(value) => serializer.collect_str(value),
+    None => serializer.serialize_none(),
+  }
+}
+
+// an indexed order. Fields only known from the creation are `None` while the
+// order has been seen closing but not yet created.
+#[derive(Clone, Debug, Serialize)]
+pub struct OrderRow {
+  pub address: String,
+  pub creator: String,
+  #[serde(serialize_with = "as_string")]
+  pub order_id: u64,
+  pub trade_type: Option<String>,
+  pub stage: String,
+  pub send_mint: Option<String>,
+  pub receive_mint: Option<String>,
+  #[serde(serialize_with = "as_optional_string")]
+  pub trade_value: Option<u64>,
+  #[serde(serialize_with = "as_optional_string")]
+  pub receive_value: Option<u64>,
+  pub specify_partner: Option<String>,
+  pub partner: Option<String>,
+  pub fee_account: Option<String>,
+  #[serde(serialize_with = "as_optional_string")]
+  pub timestamp: Option<u64>,
+  pub created_signature: Option<String>,
+  pub created_slot: Option<u64>,
+  pub closed_signature: Option<String>,
+  pub closed_slot: Option<u64>,
+}
+
+impl OrderRow {
+  fn from_row(row: &Row) -> rusqlite::Result<OrderRow> {
+    let trade_type: Option<u8> = row.get("trade_type")?;
+    // a missing mint is the SOL side, but only once the creation is known
+    let mint = |column: &str| -> rusqlite::Result<Option<String>> {
+      let mint: Option<String> = row.get(column)?;
+      Ok(match trade_type {
+        Some(_) => Some(mint.unwrap_or_else(|| NATIVE_MINT_LABEL.to_string())),
+        None => mint,
+      })
+    };
+    let u64_column = |column: &str| -> rusqlite::Result<Option<u64>> {
+      Ok(row.get::<_, Option<i64>>(column)?.map(from_sql_u64))
+    };
+    Ok(OrderRow {
+      address: row.get("address")?,
+      creator: row.get("creator")?,
+      order_id: from_sql_u64(row.get("order_id")?),
+      trade_type: trade_type.map(trade_type_name),
+      stage: stage_name(row.get("stage")?),
+      send_mint: mint("send_mint")?,
+      receive_mint: mint("receive_mint")?,
+      trade_value: u64_column("trade_value")?,
+      receive_value: u64_column("receive_value")?,
+      specify_partner: row.get("specify_partner")?,
+      partner: row.get("partner")?,
+      fee_account: row.get("fee_account")?,
+      timestamp: u64_column("timestamp")?,
+      created_signature: row.get("created_signature")?,
+      created_slot: u64_column("created_slot")?,
+      closed_signature: row.get("closed_signature")?,
+      closed_slot: u64_column("closed_slot")?,
+    })
+  }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OrderFilter {
+  // either side of the order, `SOL` matches the native side
+  pub mint: Option<String>,
+  pub creator: Option<String>,
+  // the partner the order is reserved for or the one that filled it
+  pub partner: Option<String>,
+  pub stage: Option<u8>,
+  pub limit: Option<u32>,
+  pub offset: Option<u32>,
+}
+
+// orders matching the filter, newest first
+pub fn find_orders(conn: &Connection, filter: &OrderFilter) -> Result<Vec<OrderRow>> {
+  let mut conditions = Vec::new();
+  let mut params: Vec<SqlValue> = Vec::new();
+  if let Some(mint) = &filter.mint {
+    if mint == NATIVE_MINT_LABEL {
+      conditions.push("trade_type IS NOT NULL AND (send_mint IS NULL OR receive_mint IS NULL)".to_string());
+    } else {
+      params.push(SqlValue::Text(mint.clone()));
+      conditions.push(format!("(send_mint = ?{0} OR receive_mint = ?{0})", params.len()));
+    }
+  }
+  if let Some(creator) = &filter.creator {
+    params.push(SqlValue::Text(creator.clone()));
+    conditions.push(format!("creator = ?{}", params.len()));
+  }
+  if let Some(partner) = &filter.partner {
+    params.push(SqlValue::Text(partner.clone()));
+    conditions.push(format!("(specify_partner = ?{0} OR partner = ?{0})", params.len()));
+  }
+  if let Some(stage) = filter.stage {
+    params.push(SqlValue::Integer(stage.into()));
+    conditions.push(format!("stage = ?{}", params.len()));
+  }
+  let mut sql = format!("SELECT {} FROM orders", ORDER_COLUMNS);
+  if !conditions.is_empty() {
+    sql.push_str(" WHERE ");
+    sql.push_str(&conditions.join(" AND "));
+  }
+  params.push(SqlValue::Integer(
+    filter.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT).into(),
+  ));
+  params.push(SqlValue::Integer(filter.offset.unwrap_or_default().into()));
+  sql.push_str(&format!(
+    " ORDER BY COALESCE(created_slot, closed_slot) DESC, address LIMIT ?{} OFFSET ?{}",
+    params.len() - 1,
+    params.len()
+  ));
+  let mut statement = conn.prepare(&sql)?;
+  let rows = statement
+    .query_map(rusqlite::params_from_iter(params), OrderRow::from_row)?
+    .collect::<rusqlite::Result<Vec<_>>>()?;
+  Ok(rows)
+}
+
+pub fn find_order(conn: &Connection, creator: &str, order_id: u64) -> Result<Option<OrderRow>> {
+  Ok(
+    conn
+      .query_row(
+        &format!("SELECT {} FROM orders WHERE creator = ?1 AND order_id = ?2", ORDER_COLUMNS),
+        rusqlite::params![creator, to_sql_u64(order_id)],
+        OrderRow::from_row,
+      )
+      .optional()?,
+  )
+}
+
+// activity of every (send mint, receive mint) pair. Prices are receive amount
+// per unit of trade amount, in raw token units.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PairStats {
+  pub send_mint: String,
+  pub receive_mint: String,
+  pub open_orders: u64,
+  pub fills: u64,
+  pub cancellations: u64,
+  pub declines: u64,
+  #[serde(serialize_with = "as_string_u128")]
+  pub volume_send: u128,
+  #[serde(serialize_with = "as_string_u128")]
+  pub volume_receive: u128,
+  pub best_open_price: Option<f64>,
+  pub last_fill_price: Option<f64>,
+  #[serde(skip)]
+  last_fill_slot: Option<u64>,
+}
+
+fn price(trade_value: u64, receive_value: u64) -> Option<f64> {
+  (trade_value > 0).then(|| receive_value as f64 / trade_value as f64)
+}
+
+pub fn pair_stats(conn: &Connection) -> Result<Vec<PairStats>> {
+  let mut pairs: BTreeMap<(String, String), PairStats> = BTreeMap::new();
+  let label = |mint: Option<String>| mint.unwrap_or_else(|| NATIVE_MINT_LABEL.to_string());
+
+  // orders closed before their creation was indexed have no pair yet
+  let mut statement = conn.prepare(
+    "SELECT send_mint, receive_mint, stage, trade_value, receive_value FROM orders
+     WHERE trade_type IS NOT NULL",
+  )?;
+  let mut rows = statement.query([])?;
+  while let Some(row) = rows.next()? {
+    let (send_mint, receive_mint) = (label(row.get(0)?), label(row.get(1)?));
+    let stats = pairs
+      .entry((send_mint.clone(), receive_mint.clone()))
+      .or_insert_with(|| PairStats {
+        send_mint,
+        receive_mint,
+        ..PairStats::default()
+      });
+    let stage: u8 = row.get(2)?;
+    if stage == Stage::ReadyExchange.to_code() {
+      stats.open_orders += 1;
+      let open_price = price(from_sql_u64(row.get(3)?), from_sql_u64(row.get(4)?));
+      if let Some(open_price) = open_price {
+        if stats.best_open_price.is_none_or(|best| open_price < best) {
+          stats.best_open_price = Some(open_price);
+        }
+      }
+    } else if stage == Stage::CancelTrade.to_code() {
+      stats.cancellations += 1;
+    } else if stage == Stage::Declined.to_code() {
+      stats.declines += 1;
+    }
+  }
+
+  // fills paid in a payment option are priced in another mint than the pair's
+  let mut statement = conn.prepare(
+    "SELECT orders.send_mint, orders.receive_mint, fills.trade_value, fills.receive_value,
+       fills.slot
+     FROM fills JOIN orders ON orders.address = fills.order_address
+     WHERE orders.trade_type IS NOT NULL
+       AND NOT EXISTS (SELECT 1 FROM option_payments
+         WHERE option_payments.signature = fills.signature
+           AND option_payments.order_address = fills.order_address)",
+  )?;
+  let mut rows = statement.query([])?;
+  while let Some(row) = rows.next()? {
+    let pair = (label(row.get(0)?), label(row.get(1)?));
+    let Some(stats) = pairs.get_mut(&pair) else {
+      continue;
+    };
+    let trade_value = from_sql_u64(row.get::<_, Option<i64>>(2)?.unwrap_or_default());
+    let receive_value = from_sql_u64(row.get::<_, Option<i64>>(3)?.unwrap_or_default());
+    let slot = from_sql_u64(row.get(4)?);
+    stats.fills += 1;
+    stats.volume_send += u128::from(trade_value);
+    stats.volume_receive += u128::from(receive_value);
+    if stats.last_fill_slot.is_none_or(|last| slot >= last) {
+      stats.last_fill_slot = Some(slot);
+      stats.last_fill_price = price(trade_value, receive_value);
+    }
+  }
+  Ok(pairs.into_values().collect())
+}
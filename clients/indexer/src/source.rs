@@ -0,0 +1,79 @@
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::path::Path;
+use trade_p2p_sdk::rpc::RpcClient;
+
+const SIGNATURE_PAGE_LIMIT: usize = 1000;
+
+// recorded `getTransaction` results, one per file or an array per file. A
+// complete json-rpc response is accepted as well and its `result` is used.
+pub fn read_directory(dir: &Path) -> Result<Vec<Value>> {
+  let mut paths = std::fs::read_dir(dir)
+    .with_context(|| format!("reading {}", dir.display()))?
+    .map(|entry| entry.map(|entry| entry.path()))
+    .collect::<std::io::Result<Vec<_>>>()?;
+  paths.retain(|path| path.extension().is_some_and(|extension| extension == "json"));
+  paths.sort();
+
+  let mut transactions = Vec::new();
+  for path in paths {
+    let content = std::fs::read_to_string(&path)?;
+    let value: Value =
+      serde_json::from_str(&content).with_context(|| format!("parsing {}", path.display()))?;
+    let value = match value.get("result") {
+      Some(result) => result.clone(),
+      None => value,
+    };
+    match value {
+      Value::Array(items) => transactions.extend(items),
+      Value::Null => {}
+      value => transactions.push(value),
+    }
+  }
+  Ok(transactions)
+}
+
+// signatures of the program newer than `until`, oldest first
+pub fn fetch_signatures(rpc: &RpcClient, program_id: &Pubkey, until: Option<&str>) -> Result<Vec<String>> {
+  let mut signatures = Vec::new();
+  let mut before: Option<String> = None;
+  loop {
+    let mut config = json!({ "limit": SIGNATURE_PAGE_LIMIT, "commitment": "confirmed" });
+    if let Some(before) = &before {
+      config["before"] = json!(before);
+    }
+    if let Some(until) = until {
+      config["until"] = json!(until);
+    }
+    let page: Vec<Value> = rpc.send(
+      "getSignaturesForAddress",
+      json!([program_id.to_string(), config]),
+    )?;
+    let count = page.len();
+    for item in page {
+      let signature = item["signature"]
+        .as_str()
+        .ok_or_else(|| anyhow!("unexpected signature entry {}", item))?;
+      signatures.push(signature.to_string());
+    }
+    if count < SIGNATURE_PAGE_LIMIT {
+      break;
+    }
+    before = signatures.last().cloned();
+  }
+  signatures.reverse();
+  Ok(signatures)
+}
+
+pub fn fetch_transaction(rpc: &RpcClient, signature: &str) -> Result<Value> {
+  rpc
+    .send(
+      "getTransaction",
+      json!([
+        signature,
+        { "encoding": "json", "commitment": "confirmed", "maxSupportedTransactionVersion": 0 }
+      ]),
+    )
+    .map_err(Into::into)
+}
@@ -0,0 +1,900 @@
+use crate::decode::{OrderEvent, TransactionRecord};
+
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::path::Path;
+use trade_p2p::state::Stage;
+use trade_p2p_sdk::order::trigger_direction_name;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+  signature TEXT PRIMARY KEY,
+  slot INTEGER NOT NULL,
+  block_time INTEGER
+);
+CREATE TABLE IF NOT EXISTS orders (
+  address TEXT PRIMARY KEY,
+  creator TEXT NOT NULL,
+  order_id INTEGER NOT NULL,
+  trade_type INTEGER,
+  send_mint TEXT,
+  receive_mint TEXT,
+  trade_value INTEGER,
+  receive_value INTEGER,
+  specify_partner TEXT,
+  partner TEXT,
+  fee_account TEXT,
+  timestamp INTEGER,
+  stage INTEGER NOT NULL,
+  created_signature TEXT,
+  created_slot INTEGER,
+  closed_signature TEXT,
+  closed_slot INTEGER
+);
+CREATE INDEX IF NOT EXISTS orders_creator ON orders (creator);
+CREATE INDEX IF NOT EXISTS orders_stage ON orders (stage);
+CREATE TABLE IF NOT EXISTS fills (
+  signature TEXT NOT NULL,
+  order_address TEXT NOT NULL,
+  partner TEXT NOT NULL,
+  trade_value INTEGER,
+  receive_value INTEGER,
+  slot INTEGER NOT NULL,
+  block_time INTEGER,
+  PRIMARY KEY (signature, order_address)
+);
+CREATE TABLE IF NOT EXISTS cancellations (
+  signature TEXT NOT NULL,
+  order_address TEXT NOT NULL,
+  creator TEXT NOT NULL,
+  refund_value INTEGER,
+  slot INTEGER NOT NULL,
+  block_time INTEGER,
+  PRIMARY KEY (signature, order_address)
+);
+CREATE TABLE IF NOT EXISTS declines (
+  signature TEXT NOT NULL,
+  order_address TEXT NOT NULL,
+  partner TEXT NOT NULL,
+  refund_value INTEGER,
+  slot INTEGER NOT NULL,
+  block_time INTEGER,
+  PRIMARY KEY (signature, order_address)
+);
+CREATE TABLE IF NOT EXISTS replacements (
+  signature TEXT NOT NULL,
+  order_address TEXT NOT NULL,
+  new_order_address TEXT NOT NULL,
+  creator TEXT NOT NULL,
+  moved_value INTEGER NOT NULL,
+  slot INTEGER NOT NULL,
+  block_time INTEGER,
+  PRIMARY KEY (signature, order_address)
+);
+CREATE TABLE IF NOT EXISTS matches (
+  signature TEXT NOT NULL,
+  order_a_address TEXT NOT NULL,
+  order_b_address TEXT NOT NULL,
+  matcher TEXT NOT NULL,
+  fee_a INTEGER NOT NULL,
+  fee_b INTEGER NOT NULL,
+  slot INTEGER NOT NULL,
+  block_time INTEGER,
+  PRIMARY KEY (signature, order_a_address)
+);
+CREATE TABLE IF NOT EXISTS bids (
+  signature TEXT NOT NULL,
+  order_address TEXT NOT NULL,
+  bid_address TEXT NOT NULL,
+  bidder TEXT NOT NULL,
+  bid_index INTEGER NOT NULL,
+  value INTEGER NOT NULL,
+  outbid_address TEXT,
+  slot INTEGER NOT NULL,
+  block_time INTEGER,
+  PRIMARY KEY (signature, bid_address)
+);
+CREATE TABLE IF NOT EXISTS sealed_bids (
+  signature TEXT NOT NULL,
+  order_address TEXT NOT NULL,
+  bid_address TEXT NOT NULL,
+  bidder TEXT NOT NULL,
+  action TEXT NOT NULL,
+  value INTEGER NOT NULL,
+  best INTEGER,
+  forfeited INTEGER,
+  outbid_address TEXT,
+  slot INTEGER NOT NULL,
+  block_time INTEGER,
+  PRIMARY KEY (signature, bid_address)
+);
+CREATE TABLE IF NOT EXISTS offers (
+  signature TEXT NOT NULL,
+  order_address TEXT NOT NULL,
+  offer_address TEXT NOT NULL,
+  partner TEXT NOT NULL,
+  action TEXT NOT NULL,
+  mint TEXT,
+  value INTEGER NOT NULL,
+  slot INTEGER NOT NULL,
+  block_time INTEGER,
+  PRIMARY KEY (signature, offer_address)
+);
+CREATE TABLE IF NOT EXISTS option_payments (
+  signature TEXT NOT NULL,
+  order_address TEXT NOT NULL,
+  mint TEXT,
+  receive_value INTEGER NOT NULL,
+  slot INTEGER NOT NULL,
+  block_time INTEGER,
+  PRIMARY KEY (signature, order_address)
+);
+CREATE TABLE IF NOT EXISTS triggers (
+  signature TEXT NOT NULL,
+  order_address TEXT NOT NULL,
+  action TEXT NOT NULL,
+  oracle TEXT,
+  direction TEXT,
+  price INTEGER NOT NULL,
+  publish_time INTEGER,
+  slot INTEGER NOT NULL,
+  block_time INTEGER,
+  PRIMARY KEY (signature, order_address, action)
+);
+CREATE TABLE IF NOT EXISTS fees (
+  signature TEXT PRIMARY KEY,
+  payer TEXT NOT NULL,
+  lamports INTEGER NOT NULL,
+  slot INTEGER NOT NULL,
+  block_time INTEGER
+);
+CREATE TABLE IF NOT EXISTS sync_state (
+  key TEXT PRIMARY KEY,
+  value TEXT NOT NULL
+);
+";
+
+// sqlite integers are signed, amounts are stored bit for bit and converted back on read
+pub fn to_sql_u64(value: u64) -> i64 {
+  value as i64
+}
+
+pub fn from_sql_u64(value: i64) -> u64 {
+  value as u64
+}
+
+fn key(pubkey: &Pubkey) -> String {
+  pubkey.to_string()
+}
+
+fn optional_key(pubkey: &Option<Pubkey>) -> Option<String> {
+  pubkey.as_ref().map(key)
+}
+
+pub struct Store {
+  conn: Connection,
+}
+
+impl Store {
+  pub fn open<P: AsRef<Path>>(path: P) -> Result<Store> {
+    Store::from_connection(Connection::open(path)?)
+  }
+
+  pub fn open_in_memory() -> Result<Store> {
+    Store::from_connection(Connection::open_in_memory()?)
+  }
+
+  fn from_connection(conn: Connection) -> Result<Store> {
+    conn.execute_batch(SCHEMA)?;
+    Ok(Store { conn })
+  }
+
+  pub fn connection(&self) -> &Connection {
+    &self.conn
+  }
+
+  // apply every event of a transaction atomically. Transactions already seen
+  // are skipped so replays are harmless, and events commute so transactions
+  // may arrive in any order.
+  pub fn apply(&mut self, record: &TransactionRecord) -> Result<bool> {
+    let tx = self.conn.transaction()?;
+    let inserted = tx.execute(
+      "INSERT OR IGNORE INTO transactions (signature, slot, block_time) VALUES (?1, ?2, ?3)",
+      params![record.signature, to_sql_u64(record.slot), record.block_time],
+    )?;
+    if inserted == 0 {
+      return Ok(false);
+    }
+    for event in &record.events {
+      apply_event(&tx, record, event)?;
+    }
+    tx.execute(
+      "INSERT OR IGNORE INTO fees (signature, payer, lamports, slot, block_time)
+       VALUES (?1, ?2, ?3, ?4, ?5)",
+      params![
+        record.signature,
+        key(&record.fee_payer),
+        to_sql_u64(record.fee),
+        to_sql_u64(record.slot),
+        record.block_time
+      ],
+    )?;
+    tx.commit()?;
+    Ok(true)
+  }
+
+  pub fn sync_state(&self, name: &str) -> Result<Option<String>> {
+    Ok(
+      self
+        .conn
+        .query_row("SELECT value FROM sync_state WHERE key = ?1", [name], |row| row.get(0))
+        .optional()?,
+    )
+  }
+
+  pub fn set_sync_state(&self, name: &str, value: &str) -> Result<()> {
+    self.conn.execute(
+      "INSERT INTO sync_state (key, value) VALUES (?1, ?2)
+       ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+      [name, value],
+    )?;
+    Ok(())
+  }
+}
+
+fn apply_event(tx: &Transaction, record: &TransactionRecord, event: &OrderEvent) -> Result<()> {
+  let slot = to_sql_u64(record.slot);
+  match event {
+    OrderEvent::Created {
+      order,
+      creator,
+      order_id,
+      trade_type,
+      send_mint,
+      receive_mint,
+      trade_value,
+      receive_value,
+      specify_partner,
+      fee_account,
+      timestamp,
+    } => {
+      // never touches `stage`: a close seen earlier must not be reopened
+      tx.execute(
+        "INSERT INTO orders (address, creator, order_id, trade_type, send_mint, receive_mint,
+           trade_value, receive_value, specify_partner, fee_account, timestamp, stage,
+           created_signature, created_slot)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+         ON CONFLICT (address) DO UPDATE SET
+           trade_type = excluded.trade_type,
+           send_mint = excluded.send_mint,
+           receive_mint = excluded.receive_mint,
+           trade_value = excluded.trade_value,
+           receive_value = excluded.receive_value,
+           specify_partner = excluded.specify_partner,
+           fee_account = excluded.fee_account,
+           timestamp = excluded.timestamp,
+           created_signature = excluded.created_signature,
+           created_slot = excluded.created_slot",
+        params![
+          key(order),
+          key(creator),
+          to_sql_u64(*order_id),
+          trade_type,
+          optional_key(send_mint),
+          optional_key(receive_mint),
+          to_sql_u64(*trade_value),
+          to_sql_u64(*receive_value),
+          optional_key(specify_partner),
+          key(fee_account),
+          to_sql_u64(*timestamp),
+          Stage::ReadyExchange.to_code(),
+          record.signature,
+          slot
+        ],
+      )?;
+      // fills, cancellations and declines decoded from instructions before the order was known
+      tx.execute(
+        "UPDATE fills SET trade_value = ?2, receive_value = ?3
+         WHERE order_address = ?1 AND trade_value IS NULL",
+        params![key(order), to_sql_u64(*trade_value), to_sql_u64(*receive_value)],
+      )?;
+      tx.execute(
+        "UPDATE cancellations SET refund_value = ?2
+         WHERE order_address = ?1 AND refund_value IS NULL",
+        params![key(order), to_sql_u64(*trade_value)],
+      )?;
+      tx.execute(
+        "UPDATE declines SET refund_value = ?2
+         WHERE order_address = ?1 AND refund_value IS NULL",
+        params![key(order), to_sql_u64(*trade_value)],
+      )?;
+    }
+    OrderEvent::Exchanged {
+      order,
+      creator,
+      order_id,
+      partner,
+      trade_value,
+      receive_value,
+    } => {
+      close_order(tx, record, order, creator, *order_id, Stage::Exchanged, Some(partner))?;
+      let known: Option<(Option<i64>, Option<i64>)> = tx
+        .query_row(
+          "SELECT trade_value, receive_value FROM orders WHERE address = ?1",
+          [key(order)],
+          |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+      let (known_trade_value, known_receive_value) = known.unwrap_or_default();
+      tx.execute(
+        "INSERT OR IGNORE INTO fills (signature, order_address, partner, trade_value,
+           receive_value, slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+          record.signature,
+          key(order),
+          key(partner),
+          trade_value.map(to_sql_u64).or(known_trade_value),
+          receive_value.map(to_sql_u64).or(known_receive_value),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::Cancelled {
+      order,
+      creator,
+      order_id,
+      refund_value,
+    } => {
+      close_order(tx, record, order, creator, *order_id, Stage::CancelTrade, None)?;
+      let known: Option<Option<i64>> = tx
+        .query_row(
+          "SELECT trade_value FROM orders WHERE address = ?1",
+          [key(order)],
+          |row| row.get(0),
+        )
+        .optional()?;
+      tx.execute(
+        "INSERT OR IGNORE INTO cancellations (signature, order_address, creator, refund_value,
+           slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+          record.signature,
+          key(order),
+          key(creator),
+          refund_value.map(to_sql_u64).or(known.flatten()),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::Declined {
+      order,
+      creator,
+      order_id,
+      partner,
+      refund_value,
+    } => {
+      // `partner` of the order stays the wallet that filled it, the decliner is in `declines`
+      close_order(tx, record, order, creator, *order_id, Stage::Declined, None)?;
+      let known: Option<Option<i64>> = tx
+        .query_row(
+          "SELECT trade_value FROM orders WHERE address = ?1",
+          [key(order)],
+          |row| row.get(0),
+        )
+        .optional()?;
+      tx.execute(
+        "INSERT OR IGNORE INTO declines (signature, order_address, partner, refund_value,
+           slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+          record.signature,
+          key(order),
+          key(partner),
+          refund_value.map(to_sql_u64).or(known.flatten()),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::Replaced {
+      order,
+      creator,
+      new_order,
+      moved_value,
+      ..
+    } => {
+      tx.execute(
+        "INSERT OR IGNORE INTO replacements (signature, order_address, new_order_address, creator,
+           moved_value, slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+          record.signature,
+          key(order),
+          key(new_order),
+          key(creator),
+          to_sql_u64(*moved_value),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::Matched {
+      order_a,
+      order_b,
+      matcher,
+      fee_a,
+      fee_b,
+    } => {
+      tx.execute(
+        "INSERT OR IGNORE INTO matches (signature, order_a_address, order_b_address, matcher,
+           fee_a, fee_b, slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+          record.signature,
+          key(order_a),
+          key(order_b),
+          key(matcher),
+          to_sql_u64(*fee_a),
+          to_sql_u64(*fee_b),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::BidPlaced {
+      order,
+      bid,
+      bidder,
+      bid_index,
+      value,
+      outbid,
+    } => {
+      tx.execute(
+        "INSERT OR IGNORE INTO bids (signature, order_address, bid_address, bidder, bid_index,
+           value, outbid_address, slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+          record.signature,
+          key(order),
+          key(bid),
+          key(bidder),
+          bid_index,
+          to_sql_u64(*value),
+          outbid.as_ref().map(key),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    // `value` is the deposit of commits and reclaims, the bid of reveals
+    OrderEvent::BidCommitted {
+      order,
+      bid,
+      bidder,
+      deposit,
+      ..
+    } => {
+      tx.execute(
+        "INSERT OR IGNORE INTO sealed_bids (signature, order_address, bid_address, bidder, action,
+           value, best, forfeited, outbid_address, slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+          record.signature,
+          key(order),
+          key(bid),
+          key(bidder),
+          "commit",
+          to_sql_u64(*deposit),
+          None::<bool>,
+          None::<bool>,
+          None::<String>,
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::BidRevealed {
+      order,
+      bid,
+      bidder,
+      value,
+      best,
+      outbid,
+    } => {
+      tx.execute(
+        "INSERT OR IGNORE INTO sealed_bids (signature, order_address, bid_address, bidder, action,
+           value, best, forfeited, outbid_address, slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+          record.signature,
+          key(order),
+          key(bid),
+          key(bidder),
+          "reveal",
+          to_sql_u64(*value),
+          best,
+          None::<bool>,
+          outbid.as_ref().map(key),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::BidReclaimed {
+      order,
+      bid,
+      bidder,
+      deposit,
+      forfeited,
+    } => {
+      tx.execute(
+        "INSERT OR IGNORE INTO sealed_bids (signature, order_address, bid_address, bidder, action,
+           value, best, forfeited, outbid_address, slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+          record.signature,
+          key(order),
+          key(bid),
+          key(bidder),
+          "reclaim",
+          to_sql_u64(*deposit),
+          None::<bool>,
+          forfeited,
+          None::<String>,
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::OfferMade {
+      order,
+      offer,
+      partner,
+      mint,
+      value,
+    } => {
+      tx.execute(
+        "INSERT OR IGNORE INTO offers (signature, order_address, offer_address, partner, action,
+           mint, value, slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+          record.signature,
+          key(order),
+          key(offer),
+          key(partner),
+          "make",
+          mint.as_ref().map(key),
+          to_sql_u64(*value),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::OfferAccepted {
+      order,
+      offer,
+      creator,
+      order_id,
+      partner,
+      mint,
+      value,
+    } => {
+      close_order(tx, record, order, creator, *order_id, Stage::Exchanged, Some(partner))?;
+      tx.execute(
+        "INSERT OR IGNORE INTO offers (signature, order_address, offer_address, partner, action,
+           mint, value, slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+          record.signature,
+          key(order),
+          key(offer),
+          key(partner),
+          "accept",
+          mint.as_ref().map(key),
+          to_sql_u64(*value),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::OfferWithdrawn {
+      order,
+      offer,
+      partner,
+      value,
+    } => {
+      // the mint is the one the offer was made with
+      tx.execute(
+        "INSERT OR IGNORE INTO offers (signature, order_address, offer_address, partner, action,
+           mint, value, slot, block_time)
+         SELECT ?1, ?2, ?3, ?4, ?5,
+           (SELECT mint FROM offers WHERE offer_address = ?3 AND action = 'make' ORDER BY slot DESC LIMIT 1),
+           ?6, ?7, ?8",
+        params![
+          record.signature,
+          key(order),
+          key(offer),
+          key(partner),
+          "withdraw",
+          to_sql_u64(*value),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::PaidWithOption {
+      order,
+      mint,
+      receive_value,
+    } => {
+      tx.execute(
+        "INSERT OR IGNORE INTO option_payments (signature, order_address, mint, receive_value, slot,
+           block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+          record.signature,
+          key(order),
+          optional_key(mint),
+          to_sql_u64(*receive_value),
+          slot,
+          record.block_time
+        ],
+      )?;
+    }
+    OrderEvent::TriggerArmed {
+      order,
+      oracle,
+      trigger_price,
+      direction,
+    } => {
+      tx.execute(
+        "INSERT OR IGNORE INTO triggers (signature, order_address, action, oracle, direction, price,
+           slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+          record.signature,
+          key(order),
+          "arm",
+          key(oracle),
+          trigger_direction_name(*direction),
+          trigger_price,
+          slot,
+          record.block_time
+        ],
+      )?;
+      // comes with the creation, unless the trigger fired already
+      tx.execute(
+        "UPDATE orders SET stage = ?2
+         WHERE address = ?1 AND stage = ?3
+           AND NOT EXISTS (SELECT 1 FROM triggers WHERE order_address = ?1 AND action = 'fire')",
+        params![
+          key(order),
+          Stage::AwaitingTrigger.to_code(),
+          Stage::ReadyExchange.to_code()
+        ],
+      )?;
+    }
+    OrderEvent::Triggered {
+      order,
+      price,
+      publish_time,
+      ..
+    } => {
+      tx.execute(
+        "INSERT OR IGNORE INTO triggers (signature, order_address, action, price, publish_time,
+           slot, block_time)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+          record.signature,
+          key(order),
+          "fire",
+          price,
+          publish_time,
+          slot,
+          record.block_time
+        ],
+      )?;
+      tx.execute(
+        "UPDATE orders SET stage = ?2 WHERE address = ?1 AND stage = ?3",
+        params![
+          key(order),
+          Stage::ReadyExchange.to_code(),
+          Stage::AwaitingTrigger.to_code()
+        ],
+      )?;
+    }
+  }
+  Ok(())
+}
+
+// move an order to a final stage, creating a placeholder row when the
+// creation has not been indexed yet
+fn close_order(
+  tx: &Transaction,
+  record: &TransactionRecord,
+  order: &Pubkey,
+  creator: &Pubkey,
+  order_id: u64,
+  stage: Stage,
+  partner: Option<&Pubkey>,
+) -> Result<()> {
+  tx.execute(
+    "INSERT INTO orders (address, creator, order_id, partner, stage, closed_signature, closed_slot)
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+     ON CONFLICT (address) DO UPDATE SET
+       partner = COALESCE(excluded.partner, orders.partner),
+       stage = excluded.stage,
+       closed_signature = excluded.closed_signature,
+       closed_slot = excluded.closed_slot",
+    params![
+      key(order),
+      key(creator),
+      to_sql_u64(order_id),
+      partner.map(key),
+      stage.to_code(),
+      record.signature,
+      to_sql_u64(record.slot)
+    ],
+  )?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rusqlite::types::Value;
+
+  fn record(slot: u64, events: Vec<OrderEvent>) -> TransactionRecord {
+    TransactionRecord {
+      signature: format!("signature-{}", slot),
+      slot,
+      block_time: Some(1_700_000_000 + slot as i64),
+      fee_payer: Pubkey::new_unique(),
+      fee: 5_000,
+      events,
+    }
+  }
+
+  fn created(order: Pubkey, creator: Pubkey, order_id: u64, trade_value: u64) -> OrderEvent {
+    OrderEvent::Created {
+      order,
+      creator,
+      order_id,
+      trade_type: 1,
+      send_mint: Some(Pubkey::new_unique()),
+      receive_mint: None,
+      trade_value,
+      receive_value: 2 * trade_value,
+      specify_partner: None,
+      fee_account: creator,
+      timestamp: 0,
+    }
+  }
+
+  // the life of three orders: one bid on and sold, one replaced by the third,
+  // which is then cancelled
+  fn history() -> Vec<TransactionRecord> {
+    let (creator, bidder) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (sold, replaced, replacement) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+    let bid = Pubkey::new_unique();
+    vec![
+      record(1, vec![created(sold, creator, 1, 100), created(replaced, creator, 2, 50)]),
+      record(2, vec![OrderEvent::BidPlaced {
+        order: sold,
+        bid,
+        bidder,
+        bid_index: 0,
+        value: 200,
+        outbid: None,
+      }]),
+      record(3, vec![OrderEvent::Exchanged {
+        order: sold,
+        creator,
+        order_id: 1,
+        partner: bidder,
+        trade_value: Some(100),
+        receive_value: Some(200),
+      }]),
+      record(4, vec![
+        OrderEvent::Cancelled {
+          order: replaced,
+          creator,
+          order_id: 2,
+          refund_value: Some(0),
+        },
+        created(replacement, creator, 3, 60),
+        OrderEvent::Replaced {
+          order: replaced,
+          creator,
+          order_id: 2,
+          new_order: replacement,
+          new_order_id: 3,
+          moved_value: 50,
+        },
+      ]),
+      record(5, vec![OrderEvent::Cancelled {
+        order: replacement,
+        creator,
+        order_id: 3,
+        refund_value: Some(60),
+      }]),
+    ]
+  }
+
+  // every row of every table, sorted
+  fn dump(store: &Store) -> Vec<String> {
+    let conn = store.connection();
+    let mut tables = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name").unwrap();
+    let tables = tables
+      .query_map([], |row| row.get::<_, String>(0))
+      .unwrap()
+      .collect::<rusqlite::Result<Vec<_>>>()
+      .unwrap();
+    let mut rows = Vec::new();
+    for table in tables {
+      let mut statement = conn.prepare(&format!("SELECT * FROM {}", table)).unwrap();
+      let columns = statement.column_count();
+      let table_rows = statement
+        .query_map([], |row| {
+          (0..columns)
+            .map(|index| row.get::<_, Value>(index))
+            .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .unwrap();
+      for row in table_rows {
+        rows.push(format!("{} {:?}", table, row.unwrap()));
+      }
+    }
+    rows.sort();
+    rows
+  }
+
+  fn apply_all(records: &[&TransactionRecord]) -> Store {
+    let mut store = Store::open_in_memory().unwrap();
+    for record in records {
+      store.apply(record).unwrap();
+    }
+    store
+  }
+
+  // every ordering of `items`
+  fn permutations<T: Copy>(items: &[T]) -> Vec<Vec<T>> {
+    if items.len() <= 1 {
+      return vec![items.to_vec()];
+    }
+    let mut all = Vec::new();
+    for index in 0..items.len() {
+      let mut rest = items.to_vec();
+      let first = rest.remove(index);
+      for mut permutation in permutations(&rest) {
+        permutation.insert(0, first);
+        all.push(permutation);
+      }
+    }
+    all
+  }
+
+  #[test]
+  fn replays_are_harmless() {
+    let history = history();
+    let mut store = apply_all(&history.iter().collect::<Vec<_>>());
+    let expected = dump(&store);
+    for record in &history {
+      assert!(!store.apply(record).unwrap());
+    }
+    assert_eq!(dump(&store), expected);
+  }
+
+  #[test]
+  fn events_commute() {
+    let history = history();
+    let in_order = dump(&apply_all(&history.iter().collect::<Vec<_>>()));
+    for permutation in permutations(&history.iter().collect::<Vec<_>>()) {
+      assert_eq!(dump(&apply_all(&permutation)), in_order);
+    }
+  }
+}
@@ -12,23 +12,41 @@ import {
   AnchorProvider,
   Wallet,
 } from "@project-serum/anchor";
-import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { getAssociatedTokenAddress, TOKEN_PROGRAM_ID } from "@solana/spl-token";
 import {
   TradeType,
   TradeOrderRequest,
+  CreateManyRequest,
   TradeInfo,
   CreateInstructionParams,
   STATE_SEED,
   VAULT_SEED,
+  USER_SEED,
+  BID_SEED,
+  BID_VAULT_SEED,
+  OFFER_SEED,
+  OFFER_VAULT_SEED,
   RemainAccount,
   InstructionCreateAccounts,
   PartnerInfo,
   CancelParams,
+  DeclineParams,
+  ReplaceParams,
+  MatcherInfo,
+  PriceCap,
 } from "./types";
 import {
   findPdaAccount,
+  findInboxAccount,
+  findMarketAccount,
   encodeTransaction,
   isValidTokenAccount,
+  rfqParams,
+  oraclePegParams,
+  triggerParams,
+  paymentOptionsParams,
+  paymentOptionAccounts,
+  creatorPayout,
 } from "./utils";
 import idl from "../../target/idl/trade_p2p.json";
 
@@ -61,6 +79,22 @@ export class TradeP2P {
     return this._program;
   }
 
+  async findUserAccount(creator: PublicKey): Promise<PublicKey> {
+    const [address] = await PublicKey.findProgramAddress(
+      [Buffer.from(USER_SEED), creator.toBuffer()],
+      this.programId
+    );
+    return address;
+  }
+
+  // order id `createTrade` accepts next from `creator`, 0 before their first order
+  async nextOrderId(creator: PublicKey): Promise<number> {
+    const userAccount = await this._program.account.userAccount.fetchNullable(
+      await this.findUserAccount(creator)
+    );
+    return userAccount ? (userAccount.nextOrderId as BN).toNumber() : 0;
+  }
+
   async createTrade(tradeOrderRequest: TradeOrderRequest): Promise<Buffer> {
     const { address: stateAccount, bump: _ } = await findPdaAccount(
       this.programId,
@@ -152,10 +186,43 @@ export class TradeP2P {
         isSigner: false,
       });
     }
+    // the order is listed in the inbox of its specify partner
+    if (tradeOrderRequest.specifyPartner) {
+      remainingAccounts.push({
+        pubkey: await findInboxAccount(
+          this.programId,
+          tradeOrderRequest.specifyPartner
+        ),
+        isWritable: true,
+        isSigner: false,
+      });
+    }
+    // and on the market page of its pair
+    if (tradeOrderRequest.marketPage !== undefined) {
+      remainingAccounts.push({
+        pubkey: await findMarketAccount(
+          this.programId,
+          tradeOrderRequest.tradeMint,
+          tradeOrderRequest.receiveMint,
+          tradeOrderRequest.marketPage
+        ),
+        isWritable: true,
+        isSigner: false,
+      });
+    }
+    // then the receive account of each payment option
+    remainingAccounts.push(...paymentOptionAccounts(tradeOrderRequest.paymentOptions));
+    // last the reference price account of the price guard
+    if (tradeOrderRequest.priceGuard) {
+      remainingAccounts.push({
+        pubkey: tradeOrderRequest.priceGuard.oracle,
+        isWritable: false,
+        isSigner: false,
+      });
+    }
 
     // setup params instructions
     const params: CreateInstructionParams = {
-      orderId: new BN(tradeOrderRequest.orderId),
       specifyPartner: tradeOrderRequest.specifyPartner
         ? tradeOrderRequest.specifyPartner
         : null,
@@ -163,6 +230,41 @@ export class TradeP2P {
       receiveValue: new BN(tradeOrderRequest.receiveValue),
       timestamp: new BN(tradeOrderRequest.timestamp),
       vaultBump: new BN(vaultBump),
+      partnerRoot: tradeOrderRequest.partnerRoot
+        ? tradeOrderRequest.partnerRoot
+        : null,
+      claimHash: tradeOrderRequest.claimHash
+        ? tradeOrderRequest.claimHash
+        : null,
+      marketPage:
+        tradeOrderRequest.marketPage !== undefined
+          ? tradeOrderRequest.marketPage
+          : null,
+      dutchAuction: tradeOrderRequest.dutchAuction
+        ? {
+            floorValue: new BN(tradeOrderRequest.dutchAuction.floorValue),
+            startTime: new BN(tradeOrderRequest.dutchAuction.startTime),
+            endTime: new BN(tradeOrderRequest.dutchAuction.endTime),
+          }
+        : null,
+      englishAuction: tradeOrderRequest.englishAuction
+        ? {
+            endTime: new BN(tradeOrderRequest.englishAuction.endTime),
+            minIncrement: new BN(tradeOrderRequest.englishAuction.minIncrement),
+          }
+        : null,
+      sealedAuction: tradeOrderRequest.sealedAuction
+        ? {
+            commitEnd: new BN(tradeOrderRequest.sealedAuction.commitEnd),
+            revealEnd: new BN(tradeOrderRequest.sealedAuction.revealEnd),
+            forfeitUnrevealed: tradeOrderRequest.sealedAuction.forfeitUnrevealed,
+          }
+        : null,
+      rfq: rfqParams(tradeOrderRequest.rfq),
+      oraclePeg: oraclePegParams(tradeOrderRequest.oraclePeg),
+      priceGuard: tradeOrderRequest.priceGuard ?? null,
+      trigger: triggerParams(tradeOrderRequest.trigger),
+      paymentOptions: paymentOptionsParams(tradeOrderRequest.paymentOptions),
     };
 
     // setup accounts for instructions
@@ -176,6 +278,7 @@ export class TradeP2P {
       systemProgram: SystemProgram.programId,
       tokenProgram: TOKEN_PROGRAM_ID,
       rent: SYSVAR_RENT_PUBKEY,
+      userAccount: await this.findUserAccount(tradeOrderRequest.creator),
     };
     const transaction = await this._program.methods
       .createTrade(params)
@@ -186,6 +289,75 @@ export class TradeP2P {
     return await encodeTransaction(this._connection, transaction);
   }
 
+  // create several orders in one transaction, see `CreateManyRequest`
+  async createMany(request: CreateManyRequest): Promise<Buffer> {
+    if (request.levels.length === 0) {
+      throw new Error("No order to create");
+    }
+    // the mints as in `createTrade`, then the state, vault and market page of each order
+    const remainingAccounts: RemainAccount[] = [];
+    for (const mint of [request.tradeMint, request.receiveMint]) {
+      if (mint) {
+        remainingAccounts.push({ pubkey: mint, isWritable: true, isSigner: false });
+      }
+    }
+    const orders = [];
+    for (const [index, level] of request.levels.entries()) {
+      const orderId = request.orderId + index;
+      const { address: stateAccount } = await findPdaAccount(
+        this.programId,
+        STATE_SEED,
+        request.creator,
+        orderId
+      );
+      const { address: vaultAccount, bump: vaultBump } = await findPdaAccount(
+        this.programId,
+        VAULT_SEED,
+        request.creator,
+        orderId
+      );
+      for (const pubkey of [stateAccount, vaultAccount]) {
+        remainingAccounts.push({ pubkey, isWritable: true, isSigner: false });
+      }
+      if (request.marketPage !== undefined) {
+        remainingAccounts.push({
+          pubkey: await findMarketAccount(
+            this.programId,
+            request.tradeMint,
+            request.receiveMint,
+            request.marketPage
+          ),
+          isWritable: true,
+          isSigner: false,
+        });
+      }
+      orders.push({
+        tradeValue: new BN(level.tradeValue),
+        receiveValue: new BN(level.receiveValue),
+        vaultBump,
+      });
+    }
+    const transaction = await this._program.methods
+      .createMany({
+        orders,
+        timestamp: new BN(request.timestamp),
+        marketPage: request.marketPage !== undefined ? request.marketPage : null,
+      })
+      .accounts({
+        creator: request.creator,
+        creatorSendAccount: request.creatorSendAccount,
+        creatorReceiveAccount: request.creatorReceiveAccount,
+        feeAccount: FEE_ACCOUNT,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        rent: SYSVAR_RENT_PUBKEY,
+        userAccount: await this.findUserAccount(request.creator),
+      })
+      .remainingAccounts(remainingAccounts)
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
   async exchange(
     tradeInfo: TradeInfo,
     partnerInfo: PartnerInfo
@@ -196,6 +368,7 @@ export class TradeP2P {
 
     if (
       tradeInfo.specifyPartner &&
+      !tradeInfo.partnerRoot &&
       tradeInfo.specifyPartner !== partnerInfo.partner
     ) {
       throw new Error("Invalid specify partner");
@@ -274,11 +447,45 @@ export class TradeP2P {
       partner: partnerInfo.partner
     };
 
+    const remainingAccounts: RemainAccount[] = [];
+    // an oracle-pegged order is priced from its feed
+    if (tradeInfo.oracle) {
+      remainingAccounts.push({ pubkey: tradeInfo.oracle, isWritable: false, isSigner: false });
+    }
+    if (partnerInfo.claimKeypair) {
+      remainingAccounts.push({
+        pubkey: partnerInfo.claimKeypair.publicKey,
+        isWritable: false,
+        isSigner: true,
+      });
+    }
+    remainingAccounts.push(
+      ...(await this.listingAccounts(
+        tradeInfo.specifyPartner,
+        tradeInfo.tradeMint,
+        tradeInfo.receiveMint,
+        tradeInfo.marketPage
+      ))
+    );
+
     const transaction = await this._program.methods
-      .exchange(new BN(tradeInfo.orderId), new BN(stateBump), new BN(vaultBump))
+      .exchange(
+        new BN(tradeInfo.orderId),
+        new BN(stateBump),
+        new BN(vaultBump),
+        partnerInfo.proof ? partnerInfo.proof : [],
+        partnerInfo.maxReceiveValue !== undefined
+          ? new BN(partnerInfo.maxReceiveValue)
+          : null
+      )
       .accounts(accounts)
+      .remainingAccounts(remainingAccounts)
       .transaction();
-    return await encodeTransaction(this._connection, transaction);
+    return await encodeTransaction(
+      this._connection,
+      transaction,
+      partnerInfo.claimKeypair ? [partnerInfo.claimKeypair] : []
+    );
   }
 
   async cancel(cancelParams: CancelParams): Promise<Buffer> {
@@ -333,7 +540,852 @@ export class TradeP2P {
         new BN(vaultBump)
       )
       .accounts(accounts)
+      .remainingAccounts(
+        await this.listingAccounts(
+          cancelParams.specifyPartner,
+          cancelParams.tradeType === TradeType.SOLSPL
+            ? undefined
+            : cancelParams.tradeMint,
+          cancelParams.receiveMint,
+          cancelParams.marketPage
+        )
+      )
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // fill several orders of one pair in one transaction, paying at most
+  // `budget` in total and at most `maxPrice` on average. With `atomic` every
+  // order is filled or none, otherwise orders that cannot be filled are skipped
+  async exchangeMany(
+    orders: TradeInfo[],
+    partnerInfo: PartnerInfo,
+    budget: number,
+    maxPrice?: PriceCap,
+    atomic = true
+  ): Promise<Buffer> {
+    const remainingAccounts: RemainAccount[] = [];
+    for (const order of orders) {
+      const { address: stateAccount } = await findPdaAccount(
+        this.programId,
+        STATE_SEED,
+        order.creator,
+        order.orderId
+      );
+      const { address: vaultAccount } = await findPdaAccount(
+        this.programId,
+        VAULT_SEED,
+        order.creator,
+        order.orderId
+      );
+      for (const pubkey of [
+        stateAccount,
+        vaultAccount,
+        order.creator,
+        order.creatorReceiveAccount,
+      ]) {
+        remainingAccounts.push({ pubkey, isWritable: true, isSigner: false });
+      }
+      remainingAccounts.push(
+        ...(await this.listingAccounts(
+          order.specifyPartner,
+          order.tradeMint,
+          order.receiveMint,
+          order.marketPage
+        ))
+      );
+    }
+    const transaction = await this._program.methods
+      .exchangeMany(
+        new BN(budget),
+        maxPrice
+          ? {
+              receiveValue: new BN(maxPrice.receiveValue),
+              tradeValue: new BN(maxPrice.tradeValue),
+            }
+          : null,
+        atomic
+      )
+      .accounts({
+        partnerSendAccount: partnerInfo.partnerSendAccount,
+        partnerReceiveAccount: partnerInfo.partnerReceiveAccount,
+        partner: partnerInfo.partner,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(remainingAccounts)
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // settle two unrestricted orders of opposite sides of a pair against each
+  // other. Each creator gets their receive value, the matcher keeps the rest
+  async matchOrders(
+    a: TradeInfo,
+    b: TradeInfo,
+    matcherInfo: MatcherInfo
+  ): Promise<Buffer> {
+    const accounts = [];
+    const remainingAccounts: RemainAccount[] = [];
+    for (const order of [a, b]) {
+      const { address: stateAccount } = await findPdaAccount(
+        this.programId,
+        STATE_SEED,
+        order.creator,
+        order.orderId
+      );
+      const { address: vaultAccount } = await findPdaAccount(
+        this.programId,
+        VAULT_SEED,
+        order.creator,
+        order.orderId
+      );
+      accounts.push({ stateAccount, vaultAccount });
+      remainingAccounts.push(
+        ...(await this.listingAccounts(
+          undefined,
+          order.tradeMint,
+          order.receiveMint,
+          order.marketPage
+        ))
+      );
+    }
+    const transaction = await this._program.methods
+      .matchOrders()
+      .accounts({
+        escrowStateA: accounts[0].stateAccount,
+        escrowVaultA: accounts[0].vaultAccount,
+        creatorA: a.creator,
+        creatorAReceiveAccount: creatorPayout(a),
+        escrowStateB: accounts[1].stateAccount,
+        escrowVaultB: accounts[1].vaultAccount,
+        creatorB: b.creator,
+        creatorBReceiveAccount: creatorPayout(b),
+        matcher: matcherInfo.matcher,
+        matcherAAccount: matcherInfo.matcherAAccount,
+        matcherBAccount: matcherInfo.matcherBAccount,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(remainingAccounts)
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // bid `value` on an english auction order, paid from and won into
+  // `bidderInfo`'s accounts: the bidder wallet for SOL, its associated token
+  // accounts otherwise. The best bid so far is refunded
+  async placeBid(
+    order: TradeInfo,
+    bidderInfo: PartnerInfo,
+    value: number
+  ): Promise<Buffer> {
+    const { stateAccount, escrow } = await this.fetchOrder(order);
+    const { accounts, mints, vaultBump } = await this.newBidAccounts(
+      stateAccount,
+      escrow,
+      bidderInfo
+    );
+    // the mints of the bidder's side as in `createTrade`, then the bid it beats
+    const transaction = await this._program.methods
+      .placeBid(new BN(value), vaultBump)
+      .accounts(accounts)
+      .remainingAccounts([...mints, ...(await this.bestBidAccounts(escrow))])
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // commit a sealed bid on a sealed-bid auction order, locking `deposit`, which
+  // must cover the bid and the reserve price. `commitment` is
+  // keccak("bid" + bidder + bid value as u64 little endian + salt), keep the
+  // value and the 32 bytes salt to reveal it with `revealBid`
+  async commitBid(
+    order: TradeInfo,
+    bidderInfo: PartnerInfo,
+    deposit: number,
+    commitment: number[]
+  ): Promise<Buffer> {
+    const { stateAccount, escrow } = await this.fetchOrder(order);
+    const { accounts, mints, vaultBump } = await this.newBidAccounts(
+      stateAccount,
+      escrow,
+      bidderInfo
+    );
+    const transaction = await this._program.methods
+      .commitBid(new BN(deposit), vaultBump, commitment)
+      .accounts(accounts)
+      .remainingAccounts(mints)
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // reveal the sealed bid `bid` of `bidder` with the value and salt it was
+  // committed with. A bid that does not become the best one is refunded
+  async revealBid(
+    order: TradeInfo,
+    bid: PublicKey,
+    value: number,
+    salt: number[]
+  ): Promise<Buffer> {
+    const { stateAccount, escrow } = await this.fetchOrder(order);
+    const bidAccount = await this._program.account.bidAccount.fetch(bid);
+    const transaction = await this._program.methods
+      .revealBid(new BN(value), salt)
+      .accounts({
+        escrowState: stateAccount,
+        bid,
+        bidVault: bidAccount.bidVault,
+        bidder: bidAccount.bidder,
+        bidderSendAccount: bidAccount.bidderSendAccount,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(await this.bestBidAccounts(escrow))
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // once reveals are over, close the sealed bid `bid` never revealed, its
+  // deposit going to the creator or back to the bidder as the auction says.
+  // Anyone can send it
+  async reclaimBid(order: TradeInfo, bid: PublicKey): Promise<Buffer> {
+    const { stateAccount, escrow } = await this.fetchOrder(order);
+    const bidAccount = await this._program.account.bidAccount.fetch(bid);
+    const transaction = await this._program.methods
+      .reclaimBid()
+      .accounts({
+        escrowState: stateAccount,
+        // the creator wallet for SOL, as `creatorPayout`
+        creatorReceiveAccount: escrow.creatorReceiveTokenMint
+          ? escrow.creatorReceiveAccount
+          : escrow.creator,
+        bid,
+        bidVault: bidAccount.bidVault,
+        bidder: bidAccount.bidder,
+        bidderSendAccount: bidAccount.bidderSendAccount,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // every bid placed or committed on an auction order
+  async getBids(order: TradeInfo) {
+    const { stateAccount } = await this.fetchOrder(order);
+    // `escrowState` is the first field after the discriminator
+    return await this._program.account.bidAccount.all([
+      { memcmp: { offset: 8, bytes: stateAccount.toBase58() } },
+    ]);
+  }
+
+  // once an auction has ended, swap the order with its best bid, or refund the
+  // creator when nobody bid. Anyone can send it
+  async settleAuction(order: TradeInfo): Promise<Buffer> {
+    const { stateAccount, escrow } = await this.fetchOrder(order);
+    const remainingAccounts: RemainAccount[] = [];
+    if (escrow.bestBid) {
+      const bestBid = await this._program.account.bidAccount.fetch(escrow.bestBid as PublicKey);
+      for (const pubkey of [
+        escrow.bestBid,
+        bestBid.bidVault,
+        bestBid.bidder,
+        bestBid.bidderReceiveAccount,
+        bestBid.bidderSendAccount,
+      ]) {
+        remainingAccounts.push({ pubkey: pubkey as PublicKey, isWritable: true, isSigner: false });
+      }
+    }
+    remainingAccounts.push(
+      ...(await this.listingAccounts(
+        undefined,
+        (escrow.creatorSendTokenMint as PublicKey) ?? undefined,
+        (escrow.creatorReceiveTokenMint as PublicKey) ?? undefined,
+        (escrow.marketPage as number) ?? undefined
+      ))
+    );
+    const transaction = await this._program.methods
+      .settleAuction()
+      .accounts({
+        escrowState: stateAccount,
+        escrowVault: escrow.escrowVault,
+        creator: escrow.creator,
+        creatorSendAccount: escrow.creatorSendAccount,
+        // the creator wallet for SOL, as `creatorPayout`
+        creatorReceiveAccount: escrow.creatorReceiveTokenMint
+          ? escrow.creatorReceiveAccount
+          : escrow.creator,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(remainingAccounts)
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // offer `value` of `mint` (null for SOL) for an order, paid from
+  // `partnerInfo.partnerSendAccount`. The creator may accept it with
+  // `acceptOffer`, which sends the order's vault to
+  // `partnerInfo.partnerReceiveAccount`. One open offer per partner and order.
+  // On a request for quote this is a quote, firm until its deadline
+  async makeOffer(
+    order: TradeInfo,
+    partnerInfo: PartnerInfo,
+    mint: PublicKey | null,
+    value: number
+  ): Promise<Buffer> {
+    const { stateAccount, escrow } = await this.fetchOrder(order);
+    const offer = await this.findOfferAccount(stateAccount, partnerInfo.partner);
+    const [offerVault, vaultBump] = await PublicKey.findProgramAddress(
+      [Buffer.from(OFFER_VAULT_SEED), offer.toBuffer()],
+      this.programId
+    );
+    // the mints of the partner's side as in `createTrade`
+    const remainingAccounts: RemainAccount[] = [];
+    for (const each of [mint, escrow.creatorSendTokenMint]) {
+      if (each) {
+        remainingAccounts.push({ pubkey: each as PublicKey, isWritable: true, isSigner: false });
+      }
+    }
+    // the best quote it has to beat on a best-price request for quote
+    if (escrow.bestQuote) {
+      remainingAccounts.push({ pubkey: escrow.bestQuote as PublicKey, isWritable: false, isSigner: false });
+    }
+    const transaction = await this._program.methods
+      .makeOffer(new BN(value), vaultBump)
+      .accounts({
+        escrowState: stateAccount,
+        offer,
+        offerVault,
+        partner: partnerInfo.partner,
+        partnerSendAccount: partnerInfo.partnerSendAccount,
+        partnerReceiveAccount: partnerInfo.partnerReceiveAccount,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        rent: SYSVAR_RENT_PUBKEY,
+      })
+      .remainingAccounts(remainingAccounts)
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // the creator swaps the order with the offer of `partner`, received as
+  // `offerPayout` says
+  async acceptOffer(order: TradeInfo, partner: PublicKey): Promise<Buffer> {
+    const { stateAccount, escrow } = await this.fetchOrder(order);
+    const offer = await this.findOfferAccount(stateAccount, partner);
+    const offerAccount = await this._program.account.offerAccount.fetch(offer);
+    const creatorOfferAccount = await this.offerPayout(escrow, offerAccount);
+    const transaction = await this._program.methods
+      .acceptOffer()
+      .accounts({
+        escrowState: stateAccount,
+        escrowVault: escrow.escrowVault,
+        creator: escrow.creator,
+        creatorOfferAccount,
+        offer,
+        offerVault: offerAccount.offerVault,
+        partner,
+        partnerReceiveAccount: offerAccount.partnerReceiveAccount,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(
+        await this.listingAccounts(
+          (escrow.specifyPartner as PublicKey) ?? undefined,
+          (escrow.creatorSendTokenMint as PublicKey) ?? undefined,
+          (escrow.creatorReceiveTokenMint as PublicKey) ?? undefined,
+          (escrow.marketPage as number) ?? undefined
+        )
+      )
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // `partner` takes back its offer on an order. The quotes of a request for
+  // quote stay until its deadline, the best one of a best-price request until
+  // `settleRfq`
+  async withdrawOffer(order: TradeInfo, partner: PublicKey): Promise<Buffer> {
+    const { address: stateAccount } = await findPdaAccount(
+      this.programId,
+      STATE_SEED,
+      order.creator,
+      order.orderId
+    );
+    const offer = await this.findOfferAccount(stateAccount, partner);
+    const offerAccount = await this._program.account.offerAccount.fetch(offer);
+    const transaction = await this._program.methods
+      .withdrawOffer()
+      .accounts({
+        escrowState: stateAccount,
+        offer,
+        offerVault: offerAccount.offerVault,
+        partner,
+        partnerSendAccount: offerAccount.partnerSendAccount,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .transaction();
+    // the partner only has to sign while the order is open
+    for (const key of transaction.instructions[0].keys) {
+      if (key.pubkey.equals(partner)) {
+        key.isSigner = true;
+      }
+    }
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // refund the offer of `partner` on an order that is no longer open. Anyone can send it
+  async refundOffer(order: TradeInfo, partner: PublicKey): Promise<Buffer> {
+    const { address: stateAccount } = await findPdaAccount(
+      this.programId,
+      STATE_SEED,
+      order.creator,
+      order.orderId
+    );
+    const offer = await this.findOfferAccount(stateAccount, partner);
+    const offerAccount = await this._program.account.offerAccount.fetch(offer);
+    const transaction = await this._program.methods
+      .withdrawOffer()
+      .accounts({
+        escrowState: stateAccount,
+        offer,
+        offerVault: offerAccount.offerVault,
+        partner,
+        partnerSendAccount: offerAccount.partnerSendAccount,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // once quoting is over, swap a best-price request for quote with its best
+  // quote, received as with `acceptOffer`. Anyone can send it
+  async settleRfq(order: TradeInfo): Promise<Buffer> {
+    const { stateAccount, escrow } = await this.fetchOrder(order);
+    if (!escrow.bestQuote) {
+      throw new Error("order has no best quote");
+    }
+    const offer = escrow.bestQuote as PublicKey;
+    const offerAccount = await this._program.account.offerAccount.fetch(offer);
+    const creatorOfferAccount = await this.offerPayout(escrow, offerAccount);
+    const transaction = await this._program.methods
+      .settleRfq()
+      .accounts({
+        escrowState: stateAccount,
+        escrowVault: escrow.escrowVault,
+        creator: escrow.creator,
+        creatorOfferAccount,
+        offer,
+        offerVault: offerAccount.offerVault,
+        partner: offerAccount.partner,
+        partnerReceiveAccount: offerAccount.partnerReceiveAccount,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(
+        await this.listingAccounts(
+          (escrow.specifyPartner as PublicKey) ?? undefined,
+          (escrow.creatorSendTokenMint as PublicKey) ?? undefined,
+          (escrow.creatorReceiveTokenMint as PublicKey) ?? undefined,
+          (escrow.marketPage as number) ?? undefined
+        )
+      )
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // make a stop-loss or take-profit order ready for exchange once the price of
+  // its feed has reached the trigger price. Anyone can send it
+  async trigger(order: TradeInfo): Promise<Buffer> {
+    const { stateAccount, escrow } = await this.fetchOrder(order);
+    if (!escrow.trigger) {
+      throw new Error("order has no trigger");
+    }
+    const transaction = await this._program.methods
+      .trigger()
+      .accounts({
+        escrowState: stateAccount,
+        oracle: escrow.trigger.oracle,
+      })
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // grow a deal of the first release to the current layout, `payer` paying
+  // its extra rent. The deal cannot be fetched before, so it is found by address
+  async migrateEscrow(order: TradeInfo, payer: PublicKey): Promise<Buffer> {
+    const { address: stateAccount } = await findPdaAccount(
+      this.programId,
+      STATE_SEED,
+      order.creator,
+      order.orderId
+    );
+    const transaction = await this._program.methods
+      .migrateEscrow()
+      .accounts({
+        escrowState: stateAccount,
+        creator: order.creator,
+        userAccount: await this.findUserAccount(order.creator),
+        payer: payer,
+        systemProgram: SystemProgram.programId,
+      })
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // every open offer on an order
+  async getOffers(order: TradeInfo) {
+    const { stateAccount } = await this.fetchOrder(order);
+    // `escrowState` is the first field after the discriminator
+    return await this._program.account.offerAccount.all([
+      { memcmp: { offset: 8, bytes: stateAccount.toBase58() } },
+    ]);
+  }
+
+  // cancel `old` and create `tradeOrderRequest` in its place in one transaction.
+  // When both are funded from the same account, the old vault moves straight to
+  // the new one and only the difference is paid or refunded
+  async replaceTrade(
+    old: ReplaceParams,
+    tradeOrderRequest: TradeOrderRequest
+  ): Promise<Buffer> {
+    if (!old.creator.equals(tradeOrderRequest.creator)) {
+      throw new Error("Order of another creator");
+    }
+    const { address: stateAccount } = await findPdaAccount(
+      this.programId,
+      STATE_SEED,
+      old.creator,
+      old.orderId
+    );
+    const { address: vaultAccount } = await findPdaAccount(
+      this.programId,
+      VAULT_SEED,
+      old.creator,
+      old.orderId
+    );
+    const { address: newStateAccount } = await findPdaAccount(
+      this.programId,
+      STATE_SEED,
+      tradeOrderRequest.creator,
+      tradeOrderRequest.orderId
+    );
+    const { address: newVaultAccount, bump: newVaultBump } =
+      await findPdaAccount(
+        this.programId,
+        VAULT_SEED,
+        tradeOrderRequest.creator,
+        tradeOrderRequest.orderId
+      );
+    // the inbox and market page the old order leaves, then as `createTrade`
+    const remainingAccounts: RemainAccount[] = await this.listingAccounts(
+      old.specifyPartner,
+      old.tradeType === TradeType.SOLSPL ? undefined : old.tradeMint,
+      old.receiveMint,
+      old.marketPage
+    );
+    for (const mint of [tradeOrderRequest.tradeMint, tradeOrderRequest.receiveMint]) {
+      if (mint) {
+        remainingAccounts.push({ pubkey: mint, isWritable: true, isSigner: false });
+      }
+    }
+    if (tradeOrderRequest.specifyPartner) {
+      remainingAccounts.push({
+        pubkey: await findInboxAccount(
+          this.programId,
+          tradeOrderRequest.specifyPartner
+        ),
+        isWritable: true,
+        isSigner: false,
+      });
+    }
+    if (tradeOrderRequest.marketPage !== undefined) {
+      remainingAccounts.push({
+        pubkey: await findMarketAccount(
+          this.programId,
+          tradeOrderRequest.tradeMint,
+          tradeOrderRequest.receiveMint,
+          tradeOrderRequest.marketPage
+        ),
+        isWritable: true,
+        isSigner: false,
+      });
+    }
+    remainingAccounts.push(...paymentOptionAccounts(tradeOrderRequest.paymentOptions));
+    if (tradeOrderRequest.priceGuard) {
+      remainingAccounts.push({
+        pubkey: tradeOrderRequest.priceGuard.oracle,
+        isWritable: false,
+        isSigner: false,
+      });
+    }
+    const params: CreateInstructionParams = {
+      specifyPartner: tradeOrderRequest.specifyPartner
+        ? tradeOrderRequest.specifyPartner
+        : null,
+      tradeValue: new BN(tradeOrderRequest.tradeValue),
+      receiveValue: new BN(tradeOrderRequest.receiveValue),
+      timestamp: new BN(tradeOrderRequest.timestamp),
+      vaultBump: new BN(newVaultBump),
+      partnerRoot: tradeOrderRequest.partnerRoot
+        ? tradeOrderRequest.partnerRoot
+        : null,
+      claimHash: tradeOrderRequest.claimHash
+        ? tradeOrderRequest.claimHash
+        : null,
+      marketPage:
+        tradeOrderRequest.marketPage !== undefined
+          ? tradeOrderRequest.marketPage
+          : null,
+      dutchAuction: tradeOrderRequest.dutchAuction
+        ? {
+            floorValue: new BN(tradeOrderRequest.dutchAuction.floorValue),
+            startTime: new BN(tradeOrderRequest.dutchAuction.startTime),
+            endTime: new BN(tradeOrderRequest.dutchAuction.endTime),
+          }
+        : null,
+      englishAuction: tradeOrderRequest.englishAuction
+        ? {
+            endTime: new BN(tradeOrderRequest.englishAuction.endTime),
+            minIncrement: new BN(tradeOrderRequest.englishAuction.minIncrement),
+          }
+        : null,
+      sealedAuction: tradeOrderRequest.sealedAuction
+        ? {
+            commitEnd: new BN(tradeOrderRequest.sealedAuction.commitEnd),
+            revealEnd: new BN(tradeOrderRequest.sealedAuction.revealEnd),
+            forfeitUnrevealed: tradeOrderRequest.sealedAuction.forfeitUnrevealed,
+          }
+        : null,
+      rfq: rfqParams(tradeOrderRequest.rfq),
+      oraclePeg: oraclePegParams(tradeOrderRequest.oraclePeg),
+      priceGuard: tradeOrderRequest.priceGuard ?? null,
+      trigger: triggerParams(tradeOrderRequest.trigger),
+      paymentOptions: paymentOptionsParams(tradeOrderRequest.paymentOptions),
+    };
+    const transaction = await this._program.methods
+      .replaceTrade(new BN(old.orderId), params)
+      .accounts({
+        escrowState: stateAccount,
+        escrowVault: vaultAccount,
+        refundAccount: old.refundAccount,
+        newEscrowState: newStateAccount,
+        newEscrowVault: newVaultAccount,
+        creator: tradeOrderRequest.creator,
+        creatorSendAccount: tradeOrderRequest.creatorSendAccount,
+        creatorReceiveAccount: tradeOrderRequest.creatorReceiveAccount,
+        feeAccount: FEE_ACCOUNT,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        rent: SYSVAR_RENT_PUBKEY,
+        userAccount: await this.findUserAccount(tradeOrderRequest.creator),
+      })
+      .remainingAccounts(remainingAccounts)
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  // cancel several orders of `creator` in one transaction. With `atomic` the
+  // transaction fails when one of them can no longer be cancelled, otherwise
+  // such orders are skipped
+  async cancelMany(
+    creator: PublicKey,
+    orders: CancelParams[],
+    atomic = true
+  ): Promise<Buffer> {
+    const remainingAccounts: RemainAccount[] = [];
+    for (const order of orders) {
+      if (!order.creator.equals(creator)) {
+        throw new Error("Order of another creator");
+      }
+      const { address: stateAccount } = await findPdaAccount(
+        this.programId,
+        STATE_SEED,
+        order.creator,
+        order.orderId
+      );
+      const { address: vaultAccount } = await findPdaAccount(
+        this.programId,
+        VAULT_SEED,
+        order.creator,
+        order.orderId
+      );
+      for (const pubkey of [stateAccount, vaultAccount, order.creatorSendAccount]) {
+        remainingAccounts.push({ pubkey, isWritable: true, isSigner: false });
+      }
+      remainingAccounts.push(
+        ...(await this.listingAccounts(
+          order.specifyPartner,
+          order.tradeType === TradeType.SOLSPL ? undefined : order.tradeMint,
+          order.receiveMint,
+          order.marketPage
+        ))
+      );
+    }
+    const transaction = await this._program.methods
+      .cancelMany(atomic)
+      .accounts({
+        creator,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(remainingAccounts)
+      .transaction();
+    return await encodeTransaction(this._connection, transaction);
+  }
+
+  async decline(declineParams: DeclineParams): Promise<Buffer> {
+    const { address: stateAccount, bump: stateBump } = await findPdaAccount(
+      this.programId,
+      STATE_SEED,
+      declineParams.creator,
+      declineParams.orderId
+    );
+    const { address: vaultAccount, bump: vaultBump } = await findPdaAccount(
+      this.programId,
+      VAULT_SEED,
+      declineParams.creator,
+      declineParams.orderId
+    );
+    const accounts = {
+      escrowState: stateAccount,
+      escrowVault: vaultAccount,
+      creatorSendAccount: declineParams.creatorSendAccount,
+      creator: declineParams.creator,
+      partner: declineParams.partner,
+      systemProgram: SystemProgram.programId,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+    const transaction = await this._program.methods
+      .decline(
+        new BN(declineParams.orderId),
+        new BN(stateBump),
+        new BN(vaultBump)
+      )
+      .accounts(accounts)
+      .remainingAccounts(
+        await this.listingAccounts(
+          declineParams.partner,
+          declineParams.tradeMint,
+          declineParams.receiveMint,
+          declineParams.marketPage
+        )
+      )
       .transaction();
     return await encodeTransaction(this._connection, transaction);
   }
+
+  // inbox and market page a closed order is dropped from
+  // account the creator is paid an offer into, as `EscrowAccount::offer_payout`:
+  // the order's receive account in its receive mint, the creator's associated
+  // token account in another mint, the creator wallet for SOL
+  private async offerPayout(escrow: any, offerAccount: any): Promise<PublicKey> {
+    const offerMint = (offerAccount.offerMint as PublicKey) ?? null;
+    const receiveMint = (escrow.creatorReceiveTokenMint as PublicKey) ?? null;
+    const creator = escrow.creator as PublicKey;
+    if (offerMint === null) {
+      return creator;
+    }
+    if (receiveMint !== null && offerMint.equals(receiveMint)) {
+      return escrow.creatorReceiveAccount as PublicKey;
+    }
+    return await getAssociatedTokenAddress(offerMint, creator);
+  }
+
+  private async fetchOrder(order: TradeInfo) {
+    const { address: stateAccount } = await findPdaAccount(
+      this.programId,
+      STATE_SEED,
+      order.creator,
+      order.orderId
+    );
+    const escrow = await this._program.account.escrowAccount.fetch(stateAccount);
+    return { stateAccount, escrow };
+  }
+
+  // the accounts of `placeBid` and `commitBid` for the order's next bid, and the
+  // mints of the bidder's side as in `createTrade`
+  private async newBidAccounts(
+    stateAccount: PublicKey,
+    escrow: any,
+    bidderInfo: PartnerInfo
+  ) {
+    const bidCount = new BN(escrow.bidCount as number).toBuffer("le", 4);
+    const [bidAccount] = await PublicKey.findProgramAddress(
+      [Buffer.from(BID_SEED), stateAccount.toBuffer(), bidCount],
+      this.programId
+    );
+    const [bidVault, vaultBump] = await PublicKey.findProgramAddress(
+      [Buffer.from(BID_VAULT_SEED), bidAccount.toBuffer()],
+      this.programId
+    );
+    const mints: RemainAccount[] = [];
+    for (const mint of [escrow.creatorReceiveTokenMint, escrow.creatorSendTokenMint]) {
+      if (mint) {
+        mints.push({ pubkey: mint as PublicKey, isWritable: true, isSigner: false });
+      }
+    }
+    const accounts = {
+      escrowState: stateAccount,
+      bid: bidAccount,
+      bidVault,
+      bidder: bidderInfo.partner,
+      bidderSendAccount: bidderInfo.partnerSendAccount,
+      bidderReceiveAccount: bidderInfo.partnerReceiveAccount,
+      systemProgram: SystemProgram.programId,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      rent: SYSVAR_RENT_PUBKEY,
+    };
+    return { accounts, mints, vaultBump };
+  }
+
+  private async findOfferAccount(stateAccount: PublicKey, partner: PublicKey) {
+    const [offer] = await PublicKey.findProgramAddress(
+      [Buffer.from(OFFER_SEED), stateAccount.toBuffer(), partner.toBuffer()],
+      this.programId
+    );
+    return offer;
+  }
+
+  // the order's best bid as `placeBid` and `revealBid` refund it
+  private async bestBidAccounts(escrow: any): Promise<RemainAccount[]> {
+    if (!escrow.bestBid) {
+      return [];
+    }
+    const bestBid = await this._program.account.bidAccount.fetch(escrow.bestBid as PublicKey);
+    return [escrow.bestBid, bestBid.bidVault, bestBid.bidder, bestBid.bidderSendAccount].map(
+      (pubkey) => ({ pubkey: pubkey as PublicKey, isWritable: true, isSigner: false })
+    );
+  }
+
+  private async listingAccounts(
+    specifyPartner: PublicKey | undefined,
+    tradeMint: PublicKey | undefined,
+    receiveMint: PublicKey | undefined,
+    marketPage: number | undefined
+  ): Promise<RemainAccount[]> {
+    const accounts: RemainAccount[] = [];
+    if (specifyPartner) {
+      accounts.push({
+        pubkey: await findInboxAccount(this.programId, specifyPartner),
+        isWritable: true,
+        isSigner: false,
+      });
+    }
+    if (marketPage !== undefined) {
+      accounts.push({
+        pubkey: await findMarketAccount(
+          this.programId,
+          tradeMint,
+          receiveMint,
+          marketPage
+        ),
+        isWritable: true,
+        isSigner: false,
+      });
+    }
+    return accounts;
+  }
 }
@@ -1,8 +1,15 @@
-import { PublicKey } from "@solana/web3.js";
+import { Keypair, PublicKey } from "@solana/web3.js";
 import BN from "bn.js";
 
 export const STATE_SEED = "state";
 export const VAULT_SEED = "vault";
+export const INBOX_SEED = "inbox";
+export const MARKET_SEED = "market";
+export const USER_SEED = "user";
+export const BID_SEED = "bid";
+export const BID_VAULT_SEED = "bid_vault";
+export const OFFER_SEED = "offer";
+export const OFFER_VAULT_SEED = "offer_vault";
 
 export type RemainAccount = {
   pubkey: PublicKey;
@@ -19,14 +26,57 @@ export type InstructionCreateAccounts = {
   systemProgram: PublicKey;
   tokenProgram: PublicKey;
   rent: PublicKey;
+  userAccount: PublicKey;
 };
 export type CreateInstructionParams = {
-  orderId: BN;
   specifyPartner?: PublicKey;
   tradeValue: BN;
   receiveValue: BN;
   timestamp: BN;
   vaultBump: BN;
+  partnerRoot: number[] | null;
+  claimHash: number[] | null;
+  marketPage: number | null;
+  dutchAuction: {
+    floorValue: BN;
+    startTime: BN;
+    endTime: BN;
+  } | null;
+  englishAuction: {
+    endTime: BN;
+    minIncrement: BN;
+  } | null;
+  sealedAuction: {
+    commitEnd: BN;
+    revealEnd: BN;
+    forfeitUnrevealed: boolean;
+  } | null;
+  rfq: {
+    deadline: BN;
+    extraMints: PublicKey[];
+    extraMintCount: number;
+    bestPrice: boolean;
+  } | null;
+  oraclePeg: {
+    oracle: PublicKey;
+    spreadBps: number;
+    minValue: BN;
+    maxValue: BN;
+    maxAge: number;
+    maxConfidenceBps: number;
+    decimalShift: number;
+  } | null;
+  priceGuard: PriceGuard | null;
+  trigger: {
+    oracle: PublicKey;
+    triggerPrice: BN;
+    direction: { atOrAbove: {} } | { atOrBelow: {} };
+    maxAge: number;
+  } | null;
+  paymentOptions: {
+    options: { mint: PublicKey; receiveValue: BN; receiveAccount: PublicKey }[];
+    count: number;
+  } | null;
 };
 
 export enum TradeType {
@@ -37,6 +87,8 @@ export enum TradeType {
 
 export type TradeOrderRequest = {
   creator: PublicKey;
+  // the next id of the creator's counter, which the program assigns and the
+  // addresses are derived from, see `TradeP2P.nextOrderId`
   orderId: number;
   specifyPartner?: PublicKey;
   tradeValue: number;
@@ -47,20 +99,176 @@ export type TradeOrderRequest = {
   receiveMint?: PublicKey;
   timestamp: string;
   tradeType: TradeType;
+  // merkle root of allowed partners, see `utils::partner_leaf` in the program
+  partnerRoot?: number[];
+  // keccak("claim" + claim public key), the claim keypair is shared with the partner
+  claimHash?: number[];
+  // page of the pair's on-chain market to list the order on
+  marketPage?: number;
+  // sell by dutch auction, starting at `receiveValue`
+  dutchAuction?: DutchAuction;
+  // sell by english auction, `receiveValue` being the reserve price
+  englishAuction?: EnglishAuction;
+  // sell by sealed-bid auction, `receiveValue` being the reserve price
+  sealedAuction?: SealedAuction;
+  // sell to one of the quotes made on the order, `receiveValue` being 0
+  rfq?: Rfq;
+  // sell at the price of a feed, `receiveValue` being recomputed at each exchange
+  oraclePeg?: OraclePeg;
+  // refuse a `receiveValue` too far from a reference price, see `PriceGuard`
+  priceGuard?: PriceGuard;
+  // stop-loss or take-profit order, only exchanged once `TradeP2P.trigger` has fired it
+  trigger?: Trigger;
+  // other mints the order may be paid in, each at its own price (at most 3)
+  paymentOptions?: PaymentOption[];
 };
 
+// price falling linearly from the order's receive value at `startTime` to
+// `floorValue` at `endTime` (unix seconds), see `dutchAuctionPrice`
+export type DutchAuction = {
+  floorValue: number;
+  startTime: number;
+  endTime: number;
+};
+
+// bids are taken until `endTime` (unix seconds), each raising the best one by
+// at least `minIncrement`, then `settleAuction` swaps the order with the best bid
+export type EnglishAuction = {
+  endTime: number;
+  minIncrement: number;
+};
+
+// bids are committed until `commitEnd` and revealed until `revealEnd` (unix
+// seconds), then `settleAuction` swaps the order with the best revealed bid.
+// Deposits of bids never revealed go to the creator with `forfeitUnrevealed`,
+// back to their bidder otherwise, see `reclaimBid`
+export type SealedAuction = {
+  commitEnd: number;
+  revealEnd: number;
+  forfeitUnrevealed: boolean;
+};
+
+// quotes are offers, see `TradeP2P.makeOffer`, taken until `deadline` (unix
+// seconds) in the receive mint or one of `extraMints` (null for SOL, at most 3).
+// The creator then accepts one with `acceptOffer`, or with `bestPrice` the
+// highest quote in the receive mint fills the order through `settleRfq`
+export type Rfq = {
+  deadline: number;
+  extraMints: (PublicKey | null)[];
+  bestPrice: boolean;
+};
+
+// `oracle` is a Pyth-format price account giving the price of one send token
+// in receive tokens. Orders are exchanged at that price times `spreadBps` /
+// 10_000, bounded by `minValue` and `maxValue`. Prices older than `maxAge`
+// seconds or with a confidence interval wider than `maxConfidenceBps` of the
+// price are refused. The program adjusts for the decimals of the mints
+export type OraclePeg = {
+  oracle: PublicKey;
+  spreadBps: number;
+  minValue: number;
+  maxValue: number;
+  maxAge: number;
+  maxConfidenceBps: number;
+};
+
+// fat-finger check when the order is created: `receiveValue`, and the floor of
+// a dutch auction, must be within `maxDeviationBps` of `tradeValue` at the
+// price of `oracle`, a Pyth-format price account as in `OraclePeg`, no older
+// than `maxAge` seconds. `overrideDeviation` creates the order anyway
+export type PriceGuard = {
+  oracle: PublicKey;
+  maxDeviationBps: number;
+  maxAge: number;
+  overrideDeviation: boolean;
+};
+
+// stop-loss or take-profit condition: the order waits until the price of
+// `oracle`, a Pyth-format price account as in `OraclePeg`, reaches
+// `triggerPrice` in the units of the feed, rising to it with `above` and
+// falling to it otherwise. Prices older than `maxAge` seconds do not fire it
+export type Trigger = {
+  oracle: PublicKey;
+  triggerPrice: number;
+  above: boolean;
+  maxAge: number;
+};
+
+// `receiveValue` of `mint` (null for SOL) paid into the creator's
+// `receiveAccount`, a token account of the mint or the creator wallet for SOL.
+// A partner pays in it by exchanging with `receiveAccount` as the
+// `creatorReceiveAccount` of the order
+export type PaymentOption = {
+  mint: PublicKey | null;
+  receiveValue: number;
+  receiveAccount: PublicKey;
+};
+
+// a ladder of orders on one pair, funded from the same account. They take
+// consecutive order ids from `orderId`
+export type CreateManyRequest = {
+  creator: PublicKey;
+  // the next id of the creator's counter, which the program assigns and the
+  // addresses are derived from, see `TradeP2P.nextOrderId`
+  orderId: number;
+  creatorSendAccount: PublicKey;
+  creatorReceiveAccount: PublicKey;
+  tradeMint?: PublicKey;
+  receiveMint?: PublicKey;
+  levels: { tradeValue: number; receiveValue: number }[];
+  timestamp: string;
+  // page of the pair's on-chain market to list every order on
+  marketPage?: number;
+};
 
 export type TradeInfo = {
   creator: PublicKey;
   creatorSendAccount: PublicKey;
+  // or the `receiveAccount` of a payment option to pay in its mint, `tradeType`
+  // then being the type of the trade as paid
   creatorReceiveAccount: PublicKey;
   orderId: number;
   tradeType: TradeType;
   valueTrade?: number;
   valueReceive?: number;
   specifyPartner?: PublicKey;
+  partnerRoot?: number[];
+  tradeMint?: PublicKey;
+  receiveMint?: PublicKey;
+  marketPage?: number;
+  // price feed of an oracle-pegged order
+  oracle?: PublicKey;
+};
+
+// highest average price `exchangeMany` pays: `receiveValue` paid for each `tradeValue` received
+export type PriceCap = {
+  receiveValue: number;
+  tradeValue: number;
+};
+
+// who settles two orders with `matchOrders` and where the fees are paid: a token
+// account of the mint each order sends, or the matcher wallet for SOL
+export type MatcherInfo = {
+  matcher: PublicKey;
+  matcherAAccount: PublicKey;
+  matcherBAccount: PublicKey;
+};
+
+export type DeclineParams = {
+  creator: PublicKey;
+  orderId: number;
+  creatorSendAccount: PublicKey;
+  partner: PublicKey;
+  // needed to drop a listed order from its market page
   tradeMint?: PublicKey;
   receiveMint?: PublicKey;
+  marketPage?: number;
+};
+
+// the order `replaceTrade` cancels, the new one is a `TradeOrderRequest`
+export type ReplaceParams = CancelParams & {
+  // the account the old order was funded from, refunded what is not moved
+  refundAccount: PublicKey;
 };
 
 export type CancelParams = {
@@ -69,8 +277,10 @@ export type CancelParams = {
   creatorSendAccount: PublicKey;
   tradeMint: PublicKey;
   tradeType: TradeType;
-  
-
+  // needed to drop the order from the partner's inbox and its market page
+  specifyPartner?: PublicKey;
+  receiveMint?: PublicKey;
+  marketPage?: number;
 }
 
 
@@ -78,4 +288,11 @@ export type PartnerInfo = {
   partner: PublicKey;
   partnerSendAccount: PublicKey;
   partnerReceiveAccount: PublicKey;
+  // proof that `partner` is in the order's allowlist
+  proof?: number[][];
+  // keypair derived from the claim code of claim-code orders
+  claimKeypair?: Keypair;
+  // most `exchange` pays for a dutch auction or oracle-pegged order, whose
+  // price is only known when the transaction lands
+  maxReceiveValue?: number;
 }
\ No newline at end of file
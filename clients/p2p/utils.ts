@@ -5,11 +5,53 @@ import {
   TokenInvalidAccountOwnerError,
 } from "@solana/spl-token";
 import BN from "bn.js";
+import {
+  DutchAuction,
+  INBOX_SEED,
+  MARKET_SEED,
+  OraclePeg,
+  PaymentOption,
+  Rfq,
+  TradeInfo,
+  TradeType,
+  Trigger,
+} from "./types";
+import { NATIVE_MINT } from "@solana/spl-token";
 
 export function delay(ms: number) {
   return new Promise((resolve) => setTimeout(resolve, ms));
 }
 
+// price a dutch auction order is filled at, at unix time `now` (seconds), as
+// `EscrowAccount::current_receive_value` in the program prices it
+export function dutchAuctionPrice(
+  startValue: number,
+  auction: DutchAuction,
+  now: number
+): BN {
+  const start = new BN(startValue);
+  const floor = new BN(auction.floorValue);
+  if (now <= auction.startTime || start.lte(floor)) {
+    return start;
+  }
+  if (now >= auction.endTime) {
+    return floor;
+  }
+  // the decay is rounded down, in favor of the creator
+  const decay = start
+    .sub(floor)
+    .mul(new BN(now - auction.startTime))
+    .div(new BN(auction.endTime - auction.startTime));
+  return start.sub(decay);
+}
+
+// account an order pays its creator into, as `EscrowAccount::creator_payout`:
+// the creator wallet when it receives SOL, its `creatorReceiveAccount` otherwise
+export function creatorPayout(order: TradeInfo): anchor.web3.PublicKey {
+  return order.tradeType === TradeType.SPLSOL
+    ? order.creator
+    : order.creatorReceiveAccount;
+}
 
 export async function findPdaAccount(
   programId: anchor.web3.PublicKey,
@@ -28,9 +70,118 @@ export async function findPdaAccount(
   };
 }
 
+export async function findInboxAccount(
+  programId: anchor.web3.PublicKey,
+  partner: anchor.web3.PublicKey
+): Promise<anchor.web3.PublicKey> {
+  const [address] = await anchor.web3.PublicKey.findProgramAddress(
+    [Buffer.from(INBOX_SEED), partner.toBuffer()],
+    programId
+  );
+  return address;
+}
+
+// SOL sides of a pair use the native mint
+export async function findMarketAccount(
+  programId: anchor.web3.PublicKey,
+  sendMint: anchor.web3.PublicKey | undefined,
+  receiveMint: anchor.web3.PublicKey | undefined,
+  page: number
+): Promise<anchor.web3.PublicKey> {
+  const [address] = await anchor.web3.PublicKey.findProgramAddress(
+    [
+      Buffer.from(MARKET_SEED),
+      (sendMint ?? NATIVE_MINT).toBuffer(),
+      (receiveMint ?? NATIVE_MINT).toBuffer(),
+      new BN(page).toBuffer("le", 2),
+    ],
+    programId
+  );
+  return address;
+}
+
+// `rfq` terms of `createTrade`, SOL quotes using the native mint
+export function rfqParams(rfq: Rfq | undefined) {
+  if (!rfq) {
+    return null;
+  }
+  if (rfq.extraMints.length > 3) {
+    throw new Error("at most 3 extra quote mints");
+  }
+  const extraMints = rfq.extraMints.map((mint) => mint ?? NATIVE_MINT);
+  while (extraMints.length < 3) {
+    extraMints.push(anchor.web3.PublicKey.default);
+  }
+  return {
+    deadline: new BN(rfq.deadline),
+    extraMints,
+    extraMintCount: rfq.extraMints.length,
+    bestPrice: rfq.bestPrice,
+  };
+}
+
+// `oraclePeg` terms of `createTrade`, the decimal shift being set by the program
+export function oraclePegParams(peg: OraclePeg | undefined) {
+  if (!peg) {
+    return null;
+  }
+  return {
+    oracle: peg.oracle,
+    spreadBps: peg.spreadBps,
+    minValue: new BN(peg.minValue),
+    maxValue: new BN(peg.maxValue),
+    maxAge: peg.maxAge,
+    maxConfidenceBps: peg.maxConfidenceBps,
+    decimalShift: 0,
+  };
+}
+
+// `trigger` terms of `createTrade`
+export function triggerParams(trigger: Trigger | undefined) {
+  if (!trigger) {
+    return null;
+  }
+  return {
+    oracle: trigger.oracle,
+    triggerPrice: new BN(trigger.triggerPrice),
+    direction: trigger.above ? { atOrAbove: {} } : { atOrBelow: {} },
+    maxAge: trigger.maxAge,
+  };
+}
+
+// `paymentOptions` terms of `createTrade`, padded to the 3 options of the account
+export function paymentOptionsParams(options: PaymentOption[] | undefined) {
+  if (!options || options.length === 0) {
+    return null;
+  }
+  const padded = options.map((option) => ({
+    mint: option.mint ?? NATIVE_MINT,
+    receiveValue: new BN(option.receiveValue),
+    receiveAccount: option.receiveAccount,
+  }));
+  while (padded.length < 3) {
+    padded.push({
+      mint: anchor.web3.PublicKey.default,
+      receiveValue: new BN(0),
+      receiveAccount: anchor.web3.PublicKey.default,
+    });
+  }
+  return { options: padded, count: options.length };
+}
+
+// receive accounts of the payment options, passed to `createTrade` after the listing accounts
+export function paymentOptionAccounts(options: PaymentOption[] | undefined) {
+  return (options ?? []).map((option) => ({
+    pubkey: option.receiveAccount,
+    isWritable: false,
+    isSigner: false,
+  }));
+}
+
 export async function encodeTransaction(
   connection: anchor.web3.Connection,
-  transaction: anchor.web3.Transaction
+  transaction: anchor.web3.Transaction,
+  coSigners: anchor.web3.Keypair[] = []
 ): Promise<Buffer> {
   transaction.recentBlockhash = (
     await connection.getLatestBlockhash()
@@ -40,6 +191,9 @@ export async function encodeTransaction(
     .map((item) => {
       return item.pubkey;
     })[0];
+  if (coSigners.length > 0) {
+    transaction.partialSign(...coSigners);
+  }
   return transaction.serialize({ requireAllSignatures: false });
 }
 
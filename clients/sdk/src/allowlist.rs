@@ -0,0 +1,118 @@
+use crate::error::{ClientError, Result};
+
+use anchor_lang::solana_program::pubkey::Pubkey;
+use std::path::Path;
+use std::str::FromStr;
+use trade_p2p::utils::{merkle_parent, partner_leaf};
+
+// merkle tree over the partners allowed to take an order, matching the
+// proof check of the `exchange` instruction
+#[derive(Clone, Debug)]
+pub struct PartnerAllowlist {
+  partners: Vec<Pubkey>,
+  // leaves first, root last
+  layers: Vec<Vec<[u8; 32]>>,
+}
+
+impl PartnerAllowlist {
+  pub fn new(partners: &[Pubkey]) -> Result<PartnerAllowlist> {
+    let mut partners = partners.to_vec();
+    partners.sort();
+    partners.dedup();
+    if partners.is_empty() {
+      return Err(ClientError::InvalidTrade("partner allowlist is empty".to_string()));
+    }
+    let mut leaves = partners.iter().map(partner_leaf).collect::<Vec<_>>();
+    leaves.sort();
+    let mut layers = vec![leaves];
+    while layers.last().unwrap().len() > 1 {
+      // an odd node out is carried up unchanged
+      let next = layers
+        .last()
+        .unwrap()
+        .chunks(2)
+        .map(|pair| match pair {
+          [a, b] => merkle_parent(a, b),
+          [a] => *a,
+          _ => unreachable!(),
+        })
+        .collect();
+      layers.push(next);
+    }
+    Ok(PartnerAllowlist { partners, layers })
+  }
+
+  // one base58 public key per line, blank lines and `#` comments are skipped
+  pub fn read_from_file<P: AsRef<Path>>(path: P) -> Result<PartnerAllowlist> {
+    let content = std::fs::read_to_string(path)?;
+    let partners = content
+      .lines()
+      .map(|line| line.split('#').next().unwrap_or_default().trim())
+      .filter(|line| !line.is_empty())
+      .map(|line| Pubkey::from_str(line).map_err(|_| ClientError::InvalidPubkey(line.to_string())))
+      .collect::<Result<Vec<_>>>()?;
+    PartnerAllowlist::new(&partners)
+  }
+
+  pub fn partners(&self) -> &[Pubkey] {
+    &self.partners
+  }
+
+  pub fn root(&self) -> [u8; 32] {
+    self.layers.last().unwrap()[0]
+  }
+
+  // sibling hashes from the partner's leaf up to the root
+  pub fn proof(&self, partner: &Pubkey) -> Option<Vec<[u8; 32]>> {
+    let mut index = self.layers[0].binary_search(&partner_leaf(partner)).ok()?;
+    let mut proof = Vec::new();
+    for layer in &self.layers[..self.layers.len() - 1] {
+      if let Some(sibling) = layer.get(index ^ 1) {
+        proof.push(*sibling);
+      }
+      index /= 2;
+    }
+    Some(proof)
+  }
+}
+
+pub fn encode_root(root: &[u8; 32]) -> String {
+  root.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use trade_p2p::utils::verify_merkle_proof;
+
+  #[test]
+  fn proofs_verify_for_every_partner() {
+    for size in 1..=9 {
+      let partners = (0..size).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+      let allowlist = PartnerAllowlist::new(&partners).unwrap();
+      for partner in &partners {
+        let proof = allowlist.proof(partner).unwrap();
+        assert!(verify_merkle_proof(&proof, &allowlist.root(), partner_leaf(partner)));
+      }
+    }
+  }
+
+  #[test]
+  fn outsiders_have_no_proof() {
+    let partners = (0..5).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+    let allowlist = PartnerAllowlist::new(&partners).unwrap();
+    let outsider = Pubkey::new_unique();
+    assert_eq!(allowlist.proof(&outsider), None);
+    let borrowed = allowlist.proof(&partners[0]).unwrap();
+    assert!(!verify_merkle_proof(&borrowed, &allowlist.root(), partner_leaf(&outsider)));
+  }
+
+  #[test]
+  fn partners_are_sorted_and_deduplicated() {
+    let partner = Pubkey::new_unique();
+    let allowlist = PartnerAllowlist::new(&[partner, partner]).unwrap();
+    assert_eq!(allowlist.partners(), &[partner]);
+    assert_eq!(allowlist.root(), partner_leaf(&partner));
+    assert!(PartnerAllowlist::new(&[]).is_err());
+  }
+}
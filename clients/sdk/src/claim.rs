@@ -0,0 +1,59 @@
+use crate::error::{ClientError, Result};
+use crate::keypair::Keypair;
+
+use anchor_lang::solana_program::pubkey::Pubkey;
+use rand::{rngs::OsRng, RngCore};
+use std::fmt;
+use std::str::FromStr;
+use trade_p2p::utils::claim_hash;
+
+// secret shared with the intended partner of a claim-code order. It is the
+// seed of the claim key that must co-sign the exchange, the order only stores
+// a hash of the claim public key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ClaimCode([u8; 32]);
+
+impl ClaimCode {
+  pub fn generate() -> ClaimCode {
+    let mut seed = [0u8; 32];
+    OsRng.fill_bytes(&mut seed);
+    ClaimCode(seed)
+  }
+
+  pub fn keypair(&self) -> Keypair {
+    Keypair::from_seed(&self.0)
+  }
+
+  pub fn pubkey(&self) -> Pubkey {
+    self.keypair().pubkey()
+  }
+
+  // commitment passed to `create_trade`
+  pub fn hash(&self) -> [u8; 32] {
+    claim_hash(&self.pubkey())
+  }
+}
+
+impl fmt::Display for ClaimCode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", bs58::encode(self.0).into_string())
+  }
+}
+
+// never print the secret by accident
+impl fmt::Debug for ClaimCode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "ClaimCode({})", self.pubkey())
+  }
+}
+
+impl FromStr for ClaimCode {
+  type Err = ClientError;
+
+  fn from_str(value: &str) -> Result<ClaimCode> {
+    let invalid = || ClientError::InvalidTrade("invalid claim code".to_string());
+    let bytes = bs58::decode(value.trim()).into_vec().map_err(|_| invalid())?;
+    let seed: [u8; 32] = bytes.try_into().map_err(|_| invalid())?;
+    Ok(ClaimCode(seed))
+  }
+}
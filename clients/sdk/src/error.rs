@@ -0,0 +1,111 @@
+use thiserror::Error;
+use trade_p2p::error::EscrowError;
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+  #[error("invalid public key: {0}")]
+  InvalidPubkey(String),
+  #[error("invalid keypair file {0}: {1}")]
+  InvalidKeypair(String, String),
+  #[error("account {0} not found")]
+  AccountNotFound(String),
+  #[error("account {0} is not a trade-p2p order")]
+  InvalidOrder(String),
+  #[error("account {0} is not a trade-p2p partner inbox")]
+  InvalidInbox(String),
+  #[error("account {0} is not a trade-p2p user account")]
+  InvalidUserAccount(String),
+  #[error("account {0} is not a trade-p2p market page")]
+  InvalidMarket(String),
+  #[error("account {0} is not a trade-p2p auction bid")]
+  InvalidBid(String),
+  #[error("account {0} is not a trade-p2p offer")]
+  InvalidOffer(String),
+  #[error("account {0} is not a trading Pyth price account")]
+  InvalidOracle(String),
+  #[error("invalid order {0}, expected a state address or creator/order_id")]
+  InvalidOrderRef(String),
+  #[error("invalid trade: {0}")]
+  InvalidTrade(String),
+  #[error("account {0} is not an initialized nonce account")]
+  InvalidNonce(String),
+  #[error("invalid transaction: {0}")]
+  InvalidTransaction(String),
+  #[error("rpc request failed: {0}")]
+  Rpc(String),
+  #[error("transaction failed: {0}")]
+  TransactionFailed(String),
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+}
+
+// every variant of `EscrowError`, in declaration order so that the index
+// matches the code assigned by `#[error_code]`
+const ESCROW_ERRORS: &[EscrowError] = &[
+  EscrowError::InvalidStage,
+  EscrowError::InsufficientFunds,
+  EscrowError::InvalidMint,
+  EscrowError::MissingMint,
+  EscrowError::InvalidTradeType,
+  EscrowError::InvalidAccount,
+  EscrowError::DuplicateMint,
+  EscrowError::InvalidOwner,
+  EscrowError::InvalidPartner,
+  EscrowError::ZeroValue,
+  EscrowError::MissingParams,
+  EscrowError::PartnerNotAllowed,
+  EscrowError::InvalidClaimCode,
+  EscrowError::InvalidInbox,
+  EscrowError::InvalidMarket,
+  EscrowError::MarketFull,
+  EscrowError::InvalidOrderId,
+  EscrowError::BudgetExceeded,
+  EscrowError::PriceCapExceeded,
+  EscrowError::NothingFilled,
+  EscrowError::OrdersDoNotCross,
+  EscrowError::InvalidAuction,
+  EscrowError::AuctionOrder,
+  EscrowError::NotAnAuction,
+  EscrowError::AuctionEnded,
+  EscrowError::AuctionNotEnded,
+  EscrowError::BidTooLow,
+  EscrowError::AuctionHasBids,
+  EscrowError::InvalidCommitment,
+  EscrowError::OutsideRevealWindow,
+  EscrowError::BidAlreadyRevealed,
+  EscrowError::DepositTooLow,
+  EscrowError::InvalidOffer,
+  EscrowError::InvalidRfq,
+  EscrowError::RfqOrder,
+  EscrowError::QuotingClosed,
+  EscrowError::QuotingOpen,
+  EscrowError::MintNotApproved,
+  EscrowError::BestQuoteLocked,
+  EscrowError::InvalidOraclePeg,
+  EscrowError::InvalidOracle,
+  EscrowError::StaleOracle,
+  EscrowError::OracleConfidence,
+  EscrowError::OraclePegOrder,
+  EscrowError::PriceDeviation,
+  EscrowError::InvalidTrigger,
+  EscrowError::NotTriggered,
+  EscrowError::TriggerNotReached,
+  EscrowError::InvalidPaymentOptions,
+  EscrowError::ReceiveValueExceeded,
+  EscrowError::AlreadyMigrated,
+];
+
+pub fn escrow_error(code: u32) -> Option<EscrowError> {
+  ESCROW_ERRORS
+    .iter()
+    .copied()
+    .find(|error| u32::from(*error) == code)
+}
+
+// readable message for a custom program error code, `None` when the code does
+// not belong to `EscrowError`
+pub fn describe_error_code(code: u32) -> Option<String> {
+  escrow_error(code).map(|error| format!("{} ({}): {}", error.name(), code, error))
+}
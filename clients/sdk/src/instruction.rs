@@ -0,0 +1,1281 @@
+use crate::error::{ClientError, Result};
+use crate::sealed_bid::SealedBid;
+use crate::pda::{
+  find_bid_address, find_bid_vault_address, find_inbox_address, find_market_address, find_offer_address,
+  find_offer_vault_address, find_state_address, find_user_address, find_vault_address,
+};
+
+use anchor_lang::solana_program::{
+  instruction::{AccountMeta, Instruction},
+  pubkey::Pubkey,
+  system_program, sysvar,
+};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::{associated_token::get_associated_token_address, token};
+use trade_p2p::processor::{CreateManyParams, CreateParams, OrderLevel, PriceCap};
+use trade_p2p::state::{
+  BidAccount, DutchAuction, EnglishAuction, EscrowAccount, OfferAccount, OraclePeg, PaymentOption, PaymentOptions,
+  PriceGuard, Rfq, SealedAuction, Stage, TradeType, TriggerCondition, TriggerDirection,
+};
+use trade_p2p::market::market_mint;
+use trade_p2p::utils::{claim_hash, partner_leaf, verify_merkle_proof};
+
+// trade type implied by the mint of each side, `None` stands for SOL
+pub fn trade_type_from_mints(
+  send_mint: Option<&Pubkey>,
+  receive_mint: Option<&Pubkey>,
+) -> Result<TradeType> {
+  match (send_mint, receive_mint) {
+    (Some(send_mint), Some(receive_mint)) if send_mint == receive_mint => Err(
+      ClientError::InvalidTrade("send and receive mint must be different".to_string()),
+    ),
+    (Some(_), Some(_)) => Ok(TradeType::TokenToken),
+    (Some(_), None) => Ok(TradeType::TokenSol),
+    (None, Some(_)) => Ok(TradeType::SolToken),
+    (None, None) => Err(ClientError::InvalidTrade(
+      "at least one side of the trade must be a token mint".to_string(),
+    )),
+  }
+}
+
+// account a wallet uses for one side of a trade:
+// the wallet itself for SOL, its associated token account otherwise
+pub fn side_account(wallet: &Pubkey, mint: Option<&Pubkey>) -> Pubkey {
+  match mint {
+    Some(mint) => get_associated_token_address(wallet, mint),
+    None => *wallet,
+  }
+}
+
+pub fn create_associated_token_account(payer: &Pubkey, wallet: &Pubkey, mint: &Pubkey) -> Instruction {
+  spl_associated_token_account::instruction::create_associated_token_account(payer, wallet, mint)
+}
+
+// request-for-quote terms taking quotes until `deadline` in the order's receive
+// mint and `extra_mints` (`None` for SOL). With `best_price` anyone can settle
+// the highest quote, so every quote must be in the receive mint
+pub fn rfq_terms(deadline: i64, extra_mints: &[Option<Pubkey>], best_price: bool) -> Result<Rfq> {
+  let mut rfq = Rfq {
+    deadline,
+    extra_mints: [Pubkey::default(); 3],
+    extra_mint_count: extra_mints.len() as u8,
+    best_price,
+  };
+  if extra_mints.len() > rfq.extra_mints.len() {
+    return Err(ClientError::InvalidTrade(format!(
+      "at most {} extra quote mints",
+      rfq.extra_mints.len()
+    )));
+  }
+  if best_price && !extra_mints.is_empty() {
+    return Err(ClientError::InvalidTrade("best price quotes are all in the receive mint".to_string()));
+  }
+  for (slot, mint) in rfq.extra_mints.iter_mut().zip(extra_mints) {
+    *slot = market_mint(*mint);
+  }
+  Ok(rfq)
+}
+
+// other mints (`None` for SOL) an order of `creator` may be paid in, each with
+// its receive value, paid to the creator's wallet for SOL and associated token
+// account otherwise
+pub fn payment_options_terms(creator: &Pubkey, options: &[(Option<Pubkey>, u64)]) -> Result<PaymentOptions> {
+  let mut terms = PaymentOptions {
+    options: [PaymentOption::default(); 3],
+    count: options.len() as u8,
+  };
+  if options.is_empty() || options.len() > terms.options.len() {
+    return Err(ClientError::InvalidTrade(format!(
+      "between 1 and {} payment options",
+      terms.options.len()
+    )));
+  }
+  for (slot, (mint, receive_value)) in terms.options.iter_mut().zip(options) {
+    if *receive_value == 0 {
+      return Err(ClientError::InvalidTrade("payment option values must be above zero".to_string()));
+    }
+    *slot = PaymentOption {
+      mint: market_mint(*mint),
+      receive_value: *receive_value,
+      receive_account: side_account(creator, mint.as_ref()),
+    };
+  }
+  Ok(terms)
+}
+
+// the payment option of `order` in `mint` (`None` for SOL)
+pub fn payment_option(order: &EscrowAccount, mint: Option<&Pubkey>) -> Result<PaymentOption> {
+  let mint = market_mint(mint.copied());
+  order
+    .payment_options
+    .iter()
+    .flat_map(|options| options.accepted().iter())
+    .find(|option| option.mint == mint)
+    .copied()
+    .ok_or_else(|| ClientError::InvalidTrade(format!("order cannot be paid in {}", mint)))
+}
+
+// terms pegging an order to the price account `oracle`: `spread_bps` of the
+// price (10_000 for the price itself), bounded by `min_value` and `max_value`,
+// refusing prices older than `max_age` seconds or less confident than
+// `max_confidence_bps`. The program sets the decimal shift from the mints
+pub fn oracle_peg_terms(
+  oracle: &Pubkey,
+  spread_bps: u16,
+  (min_value, max_value): (u64, u64),
+  max_age: u32,
+  max_confidence_bps: u16,
+) -> Result<OraclePeg> {
+  if spread_bps == 0 || max_age == 0 {
+    return Err(ClientError::InvalidTrade("spread and maximum age must be above zero".to_string()));
+  }
+  if min_value == 0 || min_value > max_value {
+    return Err(ClientError::InvalidTrade(format!(
+      "invalid price bounds {}..{}",
+      min_value, max_value
+    )));
+  }
+  Ok(OraclePeg {
+    oracle: *oracle,
+    spread_bps,
+    min_value,
+    max_value,
+    max_age,
+    max_confidence_bps,
+    decimal_shift: 0,
+  })
+}
+
+// stop-loss (`AtOrBelow`) or take-profit (`AtOrAbove`) condition firing once the
+// price of `oracle`, in the units of the feed, reaches `trigger_price`, refusing
+// prices older than `max_age` seconds
+pub fn trigger_terms(
+  oracle: &Pubkey,
+  trigger_price: i64,
+  direction: TriggerDirection,
+  max_age: u32,
+) -> Result<TriggerCondition> {
+  if trigger_price <= 0 || max_age == 0 {
+    return Err(ClientError::InvalidTrade(
+      "trigger price and maximum age must be above zero".to_string(),
+    ));
+  }
+  Ok(TriggerCondition {
+    oracle: *oracle,
+    trigger_price,
+    direction,
+    max_age,
+  })
+}
+
+#[derive(Clone, Debug)]
+pub struct CreateTrade {
+  pub creator: Pubkey,
+  // `RpcClient::next_order_id` of the creator, which the program assigns and
+  // the order's addresses are derived from
+  pub order_id: u64,
+  // mint the creator sends, `None` for SOL
+  pub send_mint: Option<Pubkey>,
+  // mint the creator expects to receive, `None` for SOL
+  pub receive_mint: Option<Pubkey>,
+  pub trade_value: u64,
+  pub receive_value: u64,
+  pub specify_partner: Option<Pubkey>,
+  // root of a `PartnerAllowlist`
+  pub partner_root: Option<[u8; 32]>,
+  // `ClaimCode::hash` of the code shared with the partner
+  pub claim_hash: Option<[u8; 32]>,
+  // market page to list the order on, see `RpcClient::find_market_page`
+  pub market_page: Option<u16>,
+  // sell by dutch auction, from `receive_value` down to its floor
+  pub dutch_auction: Option<DutchAuction>,
+  // sell by english auction, to the best bid of at least `receive_value`
+  pub english_auction: Option<EnglishAuction>,
+  // sell by sealed-bid auction, to the best revealed bid of at least `receive_value`
+  pub sealed_auction: Option<SealedAuction>,
+  // sell to a quote, `receive_value` being zero, see `rfq_terms`
+  pub rfq: Option<Rfq>,
+  // sell at the price of a feed, see `oracle_peg_terms`
+  pub oracle_peg: Option<OraclePeg>,
+  // refuse a receive value too far from a reference price when creating the order
+  pub price_guard: Option<PriceGuard>,
+  // only exchanged once `trigger` has fired, see `trigger_terms`
+  pub trigger: Option<TriggerCondition>,
+  // other mints the order may be paid in, see `payment_options_terms`
+  pub payment_options: Option<PaymentOptions>,
+  pub fee_account: Pubkey,
+  pub timestamp: u64,
+}
+
+impl CreateTrade {
+  pub fn trade_type(&self) -> Result<TradeType> {
+    trade_type_from_mints(self.send_mint.as_ref(), self.receive_mint.as_ref())
+  }
+
+  pub fn creator_send_account(&self) -> Pubkey {
+    side_account(&self.creator, self.send_mint.as_ref())
+  }
+
+  pub fn creator_receive_account(&self) -> Pubkey {
+    side_account(&self.creator, self.receive_mint.as_ref())
+  }
+
+  pub fn instruction(&self, program_id: &Pubkey) -> Result<Instruction> {
+    self.trade_type()?;
+    let (escrow_state, _) = find_state_address(program_id, &self.creator, self.order_id);
+    let (escrow_vault, vault_bump) = find_vault_address(program_id, &self.creator, self.order_id);
+    let mut accounts = trade_p2p::accounts::Create {
+      escrow_state,
+      escrow_vault,
+      creator: self.creator,
+      creator_send_account: self.creator_send_account(),
+      creator_receive_account: self.creator_receive_account(),
+      fee_account: self.fee_account,
+      system_program: system_program::ID,
+      token_program: token::ID,
+      rent: sysvar::rent::ID,
+      user_account: find_user_address(program_id, &self.creator).0,
+    }
+    .to_account_metas(None);
+    accounts.extend(self.remaining_accounts(program_id));
+    let params = self.params(vault_bump);
+    Ok(Instruction {
+      program_id: *program_id,
+      accounts,
+      data: trade_p2p::instruction::CreateTrade { params }.data(),
+    })
+  }
+
+  // cancel `old` and create this order in its place in one instruction
+  pub fn replace_instruction(&self, program_id: &Pubkey, old: &EscrowAccount) -> Result<Instruction> {
+    self.trade_type()?;
+    if old.creator != self.creator {
+      return Err(ClientError::InvalidTrade("order of another creator".to_string()));
+    }
+    let (escrow_state, _) = find_state_address(program_id, &self.creator, old.order_id);
+    let (new_escrow_state, _) = find_state_address(program_id, &self.creator, self.order_id);
+    let (new_escrow_vault, vault_bump) = find_vault_address(program_id, &self.creator, self.order_id);
+    let mut accounts = trade_p2p::accounts::Replace {
+      escrow_state,
+      escrow_vault: old.escrow_vault,
+      refund_account: old.creator_send_account,
+      new_escrow_state,
+      new_escrow_vault,
+      creator: self.creator,
+      creator_send_account: self.creator_send_account(),
+      creator_receive_account: self.creator_receive_account(),
+      fee_account: self.fee_account,
+      system_program: system_program::ID,
+      token_program: token::ID,
+      rent: sysvar::rent::ID,
+      user_account: find_user_address(program_id, &self.creator).0,
+    }
+    .to_account_metas(None);
+    // the inbox and market page the old order leaves, then as `create_trade`
+    accounts.extend(listing_accounts(program_id, old));
+    accounts.extend(self.remaining_accounts(program_id));
+    Ok(Instruction {
+      program_id: *program_id,
+      accounts,
+      data: trade_p2p::instruction::ReplaceTrade {
+        _old_order_id: old.order_id,
+        params: self.params(vault_bump),
+      }
+      .data(),
+    })
+  }
+
+  fn remaining_accounts(&self, program_id: &Pubkey) -> Vec<AccountMeta> {
+    // the program reads the mints from `remaining_accounts`:
+    // trade mint first, then receive mint
+    let mut accounts = self
+      .send_mint
+      .iter()
+      .chain(self.receive_mint.iter())
+      .map(|mint| AccountMeta::new_readonly(*mint, false))
+      .collect::<Vec<_>>();
+    // then the inbox and the market page the order is listed in
+    if let Some(partner) = &self.specify_partner {
+      accounts.push(inbox_account(program_id, partner));
+    }
+    if let Some(page) = self.market_page {
+      accounts.push(market_account(
+        program_id,
+        self.send_mint.as_ref(),
+        self.receive_mint.as_ref(),
+        page,
+      ));
+    }
+    // then the receive account of each payment option
+    accounts.extend(
+      self
+        .payment_options
+        .iter()
+        .flat_map(|options| options.accepted().iter())
+        .map(|option| AccountMeta::new_readonly(option.receive_account, false)),
+    );
+    // and last the reference price account of the price guard
+    if let Some(guard) = &self.price_guard {
+      accounts.push(AccountMeta::new_readonly(guard.oracle, false));
+    }
+    accounts
+  }
+
+  fn params(&self, vault_bump: u8) -> CreateParams {
+    CreateParams {
+      specify_partner: self.specify_partner,
+      partner_root: self.partner_root,
+      claim_hash: self.claim_hash,
+      market_page: self.market_page,
+      dutch_auction: self.dutch_auction,
+      english_auction: self.english_auction,
+      sealed_auction: self.sealed_auction,
+      rfq: self.rfq,
+      oracle_peg: self.oracle_peg,
+      price_guard: self.price_guard,
+      trigger: self.trigger,
+      payment_options: self.payment_options,
+      trade_value: self.trade_value,
+      receive_value: self.receive_value,
+      timestamp: self.timestamp,
+      vault_bump,
+    }
+  }
+}
+
+// most orders a `create_many` transaction fits, in both size and compute
+pub const CREATE_MANY_MAX_ORDERS: usize = 6;
+
+// a ladder of orders on one pair funded from the same account, created with
+// consecutive order ids from `order_id`
+#[derive(Clone, Debug)]
+pub struct CreateMany {
+  pub creator: Pubkey,
+  // `RpcClient::next_order_id` of the creator, which the program assigns and
+  // the order's addresses are derived from
+  pub order_id: u64,
+  // mint the creator sends, `None` for SOL
+  pub send_mint: Option<Pubkey>,
+  // mint the creator expects to receive, `None` for SOL
+  pub receive_mint: Option<Pubkey>,
+  // (trade value, receive value) of each order
+  pub levels: Vec<(u64, u64)>,
+  // market page to list every order on, see `RpcClient::find_market_page`
+  pub market_page: Option<u16>,
+  pub fee_account: Pubkey,
+  pub timestamp: u64,
+}
+
+impl CreateMany {
+  pub fn trade_type(&self) -> Result<TradeType> {
+    trade_type_from_mints(self.send_mint.as_ref(), self.receive_mint.as_ref())
+  }
+
+  // addresses of the orders, in the order of `levels`
+  pub fn order_addresses(&self, program_id: &Pubkey) -> Vec<Pubkey> {
+    (0..self.levels.len() as u64)
+      .map(|index| find_state_address(program_id, &self.creator, self.order_id + index).0)
+      .collect()
+  }
+
+  pub fn instruction(&self, program_id: &Pubkey) -> Result<Instruction> {
+    self.trade_type()?;
+    if self.levels.is_empty() {
+      return Err(ClientError::InvalidTrade("no order to create".to_string()));
+    }
+    let mut accounts = trade_p2p::accounts::CreateMany {
+      creator: self.creator,
+      creator_send_account: side_account(&self.creator, self.send_mint.as_ref()),
+      creator_receive_account: side_account(&self.creator, self.receive_mint.as_ref()),
+      fee_account: self.fee_account,
+      system_program: system_program::ID,
+      token_program: token::ID,
+      rent: sysvar::rent::ID,
+      user_account: find_user_address(program_id, &self.creator).0,
+    }
+    .to_account_metas(None);
+    // the mints as in `create_trade`, then the state, vault and market page of each order
+    accounts.extend(
+      self
+        .send_mint
+        .iter()
+        .chain(self.receive_mint.iter())
+        .map(|mint| AccountMeta::new_readonly(*mint, false)),
+    );
+    let mut orders = Vec::with_capacity(self.levels.len());
+    for (index, (trade_value, receive_value)) in self.levels.iter().enumerate() {
+      let order_id = self.order_id + index as u64;
+      let (escrow_state, _) = find_state_address(program_id, &self.creator, order_id);
+      let (escrow_vault, vault_bump) = find_vault_address(program_id, &self.creator, order_id);
+      accounts.push(AccountMeta::new(escrow_state, false));
+      accounts.push(AccountMeta::new(escrow_vault, false));
+      if let Some(page) = self.market_page {
+        accounts.push(market_account(
+          program_id,
+          self.send_mint.as_ref(),
+          self.receive_mint.as_ref(),
+          page,
+        ));
+      }
+      orders.push(OrderLevel {
+        trade_value: *trade_value,
+        receive_value: *receive_value,
+        vault_bump,
+      });
+    }
+    let params = CreateManyParams {
+      orders,
+      timestamp: self.timestamp,
+      market_page: self.market_page,
+    };
+    Ok(Instruction {
+      program_id: *program_id,
+      accounts,
+      data: trade_p2p::instruction::CreateMany { params }.data(),
+    })
+  }
+}
+
+// inbox of the specify partner, passed after the other remaining accounts of
+// every instruction that opens or closes an order addressed to them
+fn inbox_account(program_id: &Pubkey, partner: &Pubkey) -> AccountMeta {
+  AccountMeta::new(find_inbox_address(program_id, partner).0, false)
+}
+
+fn market_account(
+  program_id: &Pubkey,
+  send_mint: Option<&Pubkey>,
+  receive_mint: Option<&Pubkey>,
+  page: u16,
+) -> AccountMeta {
+  AccountMeta::new(find_market_address(program_id, send_mint, receive_mint, page).0, false)
+}
+
+// inbox and market page an order leaves when it is closed
+fn listing_accounts(program_id: &Pubkey, order: &EscrowAccount) -> Vec<AccountMeta> {
+  let mut accounts = Vec::new();
+  if let Some(specify_partner) = &order.specify_partner {
+    accounts.push(inbox_account(program_id, specify_partner));
+  }
+  if let Some(page) = order.market_page {
+    accounts.push(market_account(
+      program_id,
+      order.creator_send_token_mint.as_ref(),
+      order.creator_receive_token_mint.as_ref(),
+      page,
+    ));
+  }
+  accounts
+}
+
+// accounts the partner pays from and receives into for an order
+pub fn partner_accounts(order: &EscrowAccount, partner: &Pubkey) -> (Pubkey, Pubkey) {
+  (
+    side_account(partner, order.creator_receive_token_mint.as_ref()),
+    side_account(partner, order.creator_send_token_mint.as_ref()),
+  )
+}
+
+// what a partner presents to take a restricted order
+#[derive(Clone, Debug, Default)]
+pub struct PartnerAuth {
+  // merkle proof for orders with a partner allowlist
+  pub proof: Vec<[u8; 32]>,
+  // claim key of claim-code orders, it must co-sign the transaction
+  pub claim_key: Option<Pubkey>,
+}
+
+// the checks `exchange` makes on the order itself, so a doomed transaction is
+// reported before it is built
+pub fn check_exchange(order: &EscrowAccount, partner: &Pubkey, auth: &PartnerAuth) -> Result<()> {
+  if order.stage == Stage::AwaitingTrigger.to_code() {
+    return Err(ClientError::InvalidTrade("order waits for its trigger".to_string()));
+  }
+  if order.stage != Stage::ReadyExchange.to_code() {
+    return Err(ClientError::InvalidTrade("order is no longer open".to_string()));
+  }
+  if order.is_auction() {
+    return Err(ClientError::InvalidTrade("order is an auction, bid on it instead".to_string()));
+  }
+  if order.specify_partner != Some(*partner) {
+    match (&order.partner_root, order.specify_partner) {
+      (Some(root), _) if !verify_merkle_proof(&auth.proof, root, partner_leaf(partner)) => {
+        return Err(ClientError::InvalidTrade(format!(
+          "{} is not in the order's partner allowlist",
+          partner
+        )))
+      }
+      (None, Some(_)) => {
+        return Err(ClientError::InvalidTrade(format!(
+          "order is reserved for another partner than {}",
+          partner
+        )))
+      }
+      _ => {}
+    }
+  }
+  if let Some(expected_claim_hash) = order.claim_hash {
+    if auth.claim_key.map(|claim_key| claim_hash(&claim_key)) != Some(expected_claim_hash) {
+      return Err(ClientError::InvalidTrade(
+        "order needs its claim code to be exchanged".to_string(),
+      ));
+    }
+  }
+  Ok(())
+}
+
+// fill an order, paying at most `max_receive_value` when it is priced at fill
+// time, a dutch auction or an oracle-pegged order
+pub fn exchange(
+  program_id: &Pubkey,
+  order: &EscrowAccount,
+  partner: &Pubkey,
+  auth: &PartnerAuth,
+  max_receive_value: Option<u64>,
+) -> Instruction {
+  let (partner_send_account, partner_receive_account) = partner_accounts(order, partner);
+  exchange_with_accounts(
+    program_id,
+    order,
+    partner,
+    &partner_send_account,
+    &partner_receive_account,
+    auth,
+    max_receive_value,
+  )
+}
+
+pub fn exchange_with_accounts(
+  program_id: &Pubkey,
+  order: &EscrowAccount,
+  partner: &Pubkey,
+  partner_send_account: &Pubkey,
+  partner_receive_account: &Pubkey,
+  auth: &PartnerAuth,
+  max_receive_value: Option<u64>,
+) -> Instruction {
+  exchange_paying_into(
+    program_id,
+    order,
+    &order.creator_receive_account,
+    partner,
+    (partner_send_account, partner_receive_account),
+    auth,
+    max_receive_value,
+  )
+}
+
+// fill an order paying in the mint of one of its payment options (`None` for
+// SOL), at the price of that option
+pub fn exchange_with_payment_option(
+  program_id: &Pubkey,
+  order: &EscrowAccount,
+  partner: &Pubkey,
+  mint: Option<&Pubkey>,
+  auth: &PartnerAuth,
+  max_receive_value: Option<u64>,
+) -> Result<Instruction> {
+  let option = payment_option(order, mint)?;
+  let partner_send_account = side_account(partner, mint);
+  let (_, partner_receive_account) = partner_accounts(order, partner);
+  Ok(exchange_paying_into(
+    program_id,
+    order,
+    &option.receive_account,
+    partner,
+    (&partner_send_account, &partner_receive_account),
+    auth,
+    max_receive_value,
+  ))
+}
+
+fn exchange_paying_into(
+  program_id: &Pubkey,
+  order: &EscrowAccount,
+  creator_receive_account: &Pubkey,
+  partner: &Pubkey,
+  (partner_send_account, partner_receive_account): (&Pubkey, &Pubkey),
+  auth: &PartnerAuth,
+  max_receive_value: Option<u64>,
+) -> Instruction {
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  let accounts = trade_p2p::accounts::Exchange {
+    escrow_state,
+    escrow_vault: order.escrow_vault,
+    creator_receive_account: *creator_receive_account,
+    partner_send_account: *partner_send_account,
+    partner_receive_account: *partner_receive_account,
+    creator: order.creator,
+    partner: *partner,
+    system_program: system_program::ID,
+    token_program: token::ID,
+  };
+  let mut accounts = accounts.to_account_metas(None);
+  if let Some(peg) = &order.oracle_peg {
+    accounts.push(AccountMeta::new_readonly(peg.oracle, false));
+  }
+  if let Some(claim_key) = &auth.claim_key {
+    accounts.push(AccountMeta::new_readonly(*claim_key, true));
+  }
+  accounts.extend(listing_accounts(program_id, order));
+  Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::Exchange {
+      _order_id: order.order_id,
+      _state_bump: order.state_bump,
+      _vault_bump: order.vault_bump,
+      proof: auth.proof.clone(),
+      max_receive_value,
+    }
+    .data(),
+  }
+}
+
+// most orders an `exchange_many` transaction fits, in both size and compute,
+// next to the creation of the partner's receiving token account
+pub const EXCHANGE_MANY_MAX_ORDERS: usize = 5;
+
+// fill several orders of one pair, paying at most `budget` in total and, with
+// `max_price`, at most its average price. `atomic` fills every order or none,
+// otherwise orders the program cannot fill are skipped. Claim-code orders
+// cannot be taken this way, nor allowlisted orders not addressed to `partner`.
+pub fn exchange_many(
+  program_id: &Pubkey,
+  orders: &[EscrowAccount],
+  partner: &Pubkey,
+  budget: u64,
+  max_price: Option<PriceCap>,
+  atomic: bool,
+) -> Result<Instruction> {
+  let first = orders
+    .first()
+    .ok_or_else(|| ClientError::InvalidTrade("no order to fill".to_string()))?;
+  let pair = (first.creator_send_token_mint, first.creator_receive_token_mint);
+  let (partner_send_account, partner_receive_account) = partner_accounts(first, partner);
+  let mut accounts = trade_p2p::accounts::ExchangeMany {
+    partner_send_account,
+    partner_receive_account,
+    partner: *partner,
+    system_program: system_program::ID,
+    token_program: token::ID,
+  }
+  .to_account_metas(None);
+  for order in orders {
+    if (order.creator_send_token_mint, order.creator_receive_token_mint) != pair {
+      return Err(ClientError::InvalidTrade("orders must all be on the same pair".to_string()));
+    }
+    if order.claim_hash.is_some() {
+      return Err(ClientError::InvalidTrade(format!(
+        "order {}/{} needs its claim code, fill it with exchange",
+        order.creator, order.order_id
+      )));
+    }
+    if atomic {
+      check_exchange(order, partner, &PartnerAuth::default())?;
+    }
+    let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+    accounts.extend([
+      AccountMeta::new(escrow_state, false),
+      AccountMeta::new(order.escrow_vault, false),
+      AccountMeta::new(order.creator, false),
+      AccountMeta::new(order.creator_receive_account, false),
+    ]);
+    accounts.extend(listing_accounts(program_id, order));
+  }
+  Ok(Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::ExchangeMany {
+      budget,
+      max_price,
+      atomic,
+    }
+    .data(),
+  })
+}
+
+// fees a matcher keeps for settling `a` against `b`, in the asset each one sends.
+// Both must be open, unrestricted, on opposite sides of one pair and crossing
+pub fn check_match(a: &EscrowAccount, b: &EscrowAccount) -> Result<(u64, u64)> {
+  for order in [a, b] {
+    if order.stage != Stage::ReadyExchange.to_code() {
+      return Err(ClientError::InvalidTrade(format!(
+        "order {}/{} is not open",
+        order.creator, order.order_id
+      )));
+    }
+    if order.specify_partner.is_some() || order.partner_root.is_some() || order.claim_hash.is_some() {
+      return Err(ClientError::InvalidTrade(format!(
+        "order {}/{} is restricted to its partners",
+        order.creator, order.order_id
+      )));
+    }
+    if order.is_auction() {
+      return Err(ClientError::InvalidTrade(format!(
+        "order {}/{} is an auction",
+        order.creator, order.order_id
+      )));
+    }
+  }
+  if a.escrow_vault == b.escrow_vault {
+    return Err(ClientError::InvalidTrade("cannot match an order with itself".to_string()));
+  }
+  if (a.creator_send_token_mint, a.creator_receive_token_mint)
+    != (b.creator_receive_token_mint, b.creator_send_token_mint)
+  {
+    return Err(ClientError::InvalidTrade("orders are not on opposite sides of one pair".to_string()));
+  }
+  match (a.trade_value.checked_sub(b.receive_value), b.trade_value.checked_sub(a.receive_value)) {
+    (Some(fee_a), Some(fee_b)) => Ok((fee_a, fee_b)),
+    _ => Err(ClientError::InvalidTrade("orders do not cross".to_string())),
+  }
+}
+
+// settle two crossing orders against each other, see `check_match`. The fees go
+// to the `matcher` wallet for SOL and its associated token accounts otherwise
+pub fn match_orders(program_id: &Pubkey, a: &EscrowAccount, b: &EscrowAccount, matcher: &Pubkey) -> Result<Instruction> {
+  check_match(a, b)?;
+  let (escrow_state_a, _) = find_state_address(program_id, &a.creator, a.order_id);
+  let (escrow_state_b, _) = find_state_address(program_id, &b.creator, b.order_id);
+  let mut accounts = trade_p2p::accounts::MatchOrders {
+    escrow_state_a,
+    escrow_vault_a: a.escrow_vault,
+    creator_a: a.creator,
+    creator_a_receive_account: a.creator_payout(),
+    escrow_state_b,
+    escrow_vault_b: b.escrow_vault,
+    creator_b: b.creator,
+    creator_b_receive_account: b.creator_payout(),
+    matcher: *matcher,
+    matcher_a_account: side_account(matcher, a.creator_send_token_mint.as_ref()),
+    matcher_b_account: side_account(matcher, b.creator_send_token_mint.as_ref()),
+    system_program: system_program::ID,
+    token_program: token::ID,
+  }
+  .to_account_metas(None);
+  accounts.extend(listing_accounts(program_id, a));
+  accounts.extend(listing_accounts(program_id, b));
+  Ok(Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::MatchOrders {}.data(),
+  })
+}
+
+// pairs of `asks` and `bids`, orders on opposite sides of one pair, that
+// `match_orders` can settle. The cheapest asks are matched first, each with the
+// cheapest bid crossing it, and no order is used twice
+pub fn crossing_orders<'a>(
+  asks: &'a [EscrowAccount],
+  bids: &'a [EscrowAccount],
+) -> Vec<(&'a EscrowAccount, &'a EscrowAccount)> {
+  let by_price = |orders: &'a [EscrowAccount]| {
+    let mut orders = orders.iter().collect::<Vec<_>>();
+    orders.sort_by(|a, b| {
+      (a.receive_value as u128 * b.trade_value as u128).cmp(&(b.receive_value as u128 * a.trade_value as u128))
+    });
+    orders
+  };
+  let mut bids = by_price(bids).into_iter().map(Some).collect::<Vec<_>>();
+  let mut pairs = Vec::new();
+  for ask in by_price(asks) {
+    let bid = bids
+      .iter_mut()
+      .find(|bid| matches!(bid, Some(bid) if check_match(ask, bid).is_ok()))
+      .and_then(Option::take);
+    if let Some(bid) = bid {
+      pairs.push((ask, bid));
+    }
+  }
+  pairs
+}
+
+// lowest bid an english auction accepts next: its reserve price, then the best
+// bid plus the minimum increment
+pub fn min_bid(order: &EscrowAccount, best_bid: Option<&BidAccount>) -> u64 {
+  match (best_bid, &order.english_auction) {
+    (Some(best_bid), Some(auction)) => best_bid.value.saturating_add(auction.min_increment),
+    _ => order.receive_value,
+  }
+}
+
+// the checks `place_bid` makes before the clock, see `min_bid`
+pub fn check_bid(order: &EscrowAccount, best_bid: Option<&BidAccount>, value: u64) -> Result<()> {
+  if order.stage != Stage::ReadyExchange.to_code() {
+    return Err(ClientError::InvalidTrade("order is no longer open".to_string()));
+  }
+  if order.english_auction.is_none() {
+    return Err(ClientError::InvalidTrade("order is not an english auction".to_string()));
+  }
+  // every bid becomes the best one, so the best bid is the last placed
+  if best_bid.map(|best_bid| best_bid.bid_index) != order.best_bid.map(|_| order.bid_count - 1) {
+    return Err(ClientError::InvalidTrade("best bid is not the order's".to_string()));
+  }
+  let min_value = min_bid(order, best_bid);
+  if value < min_value {
+    return Err(ClientError::InvalidTrade(format!("bid must be at least {}", min_value)));
+  }
+  Ok(())
+}
+
+// bid `value` on an english auction order. `best_bid` is the order's current best
+// bid, refunded by the instruction. The bid is paid from and won into the
+// `bidder` wallet for SOL and its associated token accounts otherwise
+pub fn place_bid(
+  program_id: &Pubkey,
+  order: &EscrowAccount,
+  best_bid: Option<&BidAccount>,
+  bidder: &Pubkey,
+  value: u64,
+) -> Result<Instruction> {
+  check_bid(order, best_bid, value)?;
+  let (mut accounts, vault_bump) = new_bid_accounts(program_id, order, bidder);
+  if let Some(best_bid) = best_bid {
+    accounts.extend(best_bid_accounts(program_id, order, best_bid));
+  }
+  Ok(Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::PlaceBid { value, vault_bump }.data(),
+  })
+}
+
+// the accounts of `place_bid` and `commit_bid` for a new bid of `bidder`, with
+// the mints of the bidder's side as in `create_trade`, and the bump of its vault
+fn new_bid_accounts(program_id: &Pubkey, order: &EscrowAccount, bidder: &Pubkey) -> (Vec<AccountMeta>, u8) {
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  let (bid, _) = find_bid_address(program_id, &escrow_state, order.bid_count);
+  let (bid_vault, vault_bump) = find_bid_vault_address(program_id, &bid);
+  let (bidder_send_account, bidder_receive_account) = partner_accounts(order, bidder);
+  let mut accounts = trade_p2p::accounts::PlaceBid {
+    escrow_state,
+    bid,
+    bid_vault,
+    bidder: *bidder,
+    bidder_send_account,
+    bidder_receive_account,
+    system_program: system_program::ID,
+    token_program: token::ID,
+    rent: sysvar::rent::ID,
+  }
+  .to_account_metas(None);
+  accounts.extend(
+    order
+      .creator_receive_token_mint
+      .iter()
+      .chain(order.creator_send_token_mint.iter())
+      .map(|mint| AccountMeta::new_readonly(*mint, false)),
+  );
+  (accounts, vault_bump)
+}
+
+// the best bid of an order as `place_bid` and `reveal_bid` refund it
+fn best_bid_accounts(program_id: &Pubkey, order: &EscrowAccount, best_bid: &BidAccount) -> Vec<AccountMeta> {
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  vec![
+    AccountMeta::new(find_bid_address(program_id, &escrow_state, best_bid.bid_index).0, false),
+    AccountMeta::new(best_bid.bid_vault, false),
+    AccountMeta::new(best_bid.bidder, false),
+    AccountMeta::new(best_bid.bidder_send_account, false),
+  ]
+}
+
+// commit `sealed` on a sealed-bid auction order, locking `deposit`, which must
+// cover the bid and the reserve price. Paid from the `bidder` wallet for SOL and
+// its associated token account otherwise
+pub fn commit_bid(
+  program_id: &Pubkey,
+  order: &EscrowAccount,
+  bidder: &Pubkey,
+  deposit: u64,
+  sealed: &SealedBid,
+) -> Result<Instruction> {
+  if order.stage != Stage::ReadyExchange.to_code() {
+    return Err(ClientError::InvalidTrade("order is no longer open".to_string()));
+  }
+  if order.sealed_auction.is_none() {
+    return Err(ClientError::InvalidTrade("order is not a sealed-bid auction".to_string()));
+  }
+  if deposit < sealed.value.max(order.receive_value) {
+    return Err(ClientError::InvalidTrade(format!(
+      "deposit must cover the bid and the reserve price of {}",
+      order.receive_value
+    )));
+  }
+  let (accounts, vault_bump) = new_bid_accounts(program_id, order, bidder);
+  Ok(Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::CommitBid {
+      deposit,
+      vault_bump,
+      commitment: sealed.commitment(bidder),
+    }
+    .data(),
+  })
+}
+
+// reveal `bid`, committed as `sealed`, on a sealed-bid auction order whose best
+// revealed bid is `best_bid`
+pub fn reveal_bid(
+  program_id: &Pubkey,
+  order: &EscrowAccount,
+  bid: &BidAccount,
+  best_bid: Option<&BidAccount>,
+  sealed: &SealedBid,
+) -> Result<Instruction> {
+  if bid.commitment != Some(sealed.commitment(&bid.bidder)) {
+    return Err(ClientError::InvalidTrade("sealed bid does not match the bid commitment".to_string()));
+  }
+  if sealed.value > bid.deposit {
+    return Err(ClientError::InvalidTrade("deposit does not cover the bid".to_string()));
+  }
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  let mut accounts = trade_p2p::accounts::RevealBid {
+    escrow_state,
+    bid: find_bid_address(program_id, &escrow_state, bid.bid_index).0,
+    bid_vault: bid.bid_vault,
+    bidder: bid.bidder,
+    bidder_send_account: bid.bidder_send_account,
+    system_program: system_program::ID,
+    token_program: token::ID,
+  }
+  .to_account_metas(None);
+  if let Some(best_bid) = best_bid {
+    accounts.extend(best_bid_accounts(program_id, order, best_bid));
+  }
+  Ok(Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::RevealBid {
+      value: sealed.value,
+      salt: sealed.salt,
+    }
+    .data(),
+  })
+}
+
+// close a sealed bid never revealed once reveals are over, its deposit going to
+// the creator or back to the bidder as the auction says. Anyone can send it
+pub fn reclaim_bid(program_id: &Pubkey, order: &EscrowAccount, bid: &BidAccount) -> Instruction {
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  let accounts = trade_p2p::accounts::ReclaimBid {
+    escrow_state,
+    creator_receive_account: order.creator_payout(),
+    bid: find_bid_address(program_id, &escrow_state, bid.bid_index).0,
+    bid_vault: bid.bid_vault,
+    bidder: bid.bidder,
+    bidder_send_account: bid.bidder_send_account,
+    system_program: system_program::ID,
+    token_program: token::ID,
+  };
+  Instruction {
+    program_id: *program_id,
+    accounts: accounts.to_account_metas(None),
+    data: trade_p2p::instruction::ReclaimBid {}.data(),
+  }
+}
+
+// once an auction has ended, give the order's vault to `best_bid` and the bid to
+// the creator, or refund the creator when nobody bid. Anyone can send it
+pub fn settle_auction(program_id: &Pubkey, order: &EscrowAccount, best_bid: Option<&BidAccount>) -> Instruction {
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  let mut accounts = trade_p2p::accounts::SettleAuction {
+    escrow_state,
+    escrow_vault: order.escrow_vault,
+    creator: order.creator,
+    creator_send_account: order.creator_send_account,
+    creator_receive_account: order.creator_payout(),
+    system_program: system_program::ID,
+    token_program: token::ID,
+  }
+  .to_account_metas(None);
+  if let Some(best_bid) = best_bid {
+    accounts.extend([
+      AccountMeta::new(find_bid_address(program_id, &escrow_state, best_bid.bid_index).0, false),
+      AccountMeta::new(best_bid.bid_vault, false),
+      AccountMeta::new(best_bid.bidder, false),
+      AccountMeta::new(best_bid.bidder_receive_account, false),
+      AccountMeta::new(best_bid.bidder_send_account, false),
+    ]);
+  }
+  accounts.extend(listing_accounts(program_id, order));
+  Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::SettleAuction {}.data(),
+  }
+}
+
+// offer `value` of `offer_mint` (`None` for SOL) for an order, paid from the
+// `partner` wallet for SOL and its associated token accounts otherwise, which
+// also receive the order's vault when the creator accepts it
+pub fn make_offer(
+  program_id: &Pubkey,
+  order: &EscrowAccount,
+  partner: &Pubkey,
+  offer_mint: Option<&Pubkey>,
+  value: u64,
+) -> Result<Instruction> {
+  if order.stage != Stage::ReadyExchange.to_code() {
+    return Err(ClientError::InvalidTrade("order is no longer open".to_string()));
+  }
+  if order.is_auction() {
+    return Err(ClientError::InvalidTrade("auction orders take bids, not offers".to_string()));
+  }
+  if value == 0 {
+    return Err(ClientError::InvalidTrade("offer value must be larger than zero".to_string()));
+  }
+  // the partner's side of the offer is a trade of its own
+  trade_type_from_mints(offer_mint, order.creator_send_token_mint.as_ref())?;
+  if let Some(rfq) = &order.rfq {
+    if !rfq.approves(order.creator_receive_token_mint, offer_mint.copied()) {
+      return Err(ClientError::InvalidTrade("quote mint is not approved by the order".to_string()));
+    }
+  }
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  let (offer, _) = find_offer_address(program_id, &escrow_state, partner);
+  let (offer_vault, vault_bump) = find_offer_vault_address(program_id, &offer);
+  let mut accounts = trade_p2p::accounts::MakeOffer {
+    escrow_state,
+    offer,
+    offer_vault,
+    partner: *partner,
+    partner_send_account: side_account(partner, offer_mint),
+    partner_receive_account: side_account(partner, order.creator_send_token_mint.as_ref()),
+    system_program: system_program::ID,
+    token_program: token::ID,
+    rent: sysvar::rent::ID,
+  }
+  .to_account_metas(None);
+  accounts.extend(
+    offer_mint
+      .into_iter()
+      .chain(order.creator_send_token_mint.iter())
+      .map(|mint| AccountMeta::new_readonly(*mint, false)),
+  );
+  // the quote to beat on a best-price request for quote
+  if let Some(best_quote) = order.best_quote {
+    accounts.push(AccountMeta::new_readonly(best_quote, false));
+  }
+  Ok(Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::MakeOffer { value, vault_bump }.data(),
+  })
+}
+
+// the creator of an order swaps it with `offer`, which it receives as
+// `EscrowAccount::offer_payout` says
+pub fn accept_offer(program_id: &Pubkey, order: &EscrowAccount, offer: &OfferAccount) -> Instruction {
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  let mut accounts = trade_p2p::accounts::AcceptOffer {
+    escrow_state,
+    escrow_vault: order.escrow_vault,
+    creator: order.creator,
+    creator_offer_account: order.offer_payout(offer.offer_mint),
+    offer: find_offer_address(program_id, &escrow_state, &offer.partner).0,
+    offer_vault: offer.offer_vault,
+    partner: offer.partner,
+    partner_receive_account: offer.partner_receive_account,
+    system_program: system_program::ID,
+    token_program: token::ID,
+  }
+  .to_account_metas(None);
+  accounts.extend(listing_accounts(program_id, order));
+  Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::AcceptOffer {}.data(),
+  }
+}
+
+// once the deadline of a best-price request for quote has passed, swap the
+// order with `best_quote`, paid as `EscrowAccount::offer_payout` says. Anyone
+// can send it
+pub fn settle_rfq(program_id: &Pubkey, order: &EscrowAccount, best_quote: &OfferAccount) -> Instruction {
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  let mut accounts = trade_p2p::accounts::SettleRfq {
+    escrow_state,
+    escrow_vault: order.escrow_vault,
+    creator: order.creator,
+    creator_offer_account: order.offer_payout(best_quote.offer_mint),
+    offer: find_offer_address(program_id, &escrow_state, &best_quote.partner).0,
+    offer_vault: best_quote.offer_vault,
+    partner: best_quote.partner,
+    partner_receive_account: best_quote.partner_receive_account,
+    system_program: system_program::ID,
+    token_program: token::ID,
+  }
+  .to_account_metas(None);
+  accounts.extend(listing_accounts(program_id, order));
+  Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::SettleRfq {}.data(),
+  }
+}
+
+// make a conditional order whose trigger price `oracle` has reached ready for
+// exchange. Anyone can send it
+pub fn trigger(program_id: &Pubkey, order: &EscrowAccount, oracle: &Pubkey) -> Instruction {
+  let accounts = trade_p2p::accounts::Trigger {
+    escrow_state: find_state_address(program_id, &order.creator, order.order_id).0,
+    oracle: *oracle,
+  };
+  Instruction {
+    program_id: *program_id,
+    accounts: accounts.to_account_metas(None),
+    data: trade_p2p::instruction::Trigger {}.data(),
+  }
+}
+
+// migrations `trade-p2p migrate` puts in one transaction
+pub const MIGRATE_MAX_ORDERS: usize = 16;
+
+// grow a deal of `creator` of the first release of the program to the current
+// layout, paid by `payer`
+pub fn migrate_escrow(program_id: &Pubkey, escrow_state: &Pubkey, creator: &Pubkey, payer: &Pubkey) -> Instruction {
+  let accounts = trade_p2p::accounts::MigrateEscrow {
+    escrow_state: *escrow_state,
+    creator: *creator,
+    user_account: find_user_address(program_id, creator).0,
+    payer: *payer,
+    system_program: system_program::ID,
+  };
+  Instruction {
+    program_id: *program_id,
+    accounts: accounts.to_account_metas(None),
+    data: trade_p2p::instruction::MigrateEscrow {}.data(),
+  }
+}
+
+// the partner of `offer` takes it back
+pub fn withdraw_offer(program_id: &Pubkey, offer: &OfferAccount) -> Instruction {
+  let mut instruction = refund_offer(program_id, offer);
+  for account in instruction.accounts.iter_mut().filter(|account| account.pubkey == offer.partner) {
+    account.is_signer = true;
+  }
+  instruction
+}
+
+// refund `offer` to its partner once its order is closed. Anyone can send it
+pub fn refund_offer(program_id: &Pubkey, offer: &OfferAccount) -> Instruction {
+  let accounts = trade_p2p::accounts::WithdrawOffer {
+    escrow_state: offer.escrow_state,
+    offer: find_offer_address(program_id, &offer.escrow_state, &offer.partner).0,
+    offer_vault: offer.offer_vault,
+    partner: offer.partner,
+    partner_send_account: offer.partner_send_account,
+    system_program: system_program::ID,
+    token_program: token::ID,
+  };
+  Instruction {
+    program_id: *program_id,
+    accounts: accounts.to_account_metas(None),
+    data: trade_p2p::instruction::WithdrawOffer {}.data(),
+  }
+}
+
+pub fn cancel(program_id: &Pubkey, order: &EscrowAccount) -> Instruction {
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  let accounts = trade_p2p::accounts::Cancel {
+    escrow_state,
+    escrow_vault: order.escrow_vault,
+    creator_send_account: order.creator_send_account,
+    creator: order.creator,
+    system_program: system_program::ID,
+    token_program: token::ID,
+  };
+  let mut accounts = accounts.to_account_metas(None);
+  accounts.extend(listing_accounts(program_id, order));
+  Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::Cancel {
+      _order_id: order.order_id,
+      _state_bump: order.state_bump,
+      _vault_bump: order.vault_bump,
+    }
+    .data(),
+  }
+}
+
+// order accounts `cancel_many_batches` puts in one instruction, so the
+// transaction stays under the packet size
+pub const CANCEL_MANY_MAX_ACCOUNTS: usize = 24;
+
+// cancel several orders of `creator` in one instruction. With `atomic` the
+// instruction fails when one of them can no longer be cancelled, otherwise
+// such orders are skipped
+pub fn cancel_many(program_id: &Pubkey, creator: &Pubkey, orders: &[EscrowAccount], atomic: bool) -> Instruction {
+  let accounts = trade_p2p::accounts::CancelMany {
+    creator: *creator,
+    system_program: system_program::ID,
+    token_program: token::ID,
+  };
+  let mut accounts = accounts.to_account_metas(None);
+  for order in orders {
+    let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+    accounts.push(AccountMeta::new(escrow_state, false));
+    accounts.push(AccountMeta::new(order.escrow_vault, false));
+    accounts.push(AccountMeta::new(order.creator_send_account, false));
+    accounts.extend(listing_accounts(program_id, order));
+  }
+  Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::CancelMany { atomic }.data(),
+  }
+}
+
+// `cancel_many` instructions for any number of orders, one per transaction
+pub fn cancel_many_batches(
+  program_id: &Pubkey,
+  creator: &Pubkey,
+  orders: &[EscrowAccount],
+  atomic: bool,
+) -> Vec<Instruction> {
+  let mut instructions = Vec::new();
+  let (mut start, mut account_count) = (0, 0);
+  for (index, order) in orders.iter().enumerate() {
+    let order_accounts = 3 + listing_accounts(program_id, order).len();
+    if account_count + order_accounts > CANCEL_MANY_MAX_ACCOUNTS {
+      instructions.push(cancel_many(program_id, creator, &orders[start..index], atomic));
+      (start, account_count) = (index, 0);
+    }
+    account_count += order_accounts;
+  }
+  if start < orders.len() {
+    instructions.push(cancel_many(program_id, creator, &orders[start..], atomic));
+  }
+  instructions
+}
+
+// refuse an order addressed to `partner`, the vault goes back to the creator
+pub fn decline(program_id: &Pubkey, order: &EscrowAccount, partner: &Pubkey) -> Instruction {
+  let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+  let accounts = trade_p2p::accounts::Decline {
+    escrow_state,
+    escrow_vault: order.escrow_vault,
+    creator_send_account: order.creator_send_account,
+    creator: order.creator,
+    partner: *partner,
+    system_program: system_program::ID,
+    token_program: token::ID,
+  };
+  let mut accounts = accounts.to_account_metas(None);
+  // `decline` checks the order is addressed to `partner`, whose inbox lists it
+  accounts.extend(listing_accounts(program_id, order));
+  Instruction {
+    program_id: *program_id,
+    accounts,
+    data: trade_p2p::instruction::Decline {
+      _order_id: order.order_id,
+      _state_bump: order.state_bump,
+      _vault_bump: order.vault_bump,
+    }
+    .data(),
+  }
+}
@@ -0,0 +1,38 @@
+use crate::error::{ClientError, Result};
+
+use anchor_lang::solana_program::pubkey::Pubkey;
+use ed25519_dalek::Signer;
+use std::path::Path;
+
+// ed25519 keypair stored the same way as `solana-keygen` does: a JSON array of
+// the 64 secret + public key bytes
+pub struct Keypair(ed25519_dalek::Keypair);
+
+impl Keypair {
+  pub fn from_bytes(bytes: &[u8]) -> Option<Keypair> {
+    ed25519_dalek::Keypair::from_bytes(bytes).ok().map(Keypair)
+  }
+
+  // keypair whose secret key is the 32 byte `seed`
+  pub fn from_seed(seed: &[u8; 32]) -> Keypair {
+    let secret = ed25519_dalek::SecretKey::from_bytes(seed).unwrap();
+    let public = ed25519_dalek::PublicKey::from(&secret);
+    Keypair(ed25519_dalek::Keypair { secret, public })
+  }
+
+  pub fn read_from_file<P: AsRef<Path>>(path: P) -> Result<Keypair> {
+    let path = path.as_ref();
+    let invalid = |reason: String| ClientError::InvalidKeypair(path.display().to_string(), reason);
+    let content = std::fs::read_to_string(path).map_err(|err| invalid(err.to_string()))?;
+    let bytes: Vec<u8> = serde_json::from_str(&content).map_err(|err| invalid(err.to_string()))?;
+    Keypair::from_bytes(&bytes).ok_or_else(|| invalid("expected 64 key bytes".to_string()))
+  }
+
+  pub fn pubkey(&self) -> Pubkey {
+    Pubkey::new_from_array(self.0.public.to_bytes())
+  }
+
+  pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+    self.0.sign(message).to_bytes()
+  }
+}
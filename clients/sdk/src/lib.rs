@@ -0,0 +1,38 @@
+// Off-chain helpers for the trade-p2p program: PDA derivation, instruction
+// builders, account decoding, transaction signing and a small JSON-RPC client.
+pub mod allowlist;
+pub mod claim;
+pub mod error;
+pub mod instruction;
+pub mod keypair;
+pub mod nonce;
+pub mod oracle;
+pub mod order;
+pub mod pda;
+pub mod rpc;
+pub mod sealed_bid;
+pub mod transaction;
+
+pub use anchor_lang::solana_program::{hash::Hash, instruction::Instruction, pubkey::Pubkey};
+pub use error::{ClientError, Result};
+pub use trade_p2p::{
+  self,
+  state::{
+    BidAccount, DutchAuction, EnglishAuction, EscrowAccount, OfferAccount, OraclePeg, PartnerInbox, PaymentOption,
+    PaymentOptions, PriceGuard, Rfq, SealedAuction, Stage, TradeType, TriggerCondition, TriggerDirection,
+  },
+  ID as PROGRAM_ID,
+};
+
+use std::str::FromStr;
+
+// fee account used by the typescript client `clients/p2p/TradeP2P.ts`
+pub const DEFAULT_FEE_ACCOUNT: &str = "DisXwVm1T6jdajyKX6FoMmSJ98CzCPcWWqUAJ3xUASc9";
+
+pub fn default_fee_account() -> Pubkey {
+  Pubkey::from_str(DEFAULT_FEE_ACCOUNT).unwrap()
+}
+
+pub fn parse_pubkey(value: &str) -> Result<Pubkey> {
+  Pubkey::from_str(value).map_err(|_| ClientError::InvalidPubkey(value.to_string()))
+}
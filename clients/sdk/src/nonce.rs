@@ -0,0 +1,46 @@
+use crate::error::{ClientError, Result};
+
+use anchor_lang::solana_program::{
+  hash::Hash,
+  nonce::state::{State, Versions},
+  pubkey::Pubkey,
+};
+
+// durable nonce stored in a system nonce account, its blockhash stays valid
+// until the nonce is advanced so transactions can be signed offline
+#[derive(Clone, Copy, Debug)]
+pub struct NonceInfo {
+  pub authority: Pubkey,
+  pub blockhash: Hash,
+}
+
+pub fn decode_nonce(address: &Pubkey, data: &[u8]) -> Result<NonceInfo> {
+  let versions: Versions = bincode::deserialize(data)
+    .map_err(|_| ClientError::InvalidNonce(address.to_string()))?;
+  match versions.state() {
+    State::Initialized(data) => Ok(NonceInfo {
+      authority: data.authority,
+      blockhash: data.blockhash(),
+    }),
+    State::Uninitialized => Err(ClientError::InvalidNonce(address.to_string())),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anchor_lang::solana_program::nonce::state::DurableNonce;
+
+  #[test]
+  fn decodes_initialized_nonces_only() {
+    let (address, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let durable_nonce = DurableNonce::from_blockhash(&Hash::new_unique(), true);
+    let initialized = Versions::new(State::new_initialized(&authority, durable_nonce, 5_000), true);
+    let nonce = decode_nonce(&address, &bincode::serialize(&initialized).unwrap()).unwrap();
+    assert_eq!((nonce.authority, nonce.blockhash), (authority, *durable_nonce.as_hash()));
+
+    let uninitialized = bincode::serialize(&Versions::new(State::Uninitialized, true)).unwrap();
+    assert!(decode_nonce(&address, &uninitialized).is_err());
+    assert!(decode_nonce(&address, &[1, 2, 3]).is_err());
+  }
+}
@@ -0,0 +1,41 @@
+use crate::error::{ClientError, Result};
+
+use anchor_lang::solana_program::{pubkey::Pubkey, rent::Rent};
+use rand::{rngs::OsRng, RngCore};
+use serde_json::{json, Value};
+use trade_p2p::oracle::{encode_price, parse_price, pyth};
+
+pub use trade_p2p::oracle::OraclePrice;
+
+// price of the account at `address`, refused unless the Pyth program owns it
+// as the program requires
+pub fn decode_price(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<OraclePrice> {
+  if *owner != pyth::ID {
+    return Err(ClientError::InvalidOracle(address.to_string()));
+  }
+  parse_price(data).map_err(|_| ClientError::InvalidOracle(address.to_string()))
+}
+
+// random address for a mock price account, nobody needs to sign for it
+pub fn new_mock_oracle_address() -> Pubkey {
+  let mut bytes = [0u8; 32];
+  OsRng.fill_bytes(&mut bytes);
+  Pubkey::new_from_array(bytes)
+}
+
+// a price account holding `price` in the `--account` file format of
+// `solana-test-validator`, to peg orders to a local mock of a feed. The program
+// reads feeds owned by the Pyth program, so the mock is given that owner
+pub fn mock_oracle_account(address: &Pubkey, price: &OraclePrice) -> Value {
+  let data = encode_price(price);
+  json!({
+    "pubkey": address.to_string(),
+    "account": {
+      "lamports": Rent::default().minimum_balance(data.len()),
+      "data": [base64::encode(&data), "base64"],
+      "owner": pyth::ID.to_string(),
+      "executable": false,
+      "rentEpoch": 0,
+    },
+  })
+}
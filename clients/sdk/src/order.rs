@@ -0,0 +1,374 @@
+use crate::allowlist::encode_root;
+use crate::error::{ClientError, Result};
+
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_lang::{AccountDeserialize, Discriminator};
+use anchor_spl::token::spl_token::native_mint;
+use serde::Serialize;
+use trade_p2p::state::{
+  BidAccount, EscrowAccount, MarketPage, OfferAccount, PartnerInbox, Stage, TradeType, TriggerDirection,
+  UserAccount,
+};
+
+// mint label used for the SOL side of a trade
+pub const NATIVE_MINT_LABEL: &str = "SOL";
+
+pub fn decode_escrow(address: &Pubkey, data: &[u8]) -> Result<EscrowAccount> {
+  EscrowAccount::try_deserialize(&mut &data[..])
+    .map_err(|_| ClientError::InvalidOrder(address.to_string()))
+}
+
+// creator of a deal of the first release, the first field after the
+// discriminator, as such deals cannot be decoded before `migrate_escrow`
+pub fn decode_first_release_creator(address: &Pubkey, data: &[u8]) -> Result<Pubkey> {
+  match data.len() == EscrowAccount::FIRST_RELEASE_LEN && data.starts_with(&EscrowAccount::discriminator()) {
+    true => Ok(Pubkey::new_from_array(data[8..40].try_into().unwrap())),
+    false => Err(ClientError::InvalidOrder(address.to_string())),
+  }
+}
+
+pub fn decode_inbox(address: &Pubkey, data: &[u8]) -> Result<PartnerInbox> {
+  PartnerInbox::try_deserialize(&mut &data[..])
+    .map_err(|_| ClientError::InvalidInbox(address.to_string()))
+}
+
+pub fn decode_user_account(address: &Pubkey, data: &[u8]) -> Result<UserAccount> {
+  UserAccount::try_deserialize(&mut &data[..])
+    .map_err(|_| ClientError::InvalidUserAccount(address.to_string()))
+}
+
+pub fn decode_market(address: &Pubkey, data: &[u8]) -> Result<MarketPage> {
+  MarketPage::try_deserialize(&mut &data[..])
+    .map_err(|_| ClientError::InvalidMarket(address.to_string()))
+}
+
+pub fn decode_bid(address: &Pubkey, data: &[u8]) -> Result<BidAccount> {
+  BidAccount::try_deserialize(&mut &data[..]).map_err(|_| ClientError::InvalidBid(address.to_string()))
+}
+
+pub fn decode_offer(address: &Pubkey, data: &[u8]) -> Result<OfferAccount> {
+  OfferAccount::try_deserialize(&mut &data[..]).map_err(|_| ClientError::InvalidOffer(address.to_string()))
+}
+
+// every stage an order can be in, used to map codes and names without going
+// through `Stage::from`, which logs unknown codes
+pub const STAGES: &[Stage] = &[
+  Stage::ReadyExchange,
+  Stage::Exchanged,
+  Stage::CancelTrade,
+  Stage::Declined,
+  Stage::AwaitingTrigger,
+];
+
+pub fn trigger_direction_name(direction: TriggerDirection) -> &'static str {
+  match direction {
+    TriggerDirection::AtOrAbove => "at or above",
+    TriggerDirection::AtOrBelow => "at or below",
+  }
+}
+
+pub const TRADE_TYPES: &[TradeType] = &[TradeType::TokenToken, TradeType::TokenSol, TradeType::SolToken];
+
+pub fn trade_type_name(code: u8) -> String {
+  match TRADE_TYPES.iter().find(|trade_type| trade_type.to_code() == code) {
+    Some(trade_type) => format!("{:?}", trade_type),
+    None => format!("Unknown({})", code),
+  }
+}
+
+pub fn stage_name(code: u8) -> String {
+  match STAGES.iter().find(|stage| stage.to_code() == code) {
+    Some(stage) => format!("{:?}", stage),
+    None => format!("Unknown({})", code),
+  }
+}
+
+// stage code from its name (case insensitive) or numeric code
+pub fn parse_stage(value: &str) -> Option<u8> {
+  STAGES
+    .iter()
+    .map(Stage::to_code)
+    .find(|code| value.eq_ignore_ascii_case(&stage_name(*code)) || value == code.to_string())
+}
+
+pub fn mint_label(mint: Option<&Pubkey>) -> String {
+  mint
+    .map(|mint| mint.to_string())
+    .unwrap_or_else(|| NATIVE_MINT_LABEL.to_string())
+}
+
+// price an order is filled at, at unix time `now`: the current price of a dutch
+// auction, its `receive_value` otherwise. The program prices fills the same way.
+// Oracle-pegged orders are priced from their feed by `RpcClient::quote_receive_value`
+pub fn quote_receive_value(escrow: &EscrowAccount, now: i64) -> u64 {
+  escrow.current_receive_value(now)
+}
+
+// human readable view of an `EscrowAccount`
+#[derive(Clone, Debug, Serialize)]
+pub struct Order {
+  pub address: String,
+  pub creator: String,
+  pub order_id: u64,
+  pub trade_type: String,
+  pub stage: String,
+  pub send_mint: String,
+  pub receive_mint: String,
+  pub trade_value: u64,
+  pub receive_value: u64,
+  pub specify_partner: Option<String>,
+  pub partner_root: Option<String>,
+  pub claim_code_required: bool,
+  pub market_page: Option<u16>,
+  // dutch auction, `receive_value` being its start price
+  pub floor_value: Option<u64>,
+  pub auction_start: Option<i64>,
+  pub auction_end: Option<i64>,
+  // english auction, `receive_value` being its reserve price
+  pub bidding_end: Option<i64>,
+  pub min_increment: Option<u64>,
+  // sealed-bid auction, `receive_value` being its reserve price
+  pub commit_end: Option<i64>,
+  pub reveal_end: Option<i64>,
+  pub forfeit_unrevealed: Option<bool>,
+  pub best_bid: Option<String>,
+  pub bid_count: u32,
+  // request for quote, quotes are taken in the receive mint and `quote_mints`
+  pub quote_deadline: Option<i64>,
+  pub quote_mints: Vec<String>,
+  pub best_price: Option<bool>,
+  pub best_quote: Option<String>,
+  // oracle peg, `receive_value` following the price of `oracle`
+  pub oracle: Option<String>,
+  pub spread_bps: Option<u16>,
+  pub min_value: Option<u64>,
+  pub max_value: Option<u64>,
+  pub max_oracle_age: Option<u32>,
+  pub max_confidence_bps: Option<u16>,
+  // stop-loss or take-profit order, `AwaitingTrigger` until the price of
+  // `trigger_oracle` reaches `trigger_price`
+  pub trigger_oracle: Option<String>,
+  pub trigger_price: Option<i64>,
+  pub trigger_direction: Option<String>,
+  pub max_trigger_age: Option<u32>,
+  // other mints the order may be paid in, each at its own receive value
+  pub payment_options: Vec<PaymentOptionView>,
+  pub escrow_vault: String,
+  pub creator_send_account: String,
+  pub creator_receive_account: String,
+  pub fee_account: String,
+  pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PaymentOptionView {
+  pub mint: String,
+  pub receive_value: u64,
+  pub receive_account: String,
+}
+
+impl Order {
+  pub fn new(address: &Pubkey, escrow: &EscrowAccount) -> Order {
+    Order {
+      address: address.to_string(),
+      creator: escrow.creator.to_string(),
+      order_id: escrow.order_id,
+      trade_type: trade_type_name(escrow.trade_type),
+      stage: stage_name(escrow.stage),
+      send_mint: mint_label(escrow.creator_send_token_mint.as_ref()),
+      receive_mint: mint_label(escrow.creator_receive_token_mint.as_ref()),
+      trade_value: escrow.trade_value,
+      receive_value: escrow.receive_value,
+      specify_partner: escrow.specify_partner.map(|partner| partner.to_string()),
+      partner_root: escrow.partner_root.as_ref().map(encode_root),
+      claim_code_required: escrow.claim_hash.is_some(),
+      market_page: escrow.market_page,
+      floor_value: escrow.dutch_auction.map(|auction| auction.floor_value),
+      auction_start: escrow.dutch_auction.map(|auction| auction.start_time),
+      auction_end: escrow.dutch_auction.map(|auction| auction.end_time),
+      bidding_end: escrow.english_auction.map(|auction| auction.end_time),
+      min_increment: escrow.english_auction.map(|auction| auction.min_increment),
+      commit_end: escrow.sealed_auction.map(|auction| auction.commit_end),
+      reveal_end: escrow.sealed_auction.map(|auction| auction.reveal_end),
+      forfeit_unrevealed: escrow.sealed_auction.map(|auction| auction.forfeit_unrevealed),
+      best_bid: escrow.best_bid.map(|bid| bid.to_string()),
+      bid_count: escrow.bid_count,
+      quote_deadline: escrow.rfq.map(|rfq| rfq.deadline),
+      quote_mints: escrow
+        .rfq
+        .iter()
+        .flat_map(|rfq| rfq.extra_mints[..rfq.extra_mint_count as usize].to_vec())
+        .map(|mint| mint_label(Some(&mint).filter(|mint| **mint != native_mint::ID)))
+        .collect(),
+      best_price: escrow.rfq.map(|rfq| rfq.best_price),
+      best_quote: escrow.best_quote.map(|quote| quote.to_string()),
+      oracle: escrow.oracle_peg.map(|peg| peg.oracle.to_string()),
+      spread_bps: escrow.oracle_peg.map(|peg| peg.spread_bps),
+      min_value: escrow.oracle_peg.map(|peg| peg.min_value),
+      max_value: escrow.oracle_peg.map(|peg| peg.max_value),
+      max_oracle_age: escrow.oracle_peg.map(|peg| peg.max_age),
+      max_confidence_bps: escrow.oracle_peg.map(|peg| peg.max_confidence_bps),
+      trigger_oracle: escrow.trigger.map(|trigger| trigger.oracle.to_string()),
+      trigger_price: escrow.trigger.map(|trigger| trigger.trigger_price),
+      trigger_direction: escrow.trigger.map(|trigger| trigger_direction_name(trigger.direction).to_string()),
+      max_trigger_age: escrow.trigger.map(|trigger| trigger.max_age),
+      payment_options: escrow
+        .payment_options
+        .iter()
+        .flat_map(|options| options.accepted().iter())
+        .map(|option| PaymentOptionView {
+          mint: mint_label(Some(&option.mint).filter(|mint| **mint != native_mint::ID)),
+          receive_value: option.receive_value,
+          receive_account: option.receive_account.to_string(),
+        })
+        .collect(),
+      escrow_vault: escrow.escrow_vault.to_string(),
+      creator_send_account: escrow.creator_send_account.to_string(),
+      creator_receive_account: escrow.creator_receive_account.to_string(),
+      fee_account: escrow.fee_account.to_string(),
+      timestamp: escrow.timestamp,
+    }
+  }
+
+  // label/value pairs in display order
+  pub fn fields(&self) -> Vec<(&'static str, String)> {
+    vec![
+      ("address", self.address.clone()),
+      ("creator", self.creator.clone()),
+      ("order id", self.order_id.to_string()),
+      ("trade type", self.trade_type.clone()),
+      ("stage", self.stage.clone()),
+      ("send mint", self.send_mint.clone()),
+      ("trade value", self.trade_value.to_string()),
+      ("receive mint", self.receive_mint.clone()),
+      ("receive value", self.receive_value.to_string()),
+      (
+        "specify partner",
+        self.specify_partner.clone().unwrap_or_else(|| "-".to_string()),
+      ),
+      (
+        "partner allowlist root",
+        self.partner_root.clone().unwrap_or_else(|| "-".to_string()),
+      ),
+      ("claim code required", self.claim_code_required.to_string()),
+      (
+        "market page",
+        self
+          .market_page
+          .map(|page| page.to_string())
+          .unwrap_or_else(|| "-".to_string()),
+      ),
+      (
+        "dutch auction",
+        match (self.floor_value, self.auction_start, self.auction_end) {
+          (Some(floor_value), Some(start), Some(end)) => {
+            format!("down to {} from {} to {}", floor_value, start, end)
+          }
+          _ => "-".to_string(),
+        },
+      ),
+      (
+        "english auction",
+        match (self.bidding_end, self.min_increment) {
+          (Some(end), Some(min_increment)) => format!(
+            "bids until {} raised by at least {}, {} bids",
+            end, min_increment, self.bid_count
+          ),
+          _ => "-".to_string(),
+        },
+      ),
+      (
+        "sealed-bid auction",
+        match (self.commit_end, self.reveal_end, self.forfeit_unrevealed) {
+          (Some(commit_end), Some(reveal_end), Some(forfeit_unrevealed)) => format!(
+            "commits until {}, reveals until {}, unrevealed deposits {}, {} bids",
+            commit_end,
+            reveal_end,
+            if forfeit_unrevealed { "forfeited" } else { "refunded" },
+            self.bid_count
+          ),
+          _ => "-".to_string(),
+        },
+      ),
+      ("best bid", self.best_bid.clone().unwrap_or_else(|| "-".to_string())),
+      (
+        "request for quote",
+        match (self.quote_deadline, self.best_price) {
+          (Some(deadline), Some(best_price)) => format!(
+            "quotes until {} in {}, {}",
+            deadline,
+            [self.receive_mint.clone()]
+              .iter()
+              .chain(self.quote_mints.iter())
+              .cloned()
+              .collect::<Vec<_>>()
+              .join(", "),
+            if best_price { "the best settles" } else { "the creator picks one" }
+          ),
+          _ => "-".to_string(),
+        },
+      ),
+      ("best quote", self.best_quote.clone().unwrap_or_else(|| "-".to_string())),
+      (
+        "oracle peg",
+        match (&self.oracle, self.spread_bps, self.min_value, self.max_value) {
+          (Some(oracle), Some(spread_bps), Some(min_value), Some(max_value)) => format!(
+            "{} at {} bps of {}, between {} and {}",
+            self.trade_value, spread_bps, oracle, min_value, max_value
+          ),
+          _ => "-".to_string(),
+        },
+      ),
+      (
+        "trigger",
+        match (&self.trigger_oracle, self.trigger_price, &self.trigger_direction) {
+          (Some(oracle), Some(price), Some(direction)) => {
+            format!("when {} is {} {}", oracle, direction, price)
+          }
+          _ => "-".to_string(),
+        },
+      ),
+      (
+        "payment options",
+        match self.payment_options.is_empty() {
+          true => "-".to_string(),
+          false => self
+            .payment_options
+            .iter()
+            .map(|option| format!("{} {}", option.receive_value, option.mint))
+            .collect::<Vec<_>>()
+            .join(", "),
+        },
+      ),
+      ("escrow vault", self.escrow_vault.clone()),
+      ("creator send account", self.creator_send_account.clone()),
+      ("creator receive account", self.creator_receive_account.clone()),
+      ("fee account", self.fee_account.clone()),
+      ("timestamp", self.timestamp.to_string()),
+    ]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn stages_parse_by_name_or_code() {
+    for stage in STAGES {
+      let code = stage.to_code();
+      assert_eq!(parse_stage(&stage_name(code)), Some(code));
+      assert_eq!(parse_stage(&stage_name(code).to_lowercase()), Some(code));
+      assert_eq!(parse_stage(&code.to_string()), Some(code));
+    }
+    assert_eq!(parse_stage("Open"), None);
+    assert_eq!(stage_name(99), "Unknown(99)");
+  }
+
+  #[test]
+  fn sol_is_labelled() {
+    let mint = Pubkey::new_unique();
+    assert_eq!(mint_label(Some(&mint)), mint.to_string());
+    assert_eq!(mint_label(None), NATIVE_MINT_LABEL);
+  }
+}
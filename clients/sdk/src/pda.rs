@@ -0,0 +1,87 @@
+use crate::error::{ClientError, Result};
+
+use anchor_lang::solana_program::pubkey::Pubkey;
+use std::str::FromStr;
+use trade_p2p::constant::{
+  BID_PDA_SEED, BID_VAULT_PDA_SEED, INBOX_PDA_SEED, MARKET_PDA_SEED, OFFER_PDA_SEED, OFFER_VAULT_PDA_SEED,
+  STATE_PDA_SEED, USER_PDA_SEED, VAULT_PDA_SEED,
+};
+use trade_p2p::market::market_mint;
+
+pub fn find_state_address(program_id: &Pubkey, creator: &Pubkey, order_id: u64) -> (Pubkey, u8) {
+  Pubkey::find_program_address(
+    &[STATE_PDA_SEED, creator.as_ref(), order_id.to_le_bytes().as_ref()],
+    program_id,
+  )
+}
+
+pub fn find_vault_address(program_id: &Pubkey, creator: &Pubkey, order_id: u64) -> (Pubkey, u8) {
+  Pubkey::find_program_address(
+    &[VAULT_PDA_SEED, creator.as_ref(), order_id.to_le_bytes().as_ref()],
+    program_id,
+  )
+}
+
+// order id counter of `creator`
+pub fn find_user_address(program_id: &Pubkey, creator: &Pubkey) -> (Pubkey, u8) {
+  Pubkey::find_program_address(&[USER_PDA_SEED, creator.as_ref()], program_id)
+}
+
+// inbox listing the open orders addressed to `partner`
+pub fn find_inbox_address(program_id: &Pubkey, partner: &Pubkey) -> (Pubkey, u8) {
+  Pubkey::find_program_address(&[INBOX_PDA_SEED, partner.as_ref()], program_id)
+}
+
+// bid number `bid_index` on the english auction `escrow_state`
+pub fn find_bid_address(program_id: &Pubkey, escrow_state: &Pubkey, bid_index: u32) -> (Pubkey, u8) {
+  Pubkey::find_program_address(
+    &[BID_PDA_SEED, escrow_state.as_ref(), bid_index.to_le_bytes().as_ref()],
+    program_id,
+  )
+}
+
+// vault holding the value of a bid
+pub fn find_bid_vault_address(program_id: &Pubkey, bid: &Pubkey) -> (Pubkey, u8) {
+  Pubkey::find_program_address(&[BID_VAULT_PDA_SEED, bid.as_ref()], program_id)
+}
+
+// open offer of `partner` on an order, one at a time
+pub fn find_offer_address(program_id: &Pubkey, escrow_state: &Pubkey, partner: &Pubkey) -> (Pubkey, u8) {
+  Pubkey::find_program_address(&[OFFER_PDA_SEED, escrow_state.as_ref(), partner.as_ref()], program_id)
+}
+
+// vault holding the value of an offer
+pub fn find_offer_vault_address(program_id: &Pubkey, offer: &Pubkey) -> (Pubkey, u8) {
+  Pubkey::find_program_address(&[OFFER_VAULT_PDA_SEED, offer.as_ref()], program_id)
+}
+
+// page of the market of a pair, `None` mints stand for SOL
+pub fn find_market_address(
+  program_id: &Pubkey,
+  send_mint: Option<&Pubkey>,
+  receive_mint: Option<&Pubkey>,
+  page: u16,
+) -> (Pubkey, u8) {
+  Pubkey::find_program_address(
+    &[
+      MARKET_PDA_SEED,
+      market_mint(send_mint.copied()).as_ref(),
+      market_mint(receive_mint.copied()).as_ref(),
+      page.to_le_bytes().as_ref(),
+    ],
+    program_id,
+  )
+}
+
+// state address of an order given either directly or as `creator/order_id`
+pub fn parse_order_address(program_id: &Pubkey, order: &str) -> Result<Pubkey> {
+  let invalid = || ClientError::InvalidOrderRef(order.to_string());
+  match order.split_once('/') {
+    Some((creator, order_id)) => {
+      let creator = Pubkey::from_str(creator).map_err(|_| invalid())?;
+      let order_id = order_id.parse::<u64>().map_err(|_| invalid())?;
+      Ok(find_state_address(program_id, &creator, order_id).0)
+    }
+    None => Pubkey::from_str(order).map_err(|_| invalid()),
+  }
+}
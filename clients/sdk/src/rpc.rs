@@ -0,0 +1,779 @@
+use crate::error::{describe_error_code, ClientError, Result};
+use crate::instruction::{self, PartnerAuth};
+use crate::nonce::{decode_nonce, NonceInfo};
+use crate::oracle::decode_price;
+use crate::order::{
+  decode_bid, decode_escrow, decode_first_release_creator, decode_inbox, decode_market, decode_offer,
+  decode_user_account, quote_receive_value,
+};
+use crate::pda::{find_inbox_address, find_offer_address, find_state_address, find_user_address};
+use crate::sealed_bid::SealedBid;
+use crate::transaction::Transaction;
+
+use anchor_lang::solana_program::{hash::Hash, instruction::Instruction, pubkey::Pubkey, system_program};
+use anchor_lang::Discriminator;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use trade_p2p::market::market_mint;
+use trade_p2p::processor::PriceCap;
+use trade_p2p::state::{BidAccount, EscrowAccount, MarketPage, OfferAccount, PartnerInbox, Stage, UserAccount};
+
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// expand the cluster monikers accepted by the solana cli into rpc urls
+pub fn resolve_cluster_url(cluster: &str) -> String {
+  match cluster {
+    "mainnet-beta" | "m" => "https://api.mainnet-beta.solana.com".to_string(),
+    "devnet" | "d" => "https://api.devnet.solana.com".to_string(),
+    "testnet" | "t" => "https://api.testnet.solana.com".to_string(),
+    "localhost" | "localnet" | "l" => "http://127.0.0.1:8899".to_string(),
+    url => url.to_string(),
+  }
+}
+
+// readable form of a transaction error returned by the rpc node,
+// custom program errors are decoded into `EscrowError` messages
+pub fn describe_transaction_error(err: &Value) -> String {
+  if let Some([index, Value::Object(error)]) = err
+    .get("InstructionError")
+    .and_then(Value::as_array)
+    .map(Vec::as_slice)
+  {
+    let code = error.get("Custom").and_then(Value::as_u64);
+    if let Some(description) = code.and_then(|code| describe_error_code(code as u32)) {
+      return format!("instruction {}: {}", index, description);
+    }
+  }
+  err.to_string()
+}
+
+#[derive(Clone, Debug)]
+pub struct AccountData {
+  pub lamports: u64,
+  pub owner: Pubkey,
+  pub data: Vec<u8>,
+}
+
+impl AccountData {
+  fn from_value(value: &Value) -> Result<AccountData> {
+    let invalid = || ClientError::Rpc(format!("unexpected account encoding: {}", value));
+    let lamports = value.get("lamports").and_then(Value::as_u64).ok_or_else(invalid)?;
+    let owner = value
+      .get("owner")
+      .and_then(Value::as_str)
+      .and_then(|owner| Pubkey::from_str(owner).ok())
+      .ok_or_else(invalid)?;
+    let data = value
+      .get("data")
+      .and_then(|data| data.get(0))
+      .and_then(Value::as_str)
+      .and_then(|data| base64::decode(data).ok())
+      .ok_or_else(invalid)?;
+    Ok(AccountData { lamports, owner, data })
+  }
+}
+
+pub struct RpcClient {
+  url: String,
+  agent: ureq::Agent,
+}
+
+impl RpcClient {
+  pub fn new(url: &str) -> RpcClient {
+    RpcClient {
+      url: resolve_cluster_url(url),
+      agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(30)).build(),
+    }
+  }
+
+  pub fn url(&self) -> &str {
+    &self.url
+  }
+
+  pub fn send<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    let response: Value = match self.agent.post(&self.url).send_json(request) {
+      Ok(response) => response.into_json()?,
+      Err(ureq::Error::Status(_, response)) => response.into_json()?,
+      Err(err) => return Err(ClientError::Rpc(err.to_string())),
+    };
+    if let Some(error) = response.get("error") {
+      return Err(rpc_error(error));
+    }
+    serde_json::from_value(response["result"].clone())
+      .map_err(|err| ClientError::Rpc(format!("{}: {}", method, err)))
+  }
+
+  pub fn get_account(&self, address: &Pubkey) -> Result<Option<AccountData>> {
+    let result: Value = self.send(
+      "getAccountInfo",
+      json!([address.to_string(), { "encoding": "base64" }]),
+    )?;
+    match &result["value"] {
+      Value::Null => Ok(None),
+      value => AccountData::from_value(value).map(Some),
+    }
+  }
+
+  // accounts in the order of `addresses`, `None` for those that do not exist
+  pub fn get_multiple_accounts(&self, addresses: &[Pubkey]) -> Result<Vec<Option<AccountData>>> {
+    let addresses = addresses.iter().map(Pubkey::to_string).collect::<Vec<_>>();
+    let result: Value = self.send(
+      "getMultipleAccounts",
+      json!([addresses, { "encoding": "base64" }]),
+    )?;
+    let values = result["value"]
+      .as_array()
+      .ok_or_else(|| ClientError::Rpc(format!("unexpected accounts: {}", result)))?;
+    values
+      .iter()
+      .map(|value| match value {
+        Value::Null => Ok(None),
+        value => AccountData::from_value(value).map(Some),
+      })
+      .collect()
+  }
+
+  pub fn get_program_accounts(
+    &self,
+    program_id: &Pubkey,
+    filters: Vec<Value>,
+  ) -> Result<Vec<(Pubkey, AccountData)>> {
+    let result: Vec<Value> = self.send(
+      "getProgramAccounts",
+      json!([program_id.to_string(), { "encoding": "base64", "filters": filters }]),
+    )?;
+    result
+      .iter()
+      .map(|item| {
+        let address = item["pubkey"]
+          .as_str()
+          .and_then(|address| Pubkey::from_str(address).ok())
+          .ok_or_else(|| ClientError::Rpc(format!("unexpected program account: {}", item)))?;
+        Ok((address, AccountData::from_value(&item["account"])?))
+      })
+      .collect()
+  }
+
+  pub fn get_order(&self, address: &Pubkey) -> Result<EscrowAccount> {
+    let account = self
+      .get_account(address)?
+      .ok_or_else(|| ClientError::AccountNotFound(address.to_string()))?;
+    decode_escrow(address, &account.data)
+  }
+
+  // every `EscrowAccount` owned by the program, optionally only those of one
+  // creator. Deals of the first release are left out until they are migrated
+  pub fn get_orders(
+    &self,
+    program_id: &Pubkey,
+    creator: Option<&Pubkey>,
+  ) -> Result<Vec<(Pubkey, EscrowAccount)>> {
+    self
+      .get_program_accounts(program_id, escrow_filters(EscrowAccount::LEN, creator))?
+      .into_iter()
+      .map(|(address, account)| Ok((address, decode_escrow(&address, &account.data)?)))
+      .collect()
+  }
+
+  // (address, creator) of the deals of the first release of the program, which
+  // need `migrate_escrow` before any other instruction can load them
+  pub fn get_unmigrated_orders(&self, program_id: &Pubkey, creator: Option<&Pubkey>) -> Result<Vec<(Pubkey, Pubkey)>> {
+    self
+      .get_program_accounts(program_id, escrow_filters(EscrowAccount::FIRST_RELEASE_LEN, creator))?
+      .into_iter()
+      .map(|(address, account)| Ok((address, decode_first_release_creator(&address, &account.data)?)))
+      .collect()
+  }
+
+  // creator of the first-release deal at `address`
+  pub fn get_unmigrated_order(&self, address: &Pubkey) -> Result<Pubkey> {
+    let account = self
+      .get_account(address)?
+      .ok_or_else(|| ClientError::AccountNotFound(address.to_string()))?;
+    decode_first_release_creator(address, &account.data)
+  }
+
+  pub fn get_user_account(&self, program_id: &Pubkey, creator: &Pubkey) -> Result<Option<UserAccount>> {
+    let (address, _) = find_user_address(program_id, creator);
+    match self.get_account(&address)? {
+      Some(account) if account.owner == *program_id => decode_user_account(&address, &account.data).map(Some),
+      _ => Ok(None),
+    }
+  }
+
+  // the order id `create_trade` gives the next order of `creator`, zero
+  // before their first order
+  pub fn next_order_id(&self, program_id: &Pubkey, creator: &Pubkey) -> Result<u64> {
+    Ok(
+      self
+        .get_user_account(program_id, creator)?
+        .map_or(0, |user_account| user_account.next_order_id),
+    )
+  }
+
+  // inbox of `partner`, `None` until the first order addressed to them
+  pub fn get_inbox(&self, program_id: &Pubkey, partner: &Pubkey) -> Result<Option<PartnerInbox>> {
+    let (address, _) = find_inbox_address(program_id, partner);
+    match self.get_account(&address)? {
+      Some(account) if account.owner == *program_id => decode_inbox(&address, &account.data).map(Some),
+      _ => Ok(None),
+    }
+  }
+
+  // the open orders addressed to `partner`
+  pub fn get_inbox_orders(
+    &self,
+    program_id: &Pubkey,
+    partner: &Pubkey,
+  ) -> Result<Vec<(Pubkey, EscrowAccount)>> {
+    let orders = match self.get_inbox(program_id, partner)? {
+      Some(inbox) if !inbox.orders.is_empty() => inbox.orders,
+      _ => return Ok(Vec::new()),
+    };
+    let accounts = self.get_multiple_accounts(&orders)?;
+    orders
+      .into_iter()
+      .zip(accounts)
+      .filter_map(|(address, account)| account.map(|account| (address, account)))
+      .map(|(address, account)| Ok((address, decode_escrow(&address, &account.data)?)))
+      .collect()
+  }
+
+  // every page of the market of a pair, by page number. `None` mints stand for SOL
+  pub fn get_market(
+    &self,
+    program_id: &Pubkey,
+    send_mint: Option<&Pubkey>,
+    receive_mint: Option<&Pubkey>,
+  ) -> Result<Vec<MarketPage>> {
+    // `send_mint` and `receive_mint` are the first fields after the discriminator
+    let filters = vec![
+      json!({ "memcmp": { "offset": 0, "bytes": bs58::encode(MarketPage::discriminator()).into_string() } }),
+      json!({ "memcmp": { "offset": 8, "bytes": market_mint(send_mint.copied()).to_string() } }),
+      json!({ "memcmp": { "offset": 40, "bytes": market_mint(receive_mint.copied()).to_string() } }),
+    ];
+    let mut pages = self
+      .get_program_accounts(program_id, filters)?
+      .into_iter()
+      .map(|(address, account)| decode_market(&address, &account.data))
+      .collect::<Result<Vec<_>>>()?;
+    pages.sort_by_key(|page| page.page);
+    Ok(pages)
+  }
+
+  // lowest page of the market of a pair with room for `count` more orders
+  pub fn find_market_page(
+    &self,
+    program_id: &Pubkey,
+    send_mint: Option<&Pubkey>,
+    receive_mint: Option<&Pubkey>,
+    count: usize,
+  ) -> Result<u16> {
+    let pages = self.get_market(program_id, send_mint, receive_mint)?;
+    let mut next = 0;
+    for page in &pages {
+      if page.page != next || page.orders.len() + count <= MarketPage::MAX_ORDERS {
+        break;
+      }
+      next += 1;
+    }
+    Ok(next)
+  }
+
+  // instructions for `partner` to fill an order, creating the partner's
+  // receiving token account first when it does not exist yet
+  pub fn exchange_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    partner: &Pubkey,
+    auth: &PartnerAuth,
+    max_receive_value: Option<u64>,
+  ) -> Result<Vec<Instruction>> {
+    let mint = order.creator_receive_token_mint;
+    self.exchange_paying_instructions(program_id, order, partner, mint.as_ref(), auth, max_receive_value)
+  }
+
+  // `exchange_instructions` paying in `mint` (`None` for SOL), the order's own
+  // receive mint or the mint of one of its payment options
+  pub fn exchange_paying_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    partner: &Pubkey,
+    mint: Option<&Pubkey>,
+    auth: &PartnerAuth,
+    max_receive_value: Option<u64>,
+  ) -> Result<Vec<Instruction>> {
+    instruction::check_exchange(order, partner, auth)?;
+    let mut instructions = Vec::new();
+    if let Some(mint) = &order.creator_send_token_mint {
+      let (_, partner_receive_account) = instruction::partner_accounts(order, partner);
+      if self.get_account(&partner_receive_account)?.is_none() {
+        instructions.push(instruction::create_associated_token_account(partner, partner, mint));
+      }
+    }
+    instructions.push(match mint == order.creator_receive_token_mint.as_ref() {
+      true => instruction::exchange(program_id, order, partner, auth, max_receive_value),
+      false => {
+        instruction::exchange_with_payment_option(program_id, order, partner, mint, auth, max_receive_value)?
+      }
+    });
+    Ok(instructions)
+  }
+
+  // `instruction::exchange_many`, creating the partner's receiving token account
+  // first when it does not exist yet
+  pub fn exchange_many_instructions(
+    &self,
+    program_id: &Pubkey,
+    orders: &[EscrowAccount],
+    partner: &Pubkey,
+    budget: u64,
+    max_price: Option<PriceCap>,
+    atomic: bool,
+  ) -> Result<Vec<Instruction>> {
+    let mut instructions = Vec::new();
+    if let Some(order) = orders.first() {
+      if let Some(mint) = &order.creator_send_token_mint {
+        let (_, partner_receive_account) = instruction::partner_accounts(order, partner);
+        if self.get_account(&partner_receive_account)?.is_none() {
+          instructions.push(instruction::create_associated_token_account(partner, partner, mint));
+        }
+      }
+    }
+    instructions.push(instruction::exchange_many(
+      program_id, orders, partner, budget, max_price, atomic,
+    )?);
+    Ok(instructions)
+  }
+
+  // `instruction::match_orders`, creating the matcher's token accounts the fees
+  // are paid into first when they do not exist yet
+  pub fn match_orders_instructions(
+    &self,
+    program_id: &Pubkey,
+    a: &EscrowAccount,
+    b: &EscrowAccount,
+    matcher: &Pubkey,
+  ) -> Result<Vec<Instruction>> {
+    let (fee_a, fee_b) = instruction::check_match(a, b)?;
+    let mut instructions = Vec::new();
+    for (order, fee) in [(a, fee_a), (b, fee_b)] {
+      if let (Some(mint), true) = (&order.creator_send_token_mint, fee > 0) {
+        if self.get_account(&instruction::side_account(matcher, Some(mint)))?.is_none() {
+          instructions.push(instruction::create_associated_token_account(matcher, matcher, mint));
+        }
+      }
+    }
+    instructions.push(instruction::match_orders(program_id, a, b, matcher)?);
+    Ok(instructions)
+  }
+
+  // price an order is filled at, at unix time `now`, reading the feed of an
+  // oracle-pegged order as the program does
+  pub fn quote_receive_value(&self, order: &EscrowAccount, now: i64) -> Result<u64> {
+    let peg = match &order.oracle_peg {
+      Some(peg) => peg,
+      None => return Ok(quote_receive_value(order, now)),
+    };
+    let account = self
+      .get_account(&peg.oracle)?
+      .ok_or_else(|| ClientError::AccountNotFound(peg.oracle.to_string()))?;
+    let price = decode_price(&peg.oracle, &account.owner, &account.data)?;
+    peg
+      .price_at(order.trade_value, &price, now)
+      .map_err(|err| ClientError::InvalidTrade(err.to_string()))
+  }
+
+  // best bid of an english auction order, `None` before its first bid
+  pub fn get_best_bid(&self, order: &EscrowAccount) -> Result<Option<BidAccount>> {
+    let address = match order.best_bid {
+      Some(address) => address,
+      None => return Ok(None),
+    };
+    let account = self
+      .get_account(&address)?
+      .ok_or_else(|| ClientError::AccountNotFound(address.to_string()))?;
+    decode_bid(&address, &account.data).map(Some)
+  }
+
+  // every bid placed or committed on an auction order, by bid index
+  pub fn get_bids(&self, program_id: &Pubkey, order: &EscrowAccount) -> Result<Vec<(Pubkey, BidAccount)>> {
+    let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+    // `escrow_state` is the first field after the discriminator
+    let filters = vec![
+      json!({ "memcmp": { "offset": 0, "bytes": bs58::encode(BidAccount::discriminator()).into_string() } }),
+      json!({ "memcmp": { "offset": 8, "bytes": escrow_state.to_string() } }),
+    ];
+    let mut bids = self
+      .get_program_accounts(program_id, filters)?
+      .into_iter()
+      .map(|(address, account)| Ok((address, decode_bid(&address, &account.data)?)))
+      .collect::<Result<Vec<_>>>()?;
+    bids.sort_by_key(|(_, bid)| bid.bid_index);
+    Ok(bids)
+  }
+
+  // `instruction::place_bid` against the order's current best bid, creating the
+  // bidder's receiving token account and the refunded bidder's paying token
+  // account first when they do not exist yet
+  pub fn place_bid_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    bidder: &Pubkey,
+    value: u64,
+  ) -> Result<Vec<Instruction>> {
+    let best_bid = self.get_best_bid(order)?;
+    let place_bid = instruction::place_bid(program_id, order, best_bid.as_ref(), bidder, value)?;
+    let mut instructions = Vec::new();
+    if let Some(mint) = &order.creator_send_token_mint {
+      let (_, bidder_receive_account) = instruction::partner_accounts(order, bidder);
+      if self.get_account(&bidder_receive_account)?.is_none() {
+        instructions.push(instruction::create_associated_token_account(bidder, bidder, mint));
+      }
+    }
+    if let (Some(mint), Some(best_bid)) = (&order.creator_receive_token_mint, &best_bid) {
+      if self.get_account(&best_bid.bidder_send_account)?.is_none() {
+        instructions.push(instruction::create_associated_token_account(bidder, &best_bid.bidder, mint));
+      }
+    }
+    instructions.push(place_bid);
+    Ok(instructions)
+  }
+
+  // `instruction::settle_auction`, creating the winner's receiving token account
+  // first when it does not exist yet, paid by `payer`
+  pub fn settle_auction_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    payer: &Pubkey,
+  ) -> Result<Vec<Instruction>> {
+    let best_bid = self.get_best_bid(order)?;
+    let mut instructions = Vec::new();
+    if let (Some(mint), Some(best_bid)) = (&order.creator_send_token_mint, &best_bid) {
+      if self.get_account(&best_bid.bidder_receive_account)?.is_none() {
+        instructions.push(instruction::create_associated_token_account(payer, &best_bid.bidder, mint));
+      }
+    }
+    // the deposit of a sealed bid above the bid goes back to the winner
+    if let (Some(mint), Some(best_bid)) = (&order.creator_receive_token_mint, &best_bid) {
+      if best_bid.deposit > best_bid.value && self.get_account(&best_bid.bidder_send_account)?.is_none() {
+        instructions.push(instruction::create_associated_token_account(payer, &best_bid.bidder, mint));
+      }
+    }
+    instructions.push(instruction::settle_auction(program_id, order, best_bid.as_ref()));
+    Ok(instructions)
+  }
+
+  // `instruction::commit_bid`, creating the bidder's receiving token account
+  // first when it does not exist yet
+  pub fn commit_bid_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    bidder: &Pubkey,
+    deposit: u64,
+    sealed: &SealedBid,
+  ) -> Result<Vec<Instruction>> {
+    let commit_bid = instruction::commit_bid(program_id, order, bidder, deposit, sealed)?;
+    let mut instructions = Vec::new();
+    if let Some(mint) = &order.creator_send_token_mint {
+      let (_, bidder_receive_account) = instruction::partner_accounts(order, bidder);
+      if self.get_account(&bidder_receive_account)?.is_none() {
+        instructions.push(instruction::create_associated_token_account(bidder, bidder, mint));
+      }
+    }
+    instructions.push(commit_bid);
+    Ok(instructions)
+  }
+
+  // `instruction::reveal_bid` of the unrevealed bid of `bidder` committed as
+  // `sealed`, creating the token account the bid or the best bid it beats is
+  // refunded into first when it does not exist yet
+  pub fn reveal_bid_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    bidder: &Pubkey,
+    sealed: &SealedBid,
+  ) -> Result<Vec<Instruction>> {
+    let bid = self
+      .get_bids(program_id, order)?
+      .into_iter()
+      .map(|(_, bid)| bid)
+      .find(|bid| bid.bidder == *bidder && bid.commitment == Some(sealed.commitment(bidder)))
+      .ok_or_else(|| ClientError::InvalidTrade(format!("no unrevealed bid of {} matches", bidder)))?;
+    let best_bid = self.get_best_bid(order)?;
+    let reveal_bid = instruction::reveal_bid(program_id, order, &bid, best_bid.as_ref(), sealed)?;
+    let mut instructions = Vec::new();
+    if let Some(mint) = &order.creator_receive_token_mint {
+      let refunded = match &best_bid {
+        Some(best_bid) if sealed.value > best_bid.value => best_bid,
+        _ => &bid,
+      };
+      if self.get_account(&refunded.bidder_send_account)?.is_none() {
+        instructions.push(instruction::create_associated_token_account(bidder, &refunded.bidder, mint));
+      }
+    }
+    instructions.push(reveal_bid);
+    Ok(instructions)
+  }
+
+  // `instruction::reclaim_bid` for every bid of an order never revealed, creating
+  // the token account a refunded deposit goes to first when it does not exist yet
+  pub fn reclaim_bids_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    payer: &Pubkey,
+  ) -> Result<Vec<Instruction>> {
+    let auction = order
+      .sealed_auction
+      .ok_or_else(|| ClientError::InvalidTrade("order is not a sealed-bid auction".to_string()))?;
+    let mut instructions = Vec::new();
+    for (_, bid) in self.get_bids(program_id, order)? {
+      if bid.stage != Stage::ReadyExchange.to_code() || bid.commitment.is_none() {
+        continue;
+      }
+      if let (Some(mint), false) = (&order.creator_receive_token_mint, auction.forfeit_unrevealed) {
+        if self.get_account(&bid.bidder_send_account)?.is_none() {
+          instructions.push(instruction::create_associated_token_account(payer, &bid.bidder, mint));
+        }
+      }
+      instructions.push(instruction::reclaim_bid(program_id, order, &bid));
+    }
+    Ok(instructions)
+  }
+
+  // open offers on an order, largest first
+  pub fn get_offers(&self, program_id: &Pubkey, order: &EscrowAccount) -> Result<Vec<(Pubkey, OfferAccount)>> {
+    let (escrow_state, _) = find_state_address(program_id, &order.creator, order.order_id);
+    // `escrow_state` is the first field after the discriminator
+    let filters = vec![
+      json!({ "memcmp": { "offset": 0, "bytes": bs58::encode(OfferAccount::discriminator()).into_string() } }),
+      json!({ "memcmp": { "offset": 8, "bytes": escrow_state.to_string() } }),
+    ];
+    let mut offers = self
+      .get_program_accounts(program_id, filters)?
+      .into_iter()
+      .map(|(address, account)| Ok((address, decode_offer(&address, &account.data)?)))
+      .collect::<Result<Vec<_>>>()?;
+    offers.sort_by_key(|(_, offer)| std::cmp::Reverse(offer.value));
+    Ok(offers)
+  }
+
+  // open offer of `partner` on an order, if any
+  pub fn get_offer(&self, program_id: &Pubkey, order: &Pubkey, partner: &Pubkey) -> Result<Option<OfferAccount>> {
+    let (address, _) = find_offer_address(program_id, order, partner);
+    self
+      .get_account(&address)?
+      .map(|account| decode_offer(&address, &account.data))
+      .transpose()
+  }
+
+  pub fn make_offer_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    partner: &Pubkey,
+    offer_mint: Option<&Pubkey>,
+    value: u64,
+  ) -> Result<Vec<Instruction>> {
+    let make_offer = instruction::make_offer(program_id, order, partner, offer_mint, value)?;
+    let mut instructions = Vec::new();
+    if let Some(mint) = &order.creator_send_token_mint {
+      let (_, partner_receive_account) = instruction::partner_accounts(order, partner);
+      if self.get_account(&partner_receive_account)?.is_none() {
+        instructions.push(instruction::create_associated_token_account(partner, partner, mint));
+      }
+    }
+    instructions.push(make_offer);
+    Ok(instructions)
+  }
+
+  pub fn accept_offer_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    offer: &OfferAccount,
+  ) -> Result<Vec<Instruction>> {
+    let mut instructions = Vec::new();
+    if let Some(mint) = &offer.offer_mint {
+      let creator_offer_account = instruction::side_account(&order.creator, Some(mint));
+      if self.get_account(&creator_offer_account)?.is_none() {
+        instructions.push(instruction::create_associated_token_account(&order.creator, &order.creator, mint));
+      }
+    }
+    instructions.push(instruction::accept_offer(program_id, order, offer));
+    Ok(instructions)
+  }
+
+  pub fn settle_rfq_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    payer: &Pubkey,
+  ) -> Result<Vec<Instruction>> {
+    let address = order
+      .best_quote
+      .ok_or_else(|| ClientError::InvalidTrade("order has no best quote".to_string()))?;
+    let best_quote = match self.get_account(&address)? {
+      Some(account) => decode_offer(&address, &account.data)?,
+      None => return Err(ClientError::InvalidOffer(address.to_string())),
+    };
+    let mut instructions = Vec::new();
+    if let Some(mint) = &best_quote.offer_mint {
+      let creator_offer_account = instruction::side_account(&order.creator, Some(mint));
+      if self.get_account(&creator_offer_account)?.is_none() {
+        instructions.push(instruction::create_associated_token_account(payer, &order.creator, mint));
+      }
+    }
+    instructions.push(instruction::settle_rfq(program_id, order, &best_quote));
+    Ok(instructions)
+  }
+
+  // fire the trigger of a conditional order, checking first that the price of
+  // its feed has reached the trigger price at unix time `now`
+  pub fn trigger_instructions(&self, program_id: &Pubkey, order: &EscrowAccount, now: i64) -> Result<Vec<Instruction>> {
+    if order.stage != Stage::AwaitingTrigger.to_code() {
+      return Err(ClientError::InvalidTrade("order does not wait for a trigger".to_string()));
+    }
+    let trigger = order
+      .trigger
+      .ok_or_else(|| ClientError::InvalidTrade("order has no trigger".to_string()))?;
+    let account = self
+      .get_account(&trigger.oracle)?
+      .ok_or_else(|| ClientError::AccountNotFound(trigger.oracle.to_string()))?;
+    let price = decode_price(&trigger.oracle, &account.owner, &account.data)?;
+    if price.publish_time.saturating_add(trigger.max_age as i64) < now {
+      return Err(ClientError::InvalidTrade(format!(
+        "price of {} is older than {} seconds",
+        trigger.oracle, trigger.max_age
+      )));
+    }
+    if !trigger.is_reached(price.price) {
+      return Err(ClientError::InvalidTrade(format!(
+        "price {} has not reached the trigger price {}",
+        price.price, trigger.trigger_price
+      )));
+    }
+    Ok(vec![instruction::trigger(program_id, order, &trigger.oracle)])
+  }
+
+  // refund every offer left on a closed order, recreating the partners'
+  // associated token accounts they are refunded to
+  pub fn refund_offers_instructions(
+    &self,
+    program_id: &Pubkey,
+    order: &EscrowAccount,
+    payer: &Pubkey,
+  ) -> Result<Vec<Instruction>> {
+    if order.stage == Stage::ReadyExchange.to_code() {
+      return Err(ClientError::InvalidTrade("order is still open".to_string()));
+    }
+    let mut instructions = Vec::new();
+    for (_, offer) in self.get_offers(program_id, order)? {
+      if let Some(mint) = &offer.offer_mint {
+        if self.get_account(&offer.partner_send_account)?.is_none() {
+          instructions.push(instruction::create_associated_token_account(payer, &offer.partner, mint));
+        }
+      }
+      instructions.push(instruction::refund_offer(program_id, &offer));
+    }
+    Ok(instructions)
+  }
+
+  pub fn get_nonce(&self, nonce_account: &Pubkey) -> Result<NonceInfo> {
+    let account = self
+      .get_account(nonce_account)?
+      .ok_or_else(|| ClientError::AccountNotFound(nonce_account.to_string()))?;
+    if account.owner != system_program::ID {
+      return Err(ClientError::InvalidNonce(nonce_account.to_string()));
+    }
+    decode_nonce(nonce_account, &account.data)
+  }
+
+  pub fn get_latest_blockhash(&self) -> Result<Hash> {
+    let result: Value = self.send("getLatestBlockhash", json!([{ "commitment": "confirmed" }]))?;
+    result["value"]["blockhash"]
+      .as_str()
+      .and_then(|blockhash| Hash::from_str(blockhash).ok())
+      .ok_or_else(|| ClientError::Rpc(format!("unexpected blockhash response: {}", result)))
+  }
+
+  pub fn send_transaction(&self, transaction: &Transaction) -> Result<String> {
+    self.send(
+      "sendTransaction",
+      json!([
+        base64::encode(transaction.serialize()),
+        { "encoding": "base64", "preflightCommitment": "confirmed" }
+      ]),
+    )
+  }
+
+  pub fn confirm_transaction(&self, signature: &str) -> Result<()> {
+    let started = Instant::now();
+    while started.elapsed() < CONFIRM_TIMEOUT {
+      let result: Value = self.send("getSignatureStatuses", json!([[signature]]))?;
+      let status = &result["value"][0];
+      if !status.is_null() {
+        if !status["err"].is_null() {
+          return Err(ClientError::TransactionFailed(describe_transaction_error(&status["err"])));
+        }
+        if matches!(
+          status["confirmationStatus"].as_str(),
+          Some("confirmed") | Some("finalized")
+        ) {
+          return Ok(());
+        }
+      }
+      std::thread::sleep(CONFIRM_POLL_INTERVAL);
+    }
+    Err(ClientError::TransactionFailed(format!(
+      "{} was not confirmed in {}s",
+      signature,
+      CONFIRM_TIMEOUT.as_secs()
+    )))
+  }
+
+  pub fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<String> {
+    let signature = self.send_transaction(transaction)?;
+    self.confirm_transaction(&signature)?;
+    Ok(signature)
+  }
+}
+
+fn rpc_error(error: &Value) -> ClientError {
+  // preflight failures carry the transaction error and program logs in `data`
+  match error.get("data").map(|data| &data["err"]) {
+    Some(err) if !err.is_null() => ClientError::TransactionFailed(describe_transaction_error(err)),
+    _ => ClientError::Rpc(
+      error["message"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| error.to_string()),
+    ),
+  }
+}
+
+// `EscrowAccount`s of one layout size, optionally only those of one creator
+fn escrow_filters(size: usize, creator: Option<&Pubkey>) -> Vec<Value> {
+  let mut filters = vec![
+    json!({ "dataSize": size }),
+    json!({
+      "memcmp": { "offset": 0, "bytes": bs58::encode(EscrowAccount::discriminator()).into_string() }
+    }),
+  ];
+  if let Some(creator) = creator {
+    // `creator` is the first field after the discriminator
+    filters.push(json!({ "memcmp": { "offset": 8, "bytes": creator.to_string() } }));
+  }
+  filters
+}
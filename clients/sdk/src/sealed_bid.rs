@@ -0,0 +1,57 @@
+use crate::error::{ClientError, Result};
+
+use anchor_lang::solana_program::pubkey::Pubkey;
+use rand::{rngs::OsRng, RngCore};
+use std::fmt;
+use std::str::FromStr;
+use trade_p2p::utils::bid_commitment;
+
+// a sealed bid kept by the bidder between `commit_bid` and `reveal_bid`. Only
+// its commitment is on chain until it is revealed, and losing it means the bid
+// cannot be revealed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SealedBid {
+  pub value: u64,
+  pub salt: [u8; 32],
+}
+
+impl SealedBid {
+  pub fn generate(value: u64) -> SealedBid {
+    let mut salt = [0u8; 32];
+    OsRng.fill_bytes(&mut salt);
+    SealedBid { value, salt }
+  }
+
+  // commitment passed to `commit_bid`
+  pub fn commitment(&self, bidder: &Pubkey) -> [u8; 32] {
+    bid_commitment(bidder, self.value, &self.salt)
+  }
+}
+
+// `<VALUE>:<SALT>`, the salt in base58
+impl fmt::Display for SealedBid {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}", self.value, bs58::encode(self.salt).into_string())
+  }
+}
+
+// never print the salt by accident
+impl fmt::Debug for SealedBid {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "SealedBid({})", self.value)
+  }
+}
+
+impl FromStr for SealedBid {
+  type Err = ClientError;
+
+  fn from_str(value: &str) -> Result<SealedBid> {
+    let invalid = || ClientError::InvalidTrade("invalid sealed bid, expected <VALUE>:<SALT>".to_string());
+    let (bid_value, salt) = value.trim().split_once(':').ok_or_else(invalid)?;
+    let bytes = bs58::decode(salt).into_vec().map_err(|_| invalid())?;
+    Ok(SealedBid {
+      value: bid_value.parse().map_err(|_| invalid())?,
+      salt: bytes.try_into().map_err(|_| invalid())?,
+    })
+  }
+}
@@ -0,0 +1,321 @@
+use crate::error::{ClientError, Result};
+use crate::keypair::Keypair;
+
+use anchor_lang::solana_program::{
+  hash::Hash, instruction::Instruction, message::Message, pubkey::Pubkey,
+};
+use ed25519_dalek::Verifier;
+use std::str::FromStr;
+
+pub const SIGNATURE_LEN: usize = 64;
+
+// text encodings used to move transactions between online and offline machines
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionEncoding {
+  Base58,
+  Base64,
+}
+
+impl FromStr for TransactionEncoding {
+  type Err = ClientError;
+
+  fn from_str(value: &str) -> Result<TransactionEncoding> {
+    match value {
+      "base58" => Ok(TransactionEncoding::Base58),
+      "base64" => Ok(TransactionEncoding::Base64),
+      unknown => Err(ClientError::InvalidTransaction(format!(
+        "unknown encoding {}, expected base58 or base64",
+        unknown
+      ))),
+    }
+  }
+}
+
+pub fn parse_signature(value: &str) -> Result<[u8; SIGNATURE_LEN]> {
+  let bytes = bs58::decode(value)
+    .into_vec()
+    .map_err(|_| ClientError::InvalidTransaction(format!("invalid signature {}", value)))?;
+  bytes
+    .try_into()
+    .map_err(|_| ClientError::InvalidTransaction(format!("invalid signature {}", value)))
+}
+
+// legacy transaction in wire format: a compact-u16 prefixed list of
+// signatures followed by the serialized message
+#[derive(Clone, Debug)]
+pub struct Transaction {
+  pub signatures: Vec<[u8; SIGNATURE_LEN]>,
+  pub message: Message,
+}
+
+impl Transaction {
+  pub fn new_unsigned(message: Message) -> Transaction {
+    let signatures = vec![[0u8; SIGNATURE_LEN]; message.header.num_required_signatures as usize];
+    Transaction { signatures, message }
+  }
+
+  pub fn new_with_blockhash(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    recent_blockhash: Hash,
+  ) -> Transaction {
+    let message = Message::new_with_blockhash(instructions, Some(payer), &recent_blockhash);
+    Transaction::new_unsigned(message)
+  }
+
+  pub fn new_signed(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    signers: &[&Keypair],
+    recent_blockhash: Hash,
+  ) -> Result<Transaction> {
+    let mut transaction = Transaction::new_with_blockhash(instructions, payer, recent_blockhash);
+    for signer in signers {
+      transaction.sign(signer)?;
+    }
+    Ok(transaction)
+  }
+
+  // transaction whose blockhash is the durable nonce stored in `nonce_account`,
+  // the program instructions are preceded by the nonce advance instruction
+  pub fn new_with_nonce(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    nonce_account: &Pubkey,
+    nonce_authority: &Pubkey,
+    nonce_blockhash: Hash,
+  ) -> Transaction {
+    let mut message =
+      Message::new_with_nonce(instructions.to_vec(), Some(payer), nonce_account, nonce_authority);
+    message.recent_blockhash = nonce_blockhash;
+    Transaction::new_unsigned(message)
+  }
+
+  pub fn signer_keys(&self) -> &[Pubkey] {
+    &self.message.account_keys[..self.signatures.len()]
+  }
+
+  pub fn sign(&mut self, keypair: &Keypair) -> Result<()> {
+    let pubkey = keypair.pubkey();
+    let position = self.signer_position(&pubkey)?;
+    self.signatures[position] = keypair.sign(&self.message.serialize());
+    Ok(())
+  }
+
+  // attach a signature produced elsewhere, rejecting it unless it verifies
+  pub fn add_signature(&mut self, pubkey: &Pubkey, signature: [u8; SIGNATURE_LEN]) -> Result<()> {
+    let position = self.signer_position(pubkey)?;
+    let invalid = || ClientError::InvalidTransaction(format!("invalid signature for {}", pubkey));
+    let public_key = ed25519_dalek::PublicKey::from_bytes(pubkey.as_ref()).map_err(|_| invalid())?;
+    let dalek_signature = ed25519_dalek::Signature::from_bytes(&signature).map_err(|_| invalid())?;
+    public_key
+      .verify(&self.message.serialize(), &dalek_signature)
+      .map_err(|_| invalid())?;
+    self.signatures[position] = signature;
+    Ok(())
+  }
+
+  pub fn signer_position(&self, pubkey: &Pubkey) -> Result<usize> {
+    self
+      .signer_keys()
+      .iter()
+      .position(|key| key == pubkey)
+      .ok_or_else(|| ClientError::InvalidTransaction(format!("{} is not a required signer", pubkey)))
+  }
+
+  pub fn is_signed(&self) -> bool {
+    self.missing_signers().is_empty()
+  }
+
+  pub fn missing_signers(&self) -> Vec<Pubkey> {
+    self
+      .signer_keys()
+      .iter()
+      .zip(&self.signatures)
+      .filter(|(_, signature)| *signature == &[0u8; SIGNATURE_LEN])
+      .map(|(pubkey, _)| *pubkey)
+      .collect()
+  }
+
+  // first signature identifies the transaction on chain
+  pub fn signature(&self) -> String {
+    self
+      .signatures
+      .first()
+      .map(|signature| bs58::encode(signature).into_string())
+      .unwrap_or_default()
+  }
+
+  pub fn serialize(&self) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    encode_length(&mut bytes, self.signatures.len());
+    for signature in &self.signatures {
+      bytes.extend_from_slice(signature);
+    }
+    bytes.extend_from_slice(&self.message.serialize());
+    bytes
+  }
+
+  pub fn encode(&self, encoding: TransactionEncoding) -> String {
+    match encoding {
+      TransactionEncoding::Base58 => bs58::encode(self.serialize()).into_string(),
+      TransactionEncoding::Base64 => base64::encode(self.serialize()),
+    }
+  }
+
+  pub fn decode(value: &str, encoding: TransactionEncoding) -> Result<Transaction> {
+    let bytes = match encoding {
+      TransactionEncoding::Base58 => bs58::decode(value.trim()).into_vec().ok(),
+      TransactionEncoding::Base64 => base64::decode(value.trim()).ok(),
+    };
+    let bytes = bytes.ok_or_else(|| {
+      ClientError::InvalidTransaction(format!("transaction is not valid {:?}", encoding))
+    })?;
+    Transaction::deserialize(&bytes)
+  }
+
+  pub fn deserialize(bytes: &[u8]) -> Result<Transaction> {
+    let invalid = |reason: &str| ClientError::InvalidTransaction(reason.to_string());
+    let (count, offset) = decode_length(bytes).ok_or_else(|| invalid("bad signature count"))?;
+    let message_offset = offset + count * SIGNATURE_LEN;
+    if bytes.len() < message_offset {
+      return Err(invalid("truncated signatures"));
+    }
+    let signatures = bytes[offset..message_offset]
+      .chunks(SIGNATURE_LEN)
+      .map(|chunk| {
+        let mut signature = [0u8; SIGNATURE_LEN];
+        signature.copy_from_slice(chunk);
+        signature
+      })
+      .collect::<Vec<_>>();
+    let message: Message =
+      bincode::deserialize(&bytes[message_offset..]).map_err(|err| invalid(&err.to_string()))?;
+    if signatures.len() != message.header.num_required_signatures as usize {
+      return Err(invalid("signature count does not match message header"));
+    }
+    Ok(Transaction { signatures, message })
+  }
+}
+
+// compact-u16 length prefix used by the solana wire format
+fn encode_length(bytes: &mut Vec<u8>, len: usize) {
+  let mut rem = len;
+  loop {
+    let mut elem = (rem & 0x7f) as u8;
+    rem >>= 7;
+    if rem == 0 {
+      bytes.push(elem);
+      break;
+    }
+    elem |= 0x80;
+    bytes.push(elem);
+  }
+}
+
+fn decode_length(bytes: &[u8]) -> Option<(usize, usize)> {
+  let mut len = 0usize;
+  for (index, byte) in bytes.iter().take(3).enumerate() {
+    len |= ((byte & 0x7f) as usize) << (index * 7);
+    if byte & 0x80 == 0 {
+      return Some((len, index + 1));
+    }
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use anchor_lang::solana_program::instruction::AccountMeta;
+
+  #[test]
+  fn compact_length_roundtrip() {
+    for (len, encoded) in [
+      (0, vec![0x00]),
+      (127, vec![0x7f]),
+      (128, vec![0x80, 0x01]),
+      (16_383, vec![0xff, 0x7f]),
+      (16_384, vec![0x80, 0x80, 0x01]),
+    ] {
+      let mut bytes = Vec::new();
+      encode_length(&mut bytes, len);
+      assert_eq!(bytes, encoded);
+      assert_eq!(decode_length(&bytes), Some((len, encoded.len())));
+    }
+    assert_eq!(decode_length(&[0x80, 0x80, 0x80]), None);
+    assert_eq!(decode_length(&[]), None);
+  }
+
+  fn transaction(signer: &Keypair, other: &Pubkey) -> Transaction {
+    let instruction = Instruction {
+      program_id: trade_p2p::ID,
+      accounts: vec![AccountMeta::new(signer.pubkey(), true), AccountMeta::new(*other, true)],
+      data: vec![1, 2, 3],
+    };
+    Transaction::new_with_blockhash(&[instruction], &signer.pubkey(), Hash::new_unique())
+  }
+
+  #[test]
+  fn serialize_roundtrip() {
+    let signer = Keypair::from_seed(&[1; 32]);
+    let other = Keypair::from_seed(&[2; 32]);
+    let mut unsigned = transaction(&signer, &other.pubkey());
+    unsigned.sign(&signer).unwrap();
+    assert_eq!(unsigned.missing_signers(), vec![other.pubkey()]);
+
+    for encoding in [TransactionEncoding::Base58, TransactionEncoding::Base64] {
+      let decoded = Transaction::decode(&unsigned.encode(encoding), encoding).unwrap();
+      assert_eq!(decoded.signatures, unsigned.signatures);
+      assert_eq!(decoded.message, unsigned.message);
+      assert_eq!(decoded.serialize(), unsigned.serialize());
+    }
+  }
+
+  #[test]
+  fn add_signature_verifies_it() {
+    let signer = Keypair::from_seed(&[1; 32]);
+    let other = Keypair::from_seed(&[2; 32]);
+    let mut transaction = transaction(&signer, &other.pubkey());
+    let signature = other.sign(&transaction.message.serialize());
+    assert!(transaction.add_signature(&signer.pubkey(), signature).is_err());
+    transaction.add_signature(&other.pubkey(), signature).unwrap();
+    transaction.sign(&signer).unwrap();
+    assert!(transaction.is_signed());
+  }
+
+  #[test]
+  fn nonce_transactions_advance_the_nonce_first() {
+    let payer = Keypair::from_seed(&[1; 32]);
+    let (nonce_account, nonce_blockhash) = (Pubkey::new_unique(), Hash::new_unique());
+    let instruction = Instruction {
+      program_id: trade_p2p::ID,
+      accounts: vec![AccountMeta::new(payer.pubkey(), true)],
+      data: vec![1],
+    };
+    let transaction = Transaction::new_with_nonce(
+      &[instruction],
+      &payer.pubkey(),
+      &nonce_account,
+      &payer.pubkey(),
+      nonce_blockhash,
+    );
+    assert_eq!(transaction.message.recent_blockhash, nonce_blockhash);
+    assert_eq!(transaction.signer_keys(), &[payer.pubkey()]);
+    let advance = &transaction.message.instructions[0];
+    let program_id = transaction.message.account_keys[advance.program_id_index as usize];
+    assert_eq!(program_id, anchor_lang::solana_program::system_program::ID);
+    assert_eq!(transaction.message.account_keys[advance.accounts[0] as usize], nonce_account);
+    assert_eq!(transaction.message.instructions.len(), 2);
+  }
+
+  #[test]
+  fn deserialize_rejects_malformed_bytes() {
+    let signer = Keypair::from_seed(&[1; 32]);
+    let bytes = transaction(&signer, &Keypair::from_seed(&[2; 32]).pubkey()).serialize();
+    assert!(Transaction::deserialize(&bytes[..40]).is_err());
+    let mut one_signature = bytes.clone();
+    one_signature[0] = 1;
+    assert!(Transaction::deserialize(&one_signature).is_err());
+  }
+}
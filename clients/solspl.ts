@@ -113,7 +113,7 @@ async function cancelTradeSolSpl(
     peerUser,
     peerUserTokenATokenAccount,
   } = await setup();
-  const orderId = Math.floor(Math.random() * 10000);
+  const orderId = await tradeInstance.nextOrderId(tradeCreator.publicKey);
   const tradeValue = 0.01 * anchor.web3.LAMPORTS_PER_SOL;
   const receivevalue = 10;
   const signature = await createTradeSolSpl(
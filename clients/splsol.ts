@@ -108,7 +108,7 @@ async function cancelTradeSplSol(
     peerUser,
     peerUserTokenATokenAccount,
   } = await setup();
-  const orderId = Math.floor(Math.random() * 10000);
+  const orderId = await tradeInstance.nextOrderId(tradeCreator.publicKey);
   const tradeValue = 10;
   const receivevalue = 0.1 * anchor.web3.LAMPORTS_PER_SOL;
   const signature = await createTradeSplSol(
@@ -109,7 +109,7 @@ async function cancelTradeSplSpl(
     peerUserTokenATokenAccount,
     peerUserTokenBTokenAccount,
   } = await setup();
-  const orderId = Math.floor(Math.random() * 10000);
+  const orderId = await tradeInstance.nextOrderId(tradeCreator.publicKey);
   const tradeValue = 10;
   const receivevalue = 1;
 
@@ -3,6 +3,13 @@ use anchor_spl::token::{Mint, TokenAccount};
 
 pub const VAULT_PDA_SEED: &[u8] = b"vault";
 pub const STATE_PDA_SEED: &[u8] = b"state";
+pub const INBOX_PDA_SEED: &[u8] = b"inbox";
+pub const MARKET_PDA_SEED: &[u8] = b"market";
+pub const USER_PDA_SEED: &[u8] = b"user";
+pub const BID_PDA_SEED: &[u8] = b"bid";
+pub const BID_VAULT_PDA_SEED: &[u8] = b"bid_vault";
+pub const OFFER_PDA_SEED: &[u8] = b"offer";
+pub const OFFER_VAULT_PDA_SEED: &[u8] = b"offer_vault";
 
 pub type TokenAccountType<'info> = std::result::Result<Account<'info, TokenAccount>, Error>;
 pub type MintAddressType<'info> = std::result::Result<Account<'info, Mint>, Error>;
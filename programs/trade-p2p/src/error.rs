@@ -24,4 +24,84 @@ pub enum EscrowError {
   ZeroValue,
   #[msg("instruction data missing params")]
   MissingParams,
+  #[msg("Partner is not in the allowlist set in create trade")]
+  PartnerNotAllowed,
+  #[msg("Claim code signer is missing or does not match the order")]
+  InvalidClaimCode,
+  #[msg("Partner inbox account is missing or does not belong to the specify partner")]
+  InvalidInbox,
+  #[msg("Market page account is missing or does not match the order")]
+  InvalidMarket,
+  #[msg("Market page is full, list the order on another page")]
+  MarketFull,
+  #[msg("Order ids of the creator's user account are used up")]
+  InvalidOrderId,
+  #[msg("Orders cost more than the budget given")]
+  BudgetExceeded,
+  #[msg("Average price of the orders is above the price cap")]
+  PriceCapExceeded,
+  #[msg("No order could be filled")]
+  NothingFilled,
+  #[msg("Each order must give at least what the other one asks for")]
+  OrdersDoNotCross,
+  #[msg("Invalid auction terms")]
+  InvalidAuction,
+  #[msg("Auction orders are only filled by settle_auction")]
+  AuctionOrder,
+  #[msg("Order is not this kind of auction")]
+  NotAnAuction,
+  #[msg("Auction has ended")]
+  AuctionEnded,
+  #[msg("Auction has not ended yet")]
+  AuctionNotEnded,
+  #[msg("Bid is below the reserve price or the best bid plus the minimum increment")]
+  BidTooLow,
+  #[msg("Auction already has bids")]
+  AuctionHasBids,
+  #[msg("Value and salt do not match the bid commitment")]
+  InvalidCommitment,
+  #[msg("Sealed bids are only revealed between the end of commits and the end of reveals")]
+  OutsideRevealWindow,
+  #[msg("Bid was already revealed")]
+  BidAlreadyRevealed,
+  #[msg("Deposit does not cover the bid")]
+  DepositTooLow,
+  #[msg("Offer must buy what the order sells")]
+  InvalidOffer,
+  #[msg("Invalid request for quote terms")]
+  InvalidRfq,
+  #[msg("Request-for-quote orders are only filled by one of their quotes")]
+  RfqOrder,
+  #[msg("Quotes are no longer taken")]
+  QuotingClosed,
+  #[msg("Quotes are still being taken")]
+  QuotingOpen,
+  #[msg("Quote mint is not approved by the order")]
+  MintNotApproved,
+  #[msg("The best quote is settled by settle_rfq and cannot be withdrawn")]
+  BestQuoteLocked,
+  #[msg("Invalid oracle peg terms")]
+  InvalidOraclePeg,
+  #[msg("Invalid oracle price account")]
+  InvalidOracle,
+  #[msg("Oracle price is stale or not trading")]
+  StaleOracle,
+  #[msg("Oracle price confidence interval is too wide")]
+  OracleConfidence,
+  #[msg("Oracle-pegged orders are only filled by exchange")]
+  OraclePegOrder,
+  #[msg("Order price deviates from the reference price beyond the guard")]
+  PriceDeviation,
+  #[msg("Invalid trigger terms")]
+  InvalidTrigger,
+  #[msg("Conditional order has not been triggered")]
+  NotTriggered,
+  #[msg("Oracle price has not reached the trigger price")]
+  TriggerNotReached,
+  #[msg("Invalid payment options")]
+  InvalidPaymentOptions,
+  #[msg("Order price is above the partner's maximum receive value")]
+  ReceiveValueExceeded,
+  #[msg("Deal account already has the current layout")]
+  AlreadyMigrated,
 }
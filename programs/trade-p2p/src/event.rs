@@ -0,0 +1,178 @@
+use anchor_lang::prelude::*;
+
+use crate::state::TriggerDirection;
+
+// emitted once the creator funds the vault and the order is ready for exchange
+#[event]
+pub struct TradeCreated {
+  pub escrow_state: Pubkey,
+  pub creator: Pubkey,
+  pub order_id: u64,
+  pub trade_type: u8,
+  pub creator_send_token_mint: Option<Pubkey>,
+  pub creator_receive_token_mint: Option<Pubkey>,
+  pub trade_value: u64,
+  pub receive_value: u64,
+  pub specify_partner: Option<Pubkey>,
+  pub fee_account: Pubkey,
+  pub timestamp: u64,
+}
+
+// emitted when a partner takes the order
+#[event]
+pub struct TradeExchanged {
+  pub escrow_state: Pubkey,
+  pub creator: Pubkey,
+  pub order_id: u64,
+  pub partner: Pubkey,
+  pub trade_value: u64,
+  pub receive_value: u64,
+}
+
+// emitted when the creator cancels and the vault is refunded
+#[event]
+pub struct TradeCancelled {
+  pub escrow_state: Pubkey,
+  pub creator: Pubkey,
+  pub order_id: u64,
+  pub refund_value: u64,
+}
+
+// emitted when the specify partner declines the order and the vault is refunded
+#[event]
+pub struct TradeDeclined {
+  pub escrow_state: Pubkey,
+  pub creator: Pubkey,
+  pub order_id: u64,
+  pub partner: Pubkey,
+  pub refund_value: u64,
+}
+
+// emitted by `replace_trade` after the `TradeCancelled` of the old order and the
+// `TradeCreated` of the new one, `moved_value` went from the old vault to the new
+#[event]
+pub struct TradeReplaced {
+  pub escrow_state: Pubkey,
+  pub creator: Pubkey,
+  pub order_id: u64,
+  pub new_escrow_state: Pubkey,
+  pub new_order_id: u64,
+  pub moved_value: u64,
+}
+
+// emitted by `match_orders` after the `TradeExchanged` of both orders. The fees
+// are the price improvement kept by the matcher, in the asset each order sent
+#[event]
+pub struct OrdersMatched {
+  pub escrow_state_a: Pubkey,
+  pub escrow_state_b: Pubkey,
+  pub matcher: Pubkey,
+  pub fee_a: u64,
+  pub fee_b: u64,
+}
+
+// emitted by `place_bid`, `outbid` being the previous best bid it refunded
+#[event]
+pub struct BidPlaced {
+  pub escrow_state: Pubkey,
+  pub bid: Pubkey,
+  pub bidder: Pubkey,
+  pub bid_index: u32,
+  pub value: u64,
+  pub outbid: Option<Pubkey>,
+}
+
+// emitted by `commit_bid`, the bid itself stays sealed until `BidRevealed`
+#[event]
+pub struct BidCommitted {
+  pub escrow_state: Pubkey,
+  pub bid: Pubkey,
+  pub bidder: Pubkey,
+  pub bid_index: u32,
+  pub deposit: u64,
+}
+
+// emitted by `reveal_bid`. A bid that does not become the best one is refunded,
+// `outbid` being the previous best bid refunded when it does
+#[event]
+pub struct BidRevealed {
+  pub escrow_state: Pubkey,
+  pub bid: Pubkey,
+  pub bidder: Pubkey,
+  pub value: u64,
+  pub best: bool,
+  pub outbid: Option<Pubkey>,
+}
+
+// emitted by `reclaim_bid` for a sealed bid never revealed, its deposit going to
+// the creator when `forfeited`, back to the bidder otherwise
+#[event]
+pub struct BidReclaimed {
+  pub escrow_state: Pubkey,
+  pub bid: Pubkey,
+  pub bidder: Pubkey,
+  pub deposit: u64,
+  pub forfeited: bool,
+}
+
+// emitted by `make_offer`, `mint` being `None` for SOL
+#[event]
+pub struct OfferMade {
+  pub escrow_state: Pubkey,
+  pub offer: Pubkey,
+  pub partner: Pubkey,
+  pub mint: Option<Pubkey>,
+  pub value: u64,
+}
+
+// emitted by `accept_offer` and `settle_rfq` in place of `TradeExchanged`: the order's vault went
+// to the partner and the offer to the creator
+#[event]
+pub struct OfferAccepted {
+  pub escrow_state: Pubkey,
+  pub offer: Pubkey,
+  pub creator: Pubkey,
+  pub order_id: u64,
+  pub partner: Pubkey,
+  pub trade_value: u64,
+  pub mint: Option<Pubkey>,
+  pub value: u64,
+}
+
+// emitted by `withdraw_offer`, the offer is refunded to its partner
+#[event]
+pub struct OfferWithdrawn {
+  pub escrow_state: Pubkey,
+  pub offer: Pubkey,
+  pub partner: Pubkey,
+  pub value: u64,
+}
+
+// emitted by `create_trade` and `replace_trade` after `TradeCreated` for a
+// conditional order, which waits for its trigger before it can be exchanged
+#[event]
+pub struct TriggerArmed {
+  pub escrow_state: Pubkey,
+  pub oracle: Pubkey,
+  pub trigger_price: i64,
+  pub direction: TriggerDirection,
+}
+
+// emitted by `exchange` before `TradeExchanged` when the partner paid in one of
+// the order's payment options, `mint` being `None` for SOL
+#[event]
+pub struct PaymentOptionUsed {
+  pub escrow_state: Pubkey,
+  pub mint: Option<Pubkey>,
+  pub receive_value: u64,
+}
+
+// emitted by `trigger`, the conditional order can now be exchanged
+#[event]
+pub struct OrderTriggered {
+  pub escrow_state: Pubkey,
+  pub creator: Pubkey,
+  pub order_id: u64,
+  pub price: i64,
+  pub publish_time: i64,
+}
@@ -0,0 +1,60 @@
+use crate::constant::INBOX_PDA_SEED;
+use crate::error::EscrowError;
+use crate::state::PartnerInbox;
+use crate::utils::create_program_account;
+
+use anchor_lang::prelude::*;
+
+fn check_inbox_address(inbox: &AccountInfo, partner: &Pubkey) -> Result<u8> {
+  let (address, bump) = Pubkey::find_program_address(&[INBOX_PDA_SEED, partner.as_ref()], &crate::ID);
+  require_keys_eq!(inbox.key(), address, EscrowError::InvalidInbox);
+  Ok(bump)
+}
+
+// add `order` to the inbox of `partner`, creating the inbox paid by `payer` on first use.
+// A full inbox leaves the order unlisted, so cheap orders addressed to a partner
+// cannot block everyone else from dealing with them
+pub fn add_to_inbox<'info>(
+  inbox: &AccountInfo<'info>,
+  partner: &Pubkey,
+  order: Pubkey,
+  payer: &AccountInfo<'info>,
+  system_program: &AccountInfo<'info>,
+  rent: &Rent,
+) -> Result<()> {
+  let bump = check_inbox_address(inbox, partner)?;
+  if inbox.data_is_empty() {
+    let bump_bytes = [bump];
+    let seeds = &[&[INBOX_PDA_SEED, partner.as_ref(), &bump_bytes][..]];
+    create_program_account(payer, inbox, PartnerInbox::LEN, seeds, system_program, rent)?;
+    let new_inbox = PartnerInbox {
+      partner: *partner,
+      bump,
+      orders: Vec::new(),
+    };
+    let mut data = inbox.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    new_inbox.try_serialize(&mut writer)?;
+  }
+  let mut partner_inbox: Account<PartnerInbox> = Account::try_from(inbox)?;
+  if partner_inbox.orders.len() >= PartnerInbox::MAX_ORDERS {
+    msg!("inbox of {} is full, order {} is not listed", partner, order);
+    return Ok(());
+  }
+  partner_inbox.orders.push(order);
+  partner_inbox.exit(&crate::ID)?;
+  Ok(())
+}
+
+// drop `order` from the inbox of `partner`. Orders left out of a full inbox
+// have no entry, and migrated first-release orders possibly no inbox at all.
+pub fn remove_from_inbox(inbox: &AccountInfo, partner: &Pubkey, order: &Pubkey) -> Result<()> {
+  check_inbox_address(inbox, partner)?;
+  if inbox.data_is_empty() || *inbox.owner != crate::ID {
+    return Ok(());
+  }
+  let mut partner_inbox: Account<PartnerInbox> = Account::try_from(inbox)?;
+  partner_inbox.orders.retain(|entry| entry != order);
+  partner_inbox.exit(&crate::ID)?;
+  Ok(())
+}
@@ -1,8 +1,14 @@
+#![allow(clippy::result_large_err)]
+
 #[macro_use]
 pub mod seed;
 
 pub mod constant;
 pub mod error;
+pub mod event;
+pub mod inbox;
+pub mod market;
+pub mod oracle;
 pub mod processor;
 pub mod state;
 pub mod utils;
@@ -24,13 +30,114 @@ pub mod trade_p2p {
         Ok(())
     }
 
+    pub fn create_many<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateMany<'info>>,
+        params: CreateManyParams,
+    ) -> Result<()> {
+        handler_create_many(ctx, params)?;
+        Ok(())
+    }
+
+    pub fn replace_trade<'info>(
+        ctx: Context<'_, '_, '_, 'info, Replace<'info>>,
+        _old_order_id: u64,
+        params: CreateParams,
+    ) -> Result<()> {
+        handler_replace_trade(ctx, params)?;
+        Ok(())
+    }
+
     pub fn exchange(
         ctx: Context<Exchange>,
         _order_id: u64,
         _state_bump: u8,
         _vault_bump: u8,
+        proof: Vec<[u8; 32]>,
+        max_receive_value: Option<u64>,
+    ) -> Result<()> {
+        handler_exchange(ctx, proof, max_receive_value)?;
+        Ok(())
+    }
+
+    pub fn exchange_many<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExchangeMany<'info>>,
+        budget: u64,
+        max_price: Option<PriceCap>,
+        atomic: bool,
     ) -> Result<()> {
-        handler_exchange(ctx)?;
+        handler_exchange_many(ctx, budget, max_price, atomic)?;
+        Ok(())
+    }
+
+    pub fn match_orders(ctx: Context<MatchOrders>) -> Result<()> {
+        handler_match_orders(ctx)?;
+        Ok(())
+    }
+
+    pub fn place_bid<'info>(
+        ctx: Context<'_, '_, '_, 'info, PlaceBid<'info>>,
+        value: u64,
+        vault_bump: u8,
+    ) -> Result<()> {
+        handler_place_bid(ctx, value, vault_bump)?;
+        Ok(())
+    }
+
+    pub fn commit_bid<'info>(
+        ctx: Context<'_, '_, '_, 'info, PlaceBid<'info>>,
+        deposit: u64,
+        vault_bump: u8,
+        commitment: [u8; 32],
+    ) -> Result<()> {
+        handler_commit_bid(ctx, deposit, vault_bump, commitment)?;
+        Ok(())
+    }
+
+    pub fn reveal_bid<'info>(
+        ctx: Context<'_, '_, '_, 'info, RevealBid<'info>>,
+        value: u64,
+        salt: [u8; 32],
+    ) -> Result<()> {
+        handler_reveal_bid(ctx, value, salt)?;
+        Ok(())
+    }
+
+    pub fn reclaim_bid(ctx: Context<ReclaimBid>) -> Result<()> {
+        handler_reclaim_bid(ctx)?;
+        Ok(())
+    }
+
+    pub fn settle_auction<'info>(ctx: Context<'_, '_, '_, 'info, SettleAuction<'info>>) -> Result<()> {
+        handler_settle_auction(ctx)?;
+        Ok(())
+    }
+
+    pub fn make_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, MakeOffer<'info>>,
+        value: u64,
+        vault_bump: u8,
+    ) -> Result<()> {
+        handler_make_offer(ctx, value, vault_bump)?;
+        Ok(())
+    }
+
+    pub fn accept_offer<'info>(ctx: Context<'_, '_, '_, 'info, AcceptOffer<'info>>) -> Result<()> {
+        handler_accept_offer(ctx)?;
+        Ok(())
+    }
+
+    pub fn withdraw_offer(ctx: Context<WithdrawOffer>) -> Result<()> {
+        handler_withdraw_offer(ctx)?;
+        Ok(())
+    }
+
+    pub fn settle_rfq<'info>(ctx: Context<'_, '_, '_, 'info, SettleRfq<'info>>) -> Result<()> {
+        handler_settle_rfq(ctx)?;
+        Ok(())
+    }
+
+    pub fn trigger(ctx: Context<Trigger>) -> Result<()> {
+        handler_trigger(ctx)?;
         Ok(())
     }
     //
@@ -43,4 +150,27 @@ pub mod trade_p2p {
         handler_cancel(ctx)?;
         Ok(())
     }
+
+    pub fn cancel_many<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelMany<'info>>,
+        atomic: bool,
+    ) -> Result<()> {
+        handler_cancel_many(ctx, atomic)?;
+        Ok(())
+    }
+
+    pub fn decline(
+        ctx: Context<Decline>,
+        _order_id: u64,
+        _state_bump: u8,
+        _vault_bump: u8,
+    ) -> Result<()> {
+        handler_decline(ctx)?;
+        Ok(())
+    }
+
+    pub fn migrate_escrow(ctx: Context<MigrateEscrow>) -> Result<()> {
+        handler_migrate_escrow(ctx)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,81 @@
+use crate::constant::MARKET_PDA_SEED;
+use crate::error::EscrowError;
+use crate::state::{EscrowAccount, MarketEntry, MarketPage};
+use crate::utils::create_program_account;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::spl_token::native_mint;
+
+// key of one side of a pair, `None` stands for SOL
+pub fn market_mint(mint: Option<Pubkey>) -> Pubkey {
+  mint.unwrap_or(native_mint::ID)
+}
+
+// list an order on `page` of the market of its pair, creating the page paid by `payer` on first use
+pub fn add_to_market<'info>(
+  market: &AccountInfo<'info>,
+  escrow_state: &EscrowAccount,
+  order: Pubkey,
+  page: u16,
+  payer: &AccountInfo<'info>,
+  system_program: &AccountInfo<'info>,
+  rent: &Rent,
+) -> Result<()> {
+  let send_mint = market_mint(escrow_state.creator_send_token_mint);
+  let receive_mint = market_mint(escrow_state.creator_receive_token_mint);
+  let page_bytes = page.to_le_bytes();
+  let (address, bump) = Pubkey::find_program_address(
+    &[MARKET_PDA_SEED, send_mint.as_ref(), receive_mint.as_ref(), page_bytes.as_ref()],
+    &crate::ID,
+  );
+  require_keys_eq!(market.key(), address, EscrowError::InvalidMarket);
+  if market.data_is_empty() {
+    let bump_bytes = [bump];
+    let seeds = &[&[
+      MARKET_PDA_SEED,
+      send_mint.as_ref(),
+      receive_mint.as_ref(),
+      page_bytes.as_ref(),
+      &bump_bytes,
+    ][..]];
+    create_program_account(payer, market, MarketPage::LEN, seeds, system_program, rent)?;
+    let new_page = MarketPage {
+      send_mint,
+      receive_mint,
+      page,
+      bump,
+      orders: Vec::new(),
+    };
+    let mut data = market.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    new_page.try_serialize(&mut writer)?;
+  }
+  let mut market_page: Account<MarketPage> = Account::try_from(market)?;
+  require!(
+    market_page.orders.len() < MarketPage::MAX_ORDERS,
+    EscrowError::MarketFull
+  );
+  market_page.orders.push(MarketEntry {
+    order,
+    trade_value: escrow_state.trade_value,
+    receive_value: escrow_state.receive_value,
+  });
+  market_page.exit(&crate::ID)?;
+  Ok(())
+}
+
+// drop an order from the market page it was listed on. The page is only ever
+// written by this program, so its content identifies it without re-deriving the address.
+pub fn remove_from_market(market: &AccountInfo, escrow_state: &EscrowAccount, order: &Pubkey) -> Result<()> {
+  let mut market_page: Account<MarketPage> =
+    Account::try_from(market).map_err(|_| EscrowError::InvalidMarket)?;
+  require!(
+    Some(market_page.page) == escrow_state.market_page
+      && market_page.send_mint == market_mint(escrow_state.creator_send_token_mint)
+      && market_page.receive_mint == market_mint(escrow_state.creator_receive_token_mint),
+    EscrowError::InvalidMarket
+  );
+  market_page.orders.retain(|entry| entry.order != *order);
+  market_page.exit(&crate::ID)?;
+  Ok(())
+}
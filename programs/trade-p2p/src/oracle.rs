@@ -0,0 +1,177 @@
+use crate::error::EscrowError;
+
+use anchor_lang::prelude::*;
+
+// Pyth oracle program, the only owner of the price accounts read by `load_price`
+pub mod pyth {
+  #[cfg(not(feature = "devnet"))]
+  anchor_lang::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+  #[cfg(feature = "devnet")]
+  anchor_lang::declare_id!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
+}
+
+// Pyth v2 price account layout, the fields read by `parse_price`
+pub const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+pub const PYTH_VERSION: u32 = 2;
+pub const PYTH_PRICE_ACCOUNT: u32 = 3;
+// aggregate price status, only a trading price is used
+pub const PYTH_STATUS_TRADING: u32 = 1;
+
+const MAGIC_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 4;
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const EXPONENT_OFFSET: usize = 20;
+const TIMESTAMP_OFFSET: usize = 96;
+const PRICE_OFFSET: usize = 208;
+const CONFIDENCE_OFFSET: usize = 216;
+const STATUS_OFFSET: usize = 224;
+pub const PYTH_PRICE_ACCOUNT_LEN: usize = 240;
+
+// aggregate price of a feed: `price` and its confidence interval `confidence`
+// are scaled by 10^`exponent`, published at unix time `publish_time`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OraclePrice {
+  pub price: i64,
+  pub confidence: u64,
+  pub exponent: i32,
+  pub publish_time: i64,
+}
+
+impl OraclePrice {
+  // refuse a price older than `max_age` seconds at unix time `now`
+  pub fn check_age(&self, max_age: u32, now: i64) -> Result<()> {
+    require_gte!(
+      self.publish_time.saturating_add(max_age as i64),
+      now,
+      EscrowError::StaleOracle
+    );
+    Ok(())
+  }
+
+  // `amount` send tokens in receive tokens at this price times `bps` / 10_000,
+  // scaled by 10^exponent from the feed and 10^`decimal_shift` from the mints
+  // (receive decimals minus send decimals). Rounded up, `u64::MAX` when too large
+  pub fn value_of(&self, amount: u64, decimal_shift: i8, bps: u16) -> u64 {
+    let exponent = self.exponent + decimal_shift as i32;
+    (amount as u128)
+      .checked_mul(self.price as u128)
+      .and_then(|value| value.checked_mul(bps as u128))
+      .and_then(|value| match exponent >= 0 {
+        true => 10u128.checked_pow(exponent as u32).and_then(|scale| value.checked_mul(scale)),
+        false => Some(match 10u128.checked_pow(exponent.unsigned_abs()) {
+          Some(scale) => value / scale + (value % scale > 0) as u128,
+          None => (value > 0) as u128,
+        }),
+      })
+      .map(|value| value / 10_000 + (value % 10_000 > 0) as u128)
+      .map_or(u64::MAX, |value| u64::try_from(value).unwrap_or(u64::MAX))
+  }
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> [u8; N] {
+  let mut bytes = [0u8; N];
+  bytes.copy_from_slice(&data[offset..offset + N]);
+  bytes
+}
+
+// the aggregate price of the price account `oracle`, refused unless the Pyth
+// program owns it, since anyone can write an account in its layout
+pub fn load_price(oracle: &AccountInfo) -> Result<OraclePrice> {
+  require_keys_eq!(*oracle.owner, pyth::ID, EscrowError::InvalidOracle);
+  parse_price(&oracle.try_borrow_data()?)
+}
+
+// the aggregate price of a Pyth-format price account, refused unless it is trading
+pub fn parse_price(data: &[u8]) -> Result<OraclePrice> {
+  require_gte!(data.len(), PYTH_PRICE_ACCOUNT_LEN, EscrowError::InvalidOracle);
+  require!(
+    u32::from_le_bytes(read_bytes(data, MAGIC_OFFSET)) == PYTH_MAGIC
+      && u32::from_le_bytes(read_bytes(data, VERSION_OFFSET)) == PYTH_VERSION
+      && u32::from_le_bytes(read_bytes(data, ACCOUNT_TYPE_OFFSET)) == PYTH_PRICE_ACCOUNT,
+    EscrowError::InvalidOracle
+  );
+  require!(
+    u32::from_le_bytes(read_bytes(data, STATUS_OFFSET)) == PYTH_STATUS_TRADING,
+    EscrowError::StaleOracle
+  );
+  let price = OraclePrice {
+    price: i64::from_le_bytes(read_bytes(data, PRICE_OFFSET)),
+    confidence: u64::from_le_bytes(read_bytes(data, CONFIDENCE_OFFSET)),
+    exponent: i32::from_le_bytes(read_bytes(data, EXPONENT_OFFSET)),
+    publish_time: i64::from_le_bytes(read_bytes(data, TIMESTAMP_OFFSET)),
+  };
+  require_gt!(price.price, 0, EscrowError::InvalidOracle);
+  Ok(price)
+}
+
+// a Pyth-format price account holding `price`, for a local mock of a feed
+pub fn encode_price(price: &OraclePrice) -> Vec<u8> {
+  let mut data = vec![0u8; PYTH_PRICE_ACCOUNT_LEN];
+  data[MAGIC_OFFSET..MAGIC_OFFSET + 4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+  data[VERSION_OFFSET..VERSION_OFFSET + 4].copy_from_slice(&PYTH_VERSION.to_le_bytes());
+  data[ACCOUNT_TYPE_OFFSET..ACCOUNT_TYPE_OFFSET + 4].copy_from_slice(&PYTH_PRICE_ACCOUNT.to_le_bytes());
+  data[EXPONENT_OFFSET..EXPONENT_OFFSET + 4].copy_from_slice(&price.exponent.to_le_bytes());
+  data[TIMESTAMP_OFFSET..TIMESTAMP_OFFSET + 8].copy_from_slice(&price.publish_time.to_le_bytes());
+  data[PRICE_OFFSET..PRICE_OFFSET + 8].copy_from_slice(&price.price.to_le_bytes());
+  data[CONFIDENCE_OFFSET..CONFIDENCE_OFFSET + 8].copy_from_slice(&price.confidence.to_le_bytes());
+  data[STATUS_OFFSET..STATUS_OFFSET + 4].copy_from_slice(&PYTH_STATUS_TRADING.to_le_bytes());
+  data
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn price() -> OraclePrice {
+    OraclePrice {
+      price: 2_345_000,
+      confidence: 1_000,
+      exponent: -6,
+      publish_time: 1_700_000_000,
+    }
+  }
+
+  #[test]
+  fn encoded_price_parses_back() {
+    assert_eq!(parse_price(&encode_price(&price())).unwrap(), price());
+  }
+
+  #[test]
+  fn parse_price_refuses_other_accounts() {
+    let data = encode_price(&price());
+    assert!(parse_price(&data[..PYTH_PRICE_ACCOUNT_LEN - 1]).is_err());
+    let mut wrong_magic = data.clone();
+    wrong_magic[MAGIC_OFFSET] ^= 1;
+    assert_eq!(parse_price(&wrong_magic), Err(EscrowError::InvalidOracle.into()));
+    let mut halted = data.clone();
+    halted[STATUS_OFFSET..STATUS_OFFSET + 4].copy_from_slice(&0u32.to_le_bytes());
+    assert_eq!(parse_price(&halted), Err(EscrowError::StaleOracle.into()));
+    let negative = encode_price(&OraclePrice { price: -1, ..price() });
+    assert_eq!(parse_price(&negative), Err(EscrowError::InvalidOracle.into()));
+  }
+
+  #[test]
+  fn check_age_allows_max_age_seconds() {
+    let price = price();
+    assert!(price.check_age(30, price.publish_time + 30).is_ok());
+    assert_eq!(
+      price.check_age(30, price.publish_time + 31),
+      Err(EscrowError::StaleOracle.into())
+    );
+  }
+
+  #[test]
+  fn value_of_scales_and_rounds_up() {
+    let price = price();
+    // 1_000 at 2.345 is 2_345
+    assert_eq!(price.value_of(1_000, 0, 10_000), 2_345);
+    assert_eq!(price.value_of(1, 0, 10_000), 3);
+    assert_eq!(price.value_of(1_000, 3, 10_000), 2_345_000);
+    assert_eq!(price.value_of(1_000, -3, 10_000), 3);
+    assert_eq!(price.value_of(1_000, 0, 9_000), 2_111);
+    assert_eq!(price.value_of(0, 0, 10_000), 0);
+    assert_eq!(price.value_of(u64::MAX, 0, 10_000), u64::MAX);
+    assert_eq!(price.value_of(1, 60, 10_000), u64::MAX);
+    assert_eq!(price.value_of(1, -60, 10_000), 1);
+  }
+}
@@ -0,0 +1,145 @@
+use crate::error::EscrowError;
+use crate::event::OfferAccepted;
+use crate::processor::cancel::{close_vault, remove_listings, withdraw_from_vault};
+use crate::processor::make_offer::release_offer;
+use crate::state::{EscrowAccount, OfferAccount, Stage};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct AcceptOffer<'info> {
+  #[account(
+    mut,
+    has_one = creator @ EscrowError::InvalidOwner,
+    has_one = escrow_vault @ EscrowError::InvalidAccount,
+    constraint = escrow_state.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage,
+    constraint = !escrow_state.is_auction() @ EscrowError::AuctionOrder
+  )]
+  pub escrow_state: Account<'info, EscrowAccount>,
+  /// CHECK: checked by `has_one`, goes to the partner
+  #[account(mut)]
+  pub escrow_vault: AccountInfo<'info>,
+  #[account(mut)]
+  pub creator: Signer<'info>,
+  /// CHECK: checked against `offer_payout`, receives the offer
+  #[account(mut)]
+  pub creator_offer_account: AccountInfo<'info>,
+  #[account(
+    mut,
+    close = partner,
+    has_one = escrow_state @ EscrowError::InvalidAccount,
+    has_one = offer_vault @ EscrowError::InvalidAccount,
+    has_one = partner @ EscrowError::InvalidOwner,
+    has_one = partner_receive_account @ EscrowError::InvalidAccount
+  )]
+  pub offer: Account<'info, OfferAccount>,
+  /// CHECK: checked by `has_one`, goes to the creator
+  #[account(mut)]
+  pub offer_vault: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, receives the offer and vault rent
+  #[account(mut)]
+  pub partner: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, receives the order's vault
+  #[account(mut)]
+  pub partner_receive_account: AccountInfo<'info>,
+  pub system_program: Program<'info, System>,
+  pub token_program: Program<'info, Token>,
+}
+
+// the creator swaps the order with one of its offers: the vault goes to the
+// partner, the offer to the creator, and the order is exchanged. A request for
+// quote is settled once its deadline has passed, with any of its quotes or, for
+// a best-price one, its best quote.
+// remaining_accounts: inbox of the specify partner, then market page, when there are ones
+pub fn handler_accept_offer<'info>(ctx: Context<'_, '_, '_, 'info, AcceptOffer<'info>>) -> Result<()> {
+  if let Some(rfq) = &ctx.accounts.escrow_state.rfq {
+    require_gte!(Clock::get()?.unix_timestamp, rfq.deadline, EscrowError::QuotingOpen);
+    if rfq.best_price {
+      require!(
+        ctx.accounts.escrow_state.best_quote == Some(ctx.accounts.offer.key()),
+        EscrowError::InvalidOffer
+      );
+    }
+  }
+  let settlement = OfferSettlement {
+    escrow_vault: &ctx.accounts.escrow_vault,
+    creator: &ctx.accounts.creator.to_account_info(),
+    creator_offer_account: &ctx.accounts.creator_offer_account,
+    offer: &ctx.accounts.offer,
+    offer_vault: &ctx.accounts.offer_vault,
+    partner: &ctx.accounts.partner,
+    partner_receive_account: &ctx.accounts.partner_receive_account,
+    system_program: &ctx.accounts.system_program.to_account_info(),
+    token_program: &ctx.accounts.token_program.to_account_info(),
+  };
+  settle_offer(&mut ctx.accounts.escrow_state, &settlement, ctx.remaining_accounts)
+}
+
+// accounts swapping an order with one of its offers, shared by `accept_offer` and `settle_rfq`
+pub struct OfferSettlement<'a, 'info> {
+  pub escrow_vault: &'a AccountInfo<'info>,
+  pub creator: &'a AccountInfo<'info>,
+  pub creator_offer_account: &'a AccountInfo<'info>,
+  pub offer: &'a Account<'info, OfferAccount>,
+  pub offer_vault: &'a AccountInfo<'info>,
+  pub partner: &'a AccountInfo<'info>,
+  pub partner_receive_account: &'a AccountInfo<'info>,
+  pub system_program: &'a AccountInfo<'info>,
+  pub token_program: &'a AccountInfo<'info>,
+}
+
+// vault to the partner, offer to the creator's `offer_payout`, then drop the
+// order from its listings, taken from `listing_accounts`, and mark it exchanged
+pub fn settle_offer<'info>(
+  escrow_state: &mut Account<'info, EscrowAccount>,
+  settlement: &OfferSettlement<'_, 'info>,
+  listing_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+  require_keys_eq!(
+    settlement.creator_offer_account.key(),
+    escrow_state.offer_payout(settlement.offer.offer_mint),
+    EscrowError::InvalidAccount
+  );
+  let escrow_state_key = escrow_state.key();
+  remove_listings(escrow_state, &escrow_state_key, &mut listing_accounts.iter())?;
+  withdraw_from_vault(
+    escrow_state,
+    settlement.escrow_vault,
+    settlement.partner_receive_account,
+    settlement.creator,
+    escrow_state.trade_value,
+    settlement.system_program,
+    settlement.token_program,
+  )?;
+  close_vault(
+    escrow_state,
+    settlement.escrow_vault,
+    settlement.creator,
+    settlement.system_program,
+    settlement.token_program,
+  )?;
+  let offer = settlement.offer;
+  release_offer(
+    offer,
+    settlement.offer_vault,
+    settlement.creator_offer_account,
+    settlement.partner,
+    settlement.system_program,
+    settlement.token_program,
+  )?;
+
+  escrow_state.specify_partner = Some(offer.partner);
+  escrow_state.stage = Stage::Exchanged.to_code();
+  emit!(OfferAccepted {
+    escrow_state: escrow_state_key,
+    offer: offer.key(),
+    creator: escrow_state.creator,
+    order_id: escrow_state.order_id,
+    partner: offer.partner,
+    trade_value: escrow_state.trade_value,
+    mint: offer.offer_mint,
+    value: offer.value,
+  });
+  Ok(())
+}
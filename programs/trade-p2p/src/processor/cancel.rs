@@ -1,5 +1,8 @@
 use crate::constant::{STATE_PDA_SEED, VAULT_PDA_SEED};
 use crate::error::EscrowError;
+use crate::event::TradeCancelled;
+use crate::inbox::remove_from_inbox;
+use crate::market::remove_from_market;
 
 use crate::state::{EscrowAccount, Stage, TradeType};
 use crate::utils::{
@@ -19,7 +22,8 @@ pub struct Cancel<'info> {
         has_one=creator_send_account @ EscrowError::InvalidOwner,
         seeds=[STATE_PDA_SEED, creator.key().as_ref(), order_id.to_le_bytes().as_ref()],
         bump = state_bump,
-        constraint = escrow_state.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage
+        constraint = escrow_state.is_open() @ EscrowError::InvalidStage,
+        constraint = escrow_state.bid_count == 0 @ EscrowError::AuctionHasBids
     )]
   pub escrow_state: Account<'info, EscrowAccount>,
   /// CHECK: TODO
@@ -39,123 +43,248 @@ pub struct Cancel<'info> {
 }
 
 pub fn handler_cancel(ctx: Context<Cancel>, ) -> Result<()> {
-  let trade_type = TradeType::from(ctx.accounts.escrow_state.trade_type);
-  let creator_send_account = ctx.accounts.escrow_state.creator_send_account;
+  refund_and_close_vault(
+    &ctx.accounts.escrow_state,
+    &ctx.accounts.escrow_vault,
+    &ctx.accounts.creator_send_account,
+    &ctx.accounts.creator,
+    ctx.accounts.escrow_state.trade_value,
+    &ctx.accounts.system_program,
+    &ctx.accounts.token_program,
+  )?;
+  ctx.accounts.escrow_state.stage = Stage::CancelTrade.to_code();
+
+  // remaining_accounts: inbox of the specify partner, then market page, when there are ones
+  remove_listings(
+    &ctx.accounts.escrow_state,
+    &ctx.accounts.escrow_state.key(),
+    &mut ctx.remaining_accounts.iter(),
+  )?;
+
+  emit!(TradeCancelled {
+    escrow_state: ctx.accounts.escrow_state.key(),
+    creator: ctx.accounts.creator.key(),
+    order_id: ctx.accounts.escrow_state.order_id,
+    refund_value: ctx.accounts.escrow_state.trade_value,
+  });
+  Ok(())
+}
+
+// refund `refund_value` from the vault of an order to `refund_account` (the
+// creator wallet for SOL) and close it, shared by `cancel`, `cancel_many`, `decline` and `replace_trade`
+pub fn refund_and_close_vault<'info>(
+  escrow_state: &Account<'info, EscrowAccount>,
+  escrow_vault: &AccountInfo<'info>,
+  refund_account: &AccountInfo<'info>,
+  creator: &AccountInfo<'info>,
+  refund_value: u64,
+  system_program: &AccountInfo<'info>,
+  token_program: &AccountInfo<'info>,
+) -> Result<()> {
+  let trade_type = TradeType::from(escrow_state.trade_type);
+  let creator_send_account = escrow_state.creator_send_account;
   match trade_type {
     Ok(TradeType::SolToken) => {
-      require_eq!(
-        creator_send_account,
-        ctx.accounts.creator.key(),
-        EscrowError::InvalidOwner
-      );
-      ctx.accounts.with_draw_native()?;
-      // close vault native account
-      ctx.accounts.close_vault_native()?;
+      require_eq!(creator_send_account, creator.key(), EscrowError::InvalidOwner);
+      withdraw_from_vault(
+        escrow_state,
+        escrow_vault,
+        creator,
+        creator,
+        refund_value,
+        system_program,
+        token_program,
+      )?;
     }
     //
     Ok(TradeType::TokenToken) | Ok(TradeType::TokenSol) => {
       // make sure account withdraw token to invalid with account of creator send token to vault
       require_eq!(
         creator_send_account,
-        ctx.accounts.creator_send_account.key(),
+        refund_account.key(),
         EscrowError::InvalidOwner
       );
-      // Transfer SPL from Vault to Creator
-      ctx.accounts.with_draw_token()?;
-      // Close SPL Vault
-      ctx.accounts.close_vault_token()?;
+      // Transfer SPL from Vault to Creator.
+      // Withdraw to exactly account transfer token escrow vault
+      // Why not `creator_receive_account` ?
+      // Because `creator_receive_account` && `creator_send_account` are different.
+      // We can send a token to exchange and receive another token
+      withdraw_from_vault(
+        escrow_state,
+        escrow_vault,
+        refund_account,
+        creator,
+        refund_value,
+        system_program,
+        token_program,
+      )?;
     }
     _ => return Err(EscrowError::InvalidTradeType.into()),
   }
-  ctx.accounts.escrow_state.stage = Stage::CancelTrade.to_code();
-  Ok(())
+  close_vault(escrow_state, escrow_vault, creator, system_program, token_program)
 }
 
-impl<'info> Cancel<'info> {
-  fn with_draw_native(&self) -> Result<()> {
-    // withdraw SOL escrow_vault -> creator
-    let amount = self.escrow_state.trade_value;
-    let creator_key = self.creator.key();
-    let order_id_bytes = self.escrow_state.order_id.to_le_bytes();
-    let vault_bump = self.escrow_state.vault_bump;
-    let vault_signers_seeds = &[&[
-      VAULT_PDA_SEED,
-      creator_key.as_ref(),
-      order_id_bytes.as_ref(),
-      bytemuck::bytes_of(&vault_bump),
-    ][..]];
-    transfer_native_to_account(
-      self.escrow_vault.to_account_info(),
-      self.creator.to_account_info(),
-      amount,
-      self.system_program.to_account_info(),
-      Some(vault_signers_seeds),
-    )?;
-    Ok(())
+// move `amount` out of the vault of an order, signed by the vault for SOL and
+// by the state for tokens
+pub fn withdraw_from_vault<'info>(
+  escrow_state: &Account<'info, EscrowAccount>,
+  escrow_vault: &AccountInfo<'info>,
+  destination: &AccountInfo<'info>,
+  creator: &AccountInfo<'info>,
+  amount: u64,
+  system_program: &AccountInfo<'info>,
+  token_program: &AccountInfo<'info>,
+) -> Result<()> {
+  match TradeType::from(escrow_state.trade_type) {
+    Ok(TradeType::SolToken) => {
+      with_draw_native(escrow_state, escrow_vault, destination, creator, amount, system_program)
+    }
+    Ok(TradeType::TokenToken) | Ok(TradeType::TokenSol) => {
+      with_draw_token(escrow_state, escrow_vault, destination, creator, amount, token_program)
+    }
+    _ => Err(EscrowError::InvalidTradeType.into()),
   }
+}
 
-  fn with_draw_token(&self) -> Result<()> {
-    let creator = self.creator.key();
-    let state_bump = self.escrow_state.state_bump;
-    let order_id = self.escrow_state.order_id.to_le_bytes();
-    let seeds = &[&[
-      STATE_PDA_SEED,
-      creator.as_ref(),
-      order_id.as_ref(),
-      bytemuck::bytes_of(&state_bump),
-    ][..]];
-    // transfer Token escrow_vault -> creator_send_account.
-    // Withdraw to exactly account transfer token escrow vault
-    // Why not `creator_receive_account` ?
-    // Because `creator_receive_account` && `creator_send_account` are different.
-    // We can send a token to exchange and receive another token
-    transfer_token_to_account(
-      self.escrow_vault.to_account_info(),
-      self.creator_send_account.to_account_info(),
-      self.escrow_state.to_account_info(),
-      self.escrow_state.trade_value,
-      self.token_program.to_account_info(),
-      Some(seeds),
-    )?;
-    Ok(())
+// close the emptied vault of an order, its rent goes back to the creator
+pub fn close_vault<'info>(
+  escrow_state: &Account<'info, EscrowAccount>,
+  escrow_vault: &AccountInfo<'info>,
+  creator: &AccountInfo<'info>,
+  system_program: &AccountInfo<'info>,
+  token_program: &AccountInfo<'info>,
+) -> Result<()> {
+  match TradeType::from(escrow_state.trade_type) {
+    // close vault native account
+    Ok(TradeType::SolToken) => close_vault_native(escrow_state, escrow_vault, creator, system_program),
+    // Close SPL Vault
+    Ok(TradeType::TokenToken) | Ok(TradeType::TokenSol) => {
+      close_vault_token(escrow_state, escrow_vault, creator, token_program)
+    }
+    _ => Err(EscrowError::InvalidTradeType.into()),
   }
+}
 
-  fn close_vault_token(&self) -> Result<()> {
-    let creator = self.creator.key();
-    let state_bump = self.escrow_state.state_bump;
-    let order_id = self.escrow_state.order_id.to_le_bytes();
-    let signers_seeds = &[&[
-      STATE_PDA_SEED,
-      creator.as_ref(),
-      order_id.as_ref(),
-      bytemuck::bytes_of(&state_bump),
-    ][..]];
-    close_token_account(
-      self.escrow_vault.to_account_info(),
-      self.creator.to_account_info(),
-      self.escrow_state.to_account_info(),
-      signers_seeds,
-      self.token_program.to_account_info(),
-    )?;
-    Ok(())
+// drop a closed order from the inbox and the market page it is listed on,
+// taken in that order from `accounts`
+pub fn remove_listings<'a, 'info: 'a>(
+  escrow_state: &EscrowAccount,
+  order: &Pubkey,
+  accounts: &mut impl Iterator<Item = &'a AccountInfo<'info>>,
+) -> Result<()> {
+  if let Some(specify_partner) = escrow_state.specify_partner {
+    let inbox = accounts.next().ok_or(EscrowError::InvalidInbox)?;
+    remove_from_inbox(inbox, &specify_partner, order)?;
   }
-
-  fn close_vault_native(&self) -> Result<()> {
-    let vault_bump = self.escrow_state.vault_bump;
-    let creator_pubkey = self.creator.key();
-    let order_id = self.escrow_state.order_id.to_le_bytes();
-    let vault_signers_seeds = &[&[
-      VAULT_PDA_SEED,
-      creator_pubkey.as_ref(),
-      order_id.as_ref(),
-      bytemuck::bytes_of(&vault_bump),
-    ][..]];
-    close_native_account(
-      self.escrow_vault.to_account_info(),
-      self.creator.to_account_info(),
-      vault_signers_seeds,
-      self.system_program.to_account_info(),
-    )?;
-    Ok(())
+  if escrow_state.market_page.is_some() {
+    let market = accounts.next().ok_or(EscrowError::InvalidMarket)?;
+    remove_from_market(market, escrow_state, order)?;
   }
+  Ok(())
+}
+
+fn with_draw_native<'info>(
+  escrow_state: &Account<'info, EscrowAccount>,
+  escrow_vault: &AccountInfo<'info>,
+  destination: &AccountInfo<'info>,
+  creator: &AccountInfo<'info>,
+  amount: u64,
+  system_program: &AccountInfo<'info>,
+) -> Result<()> {
+  // withdraw SOL escrow_vault -> destination
+  let creator_key = creator.key();
+  let order_id_bytes = escrow_state.order_id.to_le_bytes();
+  let vault_bump = escrow_state.vault_bump;
+  let vault_signers_seeds = &[&[
+    VAULT_PDA_SEED,
+    creator_key.as_ref(),
+    order_id_bytes.as_ref(),
+    bytemuck::bytes_of(&vault_bump),
+  ][..]];
+  transfer_native_to_account(
+    escrow_vault.to_account_info(),
+    destination.to_account_info(),
+    amount,
+    system_program.to_account_info(),
+    Some(vault_signers_seeds),
+  )?;
+  Ok(())
+}
+
+fn with_draw_token<'info>(
+  escrow_state: &Account<'info, EscrowAccount>,
+  escrow_vault: &AccountInfo<'info>,
+  destination: &AccountInfo<'info>,
+  creator: &AccountInfo<'info>,
+  amount: u64,
+  token_program: &AccountInfo<'info>,
+) -> Result<()> {
+  let creator = creator.key();
+  let state_bump = escrow_state.state_bump;
+  let order_id = escrow_state.order_id.to_le_bytes();
+  let seeds = &[&[
+    STATE_PDA_SEED,
+    creator.as_ref(),
+    order_id.as_ref(),
+    bytemuck::bytes_of(&state_bump),
+  ][..]];
+  // transfer Token escrow_vault -> destination
+  transfer_token_to_account(
+    escrow_vault.to_account_info(),
+    destination.to_account_info(),
+    escrow_state.to_account_info(),
+    amount,
+    token_program.to_account_info(),
+    Some(seeds),
+  )?;
+  Ok(())
+}
+
+fn close_vault_token<'info>(
+  escrow_state: &Account<'info, EscrowAccount>,
+  escrow_vault: &AccountInfo<'info>,
+  creator: &AccountInfo<'info>,
+  token_program: &AccountInfo<'info>,
+) -> Result<()> {
+  let creator_key = creator.key();
+  let state_bump = escrow_state.state_bump;
+  let order_id = escrow_state.order_id.to_le_bytes();
+  let signers_seeds = &[&[
+    STATE_PDA_SEED,
+    creator_key.as_ref(),
+    order_id.as_ref(),
+    bytemuck::bytes_of(&state_bump),
+  ][..]];
+  close_token_account(
+    escrow_vault.to_account_info(),
+    creator.to_account_info(),
+    escrow_state.to_account_info(),
+    signers_seeds,
+    token_program.to_account_info(),
+  )?;
+  Ok(())
+}
+
+fn close_vault_native<'info>(
+  escrow_state: &Account<'info, EscrowAccount>,
+  escrow_vault: &AccountInfo<'info>,
+  creator: &AccountInfo<'info>,
+  system_program: &AccountInfo<'info>,
+) -> Result<()> {
+  let vault_bump = escrow_state.vault_bump;
+  let creator_pubkey = creator.key();
+  let order_id = escrow_state.order_id.to_le_bytes();
+  let vault_signers_seeds = &[&[
+    VAULT_PDA_SEED,
+    creator_pubkey.as_ref(),
+    order_id.as_ref(),
+    bytemuck::bytes_of(&vault_bump),
+  ][..]];
+  close_native_account(
+    escrow_vault.to_account_info(),
+    creator.to_account_info(),
+    vault_signers_seeds,
+    system_program.to_account_info(),
+  )?;
+  Ok(())
 }
@@ -0,0 +1,87 @@
+use crate::error::EscrowError;
+use crate::event::TradeCancelled;
+use crate::processor::cancel::{refund_and_close_vault, remove_listings};
+
+use crate::state::{EscrowAccount, Stage};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct CancelMany<'info> {
+  #[account(mut, constraint = creator.lamports() > 0 && creator.data_is_empty())]
+  pub creator: Signer<'info>,
+  // system
+  system_program: Program<'info, System>,
+  token_program: Program<'info, Token>,
+}
+
+// remaining_accounts: for each order, escrow_state, escrow_vault, creator_send_account,
+// then its inbox and market page when it has ones (see `cancel`).
+// `atomic` fails the whole instruction on the first order that cannot be cancelled,
+// otherwise such orders are skipped.
+pub fn handler_cancel_many<'info>(
+  ctx: Context<'_, '_, '_, 'info, CancelMany<'info>>,
+  atomic: bool,
+) -> Result<()> {
+  let creator = ctx.accounts.creator.to_account_info();
+  let mut accounts = ctx.remaining_accounts.iter();
+  while let Some(state_info) = accounts.next() {
+    // a malformed group cannot be skipped, the next groups would be misread
+    let mut escrow_state: Account<EscrowAccount> = Account::try_from(state_info)?;
+    let escrow_vault = accounts.next().ok_or(EscrowError::MissingParams)?;
+    let creator_send_account = accounts.next().ok_or(EscrowError::MissingParams)?;
+    let listing_count =
+      escrow_state.specify_partner.is_some() as usize + escrow_state.market_page.is_some() as usize;
+    let listings = accounts.by_ref().take(listing_count).collect::<Vec<_>>();
+
+    if let Err(err) = check_cancel(&escrow_state, escrow_vault, creator_send_account, &creator) {
+      if atomic {
+        return Err(err);
+      }
+      msg!("skip order {}: {}", state_info.key, err);
+      continue;
+    }
+    refund_and_close_vault(
+      &escrow_state,
+      escrow_vault,
+      creator_send_account,
+      &creator,
+      escrow_state.trade_value,
+      &ctx.accounts.system_program,
+      &ctx.accounts.token_program,
+    )?;
+    escrow_state.stage = Stage::CancelTrade.to_code();
+    remove_listings(&escrow_state, state_info.key, &mut listings.into_iter())?;
+    escrow_state.exit(&crate::ID)?;
+
+    emit!(TradeCancelled {
+      escrow_state: state_info.key(),
+      creator: creator.key(),
+      order_id: escrow_state.order_id,
+      refund_value: escrow_state.trade_value,
+    });
+  }
+  Ok(())
+}
+
+// the account constraints of `Cancel`. The state is an `EscrowAccount` owned by
+// the program, so it sits at its PDA and its vault is the one it stores.
+fn check_cancel(
+  escrow_state: &EscrowAccount,
+  escrow_vault: &AccountInfo,
+  creator_send_account: &AccountInfo,
+  creator: &AccountInfo,
+) -> Result<()> {
+  require_keys_eq!(escrow_state.creator, creator.key(), EscrowError::InvalidOwner);
+  require_keys_eq!(escrow_state.escrow_vault, escrow_vault.key(), EscrowError::InvalidAccount);
+  require_keys_eq!(
+    escrow_state.creator_send_account,
+    creator_send_account.key(),
+    EscrowError::InvalidOwner
+  );
+  require!(escrow_state.is_open(), EscrowError::InvalidStage);
+  // the bidders of an auction are owed its settlement
+  require!(escrow_state.bid_count == 0, EscrowError::AuctionHasBids);
+  Ok(())
+}
@@ -0,0 +1,40 @@
+use crate::error::EscrowError;
+use crate::event::BidCommitted;
+use crate::processor::place_bid::{open_bid, PlaceBid};
+
+use anchor_lang::prelude::*;
+
+// commit a sealed bid on a sealed-bid auction order, `commitment` being the
+// `utils::bid_commitment` of the bid. `deposit` must cover the bid, which is
+// revealed with `reveal_bid`, and at least the reserve price.
+// Takes the accounts of `place_bid`.
+// remaining_accounts: the mints of the bidder's side, see `place_bid`
+pub fn handler_commit_bid<'info>(
+  ctx: Context<'_, '_, '_, 'info, PlaceBid<'info>>,
+  deposit: u64,
+  vault_bump: u8,
+  commitment: [u8; 32],
+) -> Result<()> {
+  let escrow_state = &ctx.accounts.escrow_state;
+  let auction = escrow_state.sealed_auction.ok_or(EscrowError::NotAnAuction)?;
+  require_gt!(auction.commit_end, Clock::get()?.unix_timestamp, EscrowError::AuctionEnded);
+  require_gte!(deposit, escrow_state.receive_value, EscrowError::DepositTooLow);
+
+  open_bid(
+    ctx.accounts,
+    ctx.remaining_accounts,
+    deposit,
+    *ctx.bumps.get("bid").unwrap(),
+    vault_bump,
+  )?;
+  ctx.accounts.bid.commitment = Some(commitment);
+
+  emit!(BidCommitted {
+    escrow_state: ctx.accounts.escrow_state.key(),
+    bid: ctx.accounts.bid.key(),
+    bidder: ctx.accounts.bidder.key(),
+    bid_index: ctx.accounts.bid.bid_index,
+    deposit,
+  });
+  Ok(())
+}
@@ -1,6 +1,29 @@
-use crate::constant::{ MintAddressType, TokenAccountType, STATE_PDA_SEED, VAULT_PDA_SEED };
+use crate::constant::{
+    MintAddressType,
+    TokenAccountType,
+    STATE_PDA_SEED,
+    USER_PDA_SEED,
+    VAULT_PDA_SEED,
+};
 use crate::error::EscrowError;
-use crate::state::{ EscrowAccount, Stage, TradeType };
+use crate::event::{ TradeCreated, TriggerArmed };
+use crate::inbox::add_to_inbox;
+use crate::market::{ add_to_market, market_mint };
+use crate::oracle::load_price;
+use crate::state::{
+    DutchAuction,
+    EnglishAuction,
+    EscrowAccount,
+    OraclePeg,
+    PaymentOptions,
+    PriceGuard,
+    Rfq,
+    SealedAuction,
+    Stage,
+    TradeType,
+    TriggerCondition,
+    UserAccount,
+};
 use crate::utils::{
     create_account,
     initialize_token_account,
@@ -9,22 +32,32 @@ use crate::utils::{
 };
 
 use anchor_lang::prelude::*;
-use anchor_spl::token::Token;
+use anchor_spl::token::{ spl_token::native_mint, Mint, Token };
 
 #[derive(Accounts)]
 #[instruction(params: CreateParams)]
 pub struct Create<'info> {
+    // order id counter of the creator, created with their first order. It comes
+    // first as the order's seeds take its next id
+    #[account(
+        init_if_needed,
+        payer = creator,
+        seeds = [USER_PDA_SEED, creator.key().as_ref()],
+        bump,
+        space = UserAccount::LEN
+    )]
+    pub user_account: Account<'info, UserAccount>,
     #[account(
         init,
         payer = creator,
-        seeds = [STATE_PDA_SEED, creator.key().as_ref(), params.order_id.to_le_bytes().as_ref()],
+        seeds = [STATE_PDA_SEED, creator.key().as_ref(), user_account.next_order_id.to_le_bytes().as_ref()],
         bump,
         space = EscrowAccount::LEN
     )]
     pub escrow_state: Account<'info, EscrowAccount>,
     /// CHECK: This is init account state, this account will be create when identity trade type (SPL-SPL, SOL-SPL, SPL-SOL)
     #[account(mut,
-    seeds=[VAULT_PDA_SEED, creator.key().as_ref(), params.order_id.to_le_bytes().as_ref()],
+    seeds=[VAULT_PDA_SEED, creator.key().as_ref(), user_account.next_order_id.to_le_bytes().as_ref()],
     bump = params.vault_bump
   )]
     pub escrow_vault: AccountInfo<'info>,
@@ -46,8 +79,6 @@ pub struct Create<'info> {
 
 #[derive(AnchorSerialize, AnchorDeserialize, Eq, PartialEq, Clone, Copy, Debug)]
 pub struct CreateParams {
-    // each trade deal has specify order id to identity
-    pub order_id: u64,
     // in this case, user want to trade with specify partner
     pub specify_partner: Option<Pubkey>,
     // value of token user want to trade
@@ -56,36 +87,525 @@ pub struct CreateParams {
     pub receive_value: u64,
     pub timestamp: u64,
     pub vault_bump: u8,
+    // merkle root of partner keys allowed to exchange, in addition to `specify_partner`
+    pub partner_root: Option<[u8; 32]>,
+    // hash of a claim key whose holder alone may exchange, shared off chain as a claim code
+    pub claim_hash: Option<[u8; 32]>,
+    // page of the pair's market to list the order on, unlisted when `None`
+    pub market_page: Option<u16>,
+    // sell by dutch auction, starting at `receive_value`
+    pub dutch_auction: Option<DutchAuction>,
+    // sell by english auction, `receive_value` being the reserve price
+    pub english_auction: Option<EnglishAuction>,
+    // sell by sealed-bid auction, `receive_value` being the reserve price
+    pub sealed_auction: Option<SealedAuction>,
+    // sell to the best quote, `receive_value` being zero
+    pub rfq: Option<Rfq>,
+    // sell at an oracle price, `receive_value` being recomputed at each exchange
+    pub oracle_peg: Option<OraclePeg>,
+    // refuse a receive value too far from a reference price, see `check_price_guard`
+    pub price_guard: Option<PriceGuard>,
+    // stop-loss or take-profit order, only exchanged once `trigger` has fired it
+    pub trigger: Option<TriggerCondition>,
+    // other mints the order may be paid in, each at its own price
+    pub payment_options: Option<PaymentOptions>,
 }
 
 pub fn handler_create_trade<'info>(
     ctx: Context<'_, '_, '_, 'info, Create<'info>>,
     params: CreateParams
 ) -> Result<()> {
-    // both trade value and receive value must be larger than zero
+    // both trade value and receive value must be larger than zero, but a
+    // request for quote has no receive value
     require_gt!(params.trade_value, 0, EscrowError::ZeroValue);
-    require_gt!(params.receive_value, 0, EscrowError::ZeroValue);
+    if params.rfq.is_none() {
+        require_gt!(params.receive_value, 0, EscrowError::ZeroValue);
+    }
+    check_order_modes(&params)?;
+
+    // assign the order id from the creator's counter
+    let creator_key = ctx.accounts.creator.key();
+    let user_account_bump = *ctx.bumps.get("user_account").unwrap();
+    let order_id = assign_order_ids(&mut ctx.accounts.user_account, creator_key, user_account_bump, 1)?;
+
+    // use remaining_account to pass mint_address
+    // index 0: creator trade token mint address
+    // index 1: creator receive token token mint address
+    // then: inbox of the specify partner, when there is one
+    // then: market page to list the order on, when there is one
+    // then: receive account of each payment option, when there are ones
+    // last: reference price account of the price guard, when there is one
+    let (listing_accounts, reference_account) = split_price_guard_account(
+        ctx.remaining_accounts,
+        &params
+    )?;
+    let (listing_accounts, payment_accounts) = split_payment_accounts(
+        listing_accounts,
+        &params
+    )?;
+    let (mint_accounts, inbox_account, market_account) = split_listing_accounts(
+        listing_accounts,
+        &params
+    )?;
+    let state_bump = *ctx.bumps.get("escrow_state").unwrap();
+    let vault_bump = params.vault_bump;
+
+    // init by trade type
+    let trade = check_trade(
+        &ctx.accounts.creator,
+        &ctx.accounts.creator_send_account,
+        &ctx.accounts.creator_receive_account,
+        mint_accounts,
+        params.trade_value
+    )?;
+    check_price_guard(&params, &trade, reference_account)?;
+    check_payment_options(&params, &trade, &ctx.accounts.creator, payment_accounts)?;
+    let order_id_bytes = order_id.to_le_bytes();
+    let vault_seeds = &[
+        &[
+            VAULT_PDA_SEED,
+            creator_key.as_ref(),
+            order_id_bytes.as_ref(),
+            bytemuck::bytes_of(&vault_bump),
+        ][..],
+    ];
+    let funding = VaultFunding {
+        creator: &ctx.accounts.creator,
+        creator_send_account: &ctx.accounts.creator_send_account,
+        system_program: &ctx.accounts.system_program,
+        token_program: &ctx.accounts.token_program,
+        rent: &ctx.accounts.rent,
+    };
+    fund_vault(
+        &trade,
+        &funding,
+        &ctx.accounts.escrow_state.to_account_info(),
+        &ctx.accounts.escrow_vault,
+        vault_seeds,
+        params.trade_value
+    )?;
+    let trade_type = trade.trade_type;
+    ctx.accounts.escrow_state.creator_send_token_mint = trade.send_mint();
+    ctx.accounts.escrow_state.creator_receive_token_mint = trade.receive_mint();
+
+    // fill escrow config account data
+    ctx.accounts.escrow_state.specify_partner = params.specify_partner;
+    ctx.accounts.escrow_state.partner_root = params.partner_root;
+    ctx.accounts.escrow_state.claim_hash = params.claim_hash;
+    ctx.accounts.escrow_state.market_page = params.market_page;
+    ctx.accounts.escrow_state.dutch_auction = params.dutch_auction;
+    ctx.accounts.escrow_state.english_auction = params.english_auction;
+    ctx.accounts.escrow_state.sealed_auction = params.sealed_auction;
+    ctx.accounts.escrow_state.rfq = params.rfq;
+    ctx.accounts.escrow_state.oracle_peg = trade.oracle_peg(params.oracle_peg);
+    ctx.accounts.escrow_state.trigger = params.trigger;
+    ctx.accounts.escrow_state.payment_options = params.payment_options;
+    ctx.accounts.escrow_state.creator = ctx.accounts.creator.key();
+    ctx.accounts.escrow_state.trade_type = trade_type.to_code();
+    ctx.accounts.escrow_state.escrow_vault = ctx.accounts.escrow_vault.key();
+    ctx.accounts.escrow_state.creator_send_account = ctx.accounts.creator_send_account.key();
+    ctx.accounts.escrow_state.creator_receive_account = ctx.accounts.creator_receive_account.key();
+    ctx.accounts.escrow_state.trade_value = params.trade_value;
+    ctx.accounts.escrow_state.receive_value = params.receive_value;
+    ctx.accounts.escrow_state.fee_account = ctx.accounts.fee_account.key();
+    ctx.accounts.escrow_state.order_id = order_id;
+    ctx.accounts.escrow_state.timestamp = params.timestamp;
+    ctx.accounts.escrow_state.vault_bump = vault_bump;
+    ctx.accounts.escrow_state.state_bump = state_bump;
+    ctx.accounts.escrow_state.stage = initial_stage(&params).to_code();
+
+    list_order(
+        &ctx.accounts.escrow_state,
+        &params,
+        inbox_account,
+        market_account,
+        &ctx.accounts.creator,
+        &ctx.accounts.system_program,
+        &ctx.accounts.rent
+    )?;
+
+    emit!(TradeCreated {
+        escrow_state: ctx.accounts.escrow_state.key(),
+        creator: ctx.accounts.escrow_state.creator,
+        order_id,
+        trade_type: ctx.accounts.escrow_state.trade_type,
+        creator_send_token_mint: ctx.accounts.escrow_state.creator_send_token_mint,
+        creator_receive_token_mint: ctx.accounts.escrow_state.creator_receive_token_mint,
+        trade_value: params.trade_value,
+        receive_value: params.receive_value,
+        specify_partner: params.specify_partner,
+        fee_account: ctx.accounts.escrow_state.fee_account,
+        timestamp: params.timestamp,
+    });
+    emit_trigger_armed(ctx.accounts.escrow_state.key(), &params);
+    Ok(())
+}
+
+// a conditional order waits for its trigger, any other is ready for exchange
+pub fn initial_stage(params: &CreateParams) -> Stage {
+    match params.trigger {
+        Some(_) => Stage::AwaitingTrigger,
+        None => Stage::ReadyExchange,
+    }
+}
+
+pub fn emit_trigger_armed(escrow_state: Pubkey, params: &CreateParams) {
+    if let Some(trigger) = &params.trigger {
+        emit!(TriggerArmed {
+            escrow_state,
+            oracle: trigger.oracle,
+            trigger_price: trigger.trigger_price,
+            direction: trigger.direction,
+        });
+    }
+}
+
+// the modes of a new order: how it is priced, who may take it and whether it
+// waits for a trigger, each checked on its own
+pub fn check_order_modes(params: &CreateParams) -> Result<()> {
+    check_exclusive_pricing(params)?;
+    check_dutch_auction(params)?;
+    check_english_auction(params)?;
+    check_sealed_auction(params)?;
+    check_rfq(params)?;
+    check_oracle_peg(params)?;
+    check_trigger(params)?;
+    check_payment_prices(params)
+}
+
+// an order is priced one way only: by a dutch, english or sealed auction, a
+// request for quote, an oracle peg or fixed payment options. Two of them fail
+// with the error of the later one
+fn check_exclusive_pricing(params: &CreateParams) -> Result<()> {
+    let pricings = [
+        (params.dutch_auction.is_some(), EscrowError::InvalidAuction),
+        (params.english_auction.is_some(), EscrowError::InvalidAuction),
+        (params.sealed_auction.is_some(), EscrowError::InvalidAuction),
+        (params.rfq.is_some(), EscrowError::InvalidRfq),
+        (params.oracle_peg.is_some(), EscrowError::InvalidOraclePeg),
+        (params.payment_options.is_some(), EscrowError::InvalidPaymentOptions),
+    ];
+    let mut set = pricings.into_iter().filter(|(is_set, _)| *is_set);
+    if let (Some(_), Some((_, error))) = (set.next(), set.next()) {
+        return Err(error.into());
+    }
+    Ok(())
+}
+
+// orders anyone can bid or quote on have no partner restriction
+fn is_unrestricted(params: &CreateParams) -> bool {
+    params.specify_partner.is_none() && params.partner_root.is_none() && params.claim_hash.is_none()
+}
+
+// the price of a dutch auction falls from `receive_value` to a floor above zero
+fn check_dutch_auction(params: &CreateParams) -> Result<()> {
+    if let Some(auction) = &params.dutch_auction {
+        require!(
+            auction.floor_value > 0 &&
+                auction.floor_value <= params.receive_value &&
+                auction.end_time > auction.start_time,
+            EscrowError::InvalidAuction
+        );
+    }
+    Ok(())
+}
+
+// an english auction takes bids from anyone until a later time
+fn check_english_auction(params: &CreateParams) -> Result<()> {
+    if let Some(auction) = &params.english_auction {
+        require!(
+            auction.min_increment > 0 &&
+                auction.end_time > Clock::get()?.unix_timestamp &&
+                is_unrestricted(params),
+            EscrowError::InvalidAuction
+        );
+    }
+    Ok(())
+}
+
+// a sealed-bid auction takes commitments from anyone, then their reveals
+fn check_sealed_auction(params: &CreateParams) -> Result<()> {
+    if let Some(auction) = &params.sealed_auction {
+        require!(
+            auction.commit_end > Clock::get()?.unix_timestamp &&
+                auction.reveal_end > auction.commit_end &&
+                is_unrestricted(params),
+            EscrowError::InvalidAuction
+        );
+    }
+    Ok(())
+}
+
+// a request for quote takes quotes from anyone and has no price, so it is not
+// listed and has nothing to guard
+fn check_rfq(params: &CreateParams) -> Result<()> {
+    if let Some(rfq) = &params.rfq {
+        require!(
+            rfq.deadline > Clock::get()?.unix_timestamp &&
+                rfq.extra_mint_count as usize <= rfq.extra_mints.len() &&
+                (!rfq.best_price || rfq.extra_mint_count == 0) &&
+                params.receive_value == 0 &&
+                is_unrestricted(params) &&
+                params.market_page.is_none() &&
+                params.price_guard.is_none(),
+            EscrowError::InvalidRfq
+        );
+    }
+    Ok(())
+}
+
+// an oracle-pegged order takes its price from a fresh enough feed, within bounds
+fn check_oracle_peg(params: &CreateParams) -> Result<()> {
+    if let Some(peg) = &params.oracle_peg {
+        require!(
+            peg.spread_bps > 0 && peg.min_value > 0 && peg.min_value <= peg.max_value && peg.max_age > 0,
+            EscrowError::InvalidOraclePeg
+        );
+    }
+    Ok(())
+}
+
+// a conditional order is only exchanged, so it is no auction or request for
+// quote, and is not listed on a market where it could not be filled yet
+fn check_trigger(params: &CreateParams) -> Result<()> {
+    if let Some(trigger) = &params.trigger {
+        require!(
+            trigger.trigger_price > 0 &&
+                trigger.max_age > 0 &&
+                params.dutch_auction.is_none() &&
+                params.english_auction.is_none() &&
+                params.sealed_auction.is_none() &&
+                params.rfq.is_none() &&
+                params.market_page.is_none(),
+            EscrowError::InvalidTrigger
+        );
+    }
+    Ok(())
+}
+
+// payment options have fixed prices above zero
+fn check_payment_prices(params: &CreateParams) -> Result<()> {
+    if let Some(options) = &params.payment_options {
+        require!(
+            options.count > 0 &&
+                (options.count as usize) <= options.options.len() &&
+                options.accepted().iter().all(|option| option.receive_value > 0),
+            EscrowError::InvalidPaymentOptions
+        );
+        // the guard only checks the order's own receive mint, not the options
+        require!(params.price_guard.is_none(), EscrowError::InvalidPaymentOptions);
+    }
+    Ok(())
+}
+
+// the reference price account of a price guard, passed last in `remaining_accounts`
+pub fn split_price_guard_account<'a, 'info>(
+    accounts: &'a [AccountInfo<'info>],
+    params: &CreateParams
+) -> Result<(&'a [AccountInfo<'info>], Option<&'a AccountInfo<'info>>)> {
+    match params.price_guard {
+        Some(_) => {
+            let (reference, rest) = accounts.split_last().ok_or(EscrowError::InvalidOracle)?;
+            Ok((rest, Some(reference)))
+        }
+        None => Ok((accounts, None)),
+    }
+}
+
+// a receive value, or dutch auction floor, off by more than the guard's deviation
+// from `trade_value` at the reference price is taken for a decimals mistake and
+// refused, unless the creator overrides the guard, which only logs it
+pub fn check_price_guard<'info>(
+    params: &CreateParams,
+    trade: &TradeMints<'info>,
+    reference_account: Option<&AccountInfo<'info>>
+) -> Result<()> {
+    let (guard, reference_account) = match (&params.price_guard, reference_account) {
+        (Some(guard), Some(reference_account)) => (guard, reference_account),
+        _ => {
+            return Ok(());
+        }
+    };
+    require_keys_eq!(reference_account.key(), guard.oracle, EscrowError::InvalidOracle);
+    let price = load_price(reference_account)?;
+    price.check_age(guard.max_age, Clock::get()?.unix_timestamp)?;
+    let reference_value = price.value_of(params.trade_value, trade.decimal_shift(), 10_000);
+    let floor_value = params.dutch_auction.map(|auction| auction.floor_value);
+    for value in [Some(params.receive_value), floor_value].into_iter().flatten() {
+        let deviation = (value as u128).abs_diff(reference_value as u128) * 10_000;
+        if deviation > (reference_value as u128) * (guard.max_deviation_bps as u128) {
+            require!(guard.override_deviation, EscrowError::PriceDeviation);
+            msg!("value {} overrides the reference value {}", value, reference_value);
+        }
+    }
+    Ok(())
+}
+
+// the receive accounts of the payment options of a new order, passed in their
+// order after the listing accounts in `remaining_accounts`
+pub fn split_payment_accounts<'a, 'info>(
+    accounts: &'a [AccountInfo<'info>],
+    params: &CreateParams
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    let count = params.payment_options.map_or(0, |options| options.accepted().len());
+    let split = accounts.len().checked_sub(count).ok_or(EscrowError::InvalidPaymentOptions)?;
+    Ok(accounts.split_at(split))
+}
+
+// each payment option is a mint other than those of the pair and of the other
+// options, paid into a token account of the creator in that mint, or the creator
+// wallet for SOL
+pub fn check_payment_options<'info>(
+    params: &CreateParams,
+    trade: &TradeMints<'info>,
+    creator: &AccountInfo<'info>,
+    payment_accounts: &[AccountInfo<'info>]
+) -> Result<()> {
+    let options = match &params.payment_options {
+        Some(options) => options.accepted(),
+        None => {
+            return Ok(());
+        }
+    };
+    let mut mints = vec![market_mint(trade.send_mint()), market_mint(trade.receive_mint())];
+    for (option, receive_account) in options.iter().zip(payment_accounts) {
+        require!(!mints.contains(&option.mint), EscrowError::DuplicateMint);
+        mints.push(option.mint);
+        require_keys_eq!(
+            receive_account.key(),
+            option.receive_account,
+            EscrowError::InvalidAccount
+        );
+        if option.mint == native_mint::ID {
+            require_keys_eq!(receive_account.key(), creator.key(), EscrowError::InvalidOwner);
+        } else {
+            let token_account: TokenAccountType = Account::try_from(receive_account);
+            let token_account = token_account.map_err(|_| EscrowError::InvalidAccount)?;
+            require_keys_eq!(token_account.mint, option.mint, EscrowError::InvalidMint);
+            require_keys_eq!(token_account.owner, creator.key(), EscrowError::InvalidOwner);
+        }
+    }
+    Ok(())
+}
+
+// the inbox and market page accounts of a new order, passed last in
+// `remaining_accounts` after the mints
+pub fn split_listing_accounts<'a, 'info>(
+    accounts: &'a [AccountInfo<'info>],
+    params: &CreateParams
+) -> Result<(&'a [AccountInfo<'info>], Option<&'a AccountInfo<'info>>, Option<&'a AccountInfo<'info>>)> {
+    let mut mint_accounts = accounts;
+    let market_account = match params.market_page {
+        Some(_) => {
+            let (market, rest) = mint_accounts.split_last().ok_or(EscrowError::InvalidMarket)?;
+            mint_accounts = rest;
+            Some(market)
+        }
+        None => None,
+    };
+    let inbox_account = match params.specify_partner {
+        Some(_) => {
+            let (inbox, rest) = mint_accounts.split_last().ok_or(EscrowError::InvalidInbox)?;
+            mint_accounts = rest;
+            Some(inbox)
+        }
+        None => None,
+    };
+    Ok((mint_accounts, inbox_account, market_account))
+}
 
+// list a new order in the inbox of its specify partner and on the market of its pair
+pub fn list_order<'info>(
+    escrow_state: &Account<'info, EscrowAccount>,
+    params: &CreateParams,
+    inbox_account: Option<&AccountInfo<'info>>,
+    market_account: Option<&AccountInfo<'info>>,
+    creator: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    rent: &Rent
+) -> Result<()> {
+    // list the order in the partner's inbox
+    if let (Some(partner), Some(inbox)) = (params.specify_partner, inbox_account) {
+        add_to_inbox(inbox, &partner, escrow_state.key(), creator, system_program, rent)?;
+    }
+    // and on the market of its pair
+    if let (Some(page), Some(market)) = (params.market_page, market_account) {
+        add_to_market(market, escrow_state, escrow_state.key(), page, creator, system_program, rent)?;
+    }
+    Ok(())
+}
+
+// take `count` consecutive order ids from the creator's counter, which starts
+// at zero, returning the first
+pub fn assign_order_ids(user_account: &mut UserAccount, creator: Pubkey, bump: u8, count: u64) -> Result<u64> {
+    if user_account.creator == Pubkey::default() {
+        user_account.creator = creator;
+        user_account.bump = bump;
+    }
+    let first_order_id = user_account.next_order_id;
+    user_account.next_order_id = first_order_id
+        .checked_add(count)
+        .ok_or(EscrowError::InvalidOrderId)?;
+    Ok(first_order_id)
+}
+
+// trade type and mints of an order, from the creator accounts and the mints
+// passed in `remaining_accounts`
+pub struct TradeMints<'info> {
+    pub trade_type: TradeType,
+    pub send_mint: Option<Account<'info, Mint>>,
+    pub receive_mint: Option<Account<'info, Mint>>,
+}
+
+impl<'info> TradeMints<'info> {
+    pub fn send_mint(&self) -> Option<Pubkey> {
+        self.send_mint.as_ref().map(|mint| mint.key())
+    }
+
+    pub fn receive_mint(&self) -> Option<Pubkey> {
+        self.receive_mint.as_ref().map(|mint| mint.key())
+    }
+
+    // decimals of the receive mint minus those of the send mint
+    pub fn decimal_shift(&self) -> i8 {
+        let decimals = |mint: &Option<Account<'info, Mint>>| {
+            mint.as_ref().map_or(native_mint::DECIMALS, |mint| mint.decimals) as i8
+        };
+        decimals(&self.receive_mint) - decimals(&self.send_mint)
+    }
+
+    // the peg of an order between these mints, its decimal shift taken from them
+    pub fn oracle_peg(&self, peg: Option<OraclePeg>) -> Option<OraclePeg> {
+        peg.map(|peg| OraclePeg {
+            decimal_shift: self.decimal_shift(),
+            ..peg
+        })
+    }
+}
+
+// the per trade type checks of `create_trade`, `trade_value` being what the
+// creator is about to lock from `creator_send_account`
+pub fn check_trade<'info>(
+    creator: &AccountInfo<'info>,
+    creator_send_account: &AccountInfo<'info>,
+    creator_receive_account: &AccountInfo<'info>,
+    mint_accounts: &[AccountInfo<'info>],
+    trade_value: u64
+) -> Result<TradeMints<'info>> {
     // extract account
     // its work if it account is TokenAccount
     let creator_accounts: (TokenAccountType, TokenAccountType) = (
-        Account::try_from(&ctx.accounts.creator_send_account),
-        Account::try_from(&ctx.accounts.creator_receive_account),
+        Account::try_from(creator_send_account),
+        Account::try_from(creator_receive_account),
     );
     // extract mint address account
     // its work if it account is Mint
-    // use remaining_account to pass mint_address
-    // index 0: creator trade token mint address
-    // index 1: creator receive token token mint address
     let mints_address: (Option<MintAddressType>, Option<MintAddressType>) = (
-        ctx.remaining_accounts.get(0).map(Account::try_from),
-        ctx.remaining_accounts.get(1).map(Account::try_from),
+        mint_accounts.first().map(Account::try_from),
+        mint_accounts.get(1).map(Account::try_from),
     );
-    let state_bump = *ctx.bumps.get("escrow_state").unwrap();
-    let vault_bump = params.vault_bump;
+    let trade = match creator_accounts {
 
-    // init by trade type
-    let trade_type: TradeType = match creator_accounts {
         /////// CASE 1: SPL <-> SPL
         // `creator_send_account` & `creator_receive_account` are Associate-Token-Account corresponding with mint addresses
         (Ok(creator_send_account), Ok(creator_receive_account)) =>
@@ -106,13 +626,13 @@ pub fn handler_create_trade<'info>(
                     // make sure creator is owner of `creator_send_account`
                     require_eq!(
                         creator_send_account.owner,
-                        ctx.accounts.creator.key(),
+                        creator.key(),
                         EscrowError::InvalidOwner
                     );
                     // make sure creator is owner of `creator_receive_account`
                     require_eq!(
                         creator_receive_account.owner,
-                        ctx.accounts.creator.key(),
+                        creator.key(),
                         EscrowError::InvalidOwner
                     );
                     // make sure never duplicate trade p2p between same token
@@ -124,31 +644,14 @@ pub fn handler_create_trade<'info>(
                     // make sure token balance of creator greater than or equal value_trade
                     require_gte!(
                         creator_send_account.amount,
-                        params.trade_value,
+                        trade_value,
                         EscrowError::InsufficientFunds
                     );
-
-                    // create account associated token account
-                    ctx.accounts.create_token_account_vault(
-                        mint_token_creator_trade.to_account_info(),
-                        vault_bump,
-                        params.order_id
-                    )?;
-
-                    // transfer token to escrow account
-                    ctx.accounts.transfer_token_to_vault(params.trade_value)?;
-
-                    msg!("Created trading P2P between SPL <-> SPL. Now ready for trade");
-
-                    //
-                    ctx.accounts.escrow_state.creator_send_token_mint = Some(
-                        mint_token_creator_trade.key()
-                    );
-                    ctx.accounts.escrow_state.creator_receive_token_mint = Some(
-                        mint_token_creator_receive.key()
-                    );
-                    //
-                    TradeType::TokenToken
+                    TradeMints {
+                        trade_type: TradeType::TokenToken,
+                        send_mint: Some(mint_token_creator_trade),
+                        receive_mint: Some(mint_token_creator_receive),
+                    }
                 }
                 (None, _) | (_, None) => {
                     return Err(EscrowError::MissingMint.into());
@@ -173,32 +676,20 @@ pub fn handler_create_trade<'info>(
                     // make sure creator is owner of `creator_send_account`
                     require_eq!(
                         creator_send_account.owner,
-                        ctx.accounts.creator.key(),
+                        creator.key(),
                         EscrowError::InvalidOwner
                     );
                     // make sure token balance of creator greater than or equal value_trade
                     require_gte!(
                         creator_send_account.amount,
-                        params.trade_value,
+                        trade_value,
                         EscrowError::InsufficientFunds
                     );
-
-                    // create token account
-                    ctx.accounts.create_token_account_vault(
-                        mint_token_creator_trade.to_account_info(),
-                        vault_bump,
-                        params.order_id
-                    )?;
-
-                    // transfer token to escrow vault token account
-                    ctx.accounts.transfer_token_to_vault(params.trade_value)?;
-                    msg!("Created trading P2P between SPL <-> SOL. Now ready for trade");
-                    //
-                    ctx.accounts.escrow_state.creator_send_token_mint = Some(
-                        mint_token_creator_trade.key()
-                    );
-                    //
-                    TradeType::TokenSol
+                    TradeMints {
+                        trade_type: TradeType::TokenSol,
+                        send_mint: Some(mint_token_creator_trade),
+                        receive_mint: None,
+                    }
                 }
                 (None, _) | (_, None) => {
                     return Err(EscrowError::MissingMint.into());
@@ -221,28 +712,20 @@ pub fn handler_create_trade<'info>(
                     // make sure owner of `creator_receive_account` is creator
                     require_eq!(
                         creator_receive_account.owner,
-                        ctx.accounts.creator.key(),
+                        creator.key(),
                         EscrowError::InvalidOwner
                     );
                     // make sure enough SOL for trade
                     require_gte!(
-                        ctx.accounts.creator.lamports(),
-                        params.trade_value,
+                        creator.lamports(),
+                        trade_value,
                         EscrowError::InsufficientFunds
                     );
-
-                    // create escrow vault account to hold lamports
-                    ctx.accounts.create_native_account_vault(vault_bump, params.order_id)?;
-                    // transfer SOL -> Vault Escrow
-                    ctx.accounts.transfer_native_vault(params.trade_value)?;
-
-                    msg!("Created trading P2P between SOL <-> SPL. Now ready for trade");
-                    //
-                    ctx.accounts.escrow_state.creator_receive_token_mint = Some(
-                        mint_token_creator_receive.key()
-                    );
-                    // // trade type
-                    TradeType::SolToken
+                    TradeMints {
+                        trade_type: TradeType::SolToken,
+                        send_mint: None,
+                        receive_mint: Some(mint_token_creator_receive),
+                    }
                 }
                 (None, _) | (_, None) => {
                     return Err(EscrowError::MissingMint.into());
@@ -255,116 +738,83 @@ pub fn handler_create_trade<'info>(
             return Err(EscrowError::InvalidTradeType.into());
         }
     };
-
-    // fill escrow config account data
-    ctx.accounts.escrow_state.specify_partner = params.specify_partner;
-    ctx.accounts.escrow_state.creator = ctx.accounts.creator.key();
-    ctx.accounts.escrow_state.trade_type = trade_type.to_code();
-    ctx.accounts.escrow_state.escrow_vault = ctx.accounts.escrow_vault.key();
-    ctx.accounts.escrow_state.creator_send_account = ctx.accounts.creator_send_account.key();
-    ctx.accounts.escrow_state.creator_receive_account = ctx.accounts.creator_receive_account.key();
-    ctx.accounts.escrow_state.trade_value = params.trade_value;
-    ctx.accounts.escrow_state.receive_value = params.receive_value;
-    ctx.accounts.escrow_state.fee_account = ctx.accounts.fee_account.key();
-    ctx.accounts.escrow_state.order_id = params.order_id;
-    ctx.accounts.escrow_state.timestamp = params.timestamp;
-    ctx.accounts.escrow_state.vault_bump = vault_bump;
-    ctx.accounts.escrow_state.state_bump = state_bump;
-    ctx.accounts.escrow_state.stage = Stage::ReadyExchange.to_code();
-    Ok(())
+    Ok(trade)
 }
 
+// accounts paying for and funding the vaults of a creator's orders
+pub struct VaultFunding<'a, 'info> {
+    pub creator: &'a AccountInfo<'info>,
+    pub creator_send_account: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+    pub token_program: &'a AccountInfo<'info>,
+    pub rent: &'a Sysvar<'info, Rent>,
+}
 
-impl<'info> Create<'info> {
-    fn create_token_account_vault(
-        &self,
-        mint: AccountInfo<'info>,
-        vault_bump: u8,
-        order_id: u64
-    ) -> Result<()> {
-        //
-        let space = anchor_spl::token::TokenAccount::LEN;
-        // get signers seeds
-        let creator_key = self.creator.key();
-        let order_id_bytes = order_id.to_le_bytes();
-        let vault_seed = &[
-            &[
-                VAULT_PDA_SEED,
-                creator_key.as_ref(),
-                order_id_bytes.as_ref(),
-                bytemuck::bytes_of(&vault_bump),
-            ][..],
-        ];
-        // create account
-        // with pda vault token account
-        // owner of program is Token program ID. Not itself program
-        create_account(
-            self.creator.to_account_info(),
-            self.escrow_vault.to_account_info(),
-            space,
-            vault_seed,
-            self.token_program.to_account_info(),
-            self.rent.clone()
-        )?;
-
-        // init vault pda token account
-        initialize_token_account(
-            self.escrow_vault.to_account_info(),
-            mint,
-            self.escrow_state.to_account_info(),
-            self.token_program.to_account_info(),
-            self.rent.clone()
-        )?;
-        Ok(())
-    }
-
-    fn create_native_account_vault(&self, vault_bump: u8, order_id: u64) -> Result<()> {
-        let creator_key = self.creator.key();
-        let order_id_bytes = order_id.to_le_bytes();
-        let vault_seed = &[
-            &[
-                VAULT_PDA_SEED,
-                creator_key.as_ref(),
-                order_id_bytes.as_ref(),
-                bytemuck::bytes_of(&vault_bump),
-            ][..],
-        ];
-
-        // create account
-        // assign vault account for system program rather than itself program id
-        create_account(
-            self.creator.to_account_info(),
-            self.escrow_vault.to_account_info(),
-            0,
-            vault_seed,
-            self.system_program.to_account_info(),
-            self.rent.clone()
-        )?;
-        Ok(())
-    }
-    //
-    fn transfer_native_vault(&self, amount: u64) -> Result<()> {
-        // transfer SOL creator -> vault
-        transfer_native_to_account(
-            self.creator.to_account_info(),
-            self.escrow_vault.to_account_info(),
-            amount,
-            self.system_program.to_account_info(),
-            None
-        )?;
-        Ok(())
-    }
-
-    fn transfer_token_to_vault(&self, amount: u64) -> Result<()> {
-        // transfer Token creator -> vault
-        transfer_token_to_account(
-            self.creator_send_account.to_account_info(),
-            self.escrow_vault.to_account_info(),
-            self.creator.to_account_info(),
-            amount,
-            self.token_program.to_account_info(),
-            None
-        )?;
-        Ok(())
+// create the vault of an order at its PDA and lock `trade_value` in it
+pub fn fund_vault<'info>(
+    trade: &TradeMints<'info>,
+    funding: &VaultFunding<'_, 'info>,
+    escrow_state: &AccountInfo<'info>,
+    escrow_vault: &AccountInfo<'info>,
+    vault_seeds: &[&[&[u8]]],
+    trade_value: u64
+) -> Result<()> {
+    let VaultFunding { creator, creator_send_account, system_program, token_program, rent } =
+        *funding;
+    match &trade.send_mint {
+        // SPL <-> SPL, SPL <-> SOL
+        Some(mint_token_creator_trade) => {
+            // create account associated token account
+            // with pda vault token account
+            // owner of program is Token program ID. Not itself program
+            create_account(
+                creator.to_account_info(),
+                escrow_vault.to_account_info(),
+                anchor_spl::token::TokenAccount::LEN,
+                vault_seeds,
+                token_program.to_account_info(),
+                rent.clone()
+            )?;
+            // init vault pda token account
+            initialize_token_account(
+                escrow_vault.to_account_info(),
+                mint_token_creator_trade.to_account_info(),
+                escrow_state.to_account_info(),
+                token_program.to_account_info(),
+                rent.clone()
+            )?;
+            // transfer token to escrow account
+            transfer_token_to_account(
+                creator_send_account.to_account_info(),
+                escrow_vault.to_account_info(),
+                creator.to_account_info(),
+                trade_value,
+                token_program.to_account_info(),
+                None
+            )?;
+        }
+        // SOL <-> SPL
+        None => {
+            // create escrow vault account to hold lamports
+            // assign vault account for system program rather than itself program id
+            create_account(
+                creator.to_account_info(),
+                escrow_vault.to_account_info(),
+                0,
+                vault_seeds,
+                system_program.to_account_info(),
+                rent.clone()
+            )?;
+            // transfer SOL -> Vault Escrow
+            transfer_native_to_account(
+                creator.to_account_info(),
+                escrow_vault.to_account_info(),
+                trade_value,
+                system_program.to_account_info(),
+                None
+            )?;
+        }
     }
-}
\ No newline at end of file
+    msg!("Created trading P2P {:?}. Now ready for trade", trade.trade_type);
+    Ok(())
+}
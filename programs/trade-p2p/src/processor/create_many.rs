@@ -0,0 +1,226 @@
+use crate::constant::{STATE_PDA_SEED, USER_PDA_SEED, VAULT_PDA_SEED};
+use crate::error::EscrowError;
+use crate::event::TradeCreated;
+use crate::market::add_to_market;
+use crate::processor::create::{assign_order_ids, check_trade, fund_vault, VaultFunding};
+use crate::state::{EscrowAccount, Stage, UserAccount};
+use crate::utils::create_program_account;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct CreateMany<'info> {
+  #[account(mut, constraint = creator.lamports() > 0 && creator.data_is_empty())]
+  pub creator: Signer<'info>,
+  /// CHECK: This account use to send `Token` to swap, funds every order (Token can be SOL or SPL Token)
+  #[account(mut)]
+  pub creator_send_account: AccountInfo<'info>,
+  /// CHECK: This account use to receive `Token` swapped (Token can be SOL or SPL Token)
+  #[account(mut)]
+  pub creator_receive_account: AccountInfo<'info>,
+  /// CHECK: receive fee for each deal
+  #[account(mut)]
+  pub fee_account: AccountInfo<'info>,
+  pub system_program: Program<'info, System>,
+  pub token_program: Program<'info, Token>,
+  pub rent: Sysvar<'info, Rent>,
+  // order id counter of the creator, created with their first order
+  #[account(
+    init_if_needed,
+    payer = creator,
+    seeds = [USER_PDA_SEED, creator.key().as_ref()],
+    bump,
+    space = UserAccount::LEN
+  )]
+  pub user_account: Account<'info, UserAccount>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Eq, PartialEq, Clone, Debug)]
+pub struct CreateManyParams {
+  pub orders: Vec<OrderLevel>,
+  pub timestamp: u64,
+  // page of the pair's market to list every order on, unlisted when `None`
+  pub market_page: Option<u16>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Eq, PartialEq, Clone, Copy, Debug)]
+pub struct OrderLevel {
+  // value of token user want to trade
+  pub trade_value: u64,
+  // value of token user expect to receive
+  pub receive_value: u64,
+  pub vault_bump: u8,
+}
+
+// remaining_accounts: the mints as in `create_trade`, then for each order its
+// escrow_state and escrow_vault, followed by the market page when `market_page` is set
+pub fn handler_create_many<'info>(
+  ctx: Context<'_, '_, '_, 'info, CreateMany<'info>>,
+  params: CreateManyParams,
+) -> Result<()> {
+  require!(!params.orders.is_empty(), EscrowError::MissingParams);
+  let mut total_value: u64 = 0;
+  for level in &params.orders {
+    // both trade value and receive value must be larger than zero
+    require_gt!(level.trade_value, 0, EscrowError::ZeroValue);
+    require_gt!(level.receive_value, 0, EscrowError::ZeroValue);
+    total_value = total_value
+      .checked_add(level.trade_value)
+      .ok_or(EscrowError::InsufficientFunds)?;
+  }
+
+  // assign the order ids from the creator's counter
+  let creator_key = ctx.accounts.creator.key();
+  let user_account_bump = *ctx.bumps.get("user_account").unwrap();
+  let first_order_id = assign_order_ids(
+    &mut ctx.accounts.user_account,
+    creator_key,
+    user_account_bump,
+    params.orders.len() as u64,
+  )?;
+
+  // the mints are whatever precedes the order groups
+  let group_len = 2 + params.market_page.is_some() as usize;
+  let mint_count = ctx
+    .remaining_accounts
+    .len()
+    .checked_sub(params.orders.len() * group_len)
+    .ok_or(EscrowError::MissingParams)?;
+  require!(mint_count > 0, EscrowError::MissingMint);
+  let (mint_accounts, order_accounts) = ctx.remaining_accounts.split_at(mint_count);
+
+  // the creator must hold enough for every order
+  let trade = check_trade(
+    &ctx.accounts.creator,
+    &ctx.accounts.creator_send_account,
+    &ctx.accounts.creator_receive_account,
+    mint_accounts,
+    total_value,
+  )?;
+
+  let creator = ctx.accounts.creator.to_account_info();
+  let system_program = ctx.accounts.system_program.to_account_info();
+  let token_program = ctx.accounts.token_program.to_account_info();
+  let funding = VaultFunding {
+    creator: &creator,
+    creator_send_account: &ctx.accounts.creator_send_account,
+    system_program: &system_program,
+    token_program: &token_program,
+    rent: &ctx.accounts.rent,
+  };
+  for (index, (level, accounts)) in params
+    .orders
+    .iter()
+    .zip(order_accounts.chunks(group_len))
+    .enumerate()
+  {
+    let order_id = first_order_id + index as u64;
+    let order_id_bytes = order_id.to_le_bytes();
+    let (escrow_state, escrow_vault) = (&accounts[0], &accounts[1]);
+
+    // escrow_state and escrow_vault must be the canonical PDAs of the order
+    let (state_key, state_bump) = Pubkey::find_program_address(
+      &[STATE_PDA_SEED, creator_key.as_ref(), order_id_bytes.as_ref()],
+      &crate::ID,
+    );
+    let (vault_key, vault_bump) = Pubkey::find_program_address(
+      &[VAULT_PDA_SEED, creator_key.as_ref(), order_id_bytes.as_ref()],
+      &crate::ID,
+    );
+    require_keys_eq!(escrow_state.key(), state_key, EscrowError::InvalidAccount);
+    require_keys_eq!(escrow_vault.key(), vault_key, EscrowError::InvalidAccount);
+    require_eq!(level.vault_bump, vault_bump, EscrowError::InvalidAccount);
+    let state_seeds = &[&[
+      STATE_PDA_SEED,
+      creator_key.as_ref(),
+      order_id_bytes.as_ref(),
+      bytemuck::bytes_of(&state_bump),
+    ][..]];
+    let vault_seeds = &[&[
+      VAULT_PDA_SEED,
+      creator_key.as_ref(),
+      order_id_bytes.as_ref(),
+      bytemuck::bytes_of(&vault_bump),
+    ][..]];
+
+    create_program_account(
+      &creator,
+      escrow_state,
+      EscrowAccount::LEN,
+      state_seeds,
+      &system_program,
+      &ctx.accounts.rent,
+    )?;
+    fund_vault(&trade, &funding, escrow_state, escrow_vault, vault_seeds, level.trade_value)?;
+
+    // fill escrow config account data
+    let order = EscrowAccount {
+      creator: creator_key,
+      partner: Pubkey::default(),
+      specify_partner: None,
+      fee_account: ctx.accounts.fee_account.key(),
+      trade_token_mint: Pubkey::default(),
+      receive_token_mint: Pubkey::default(),
+      escrow_vault: escrow_vault.key(),
+      creator_send_account: ctx.accounts.creator_send_account.key(),
+      creator_receive_account: ctx.accounts.creator_receive_account.key(),
+      creator_send_token_mint: trade.send_mint(),
+      creator_receive_token_mint: trade.receive_mint(),
+      trade_value: level.trade_value,
+      receive_value: level.receive_value,
+      timestamp: params.timestamp,
+      order_id,
+      state_bump,
+      vault_bump: level.vault_bump,
+      trade_type: trade.trade_type.to_code(),
+      stage: Stage::ReadyExchange.to_code(),
+      partner_root: None,
+      claim_hash: None,
+      market_page: params.market_page,
+      dutch_auction: None,
+      english_auction: None,
+      sealed_auction: None,
+      best_bid: None,
+      bid_count: 0,
+      rfq: None,
+      oracle_peg: None,
+      best_quote: None,
+      trigger: None,
+      payment_options: None,
+    };
+    {
+      let mut data = escrow_state.try_borrow_mut_data()?;
+      let mut writer: &mut [u8] = &mut data;
+      order.try_serialize(&mut writer)?;
+    }
+
+    // list the order on the market of its pair
+    if let (Some(page), Some(market)) = (params.market_page, accounts.get(2)) {
+      add_to_market(
+        market,
+        &order,
+        escrow_state.key(),
+        page,
+        &creator,
+        &system_program,
+        &ctx.accounts.rent,
+      )?;
+    }
+
+    emit!(TradeCreated {
+      escrow_state: escrow_state.key(),
+      creator: creator_key,
+      order_id,
+      trade_type: order.trade_type,
+      creator_send_token_mint: order.creator_send_token_mint,
+      creator_receive_token_mint: order.creator_receive_token_mint,
+      trade_value: order.trade_value,
+      receive_value: order.receive_value,
+      specify_partner: None,
+      fee_account: order.fee_account,
+      timestamp: params.timestamp,
+    });
+  }
+  Ok(())
+}
@@ -0,0 +1,71 @@
+use crate::constant::{STATE_PDA_SEED, VAULT_PDA_SEED};
+use crate::error::EscrowError;
+use crate::event::TradeDeclined;
+use crate::processor::cancel::{refund_and_close_vault, remove_listings};
+
+use crate::state::{EscrowAccount, Stage};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+#[instruction(order_id: u64, state_bump: u8, vault_bump: u8)]
+pub struct Decline<'info> {
+  #[account(
+        mut,
+        has_one=creator,
+        has_one=escrow_vault @ EscrowError::InvalidAccount,
+        has_one=creator_send_account @ EscrowError::InvalidOwner,
+        seeds=[STATE_PDA_SEED, creator.key().as_ref(), order_id.to_le_bytes().as_ref()],
+        bump = state_bump,
+        constraint = escrow_state.is_open() @ EscrowError::InvalidStage,
+        constraint = escrow_state.specify_partner == Some(partner.key()) @ EscrowError::InvalidPartner
+    )]
+  pub escrow_state: Account<'info, EscrowAccount>,
+  /// CHECK: vault refunded to the creator
+  #[account(mut,
+    seeds=[VAULT_PDA_SEED, creator.key().as_ref(), order_id.to_le_bytes().as_ref()],
+    bump = vault_bump
+  )]
+  pub escrow_vault: AccountInfo<'info>,
+  /// CHECK: This account use to receive `Token` (Token can be SOL or SPL Token)
+  #[account(mut)]
+  pub creator_send_account: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, receives the refund and the vault rent
+  #[account(mut)]
+  pub creator: AccountInfo<'info>,
+  // the partner the order is addressed to
+  pub partner: Signer<'info>,
+  // system
+  system_program: Program<'info, System>,
+  token_program: Program<'info, Token>,
+}
+
+pub fn handler_decline(ctx: Context<Decline>) -> Result<()> {
+  refund_and_close_vault(
+    &ctx.accounts.escrow_state,
+    &ctx.accounts.escrow_vault,
+    &ctx.accounts.creator_send_account,
+    &ctx.accounts.creator,
+    ctx.accounts.escrow_state.trade_value,
+    &ctx.accounts.system_program,
+    &ctx.accounts.token_program,
+  )?;
+  ctx.accounts.escrow_state.stage = Stage::Declined.to_code();
+
+  // remaining_accounts: inbox of the partner, then market page when the order is listed
+  remove_listings(
+    &ctx.accounts.escrow_state,
+    &ctx.accounts.escrow_state.key(),
+    &mut ctx.remaining_accounts.iter(),
+  )?;
+
+  emit!(TradeDeclined {
+    escrow_state: ctx.accounts.escrow_state.key(),
+    creator: ctx.accounts.creator.key(),
+    order_id: ctx.accounts.escrow_state.order_id,
+    partner: ctx.accounts.partner.key(),
+    refund_value: ctx.accounts.escrow_state.trade_value,
+  });
+  Ok(())
+}
@@ -1,12 +1,18 @@
 use crate::constant::{ TokenAccountType, STATE_PDA_SEED, VAULT_PDA_SEED };
 use crate::error::EscrowError;
+use crate::event::{ PaymentOptionUsed, TradeExchanged };
+use crate::inbox::remove_from_inbox;
+use crate::market::remove_from_market;
 
 use crate::state::{ EscrowAccount, Stage, TradeType };
 use crate::utils::{
+    claim_hash,
     close_native_account,
     close_token_account,
+    partner_leaf,
     transfer_native_to_account,
     transfer_token_to_account,
+    verify_merkle_proof,
 };
 
 use anchor_lang::prelude::*;
@@ -21,7 +27,10 @@ pub struct Exchange<'info> {
         has_one=escrow_vault,
         seeds=[STATE_PDA_SEED, creator.key().as_ref(), order_id.to_le_bytes().as_ref()],
         bump = state_bump,
-        constraint = escrow_state.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage
+        constraint = escrow_state.stage != Stage::AwaitingTrigger.to_code() @ EscrowError::NotTriggered,
+        constraint = escrow_state.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage,
+        constraint = !escrow_state.is_auction() @ EscrowError::AuctionOrder,
+        constraint = escrow_state.rfq.is_none() @ EscrowError::RfqOrder
     )]
     pub escrow_state: Account<'info, EscrowAccount>,
     /// CHECK: this account use to transfer token to receiverF
@@ -30,9 +39,10 @@ pub struct Exchange<'info> {
     bump = vault_bump
   )]
     pub escrow_vault: AccountInfo<'info>,
-    /// CHECK: This account use to receive `Token` (Token can be SOL or SPL Token)
+    /// CHECK: This account use to receive `Token` (Token can be SOL or SPL Token), the
+    /// receive account of a payment option to pay in its mint
     #[account(mut,
-    constraint = creator_receive_account.key() == escrow_state.creator_receive_account @ EscrowError::InvalidAccount
+    constraint = escrow_state.accepts_payment_to(&creator_receive_account.key()) @ EscrowError::InvalidAccount
   )]
     pub creator_receive_account: AccountInfo<'info>,
     /// CHECK: This account use to send token to creator
@@ -51,31 +61,146 @@ pub struct Exchange<'info> {
     token_program: Program<'info, Token>,
 }
 
-pub fn handler_exchange(ctx: Context<Exchange>) -> Result<()> {
-    // extract in case have specify partner to exchange
-    let has_specify_partner = ctx.accounts.escrow_state.specify_partner;
-    match has_specify_partner {
-        Some(has_specify_partner) => {
-            require_eq!(
-                has_specify_partner,
-                ctx.accounts.partner.key(),
-                EscrowError::InvalidPartner
+// `max_receive_value` caps what the partner pays for an order priced at fill
+// time, a dutch auction or an oracle-pegged order
+pub fn handler_exchange(ctx: Context<Exchange>, proof: Vec<[u8; 32]>, max_receive_value: Option<u64>) -> Result<()> {
+    let partner = ctx.accounts.partner.key();
+    // dutch auctions are filled at their current price and oracle-pegged orders
+    // at the price of their feed, which the order keeps
+    let now = Clock::get()?.unix_timestamp;
+    let mut remaining_accounts = ctx.remaining_accounts.iter();
+    ctx.accounts.escrow_state.receive_value = match ctx.accounts.escrow_state.oracle_peg {
+        Some(peg) => {
+            let oracle = remaining_accounts.next().ok_or(EscrowError::InvalidOracle)?;
+            peg.receive_value(ctx.accounts.escrow_state.trade_value, oracle, now)?
+        }
+        None => ctx.accounts.escrow_state.current_receive_value(now),
+    };
+    check_partner(&ctx.accounts.escrow_state, &partner, &proof)?;
+    // remaining_accounts: oracle of a pegged order, then claim key, inbox and
+    // market page of the order, when it has ones
+    take_order_listings(
+        &ctx.accounts.escrow_state,
+        &ctx.accounts.escrow_state.key(),
+        &mut remaining_accounts
+    )?;
+    // paid into the receive account of a payment option, the order settles in
+    // its mint at its price, once it has left the market of its own pair
+    let paid_to = ctx.accounts.creator_receive_account.key();
+    if paid_to != ctx.accounts.escrow_state.creator_receive_account {
+        let option = ctx.accounts.escrow_state.pay_with(&paid_to)?;
+        emit!(PaymentOptionUsed {
+            escrow_state: ctx.accounts.escrow_state.key(),
+            mint: ctx.accounts.escrow_state.creator_receive_token_mint,
+            receive_value: option.receive_value,
+        });
+    }
+    // the cap applies to the price paid, an option's when paying in one
+    if let Some(max_receive_value) = max_receive_value {
+        require_gte!(
+            max_receive_value,
+            ctx.accounts.escrow_state.receive_value,
+            EscrowError::ReceiveValueExceeded
+        );
+    }
+
+    let settlement = Settlement {
+        escrow_state: &ctx.accounts.escrow_state,
+        escrow_vault: &ctx.accounts.escrow_vault,
+        creator_receive_account: &ctx.accounts.creator_receive_account,
+        partner_send_account: &ctx.accounts.partner_send_account,
+        partner_receive_account: &ctx.accounts.partner_receive_account,
+        creator: &ctx.accounts.creator,
+        partner: &ctx.accounts.partner,
+        system_program: &ctx.accounts.system_program,
+        token_program: &ctx.accounts.token_program,
+    };
+    settle_exchange(&settlement)?;
+    ctx.accounts.escrow_state.specify_partner = Some(partner);
+    ctx.accounts.escrow_state.stage = Stage::Exchanged.to_code();
+
+    emit!(TradeExchanged {
+        escrow_state: ctx.accounts.escrow_state.key(),
+        creator: ctx.accounts.creator.key(),
+        order_id: ctx.accounts.escrow_state.order_id,
+        partner,
+        trade_value: ctx.accounts.escrow_state.trade_value,
+        receive_value: ctx.accounts.escrow_state.receive_value,
+    });
+    Ok(())
+}
+
+// restricted orders accept the specify partner or any partner proven to be in the allowlist
+pub fn check_partner(escrow_state: &EscrowAccount, partner: &Pubkey, proof: &[[u8; 32]]) -> Result<()> {
+    let specify_partner = escrow_state.specify_partner;
+    if specify_partner != Some(*partner) {
+        if let Some(root) = escrow_state.partner_root {
+            require!(
+                verify_merkle_proof(proof, &root, partner_leaf(partner)),
+                EscrowError::PartnerNotAllowed
             );
+        } else if let Some(has_specify_partner) = specify_partner {
+            require_eq!(has_specify_partner, *partner, EscrowError::InvalidPartner);
         }
-        None => {}
     }
+    Ok(())
+}
+
+// check the claim key of an order and drop it from its inbox and market page,
+// taken in that order from `accounts`
+pub fn take_order_listings<'a, 'info: 'a>(
+    escrow_state: &EscrowAccount,
+    order: &Pubkey,
+    accounts: &mut impl Iterator<Item = &'a AccountInfo<'info>>
+) -> Result<()> {
+    // claim-code orders need the claim key as a co-signer, which ties the code
+    // to this partner's transaction so it cannot be copied and front-run
+    if let Some(expected_claim_hash) = escrow_state.claim_hash {
+        let claim_signer = accounts.next().ok_or(EscrowError::InvalidClaimCode)?;
+        require!(
+            claim_signer.is_signer && claim_hash(claim_signer.key) == expected_claim_hash,
+            EscrowError::InvalidClaimCode
+        );
+    }
+    // then the inbox of the specify partner, the order leaves it
+    if let Some(has_specify_partner) = escrow_state.specify_partner {
+        let inbox = accounts.next().ok_or(EscrowError::InvalidInbox)?;
+        remove_from_inbox(inbox, &has_specify_partner, order)?;
+    }
+    // and the market page it is listed on
+    if escrow_state.market_page.is_some() {
+        let market = accounts.next().ok_or(EscrowError::InvalidMarket)?;
+        remove_from_market(market, escrow_state, order)?;
+    }
+    Ok(())
+}
+
+// accounts moving the funds of one exchange, shared by `exchange` and `exchange_many`
+pub struct Settlement<'a, 'info> {
+    pub escrow_state: &'a Account<'info, EscrowAccount>,
+    pub escrow_vault: &'a AccountInfo<'info>,
+    pub creator_receive_account: &'a AccountInfo<'info>,
+    pub partner_send_account: &'a AccountInfo<'info>,
+    pub partner_receive_account: &'a AccountInfo<'info>,
+    pub creator: &'a AccountInfo<'info>,
+    pub partner: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+    pub token_program: &'a AccountInfo<'info>,
+}
 
-    let trade_value = ctx.accounts.escrow_state.trade_value;
-    let receive_value = ctx.accounts.escrow_state.receive_value;
+// vault to the partner, `receive_value` from the partner to the creator, then close the vault
+pub fn settle_exchange(settlement: &Settlement) -> Result<()> {
+    let trade_value = settlement.escrow_state.trade_value;
+    let receive_value = settlement.escrow_state.receive_value;
     //
-    let trade_type = TradeType::from(ctx.accounts.escrow_state.trade_type);
+    let trade_type = TradeType::from(settlement.escrow_state.trade_type);
     match trade_type {
         // Case SPL - SPL
         Ok(TradeType::TokenToken) => {
             // convert `partner_send_account` to TokenAccount
             let partner_token_accounts: (TokenAccountType, TokenAccountType) = (
-                Account::try_from(&ctx.accounts.partner_send_account),
-                Account::try_from(&ctx.accounts.partner_receive_account),
+                Account::try_from(settlement.partner_send_account),
+                Account::try_from(settlement.partner_receive_account),
             );
             match partner_token_accounts {
                 (Ok(partner_send_token_account), Ok(partner_receive_token_account)) => {
@@ -88,7 +213,7 @@ pub fn handler_exchange(ctx: Context<Exchange>) -> Result<()> {
                     );
                     // make sure `partner_send_account` is associated token account with `creator_receive_token_mint`
                     let creator_receive_token_mint =
-                        ctx.accounts.escrow_state.creator_receive_token_mint.unwrap();
+                        settlement.escrow_state.creator_receive_token_mint.unwrap();
                     require_eq!(
                         creator_receive_token_mint,
                         partner_send_token_account.mint,
@@ -96,7 +221,7 @@ pub fn handler_exchange(ctx: Context<Exchange>) -> Result<()> {
                     );
                     // make sure `partner_receive_token_account` is associated token account with `creator_send_token_mint`
                     let creator_send_token_mint =
-                        ctx.accounts.escrow_state.creator_send_token_mint.unwrap();
+                        settlement.escrow_state.creator_send_token_mint.unwrap();
                     require_eq!(
                         creator_send_token_mint,
                         partner_receive_token_account.mint,
@@ -105,13 +230,13 @@ pub fn handler_exchange(ctx: Context<Exchange>) -> Result<()> {
                     //
                     require_eq!(
                         partner_receive_token_account.owner,
-                        ctx.accounts.partner.key(),
+                        settlement.partner.key(),
                         EscrowError::InvalidOwner
                     );
                     //
                     require_eq!(
                         partner_send_token_account.owner,
-                        ctx.accounts.partner.key(),
+                        settlement.partner.key(),
                         EscrowError::InvalidOwner
                     );
                 }
@@ -120,30 +245,30 @@ pub fn handler_exchange(ctx: Context<Exchange>) -> Result<()> {
                 }
             }
             // transfer TOKEN: escrow vault -> partner
-            ctx.accounts.transfer_to_partner_token(trade_value)?;
+            transfer_to_partner_token(settlement, trade_value)?;
             // transfer TOKEN: partner -> Creator
-            ctx.accounts.transfer_to_creator_token(receive_value)?;
+            transfer_to_creator_token(settlement, receive_value)?;
             // close vault token account
-            ctx.accounts.close_vault_token()?;
+            close_vault_token(settlement)?;
         }
 
         // Case SPL - SOL
         Ok(TradeType::TokenSol) => {
             let partner_receive_token_account: TokenAccountType = Account::try_from(
-                &ctx.accounts.partner_receive_account
+                settlement.partner_receive_account
             );
             match partner_receive_token_account {
                 Ok(partner_receive_token_account) => {
                     //
                     require_eq!(
                         partner_receive_token_account.mint,
-                        ctx.accounts.escrow_state.creator_send_token_mint.unwrap(),
+                        settlement.escrow_state.creator_send_token_mint.unwrap(),
                         EscrowError::InvalidAccount
                     );
                     //
                     require_eq!(
                         partner_receive_token_account.owner,
-                        ctx.accounts.partner.key(),
+                        settlement.partner.key(),
                         EscrowError::InvalidOwner
                     );
                 }
@@ -153,27 +278,27 @@ pub fn handler_exchange(ctx: Context<Exchange>) -> Result<()> {
             }
             // sol balance of partner enough for trade
             require_gte!(
-                ctx.accounts.partner.lamports(),
+                settlement.partner.lamports(),
                 receive_value,
                 EscrowError::InsufficientFunds
             );
 
             // transfer TOKEN: escrow vault -> partner
-            ctx.accounts.transfer_to_partner_token(trade_value)?;
+            transfer_to_partner_token(settlement, trade_value)?;
             // transfer SOL: partner -> Creator
-            ctx.accounts.transfer_to_creator_native(receive_value)?;
-            ctx.accounts.close_vault_token()?;
+            transfer_to_creator_native(settlement, receive_value)?;
+            close_vault_token(settlement)?;
         }
         // Case SOL - SPL
         Ok(TradeType::SolToken) => {
             let partner_token_account: TokenAccountType = Account::try_from(
-                &ctx.accounts.partner_send_account
+                settlement.partner_send_account
             );
             match partner_token_account {
                 Ok(partner_token_account) => {
                     // check mint
                     let creator_receive_token_mint =
-                        ctx.accounts.escrow_state.creator_receive_token_mint.unwrap();
+                        settlement.escrow_state.creator_receive_token_mint.unwrap();
                     require_eq!(
                         creator_receive_token_mint,
                         partner_token_account.mint,
@@ -182,7 +307,7 @@ pub fn handler_exchange(ctx: Context<Exchange>) -> Result<()> {
                     // check owner
                     require_eq!(
                         partner_token_account.owner,
-                        ctx.accounts.partner.key(),
+                        settlement.partner.key(),
                         EscrowError::InvalidOwner
                     );
                     // check balance
@@ -198,135 +323,130 @@ pub fn handler_exchange(ctx: Context<Exchange>) -> Result<()> {
             }
             //
             require_eq!(
-                ctx.accounts.creator_receive_account.key(),
-                ctx.accounts.escrow_state.creator_receive_account,
+                settlement.creator_receive_account.key(),
+                settlement.escrow_state.creator_receive_account,
                 EscrowError::InvalidAccount
             );
             // Transfer SOL: Vault -> partner
-            ctx.accounts.transfer_to_partner_native(trade_value)?;
+            transfer_to_partner_native(settlement, trade_value)?;
 
             // Transfer SPL: partner -> creator
-            ctx.accounts.transfer_to_creator_token(receive_value)?;
+            transfer_to_creator_token(settlement, receive_value)?;
 
             // Close SPL Vault
-            ctx.accounts.close_vault_native()?;
+            close_vault_native(settlement)?;
         }
         // error case
         _ => {
             return Err(EscrowError::InvalidTradeType.into());
         }
     }
-    ctx.accounts.escrow_state.specify_partner = Some(ctx.accounts.partner.key());
-    ctx.accounts.escrow_state.stage = Stage::Exchanged.to_code();
+    Ok(())
+}
+fn transfer_to_partner_native<'info>(settlement: &Settlement<'_, 'info>, amount: u64) -> Result<()> {
+    // transfer SOL escrow_vault -> partner
+    let creator_key = settlement.creator.key();
+    let order_id_bytes = settlement.escrow_state.order_id.to_le_bytes();
+    let vault_bump = settlement.escrow_state.vault_bump;
+    let vault_seed = &[
+        &[
+            VAULT_PDA_SEED,
+            creator_key.as_ref(),
+            order_id_bytes.as_ref(),
+            bytemuck::bytes_of(&vault_bump),
+        ][..],
+    ];
+    transfer_native_to_account(
+        settlement.escrow_vault.to_account_info(),
+        settlement.partner.to_account_info(),
+        amount,
+        settlement.system_program.to_account_info(),
+        Some(vault_seed)
+    )?;
     Ok(())
 }
 
-impl<'info> Exchange<'info> {
-    fn transfer_to_partner_native(&self, amount: u64) -> Result<()> {
-        // transfer SOL escrow_vault -> partner
-        let creator_key = self.creator.key();
-        let order_id_bytes = self.escrow_state.order_id.to_le_bytes();
-        let vault_bump = self.escrow_state.vault_bump;
-        let vault_seed = &[
-            &[
-                VAULT_PDA_SEED,
-                creator_key.as_ref(),
-                order_id_bytes.as_ref(),
-                bytemuck::bytes_of(&vault_bump),
-            ][..],
-        ];
-        transfer_native_to_account(
-            self.escrow_vault.to_account_info(),
-            self.partner.to_account_info(),
-            amount,
-            self.system_program.to_account_info(),
-            Some(vault_seed)
-        )?;
-        Ok(())
-    }
-
-    fn transfer_to_partner_token(&self, amount: u64) -> Result<()> {
-        let creator_key = self.creator.key();
-        let order_id_bytes = self.escrow_state.order_id.to_le_bytes();
-        let state_bump = self.escrow_state.state_bump;
-        let state_signers_seeds = &[
-            &[
-                STATE_PDA_SEED,
-                creator_key.as_ref(),
-                order_id_bytes.as_ref(),
-                bytemuck::bytes_of(&state_bump),
-            ][..],
-        ];
-        // transfer token escrow_vault -> partner
-        transfer_token_to_account(
-            self.escrow_vault.to_account_info(),
-            self.partner_receive_account.to_account_info(),
-            self.escrow_state.to_account_info(),
-            amount,
-            self.token_program.to_account_info(),
-            Some(state_signers_seeds)
-        )?;
-        Ok(())
-    }
+fn transfer_to_partner_token<'info>(settlement: &Settlement<'_, 'info>, amount: u64) -> Result<()> {
+    let creator_key = settlement.creator.key();
+    let order_id_bytes = settlement.escrow_state.order_id.to_le_bytes();
+    let state_bump = settlement.escrow_state.state_bump;
+    let state_signers_seeds = &[
+        &[
+            STATE_PDA_SEED,
+            creator_key.as_ref(),
+            order_id_bytes.as_ref(),
+            bytemuck::bytes_of(&state_bump),
+        ][..],
+    ];
+    // transfer token escrow_vault -> partner
+    transfer_token_to_account(
+        settlement.escrow_vault.to_account_info(),
+        settlement.partner_receive_account.to_account_info(),
+        settlement.escrow_state.to_account_info(),
+        amount,
+        settlement.token_program.to_account_info(),
+        Some(state_signers_seeds)
+    )?;
+    Ok(())
+}
 
-    fn transfer_to_creator_native(&self, amount: u64) -> Result<()> {
-        // transfer SOL partner -> creator
-        transfer_native_to_account(
-            self.partner.to_account_info(),
-            self.creator.to_account_info(),
-            amount,
-            self.system_program.to_account_info(),
-            None
-        )?;
-        Ok(())
-    }
+fn transfer_to_creator_native<'info>(settlement: &Settlement<'_, 'info>, amount: u64) -> Result<()> {
+    // transfer SOL partner -> creator
+    transfer_native_to_account(
+        settlement.partner.to_account_info(),
+        settlement.creator.to_account_info(),
+        amount,
+        settlement.system_program.to_account_info(),
+        None
+    )?;
+    Ok(())
+}
 
-    fn transfer_to_creator_token(&self, amount: u64) -> Result<()> {
-        // transfer token partner -> creator
-        transfer_token_to_account(
-            self.partner_send_account.to_account_info(),
-            self.creator_receive_account.to_account_info(),
-            self.partner.to_account_info(),
-            amount,
-            self.token_program.to_account_info(),
-            None
-        )?;
-        Ok(())
-    }
+fn transfer_to_creator_token<'info>(settlement: &Settlement<'_, 'info>, amount: u64) -> Result<()> {
+    // transfer token partner -> creator
+    transfer_token_to_account(
+        settlement.partner_send_account.to_account_info(),
+        settlement.creator_receive_account.to_account_info(),
+        settlement.partner.to_account_info(),
+        amount,
+        settlement.token_program.to_account_info(),
+        None
+    )?;
+    Ok(())
+}
 
-    fn close_vault_token(&self) -> Result<()> {
-        let creator = self.creator.key();
-        let state_bump = self.escrow_state.state_bump;
-        let order_id = self.escrow_state.order_id.to_le_bytes();
-        let signers_seeds = &[
-            &[STATE_PDA_SEED, creator.as_ref(), order_id.as_ref(), bytemuck::bytes_of(&state_bump)]
-                [..],
-        ];
+fn close_vault_token<'info>(settlement: &Settlement<'_, 'info>) -> Result<()> {
+    let creator = settlement.creator.key();
+    let state_bump = settlement.escrow_state.state_bump;
+    let order_id = settlement.escrow_state.order_id.to_le_bytes();
+    let signers_seeds = &[
+        &[STATE_PDA_SEED, creator.as_ref(), order_id.as_ref(), bytemuck::bytes_of(&state_bump)]
+            [..],
+    ];
 
-        close_token_account(
-            self.escrow_vault.to_account_info(),
-            self.creator.to_account_info(),
-            self.escrow_state.to_account_info(),
-            signers_seeds,
-            self.token_program.to_account_info()
-        )?;
-        Ok(())
-    }
+    close_token_account(
+        settlement.escrow_vault.to_account_info(),
+        settlement.creator.to_account_info(),
+        settlement.escrow_state.to_account_info(),
+        signers_seeds,
+        settlement.token_program.to_account_info()
+    )?;
+    Ok(())
+}
 
-    fn close_vault_native(&self) -> Result<()> {
-        let creator = self.creator.key();
-        let vault_bump = self.escrow_state.vault_bump;
-        let order_id = self.escrow_state.order_id.to_le_bytes();
-        let vault_signers_seeds = &[
-            &[VAULT_PDA_SEED, creator.as_ref(), order_id.as_ref(), bytemuck::bytes_of(&vault_bump)]
-                [..],
-        ];
-        close_native_account(
-            self.escrow_vault.to_account_info(),
-            self.creator.to_account_info(),
-            vault_signers_seeds,
-            self.system_program.to_account_info()
-        )?;
-        Ok(())
-    }
-}
\ No newline at end of file
+fn close_vault_native<'info>(settlement: &Settlement<'_, 'info>) -> Result<()> {
+    let creator = settlement.creator.key();
+    let vault_bump = settlement.escrow_state.vault_bump;
+    let order_id = settlement.escrow_state.order_id.to_le_bytes();
+    let vault_signers_seeds = &[
+        &[VAULT_PDA_SEED, creator.as_ref(), order_id.as_ref(), bytemuck::bytes_of(&vault_bump)]
+            [..],
+    ];
+    close_native_account(
+        settlement.escrow_vault.to_account_info(),
+        settlement.creator.to_account_info(),
+        vault_signers_seeds,
+        settlement.system_program.to_account_info()
+    )?;
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use crate::error::EscrowError;
+use crate::event::TradeExchanged;
+use crate::processor::exchange::{check_partner, settle_exchange, take_order_listings, Settlement};
+
+use crate::state::{EscrowAccount, Stage};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct ExchangeMany<'info> {
+  /// CHECK: This account use to send token to the creators
+  #[account(mut)]
+  pub partner_send_account: AccountInfo<'info>,
+  /// CHECK: This account use to receive token from the escrow vaults
+  #[account(mut)]
+  pub partner_receive_account: AccountInfo<'info>,
+  #[account(mut, constraint = partner.lamports() > 0 && partner.data_is_empty())]
+  pub partner: Signer<'info>,
+  // system
+  system_program: Program<'info, System>,
+  token_program: Program<'info, Token>,
+}
+
+// highest average price the partner accepts: `receive_value` paid for each
+// `trade_value` received
+#[derive(AnchorSerialize, AnchorDeserialize, Eq, PartialEq, Clone, Copy, Debug)]
+pub struct PriceCap {
+  pub receive_value: u64,
+  pub trade_value: u64,
+}
+
+impl PriceCap {
+  fn allows(&self, paid: u64, received: u64) -> bool {
+    paid as u128 * self.trade_value as u128 <= received as u128 * self.receive_value as u128
+  }
+}
+
+// remaining_accounts: for each order, escrow_state, escrow_vault, creator,
+// creator_receive_account, then its inbox and market page when it has ones
+// (see `exchange`). Every order must be on the same pair.
+// `atomic` fills every order or fails, otherwise orders that are no longer open,
+// restricted to other partners, over `budget` or over `max_price` are skipped.
+pub fn handler_exchange_many<'info>(
+  ctx: Context<'_, '_, '_, 'info, ExchangeMany<'info>>,
+  budget: u64,
+  max_price: Option<PriceCap>,
+  atomic: bool,
+) -> Result<()> {
+  let partner = ctx.accounts.partner.key();
+  let now = Clock::get()?.unix_timestamp;
+  let mut pair = None;
+  let (mut paid, mut received) = (0u64, 0u64);
+  let mut accounts = ctx.remaining_accounts.iter();
+  while let Some(state_info) = accounts.next() {
+    // a malformed group cannot be skipped, the next groups would be misread
+    let mut escrow_state: Account<EscrowAccount> = Account::try_from(state_info)?;
+    // dutch auctions are filled at their current price, see `exchange`
+    escrow_state.receive_value = escrow_state.current_receive_value(now);
+    let escrow_vault = accounts.next().ok_or(EscrowError::MissingParams)?;
+    let creator = accounts.next().ok_or(EscrowError::MissingParams)?;
+    let creator_receive_account = accounts.next().ok_or(EscrowError::MissingParams)?;
+    let listing_count =
+      escrow_state.specify_partner.is_some() as usize + escrow_state.market_page.is_some() as usize;
+    let listings = accounts.by_ref().take(listing_count).collect::<Vec<_>>();
+
+    let order_pair = (
+      escrow_state.creator_send_token_mint,
+      escrow_state.creator_receive_token_mint,
+    );
+    require!(
+      *pair.get_or_insert(order_pair) == order_pair,
+      EscrowError::InvalidMint
+    );
+    let total_paid = paid.checked_add(escrow_state.receive_value);
+    let total_received = received.checked_add(escrow_state.trade_value);
+    let checked = check_fill(&escrow_state, escrow_vault, creator, creator_receive_account, &partner)
+      .and_then(|_| {
+        let total_paid = total_paid
+          .filter(|total_paid| *total_paid <= budget)
+          .ok_or(EscrowError::BudgetExceeded)?;
+        // fill-or-kill only caps the average of the whole fill, checked below
+        if let (Some(max_price), false) = (max_price, atomic) {
+          require!(
+            max_price.allows(total_paid, total_received.unwrap_or(u64::MAX)),
+            EscrowError::PriceCapExceeded
+          );
+        }
+        take_order_listings(&escrow_state, state_info.key, &mut listings.into_iter())
+      });
+    if let Err(err) = checked {
+      if atomic {
+        return Err(err);
+      }
+      msg!("skip order {}: {}", state_info.key, err);
+      continue;
+    }
+
+    settle_exchange(&Settlement {
+      escrow_state: &escrow_state,
+      escrow_vault,
+      creator_receive_account,
+      partner_send_account: &ctx.accounts.partner_send_account,
+      partner_receive_account: &ctx.accounts.partner_receive_account,
+      creator,
+      partner: &ctx.accounts.partner,
+      system_program: &ctx.accounts.system_program,
+      token_program: &ctx.accounts.token_program,
+    })?;
+    escrow_state.specify_partner = Some(partner);
+    escrow_state.stage = Stage::Exchanged.to_code();
+    escrow_state.exit(&crate::ID)?;
+    paid = total_paid.ok_or(EscrowError::BudgetExceeded)?;
+    received = total_received.ok_or(EscrowError::BudgetExceeded)?;
+
+    emit!(TradeExchanged {
+      escrow_state: state_info.key(),
+      creator: creator.key(),
+      order_id: escrow_state.order_id,
+      partner,
+      trade_value: escrow_state.trade_value,
+      receive_value: escrow_state.receive_value,
+    });
+  }
+  require_gt!(received, 0, EscrowError::NothingFilled);
+  if let Some(max_price) = max_price {
+    require!(max_price.allows(paid, received), EscrowError::PriceCapExceeded);
+  }
+  Ok(())
+}
+
+// the account constraints of `Exchange`. The state is an `EscrowAccount` owned by
+// the program, so it sits at its PDA and its vault is the one it stores.
+fn check_fill(
+  escrow_state: &EscrowAccount,
+  escrow_vault: &AccountInfo,
+  creator: &AccountInfo,
+  creator_receive_account: &AccountInfo,
+  partner: &Pubkey,
+) -> Result<()> {
+  require_keys_eq!(escrow_state.creator, creator.key(), EscrowError::InvalidOwner);
+  require_keys_eq!(escrow_state.escrow_vault, escrow_vault.key(), EscrowError::InvalidAccount);
+  require_keys_eq!(
+    escrow_state.creator_receive_account,
+    creator_receive_account.key(),
+    EscrowError::InvalidAccount
+  );
+  require!(
+    escrow_state.stage == Stage::ReadyExchange.to_code(),
+    EscrowError::InvalidStage
+  );
+  require!(!escrow_state.is_auction(), EscrowError::AuctionOrder);
+  require!(escrow_state.rfq.is_none(), EscrowError::RfqOrder);
+  require!(escrow_state.oracle_peg.is_none(), EscrowError::OraclePegOrder);
+  // no claim key is taken either, claim-code orders are filled with `exchange`
+  require!(escrow_state.claim_hash.is_none(), EscrowError::InvalidClaimCode);
+  // no allowlist proof is taken here, allowlisted orders are only open to their specify partner
+  check_partner(escrow_state, partner, &[])
+}
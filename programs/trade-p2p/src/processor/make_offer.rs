@@ -0,0 +1,191 @@
+use crate::constant::{OFFER_PDA_SEED, OFFER_VAULT_PDA_SEED};
+use crate::error::EscrowError;
+use crate::event::OfferMade;
+use crate::processor::create::{check_trade, fund_vault, VaultFunding};
+use crate::state::{EscrowAccount, OfferAccount, Stage};
+use crate::utils::{close_native_account, close_token_account, transfer_native_to_account, transfer_token_to_account};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+#[instruction(value: u64, vault_bump: u8)]
+pub struct MakeOffer<'info> {
+  #[account(
+    mut,
+    constraint = escrow_state.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage,
+    constraint = !escrow_state.is_auction() @ EscrowError::AuctionOrder
+  )]
+  pub escrow_state: Account<'info, EscrowAccount>,
+  #[account(
+    init,
+    payer = partner,
+    seeds = [OFFER_PDA_SEED, escrow_state.key().as_ref(), partner.key().as_ref()],
+    bump,
+    space = OfferAccount::LEN
+  )]
+  pub offer: Account<'info, OfferAccount>,
+  /// CHECK: vault of the offer, created by the instruction
+  #[account(mut, seeds = [OFFER_VAULT_PDA_SEED, offer.key().as_ref()], bump = vault_bump)]
+  pub offer_vault: AccountInfo<'info>,
+  #[account(mut, constraint = partner.lamports() > 0 && partner.data_is_empty())]
+  pub partner: Signer<'info>,
+  /// CHECK: pays the offer and is refunded when it is withdrawn (Token can be SOL or SPL Token)
+  #[account(mut)]
+  pub partner_send_account: AccountInfo<'info>,
+  /// CHECK: receives the order's vault when the offer is accepted (Token can be SOL or SPL Token)
+  #[account(mut)]
+  pub partner_receive_account: AccountInfo<'info>,
+  pub system_program: Program<'info, System>,
+  pub token_program: Program<'info, Token>,
+  pub rent: Sysvar<'info, Rent>,
+}
+
+// offer `value` of the partner's own mint for an order, whatever its receive mint
+// and value. Anyone can make one, the creator picks which to accept. On a request
+// for quote this is a quote, made before its deadline in an approved mint.
+// remaining_accounts: the mints of the partner's side as in `create_trade` (the
+// offer's mint, then the order's send mint), then the best quote of a
+// best-price request for quote when there is one
+pub fn handler_make_offer<'info>(
+  ctx: Context<'_, '_, '_, 'info, MakeOffer<'info>>,
+  value: u64,
+  vault_bump: u8,
+) -> Result<()> {
+  require_gt!(value, 0, EscrowError::ZeroValue);
+  let escrow_state = &ctx.accounts.escrow_state;
+  let best_quote_count = escrow_state.best_quote.is_some() as usize;
+  let mint_count = ctx
+    .remaining_accounts
+    .len()
+    .checked_sub(best_quote_count)
+    .ok_or(EscrowError::MissingParams)?;
+  let (mint_accounts, best_quote_accounts) = ctx.remaining_accounts.split_at(mint_count);
+  let trade = check_trade(
+    &ctx.accounts.partner,
+    &ctx.accounts.partner_send_account,
+    &ctx.accounts.partner_receive_account,
+    mint_accounts,
+    value,
+  )?;
+  require!(
+    trade.receive_mint() == escrow_state.creator_send_token_mint,
+    EscrowError::InvalidOffer
+  );
+  if let Some(rfq) = &escrow_state.rfq {
+    require_gt!(rfq.deadline, Clock::get()?.unix_timestamp, EscrowError::QuotingClosed);
+    require!(
+      rfq.approves(escrow_state.creator_receive_token_mint, trade.send_mint()),
+      EscrowError::MintNotApproved
+    );
+    // a best-price request for quote keeps its highest quote, ties going to the first
+    if rfq.best_price {
+      let best_value = match escrow_state.best_quote {
+        Some(best_quote) => {
+          let best: Account<OfferAccount> = Account::try_from(&best_quote_accounts[0])?;
+          require_keys_eq!(best.key(), best_quote, EscrowError::InvalidAccount);
+          Some(best.value)
+        }
+        None => None,
+      };
+      if best_value.is_none_or(|best_value| value > best_value) {
+        ctx.accounts.escrow_state.best_quote = Some(ctx.accounts.offer.key());
+      }
+    }
+  }
+
+  let partner = ctx.accounts.partner.to_account_info();
+  let system_program = ctx.accounts.system_program.to_account_info();
+  let token_program = ctx.accounts.token_program.to_account_info();
+  let offer_key = ctx.accounts.offer.key();
+  let vault_seeds = &[&[OFFER_VAULT_PDA_SEED, offer_key.as_ref(), bytemuck::bytes_of(&vault_bump)][..]];
+  let funding = VaultFunding {
+    creator: &partner,
+    creator_send_account: &ctx.accounts.partner_send_account,
+    system_program: &system_program,
+    token_program: &token_program,
+    rent: &ctx.accounts.rent,
+  };
+  fund_vault(
+    &trade,
+    &funding,
+    &ctx.accounts.offer.to_account_info(),
+    &ctx.accounts.offer_vault,
+    vault_seeds,
+    value,
+  )?;
+
+  let offer = &mut ctx.accounts.offer;
+  offer.escrow_state = ctx.accounts.escrow_state.key();
+  offer.partner = partner.key();
+  offer.partner_send_account = ctx.accounts.partner_send_account.key();
+  offer.partner_receive_account = ctx.accounts.partner_receive_account.key();
+  offer.offer_vault = ctx.accounts.offer_vault.key();
+  offer.offer_mint = trade.send_mint();
+  offer.value = value;
+  offer.bump = *ctx.bumps.get("offer").unwrap();
+  offer.vault_bump = vault_bump;
+
+  emit!(OfferMade {
+    escrow_state: offer.escrow_state,
+    offer: offer_key,
+    partner: offer.partner,
+    mint: offer.offer_mint,
+    value,
+  });
+  Ok(())
+}
+
+// pay the whole offer from its vault to `destination`, then close the vault, its
+// rent going back to the partner
+pub fn release_offer<'info>(
+  offer: &Account<'info, OfferAccount>,
+  offer_vault: &AccountInfo<'info>,
+  destination: &AccountInfo<'info>,
+  partner: &AccountInfo<'info>,
+  system_program: &AccountInfo<'info>,
+  token_program: &AccountInfo<'info>,
+) -> Result<()> {
+  match offer.offer_mint {
+    None => {
+      let offer_key = offer.key();
+      let vault_seeds = &[&[OFFER_VAULT_PDA_SEED, offer_key.as_ref(), bytemuck::bytes_of(&offer.vault_bump)][..]];
+      transfer_native_to_account(
+        offer_vault.to_account_info(),
+        destination.to_account_info(),
+        offer.value,
+        system_program.to_account_info(),
+        Some(vault_seeds),
+      )?;
+      close_native_account(
+        offer_vault.to_account_info(),
+        partner.to_account_info(),
+        vault_seeds,
+        system_program.to_account_info(),
+      )
+    }
+    Some(_) => {
+      let offer_seeds = &[&[
+        OFFER_PDA_SEED,
+        offer.escrow_state.as_ref(),
+        offer.partner.as_ref(),
+        bytemuck::bytes_of(&offer.bump),
+      ][..]];
+      transfer_token_to_account(
+        offer_vault.to_account_info(),
+        destination.to_account_info(),
+        offer.to_account_info(),
+        offer.value,
+        token_program.to_account_info(),
+        Some(offer_seeds),
+      )?;
+      close_token_account(
+        offer_vault.to_account_info(),
+        partner.to_account_info(),
+        offer.to_account_info(),
+        offer_seeds,
+        token_program.to_account_info(),
+      )
+    }
+  }
+}
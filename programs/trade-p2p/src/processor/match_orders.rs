@@ -0,0 +1,179 @@
+use crate::error::EscrowError;
+use crate::event::{OrdersMatched, TradeExchanged};
+use crate::processor::cancel::{close_vault, remove_listings, withdraw_from_vault};
+
+use crate::state::{EscrowAccount, Stage};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct MatchOrders<'info> {
+  #[account(
+    mut,
+    constraint = escrow_state_a.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage
+  )]
+  pub escrow_state_a: Account<'info, EscrowAccount>,
+  /// CHECK: vault stored in `escrow_state_a`
+  #[account(mut, constraint = escrow_vault_a.key() == escrow_state_a.escrow_vault @ EscrowError::InvalidAccount)]
+  pub escrow_vault_a: AccountInfo<'info>,
+  /// CHECK: creator of `escrow_state_a`, receives the vault rent
+  #[account(mut, constraint = creator_a.key() == escrow_state_a.creator @ EscrowError::InvalidOwner)]
+  pub creator_a: AccountInfo<'info>,
+  /// CHECK: account `escrow_state_a` receives into, the creator wallet for SOL
+  #[account(mut,
+    constraint = creator_a_receive_account.key() == escrow_state_a.creator_payout() @ EscrowError::InvalidAccount
+  )]
+  pub creator_a_receive_account: AccountInfo<'info>,
+  #[account(
+    mut,
+    constraint = escrow_state_b.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage
+  )]
+  pub escrow_state_b: Account<'info, EscrowAccount>,
+  /// CHECK: vault stored in `escrow_state_b`
+  #[account(mut, constraint = escrow_vault_b.key() == escrow_state_b.escrow_vault @ EscrowError::InvalidAccount)]
+  pub escrow_vault_b: AccountInfo<'info>,
+  /// CHECK: creator of `escrow_state_b`, receives the vault rent
+  #[account(mut, constraint = creator_b.key() == escrow_state_b.creator @ EscrowError::InvalidOwner)]
+  pub creator_b: AccountInfo<'info>,
+  /// CHECK: account `escrow_state_b` receives into, the creator wallet for SOL
+  #[account(mut,
+    constraint = creator_b_receive_account.key() == escrow_state_b.creator_payout() @ EscrowError::InvalidAccount
+  )]
+  pub creator_b_receive_account: AccountInfo<'info>,
+  pub matcher: Signer<'info>,
+  /// CHECK: receives the fee in the asset `escrow_state_a` sends, a token account or a wallet for SOL
+  #[account(mut)]
+  pub matcher_a_account: AccountInfo<'info>,
+  /// CHECK: receives the fee in the asset `escrow_state_b` sends, a token account or a wallet for SOL
+  #[account(mut)]
+  pub matcher_b_account: AccountInfo<'info>,
+  // system
+  system_program: Program<'info, System>,
+  token_program: Program<'info, Token>,
+}
+
+// settle two orders of opposite sides of a pair against each other. Each creator
+// gets exactly their `receive_value` from the other vault, what is left in the
+// vaults goes to the matcher, then both vaults are closed.
+// remaining_accounts: market page of order a, then of order b, when they are listed
+pub fn handler_match_orders(ctx: Context<MatchOrders>) -> Result<()> {
+  // dutch auctions are matched at their current price, see `exchange`
+  let now = Clock::get()?.unix_timestamp;
+  for order in [&mut ctx.accounts.escrow_state_a, &mut ctx.accounts.escrow_state_b] {
+    order.receive_value = order.current_receive_value(now);
+  }
+  let (order_a, order_b) = (&ctx.accounts.escrow_state_a, &ctx.accounts.escrow_state_b);
+  require_keys_neq!(order_a.key(), order_b.key(), EscrowError::InvalidAccount);
+  // restricted orders are only for the partner they name
+  for order in [order_a, order_b] {
+    require!(
+      order.specify_partner.is_none() && order.partner_root.is_none() && order.claim_hash.is_none(),
+      EscrowError::InvalidPartner
+    );
+    require!(!order.is_auction(), EscrowError::AuctionOrder);
+    require!(order.rfq.is_none(), EscrowError::RfqOrder);
+    require!(order.oracle_peg.is_none(), EscrowError::OraclePegOrder);
+  }
+  require!(
+    order_a.creator_send_token_mint == order_b.creator_receive_token_mint
+      && order_a.creator_receive_token_mint == order_b.creator_send_token_mint,
+    EscrowError::InvalidMint
+  );
+  require_gte!(order_a.trade_value, order_b.receive_value, EscrowError::OrdersDoNotCross);
+  require_gte!(order_b.trade_value, order_a.receive_value, EscrowError::OrdersDoNotCross);
+  let fee_a = order_a.trade_value - order_b.receive_value;
+  let fee_b = order_b.trade_value - order_a.receive_value;
+
+  let system_program = ctx.accounts.system_program.to_account_info();
+  let token_program = ctx.accounts.token_program.to_account_info();
+  // vault a -> creator b, the rest to the matcher
+  withdraw_from_vault(
+    order_a,
+    &ctx.accounts.escrow_vault_a,
+    &ctx.accounts.creator_b_receive_account,
+    &ctx.accounts.creator_a,
+    order_b.receive_value,
+    &system_program,
+    &token_program,
+  )?;
+  if fee_a > 0 {
+    withdraw_from_vault(
+      order_a,
+      &ctx.accounts.escrow_vault_a,
+      &ctx.accounts.matcher_a_account,
+      &ctx.accounts.creator_a,
+      fee_a,
+      &system_program,
+      &token_program,
+    )?;
+  }
+  // vault b -> creator a, the rest to the matcher
+  withdraw_from_vault(
+    order_b,
+    &ctx.accounts.escrow_vault_b,
+    &ctx.accounts.creator_a_receive_account,
+    &ctx.accounts.creator_b,
+    order_a.receive_value,
+    &system_program,
+    &token_program,
+  )?;
+  if fee_b > 0 {
+    withdraw_from_vault(
+      order_b,
+      &ctx.accounts.escrow_vault_b,
+      &ctx.accounts.matcher_b_account,
+      &ctx.accounts.creator_b,
+      fee_b,
+      &system_program,
+      &token_program,
+    )?;
+  }
+  close_vault(
+    order_a,
+    &ctx.accounts.escrow_vault_a,
+    &ctx.accounts.creator_a,
+    &system_program,
+    &token_program,
+  )?;
+  close_vault(
+    order_b,
+    &ctx.accounts.escrow_vault_b,
+    &ctx.accounts.creator_b,
+    &system_program,
+    &token_program,
+  )?;
+
+  let mut remaining_accounts = ctx.remaining_accounts.iter();
+  remove_listings(order_a, &order_a.key(), &mut remaining_accounts)?;
+  remove_listings(order_b, &order_b.key(), &mut remaining_accounts)?;
+
+  // each order was taken by the creator of the other
+  let (creator_a, creator_b) = (order_a.creator, order_b.creator);
+  ctx.accounts.escrow_state_a.specify_partner = Some(creator_b);
+  ctx.accounts.escrow_state_a.stage = Stage::Exchanged.to_code();
+  ctx.accounts.escrow_state_b.specify_partner = Some(creator_a);
+  ctx.accounts.escrow_state_b.stage = Stage::Exchanged.to_code();
+
+  for (order, partner) in [
+    (&ctx.accounts.escrow_state_a, creator_b),
+    (&ctx.accounts.escrow_state_b, creator_a),
+  ] {
+    emit!(TradeExchanged {
+      escrow_state: order.key(),
+      creator: order.creator,
+      order_id: order.order_id,
+      partner,
+      trade_value: order.trade_value,
+      receive_value: order.receive_value,
+    });
+  }
+  emit!(OrdersMatched {
+    escrow_state_a: ctx.accounts.escrow_state_a.key(),
+    escrow_state_b: ctx.accounts.escrow_state_b.key(),
+    matcher: ctx.accounts.matcher.key(),
+    fee_a,
+    fee_b,
+  });
+  Ok(())
+}
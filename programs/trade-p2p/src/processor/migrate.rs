@@ -0,0 +1,67 @@
+use crate::constant::USER_PDA_SEED;
+use crate::error::EscrowError;
+use crate::state::{EscrowAccount, UserAccount};
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program::invoke, system_instruction};
+use anchor_lang::Discriminator;
+
+#[derive(Accounts)]
+pub struct MigrateEscrow<'info> {
+  /// CHECK: a deal too short to load as `EscrowAccount`, checked by the handler
+  #[account(mut, owner = crate::ID @ EscrowError::InvalidAccount)]
+  pub escrow_state: AccountInfo<'info>,
+  /// CHECK: creator of the deal, checked by the handler
+  pub creator: AccountInfo<'info>,
+  // order id counter of the creator, moved past the id of the deal
+  #[account(
+    init_if_needed,
+    payer = payer,
+    seeds = [USER_PDA_SEED, creator.key().as_ref()],
+    bump,
+    space = UserAccount::LEN
+  )]
+  pub user_account: Account<'info, UserAccount>,
+  #[account(mut)]
+  pub payer: Signer<'info>,
+  pub system_program: Program<'info, System>,
+}
+
+// grow a deal of the first release (`EscrowAccount::FIRST_RELEASE_LEN`) to
+// `EscrowAccount::LEN`, the payer topping up its rent. The fields added since
+// are appended and zeroed, so they read as `None`. First-release clients chose
+// their order ids, so the creator's counter is moved past the deal's id for
+// new orders not to collide with it. Anyone can migrate a deal
+pub fn handler_migrate_escrow(ctx: Context<MigrateEscrow>) -> Result<()> {
+  let escrow_state = &ctx.accounts.escrow_state;
+  {
+    let data = escrow_state.try_borrow_data()?;
+    require!(data.starts_with(&EscrowAccount::discriminator()), EscrowError::InvalidAccount);
+    require_gt!(EscrowAccount::LEN, data.len(), EscrowError::AlreadyMigrated);
+  }
+  let top_up = Rent::get()?
+    .minimum_balance(EscrowAccount::LEN)
+    .saturating_sub(escrow_state.lamports());
+  if top_up > 0 {
+    invoke(
+      &system_instruction::transfer(ctx.accounts.payer.key, escrow_state.key, top_up),
+      &[
+        ctx.accounts.payer.to_account_info(),
+        escrow_state.clone(),
+        ctx.accounts.system_program.to_account_info(),
+      ],
+    )?;
+  }
+  escrow_state.realloc(EscrowAccount::LEN, true)?;
+  // the fields of the first release still decode
+  let escrow = EscrowAccount::try_deserialize(&mut &escrow_state.try_borrow_data()?[..])?;
+  require_keys_eq!(escrow.creator, ctx.accounts.creator.key(), EscrowError::InvalidAccount);
+
+  let user_account = &mut ctx.accounts.user_account;
+  if user_account.creator == Pubkey::default() {
+    user_account.creator = escrow.creator;
+    user_account.bump = *ctx.bumps.get("user_account").unwrap();
+  }
+  user_account.next_order_id = user_account.next_order_id.max(escrow.order_id.saturating_add(1));
+  Ok(())
+}
@@ -1,7 +1,41 @@
+pub mod accept_offer;
 pub mod cancel;
+pub mod cancel_many;
+pub mod commit_bid;
 pub mod create;
+pub mod create_many;
+pub mod decline;
 pub mod exchange;
+pub mod exchange_many;
+pub mod make_offer;
+pub mod match_orders;
+pub mod migrate;
+pub mod place_bid;
+pub mod reclaim_bid;
+pub mod replace;
+pub mod reveal_bid;
+pub mod settle_auction;
+pub mod settle_rfq;
+pub mod trigger;
+pub mod withdraw_offer;
 
+pub use accept_offer::*;
 pub use cancel::*;
+pub use cancel_many::*;
+pub use commit_bid::*;
 pub use create::*;
+pub use create_many::*;
+pub use decline::*;
 pub use exchange::*;
+pub use exchange_many::*;
+pub use make_offer::*;
+pub use match_orders::*;
+pub use migrate::*;
+pub use place_bid::*;
+pub use reclaim_bid::*;
+pub use replace::*;
+pub use reveal_bid::*;
+pub use settle_auction::*;
+pub use settle_rfq::*;
+pub use trigger::*;
+pub use withdraw_offer::*;
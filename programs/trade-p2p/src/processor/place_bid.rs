@@ -0,0 +1,316 @@
+use crate::constant::{BID_PDA_SEED, BID_VAULT_PDA_SEED};
+use crate::error::EscrowError;
+use crate::event::BidPlaced;
+use crate::processor::create::{check_trade, fund_vault, VaultFunding};
+use crate::state::{BidAccount, EscrowAccount, Stage};
+use crate::utils::{close_native_account, close_token_account, transfer_native_to_account, transfer_token_to_account};
+
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::Token;
+
+// also the accounts of `commit_bid`, whose arguments start like these
+#[derive(Accounts)]
+#[instruction(value: u64, vault_bump: u8)]
+pub struct PlaceBid<'info> {
+  #[account(
+    mut,
+    constraint = escrow_state.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage
+  )]
+  pub escrow_state: Account<'info, EscrowAccount>,
+  #[account(
+    init,
+    payer = bidder,
+    seeds = [BID_PDA_SEED, escrow_state.key().as_ref(), escrow_state.bid_count.to_le_bytes().as_ref()],
+    bump,
+    space = BidAccount::LEN
+  )]
+  pub bid: Account<'info, BidAccount>,
+  /// CHECK: vault of the bid, created by the instruction
+  #[account(mut, seeds = [BID_VAULT_PDA_SEED, bid.key().as_ref()], bump = vault_bump)]
+  pub bid_vault: AccountInfo<'info>,
+  #[account(mut, constraint = bidder.lamports() > 0 && bidder.data_is_empty())]
+  pub bidder: Signer<'info>,
+  /// CHECK: pays the bid and is refunded when outbid (Token can be SOL or SPL Token)
+  #[account(mut)]
+  pub bidder_send_account: AccountInfo<'info>,
+  /// CHECK: receives the order's vault when the bid wins (Token can be SOL or SPL Token)
+  #[account(mut)]
+  pub bidder_receive_account: AccountInfo<'info>,
+  pub system_program: Program<'info, System>,
+  pub token_program: Program<'info, Token>,
+  pub rent: Sysvar<'info, Rent>,
+}
+
+// bid `value` on an english auction order. The bid must reach the reserve price,
+// or the best bid plus the minimum increment, and the best bid is refunded.
+// remaining_accounts: the mints of the bidder's side as in `create_trade` (the
+// order's receive mint, then its send mint), then the best bid, its bid vault,
+// bidder and bidder_send_account when there is one
+pub fn handler_place_bid<'info>(
+  ctx: Context<'_, '_, '_, 'info, PlaceBid<'info>>,
+  value: u64,
+  vault_bump: u8,
+) -> Result<()> {
+  let escrow_state = &ctx.accounts.escrow_state;
+  let auction = escrow_state.english_auction.ok_or(EscrowError::NotAnAuction)?;
+  require_gt!(auction.end_time, Clock::get()?.unix_timestamp, EscrowError::AuctionEnded);
+
+  let outbid_count = 4 * escrow_state.best_bid.is_some() as usize;
+  let mint_count = ctx
+    .remaining_accounts
+    .len()
+    .checked_sub(outbid_count)
+    .ok_or(EscrowError::MissingParams)?;
+  let (mint_accounts, outbid_accounts) = ctx.remaining_accounts.split_at(mint_count);
+  let min_value = match escrow_state.best_bid {
+    Some(_) => {
+      let outbid: Account<BidAccount> = Account::try_from(&outbid_accounts[0])?;
+      outbid.value.checked_add(auction.min_increment).ok_or(EscrowError::BidTooLow)?
+    }
+    None => escrow_state.receive_value,
+  };
+  require_gte!(value, min_value, EscrowError::BidTooLow);
+
+  open_bid(ctx.accounts, mint_accounts, value, *ctx.bumps.get("bid").unwrap(), vault_bump)?;
+  ctx.accounts.bid.value = value;
+
+  // refund the bid this one beats
+  let outbid = ctx.accounts.escrow_state.best_bid;
+  if let Some(outbid) = outbid {
+    refund_best_bid(
+      &ctx.accounts.escrow_state,
+      &outbid,
+      outbid_accounts,
+      &ctx.accounts.system_program,
+      &ctx.accounts.token_program,
+    )?;
+  }
+  ctx.accounts.escrow_state.best_bid = Some(ctx.accounts.bid.key());
+
+  emit!(BidPlaced {
+    escrow_state: ctx.accounts.escrow_state.key(),
+    bid: ctx.accounts.bid.key(),
+    bidder: ctx.accounts.bidder.key(),
+    bid_index: ctx.accounts.bid.bid_index,
+    value,
+    outbid,
+  });
+  Ok(())
+}
+
+// check the bidder's accounts, lock `deposit` in the bid vault, then fill the new
+// bid and count it on the order. The bid's value is left for the caller to set
+pub fn open_bid<'info>(
+  accounts: &mut PlaceBid<'info>,
+  mint_accounts: &[AccountInfo<'info>],
+  deposit: u64,
+  bump: u8,
+  vault_bump: u8,
+) -> Result<()> {
+  let escrow_state = &accounts.escrow_state;
+  // the bidder pays what the creator receives. Bids are refunded to and won into
+  // the bidder's wallet or associated token accounts, which anyone can recreate,
+  // so closing them cannot block higher bids or the settlement
+  let bidder_key = accounts.bidder.key();
+  let side_account = |mint: &Option<Pubkey>| match mint {
+    Some(mint) => get_associated_token_address(&bidder_key, mint),
+    None => bidder_key,
+  };
+  require_keys_eq!(
+    accounts.bidder_send_account.key(),
+    side_account(&escrow_state.creator_receive_token_mint),
+    EscrowError::InvalidAccount
+  );
+  require_keys_eq!(
+    accounts.bidder_receive_account.key(),
+    side_account(&escrow_state.creator_send_token_mint),
+    EscrowError::InvalidAccount
+  );
+  let trade = check_trade(
+    &accounts.bidder,
+    &accounts.bidder_send_account,
+    &accounts.bidder_receive_account,
+    mint_accounts,
+    deposit,
+  )?;
+  require!(
+    trade.send_mint() == escrow_state.creator_receive_token_mint
+      && trade.receive_mint() == escrow_state.creator_send_token_mint,
+    EscrowError::InvalidMint
+  );
+
+  let bidder = accounts.bidder.to_account_info();
+  let system_program = accounts.system_program.to_account_info();
+  let token_program = accounts.token_program.to_account_info();
+  let bid_key = accounts.bid.key();
+  let vault_seeds = &[&[BID_VAULT_PDA_SEED, bid_key.as_ref(), bytemuck::bytes_of(&vault_bump)][..]];
+  let funding = VaultFunding {
+    creator: &bidder,
+    creator_send_account: &accounts.bidder_send_account,
+    system_program: &system_program,
+    token_program: &token_program,
+    rent: &accounts.rent,
+  };
+  fund_vault(
+    &trade,
+    &funding,
+    &accounts.bid.to_account_info(),
+    &accounts.bid_vault,
+    vault_seeds,
+    deposit,
+  )?;
+
+  let escrow_state_key = accounts.escrow_state.key();
+  let bid_index = accounts.escrow_state.bid_count;
+  let bid = &mut accounts.bid;
+  bid.escrow_state = escrow_state_key;
+  bid.bidder = bidder_key;
+  bid.bidder_send_account = accounts.bidder_send_account.key();
+  bid.bidder_receive_account = accounts.bidder_receive_account.key();
+  bid.bid_vault = accounts.bid_vault.key();
+  bid.deposit = deposit;
+  bid.bid_index = bid_index;
+  bid.bump = bump;
+  bid.vault_bump = vault_bump;
+  bid.stage = Stage::ReadyExchange.to_code();
+  accounts.escrow_state.bid_count += 1;
+  Ok(())
+}
+
+// refund `best_bid`, the best bid of `escrow_state`, from the accounts: the bid,
+// its bid vault, bidder and bidder_send_account
+pub fn refund_best_bid<'info>(
+  escrow_state: &EscrowAccount,
+  best_bid: &Pubkey,
+  accounts: &[AccountInfo<'info>],
+  system_program: &AccountInfo<'info>,
+  token_program: &AccountInfo<'info>,
+) -> Result<()> {
+  require_gte!(accounts.len(), 4, EscrowError::MissingParams);
+  let mut bid: Account<BidAccount> = Account::try_from(&accounts[0])?;
+  let (bid_vault, bidder, bidder_send_account) = (&accounts[1], &accounts[2], &accounts[3]);
+  require_keys_eq!(bid.key(), *best_bid, EscrowError::InvalidAccount);
+  require_keys_eq!(bid_vault.key(), bid.bid_vault, EscrowError::InvalidAccount);
+  require_keys_eq!(bidder.key(), bid.bidder, EscrowError::InvalidOwner);
+  require_keys_eq!(
+    bidder_send_account.key(),
+    bid.bidder_send_account,
+    EscrowError::InvalidAccount
+  );
+  release_bid(
+    &bid,
+    bid_vault,
+    bidder_send_account,
+    bidder,
+    escrow_state.creator_receive_token_mint.is_none(),
+    system_program,
+    token_program,
+  )?;
+  bid.stage = Stage::CancelTrade.to_code();
+  bid.exit(&crate::ID)
+}
+
+// pay the whole deposit of a bid from its vault to `destination`, then close the
+// vault, its rent going back to the bidder. `native` for bids paid in SOL
+pub fn release_bid<'info>(
+  bid: &Account<'info, BidAccount>,
+  bid_vault: &AccountInfo<'info>,
+  destination: &AccountInfo<'info>,
+  bidder: &AccountInfo<'info>,
+  native: bool,
+  system_program: &AccountInfo<'info>,
+  token_program: &AccountInfo<'info>,
+) -> Result<()> {
+  pay_from_bid(
+    bid,
+    bid_vault,
+    destination,
+    bid.deposit,
+    native,
+    system_program,
+    token_program,
+  )?;
+  close_bid_vault(bid, bid_vault, bidder, native, system_program, token_program)
+}
+
+// pay `amount` of the deposit of a bid from its vault to `destination`
+pub fn pay_from_bid<'info>(
+  bid: &Account<'info, BidAccount>,
+  bid_vault: &AccountInfo<'info>,
+  destination: &AccountInfo<'info>,
+  amount: u64,
+  native: bool,
+  system_program: &AccountInfo<'info>,
+  token_program: &AccountInfo<'info>,
+) -> Result<()> {
+  match native {
+    true => {
+      let bid_key = bid.key();
+      let vault_seeds = &[&[BID_VAULT_PDA_SEED, bid_key.as_ref(), bytemuck::bytes_of(&bid.vault_bump)][..]];
+      transfer_native_to_account(
+        bid_vault.to_account_info(),
+        destination.to_account_info(),
+        amount,
+        system_program.to_account_info(),
+        Some(vault_seeds),
+      )
+    }
+    false => {
+      let bid_index = bid.bid_index.to_le_bytes();
+      let bid_seeds = &[&[
+        BID_PDA_SEED,
+        bid.escrow_state.as_ref(),
+        bid_index.as_ref(),
+        bytemuck::bytes_of(&bid.bump),
+      ][..]];
+      transfer_token_to_account(
+        bid_vault.to_account_info(),
+        destination.to_account_info(),
+        bid.to_account_info(),
+        amount,
+        token_program.to_account_info(),
+        Some(bid_seeds),
+      )
+    }
+  }
+}
+
+// close a bid vault emptied by `pay_from_bid`, its rent going back to the bidder
+pub fn close_bid_vault<'info>(
+  bid: &Account<'info, BidAccount>,
+  bid_vault: &AccountInfo<'info>,
+  bidder: &AccountInfo<'info>,
+  native: bool,
+  system_program: &AccountInfo<'info>,
+  token_program: &AccountInfo<'info>,
+) -> Result<()> {
+  match native {
+    true => {
+      let bid_key = bid.key();
+      let vault_seeds = &[&[BID_VAULT_PDA_SEED, bid_key.as_ref(), bytemuck::bytes_of(&bid.vault_bump)][..]];
+      close_native_account(
+        bid_vault.to_account_info(),
+        bidder.to_account_info(),
+        vault_seeds,
+        system_program.to_account_info(),
+      )
+    }
+    false => {
+      let bid_index = bid.bid_index.to_le_bytes();
+      let bid_seeds = &[&[
+        BID_PDA_SEED,
+        bid.escrow_state.as_ref(),
+        bid_index.as_ref(),
+        bytemuck::bytes_of(&bid.bump),
+      ][..]];
+      close_token_account(
+        bid_vault.to_account_info(),
+        bidder.to_account_info(),
+        bid.to_account_info(),
+        bid_seeds,
+        token_program.to_account_info(),
+      )
+    }
+  }
+}
@@ -0,0 +1,75 @@
+use crate::error::EscrowError;
+use crate::event::BidReclaimed;
+use crate::processor::place_bid::release_bid;
+use crate::state::{BidAccount, EscrowAccount, Stage};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct ReclaimBid<'info> {
+  #[account(
+    constraint = creator_receive_account.key() == escrow_state.creator_payout() @ EscrowError::InvalidAccount
+  )]
+  pub escrow_state: Account<'info, EscrowAccount>,
+  /// CHECK: checked against `creator_payout`, receives forfeited deposits, the creator wallet for SOL
+  #[account(mut)]
+  pub creator_receive_account: AccountInfo<'info>,
+  #[account(
+    mut,
+    has_one = escrow_state @ EscrowError::InvalidAccount,
+    has_one = bid_vault @ EscrowError::InvalidAccount,
+    has_one = bidder @ EscrowError::InvalidOwner,
+    has_one = bidder_send_account @ EscrowError::InvalidAccount,
+    constraint = bid.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage
+  )]
+  pub bid: Account<'info, BidAccount>,
+  /// CHECK: checked by `has_one`, emptied then closed
+  #[account(mut)]
+  pub bid_vault: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, receives the vault rent
+  #[account(mut)]
+  pub bidder: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, refunded unless the deposit is forfeited
+  #[account(mut)]
+  pub bidder_send_account: AccountInfo<'info>,
+  pub system_program: Program<'info, System>,
+  pub token_program: Program<'info, Token>,
+}
+
+// once the reveals of a sealed-bid auction are over, anyone can close a bid that
+// was never revealed. Its deposit goes to the creator when the auction forfeits
+// unrevealed bids, back to the bidder otherwise
+pub fn handler_reclaim_bid(ctx: Context<ReclaimBid>) -> Result<()> {
+  let auction = ctx.accounts.escrow_state.sealed_auction.ok_or(EscrowError::NotAnAuction)?;
+  require_gte!(Clock::get()?.unix_timestamp, auction.reveal_end, EscrowError::AuctionNotEnded);
+  require!(ctx.accounts.bid.commitment.is_some(), EscrowError::BidAlreadyRevealed);
+
+  let forfeited = auction.forfeit_unrevealed;
+  let destination = match forfeited {
+    true => &ctx.accounts.creator_receive_account,
+    false => &ctx.accounts.bidder_send_account,
+  };
+  release_bid(
+    &ctx.accounts.bid,
+    &ctx.accounts.bid_vault,
+    destination,
+    &ctx.accounts.bidder,
+    ctx.accounts.escrow_state.creator_receive_token_mint.is_none(),
+    &ctx.accounts.system_program.to_account_info(),
+    &ctx.accounts.token_program.to_account_info(),
+  )?;
+  ctx.accounts.bid.stage = match forfeited {
+    true => Stage::Declined.to_code(),
+    false => Stage::CancelTrade.to_code(),
+  };
+
+  emit!(BidReclaimed {
+    escrow_state: ctx.accounts.escrow_state.key(),
+    bid: ctx.accounts.bid.key(),
+    bidder: ctx.accounts.bidder.key(),
+    deposit: ctx.accounts.bid.deposit,
+    forfeited,
+  });
+  Ok(())
+}
@@ -0,0 +1,248 @@
+use crate::constant::{STATE_PDA_SEED, USER_PDA_SEED, VAULT_PDA_SEED};
+use crate::error::EscrowError;
+use crate::event::{TradeCancelled, TradeCreated, TradeReplaced};
+use crate::processor::cancel::{refund_and_close_vault, remove_listings, withdraw_from_vault};
+use crate::processor::create::{
+  assign_order_ids, check_order_modes, check_payment_options, check_price_guard, check_trade,
+  emit_trigger_armed, fund_vault, initial_stage, list_order, split_listing_accounts, split_payment_accounts,
+  split_price_guard_account, CreateParams, VaultFunding,
+};
+use crate::state::{EscrowAccount, Stage, UserAccount};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+#[instruction(old_order_id: u64, params: CreateParams)]
+pub struct Replace<'info> {
+  // the order being replaced
+  #[account(
+        mut,
+        has_one=creator,
+        has_one=escrow_vault @ EscrowError::InvalidAccount,
+        seeds=[STATE_PDA_SEED, creator.key().as_ref(), old_order_id.to_le_bytes().as_ref()],
+        bump = escrow_state.state_bump,
+        constraint = escrow_state.is_open() @ EscrowError::InvalidStage,
+        constraint = escrow_state.bid_count == 0 @ EscrowError::AuctionHasBids
+    )]
+  pub escrow_state: Account<'info, EscrowAccount>,
+  /// CHECK: checked by `has_one`, emptied into the new vault and the refund account then closed
+  #[account(mut)]
+  pub escrow_vault: AccountInfo<'info>,
+  /// CHECK: the account the old order was funded from, receives what is not moved to the new vault
+  #[account(mut, constraint = refund_account.key() == escrow_state.creator_send_account @ EscrowError::InvalidOwner)]
+  pub refund_account: AccountInfo<'info>,
+  // order id counter of the creator, ahead of the new order whose seeds take its next id
+  #[account(
+    init_if_needed,
+    payer = creator,
+    seeds = [USER_PDA_SEED, creator.key().as_ref()],
+    bump,
+    space = UserAccount::LEN
+  )]
+  pub user_account: Account<'info, UserAccount>,
+  #[account(
+    init,
+    payer = creator,
+    seeds = [STATE_PDA_SEED, creator.key().as_ref(), user_account.next_order_id.to_le_bytes().as_ref()],
+    bump,
+    space = EscrowAccount::LEN
+  )]
+  pub new_escrow_state: Account<'info, EscrowAccount>,
+  /// CHECK: vault of the new order, created by the instruction
+  #[account(mut,
+    seeds=[VAULT_PDA_SEED, creator.key().as_ref(), user_account.next_order_id.to_le_bytes().as_ref()],
+    bump = params.vault_bump
+  )]
+  pub new_escrow_vault: AccountInfo<'info>,
+  #[account(mut, constraint = creator.lamports() > 0 && creator.data_is_empty())]
+  pub creator: Signer<'info>,
+  /// CHECK: This account use to send `Token` to swap (Token can be SOL or SPL Token)
+  #[account(mut)]
+  pub creator_send_account: AccountInfo<'info>,
+  /// CHECK: This account use to receive `Token` swapped (Token can be SOL or SPL Token)
+  #[account(mut)]
+  pub creator_receive_account: AccountInfo<'info>,
+  /// CHECK: receive fee for each deal
+  #[account(mut)]
+  pub fee_account: AccountInfo<'info>,
+  pub system_program: Program<'info, System>,
+  pub token_program: Program<'info, Token>,
+  pub rent: Sysvar<'info, Rent>,
+}
+
+// cancel an order and create `params` in its place. When the new order is funded
+// from the account the old one was, up to its trade value moves from the old
+// vault to the new one and only the difference is paid or refunded.
+// remaining_accounts: the inbox and market page of the old order when it has
+// ones (see `cancel`), then the accounts of the new order (see `create_trade`)
+pub fn handler_replace_trade<'info>(
+  ctx: Context<'_, '_, '_, 'info, Replace<'info>>,
+  params: CreateParams,
+) -> Result<()> {
+  // both trade value and receive value must be larger than zero, but a
+  // request for quote has no receive value
+  require_gt!(params.trade_value, 0, EscrowError::ZeroValue);
+  if params.rfq.is_none() {
+    require_gt!(params.receive_value, 0, EscrowError::ZeroValue);
+  }
+  check_order_modes(&params)?;
+
+  // assign the order id from the creator's counter
+  let creator_key = ctx.accounts.creator.key();
+  let user_account_bump = *ctx.bumps.get("user_account").unwrap();
+  let order_id = assign_order_ids(&mut ctx.accounts.user_account, creator_key, user_account_bump, 1)?;
+
+  let old_listing_count = ctx.accounts.escrow_state.specify_partner.is_some() as usize
+    + ctx.accounts.escrow_state.market_page.is_some() as usize;
+  require_gte!(
+    ctx.remaining_accounts.len(),
+    old_listing_count,
+    EscrowError::MissingParams
+  );
+  let (old_listings, new_accounts) = ctx.remaining_accounts.split_at(old_listing_count);
+  let (new_accounts, reference_account) = split_price_guard_account(new_accounts, &params)?;
+  let (new_accounts, payment_accounts) = split_payment_accounts(new_accounts, &params)?;
+  let (mint_accounts, inbox_account, market_account) =
+    split_listing_accounts(new_accounts, &params)?;
+
+  // the funds already in escrow only count for an order funded from the same account
+  let old_value = ctx.accounts.escrow_state.trade_value;
+  let moved_value =
+    match ctx.accounts.escrow_state.creator_send_account == ctx.accounts.creator_send_account.key() {
+      true => old_value.min(params.trade_value),
+      false => 0,
+    };
+  let top_up_value = params.trade_value - moved_value;
+  let trade = check_trade(
+    &ctx.accounts.creator,
+    &ctx.accounts.creator_send_account,
+    &ctx.accounts.creator_receive_account,
+    mint_accounts,
+    top_up_value,
+  )?;
+  check_price_guard(&params, &trade, reference_account)?;
+  check_payment_options(&params, &trade, &ctx.accounts.creator, payment_accounts)?;
+
+  // create and fund the new vault, then empty and close the old one
+  let creator = ctx.accounts.creator.to_account_info();
+  let system_program = ctx.accounts.system_program.to_account_info();
+  let token_program = ctx.accounts.token_program.to_account_info();
+  let order_id_bytes = order_id.to_le_bytes();
+  let vault_seeds = &[&[
+    VAULT_PDA_SEED,
+    creator_key.as_ref(),
+    order_id_bytes.as_ref(),
+    bytemuck::bytes_of(&params.vault_bump),
+  ][..]];
+  let funding = VaultFunding {
+    creator: &creator,
+    creator_send_account: &ctx.accounts.creator_send_account,
+    system_program: &system_program,
+    token_program: &token_program,
+    rent: &ctx.accounts.rent,
+  };
+  fund_vault(
+    &trade,
+    &funding,
+    &ctx.accounts.new_escrow_state.to_account_info(),
+    &ctx.accounts.new_escrow_vault,
+    vault_seeds,
+    top_up_value,
+  )?;
+  // nothing moves to a vault of another send mint
+  if moved_value > 0 {
+    withdraw_from_vault(
+      &ctx.accounts.escrow_state,
+      &ctx.accounts.escrow_vault,
+      &ctx.accounts.new_escrow_vault,
+      &creator,
+      moved_value,
+      &system_program,
+      &token_program,
+    )?;
+  }
+  refund_and_close_vault(
+    &ctx.accounts.escrow_state,
+    &ctx.accounts.escrow_vault,
+    &ctx.accounts.refund_account,
+    &creator,
+    old_value - moved_value,
+    &system_program,
+    &token_program,
+  )?;
+  ctx.accounts.escrow_state.stage = Stage::CancelTrade.to_code();
+  remove_listings(
+    &ctx.accounts.escrow_state,
+    &ctx.accounts.escrow_state.key(),
+    &mut old_listings.iter(),
+  )?;
+
+  // fill escrow config account data of the new order
+  let new_escrow_state = &mut ctx.accounts.new_escrow_state;
+  new_escrow_state.specify_partner = params.specify_partner;
+  new_escrow_state.partner_root = params.partner_root;
+  new_escrow_state.claim_hash = params.claim_hash;
+  new_escrow_state.market_page = params.market_page;
+  new_escrow_state.dutch_auction = params.dutch_auction;
+  new_escrow_state.english_auction = params.english_auction;
+  new_escrow_state.sealed_auction = params.sealed_auction;
+  new_escrow_state.rfq = params.rfq;
+  new_escrow_state.oracle_peg = trade.oracle_peg(params.oracle_peg);
+  new_escrow_state.trigger = params.trigger;
+  new_escrow_state.payment_options = params.payment_options;
+  new_escrow_state.creator = creator_key;
+  new_escrow_state.trade_type = trade.trade_type.to_code();
+  new_escrow_state.escrow_vault = ctx.accounts.new_escrow_vault.key();
+  new_escrow_state.creator_send_account = ctx.accounts.creator_send_account.key();
+  new_escrow_state.creator_receive_account = ctx.accounts.creator_receive_account.key();
+  new_escrow_state.creator_send_token_mint = trade.send_mint();
+  new_escrow_state.creator_receive_token_mint = trade.receive_mint();
+  new_escrow_state.trade_value = params.trade_value;
+  new_escrow_state.receive_value = params.receive_value;
+  new_escrow_state.fee_account = ctx.accounts.fee_account.key();
+  new_escrow_state.order_id = order_id;
+  new_escrow_state.timestamp = params.timestamp;
+  new_escrow_state.vault_bump = params.vault_bump;
+  new_escrow_state.state_bump = *ctx.bumps.get("new_escrow_state").unwrap();
+  new_escrow_state.stage = initial_stage(&params).to_code();
+  list_order(
+    &ctx.accounts.new_escrow_state,
+    &params,
+    inbox_account,
+    market_account,
+    &creator,
+    &system_program,
+    &ctx.accounts.rent,
+  )?;
+
+  emit!(TradeCancelled {
+    escrow_state: ctx.accounts.escrow_state.key(),
+    creator: creator_key,
+    order_id: ctx.accounts.escrow_state.order_id,
+    refund_value: old_value - moved_value,
+  });
+  emit!(TradeCreated {
+    escrow_state: ctx.accounts.new_escrow_state.key(),
+    creator: creator_key,
+    order_id,
+    trade_type: ctx.accounts.new_escrow_state.trade_type,
+    creator_send_token_mint: ctx.accounts.new_escrow_state.creator_send_token_mint,
+    creator_receive_token_mint: ctx.accounts.new_escrow_state.creator_receive_token_mint,
+    trade_value: params.trade_value,
+    receive_value: params.receive_value,
+    specify_partner: params.specify_partner,
+    fee_account: ctx.accounts.new_escrow_state.fee_account,
+    timestamp: params.timestamp,
+  });
+  emit_trigger_armed(ctx.accounts.new_escrow_state.key(), &params);
+  emit!(TradeReplaced {
+    escrow_state: ctx.accounts.escrow_state.key(),
+    creator: creator_key,
+    order_id: ctx.accounts.escrow_state.order_id,
+    new_escrow_state: ctx.accounts.new_escrow_state.key(),
+    new_order_id: order_id,
+    moved_value,
+  });
+  Ok(())
+}
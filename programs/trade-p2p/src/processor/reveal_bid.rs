@@ -0,0 +1,117 @@
+use crate::error::EscrowError;
+use crate::event::BidRevealed;
+use crate::processor::place_bid::{refund_best_bid, release_bid};
+use crate::state::{BidAccount, EscrowAccount, Stage};
+use crate::utils::bid_commitment;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct RevealBid<'info> {
+  #[account(
+    mut,
+    constraint = escrow_state.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage
+  )]
+  pub escrow_state: Account<'info, EscrowAccount>,
+  #[account(
+    mut,
+    has_one = escrow_state @ EscrowError::InvalidAccount,
+    has_one = bid_vault @ EscrowError::InvalidAccount,
+    has_one = bidder @ EscrowError::InvalidOwner,
+    has_one = bidder_send_account @ EscrowError::InvalidAccount,
+    constraint = bid.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage
+  )]
+  pub bid: Account<'info, BidAccount>,
+  /// CHECK: checked by `has_one`, refunded when the bid is not the best one
+  #[account(mut)]
+  pub bid_vault: AccountInfo<'info>,
+  #[account(mut)]
+  pub bidder: Signer<'info>,
+  /// CHECK: checked by `has_one`, refunded when the bid is not the best one
+  #[account(mut)]
+  pub bidder_send_account: AccountInfo<'info>,
+  pub system_program: Program<'info, System>,
+  pub token_program: Program<'info, Token>,
+}
+
+// reveal the sealed bid `value` with the `salt` it was committed with. A bid of
+// at least the reserve price and above the best revealed bid becomes the best
+// one and the previous best is refunded, any other bid is refunded now.
+// remaining_accounts: the best bid, its bid vault, bidder and bidder_send_account
+// when there is one
+pub fn handler_reveal_bid<'info>(
+  ctx: Context<'_, '_, '_, 'info, RevealBid<'info>>,
+  value: u64,
+  salt: [u8; 32],
+) -> Result<()> {
+  let escrow_state = &ctx.accounts.escrow_state;
+  let auction = escrow_state.sealed_auction.ok_or(EscrowError::NotAnAuction)?;
+  let now = Clock::get()?.unix_timestamp;
+  require!(
+    now >= auction.commit_end && now < auction.reveal_end,
+    EscrowError::OutsideRevealWindow
+  );
+  let commitment = ctx.accounts.bid.commitment.ok_or(EscrowError::BidAlreadyRevealed)?;
+  require!(
+    bid_commitment(&ctx.accounts.bidder.key(), value, &salt) == commitment,
+    EscrowError::InvalidCommitment
+  );
+  require_gte!(ctx.accounts.bid.deposit, value, EscrowError::DepositTooLow);
+
+  // ties go to the bid revealed first
+  let best_value = match escrow_state.best_bid {
+    Some(best_bid) => {
+      let best_bid_info = ctx.remaining_accounts.first().ok_or(EscrowError::MissingParams)?;
+      require_keys_eq!(best_bid_info.key(), best_bid, EscrowError::InvalidAccount);
+      let best_bid: Account<BidAccount> = Account::try_from(best_bid_info)?;
+      Some(best_bid.value)
+    }
+    None => None,
+  };
+  let best = value >= escrow_state.receive_value && best_value.is_none_or(|best_value| value > best_value);
+  ctx.accounts.bid.value = value;
+  ctx.accounts.bid.commitment = None;
+
+  let system_program = ctx.accounts.system_program.to_account_info();
+  let token_program = ctx.accounts.token_program.to_account_info();
+  let outbid = match best {
+    true => {
+      let outbid = ctx.accounts.escrow_state.best_bid;
+      if let Some(outbid) = &outbid {
+        refund_best_bid(
+          &ctx.accounts.escrow_state,
+          outbid,
+          ctx.remaining_accounts,
+          &system_program,
+          &token_program,
+        )?;
+      }
+      ctx.accounts.escrow_state.best_bid = Some(ctx.accounts.bid.key());
+      outbid
+    }
+    false => {
+      release_bid(
+        &ctx.accounts.bid,
+        &ctx.accounts.bid_vault,
+        &ctx.accounts.bidder_send_account,
+        &ctx.accounts.bidder,
+        ctx.accounts.escrow_state.creator_receive_token_mint.is_none(),
+        &system_program,
+        &token_program,
+      )?;
+      ctx.accounts.bid.stage = Stage::CancelTrade.to_code();
+      None
+    }
+  };
+
+  emit!(BidRevealed {
+    escrow_state: ctx.accounts.escrow_state.key(),
+    bid: ctx.accounts.bid.key(),
+    bidder: ctx.accounts.bidder.key(),
+    value,
+    best,
+    outbid,
+  });
+  Ok(())
+}
@@ -0,0 +1,153 @@
+use crate::error::EscrowError;
+use crate::event::{TradeCancelled, TradeExchanged};
+use crate::processor::cancel::{close_vault, refund_and_close_vault, remove_listings, withdraw_from_vault};
+use crate::processor::place_bid::{close_bid_vault, pay_from_bid};
+use crate::state::{BidAccount, EscrowAccount, Stage};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct SettleAuction<'info> {
+  #[account(
+    mut,
+    has_one = creator @ EscrowError::InvalidOwner,
+    has_one = escrow_vault @ EscrowError::InvalidAccount,
+    has_one = creator_send_account @ EscrowError::InvalidAccount,
+    constraint = creator_receive_account.key() == escrow_state.creator_payout() @ EscrowError::InvalidAccount,
+    constraint = escrow_state.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage
+  )]
+  pub escrow_state: Account<'info, EscrowAccount>,
+  /// CHECK: checked by `has_one`, goes to the winner or back to the creator
+  #[account(mut)]
+  pub escrow_vault: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, receives the vault rent
+  #[account(mut)]
+  pub creator: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, refunded when nobody bid
+  #[account(mut)]
+  pub creator_send_account: AccountInfo<'info>,
+  /// CHECK: checked against `creator_payout`, receives the winning bid, the creator wallet for SOL
+  #[account(mut)]
+  pub creator_receive_account: AccountInfo<'info>,
+  pub system_program: Program<'info, System>,
+  pub token_program: Program<'info, Token>,
+}
+
+// once the bidding of an english auction or the reveals of a sealed-bid auction
+// have ended, anyone can swap the order's vault with the best bid, or refund the
+// creator when there was no bid. The winner gets back what its deposit holds
+// above its bid.
+// remaining_accounts: the best bid, its bid vault, bidder, the bidder's
+// receiving then paying account when there is one, then the market page when listed
+pub fn handler_settle_auction<'info>(ctx: Context<'_, '_, '_, 'info, SettleAuction<'info>>) -> Result<()> {
+  let end_time = ctx.accounts.escrow_state.auction_end().ok_or(EscrowError::NotAnAuction)?;
+  require_gte!(Clock::get()?.unix_timestamp, end_time, EscrowError::AuctionNotEnded);
+
+  let escrow_state_key = ctx.accounts.escrow_state.key();
+  let creator = ctx.accounts.creator.to_account_info();
+  let system_program = ctx.accounts.system_program.to_account_info();
+  let token_program = ctx.accounts.token_program.to_account_info();
+  let mut remaining_accounts = ctx.remaining_accounts.iter();
+  let best_bid = match ctx.accounts.escrow_state.best_bid {
+    Some(best_bid) => {
+      let mut bid_accounts = remaining_accounts.by_ref().take(5);
+      let mut next = || bid_accounts.next().ok_or(EscrowError::MissingParams);
+      let bid: Account<BidAccount> = Account::try_from(next()?)?;
+      let (bid_vault, bidder, bidder_receive_account, bidder_send_account) =
+        (next()?, next()?, next()?, next()?);
+      require_keys_eq!(bid.key(), best_bid, EscrowError::InvalidAccount);
+      require_keys_eq!(bid_vault.key(), bid.bid_vault, EscrowError::InvalidAccount);
+      require_keys_eq!(bidder.key(), bid.bidder, EscrowError::InvalidOwner);
+      require_keys_eq!(
+        bidder_receive_account.key(),
+        bid.bidder_receive_account,
+        EscrowError::InvalidAccount
+      );
+      require_keys_eq!(
+        bidder_send_account.key(),
+        bid.bidder_send_account,
+        EscrowError::InvalidAccount
+      );
+      Some((bid, bid_vault, bidder, bidder_receive_account, bidder_send_account))
+    }
+    None => None,
+  };
+  remove_listings(&ctx.accounts.escrow_state, &escrow_state_key, &mut remaining_accounts)?;
+
+  let escrow_state = &mut ctx.accounts.escrow_state;
+  match best_bid {
+    // the vault goes to the winner, the bid to the creator
+    Some((mut bid, bid_vault, bidder, bidder_receive_account, bidder_send_account)) => {
+      withdraw_from_vault(
+        escrow_state,
+        &ctx.accounts.escrow_vault,
+        bidder_receive_account,
+        &creator,
+        escrow_state.trade_value,
+        &system_program,
+        &token_program,
+      )?;
+      close_vault(escrow_state, &ctx.accounts.escrow_vault, &creator, &system_program, &token_program)?;
+      let native = escrow_state.creator_receive_token_mint.is_none();
+      pay_from_bid(
+        &bid,
+        bid_vault,
+        &ctx.accounts.creator_receive_account,
+        bid.value,
+        native,
+        &system_program,
+        &token_program,
+      )?;
+      // a sealed bid's deposit may cover more than the bid
+      let excess = bid.deposit - bid.value;
+      if excess > 0 {
+        pay_from_bid(
+          &bid,
+          bid_vault,
+          bidder_send_account,
+          excess,
+          native,
+          &system_program,
+          &token_program,
+        )?;
+      }
+      close_bid_vault(&bid, bid_vault, bidder, native, &system_program, &token_program)?;
+      bid.stage = Stage::Exchanged.to_code();
+      bid.exit(&crate::ID)?;
+
+      // the order keeps the price it was sold at, as `exchange` does
+      escrow_state.receive_value = bid.value;
+      escrow_state.specify_partner = Some(bid.bidder);
+      escrow_state.stage = Stage::Exchanged.to_code();
+      emit!(TradeExchanged {
+        escrow_state: escrow_state_key,
+        creator: escrow_state.creator,
+        order_id: escrow_state.order_id,
+        partner: bid.bidder,
+        trade_value: escrow_state.trade_value,
+        receive_value: bid.value,
+      });
+    }
+    // nobody bid, the vault goes back to the creator
+    None => {
+      refund_and_close_vault(
+        escrow_state,
+        &ctx.accounts.escrow_vault,
+        &ctx.accounts.creator_send_account,
+        &creator,
+        escrow_state.trade_value,
+        &system_program,
+        &token_program,
+      )?;
+      escrow_state.stage = Stage::CancelTrade.to_code();
+      emit!(TradeCancelled {
+        escrow_state: escrow_state_key,
+        creator: escrow_state.creator,
+        order_id: escrow_state.order_id,
+        refund_value: escrow_state.trade_value,
+      });
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,71 @@
+use crate::error::EscrowError;
+use crate::processor::accept_offer::{settle_offer, OfferSettlement};
+use crate::state::{EscrowAccount, OfferAccount, Stage};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct SettleRfq<'info> {
+  #[account(
+    mut,
+    has_one = creator @ EscrowError::InvalidOwner,
+    has_one = escrow_vault @ EscrowError::InvalidAccount,
+    constraint = escrow_state.stage == Stage::ReadyExchange.to_code() @ EscrowError::InvalidStage
+  )]
+  pub escrow_state: Account<'info, EscrowAccount>,
+  /// CHECK: checked by `has_one`, goes to the best quote
+  #[account(mut)]
+  pub escrow_vault: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, receives the vault rent
+  #[account(mut)]
+  pub creator: AccountInfo<'info>,
+  /// CHECK: checked against `offer_payout`, receives the quote
+  #[account(mut)]
+  pub creator_offer_account: AccountInfo<'info>,
+  #[account(
+    mut,
+    close = partner,
+    has_one = escrow_state @ EscrowError::InvalidAccount,
+    has_one = offer_vault @ EscrowError::InvalidAccount,
+    has_one = partner @ EscrowError::InvalidOwner,
+    has_one = partner_receive_account @ EscrowError::InvalidAccount
+  )]
+  pub offer: Account<'info, OfferAccount>,
+  /// CHECK: checked by `has_one`, goes to the creator
+  #[account(mut)]
+  pub offer_vault: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, receives the offer and vault rent
+  #[account(mut)]
+  pub partner: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, receives the order's vault
+  #[account(mut)]
+  pub partner_receive_account: AccountInfo<'info>,
+  pub system_program: Program<'info, System>,
+  pub token_program: Program<'info, Token>,
+}
+
+// once the deadline of a best-price request for quote has passed, anyone can
+// swap the order with its best quote, as the creator would with `accept_offer`.
+// remaining_accounts: inbox of the specify partner, then market page, when there are ones
+pub fn handler_settle_rfq<'info>(ctx: Context<'_, '_, '_, 'info, SettleRfq<'info>>) -> Result<()> {
+  let escrow_state = &ctx.accounts.escrow_state;
+  let rfq = escrow_state.rfq.filter(|rfq| rfq.best_price).ok_or(EscrowError::InvalidRfq)?;
+  require_gte!(Clock::get()?.unix_timestamp, rfq.deadline, EscrowError::QuotingOpen);
+  require!(
+    escrow_state.best_quote == Some(ctx.accounts.offer.key()),
+    EscrowError::InvalidAccount
+  );
+  let settlement = OfferSettlement {
+    escrow_vault: &ctx.accounts.escrow_vault,
+    creator: &ctx.accounts.creator,
+    creator_offer_account: &ctx.accounts.creator_offer_account,
+    offer: &ctx.accounts.offer,
+    offer_vault: &ctx.accounts.offer_vault,
+    partner: &ctx.accounts.partner,
+    partner_receive_account: &ctx.accounts.partner_receive_account,
+    system_program: &ctx.accounts.system_program.to_account_info(),
+    token_program: &ctx.accounts.token_program.to_account_info(),
+  };
+  settle_offer(&mut ctx.accounts.escrow_state, &settlement, ctx.remaining_accounts)
+}
@@ -0,0 +1,33 @@
+use crate::error::EscrowError;
+use crate::event::OrderTriggered;
+use crate::state::{EscrowAccount, Stage};
+
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct Trigger<'info> {
+  #[account(
+    mut,
+    constraint = escrow_state.stage == Stage::AwaitingTrigger.to_code() @ EscrowError::InvalidStage
+  )]
+  pub escrow_state: Account<'info, EscrowAccount>,
+  /// CHECK: price account of the order's trigger, checked and parsed by `TriggerCondition::fired_price`
+  pub oracle: AccountInfo<'info>,
+}
+
+// once the oracle price has reached the trigger price of a stop-loss or
+// take-profit order, anyone can make the order ready for exchange
+pub fn handler_trigger(ctx: Context<Trigger>) -> Result<()> {
+  let escrow_state = &mut ctx.accounts.escrow_state;
+  let trigger = escrow_state.trigger.ok_or(EscrowError::InvalidTrigger)?;
+  let price = trigger.fired_price(&ctx.accounts.oracle, Clock::get()?.unix_timestamp)?;
+  escrow_state.stage = Stage::ReadyExchange.to_code();
+  emit!(OrderTriggered {
+    escrow_state: escrow_state.key(),
+    creator: escrow_state.creator,
+    order_id: escrow_state.order_id,
+    price: price.price,
+    publish_time: price.publish_time,
+  });
+  Ok(())
+}
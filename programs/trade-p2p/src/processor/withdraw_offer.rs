@@ -0,0 +1,63 @@
+use crate::error::EscrowError;
+use crate::event::OfferWithdrawn;
+use crate::processor::make_offer::release_offer;
+use crate::state::{EscrowAccount, OfferAccount, Stage};
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct WithdrawOffer<'info> {
+  pub escrow_state: Account<'info, EscrowAccount>,
+  #[account(
+    mut,
+    close = partner,
+    has_one = escrow_state @ EscrowError::InvalidAccount,
+    has_one = offer_vault @ EscrowError::InvalidAccount,
+    has_one = partner @ EscrowError::InvalidOwner,
+    has_one = partner_send_account @ EscrowError::InvalidAccount
+  )]
+  pub offer: Account<'info, OfferAccount>,
+  /// CHECK: checked by `has_one`, refunded then closed
+  #[account(mut)]
+  pub offer_vault: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, signs while the order is open
+  #[account(mut)]
+  pub partner: AccountInfo<'info>,
+  /// CHECK: checked by `has_one`, refunded
+  #[account(mut)]
+  pub partner_send_account: AccountInfo<'info>,
+  pub system_program: Program<'info, System>,
+  pub token_program: Program<'info, Token>,
+}
+
+// the partner takes back an open offer. Quotes stay firm until the deadline of
+// their request for quote, and the best quote of a best-price one until it is
+// settled. Once the order is closed anyone can refund its offers
+pub fn handler_withdraw_offer(ctx: Context<WithdrawOffer>) -> Result<()> {
+  let escrow_state = &ctx.accounts.escrow_state;
+  let offer = &ctx.accounts.offer;
+  if escrow_state.stage == Stage::ReadyExchange.to_code() {
+    require!(ctx.accounts.partner.is_signer, EscrowError::InvalidOwner);
+    if let Some(rfq) = &escrow_state.rfq {
+      require_gte!(Clock::get()?.unix_timestamp, rfq.deadline, EscrowError::QuotingOpen);
+      require!(escrow_state.best_quote != Some(offer.key()), EscrowError::BestQuoteLocked);
+    }
+  }
+  release_offer(
+    offer,
+    &ctx.accounts.offer_vault,
+    &ctx.accounts.partner_send_account,
+    &ctx.accounts.partner,
+    &ctx.accounts.system_program.to_account_info(),
+    &ctx.accounts.token_program.to_account_info(),
+  )?;
+
+  emit!(OfferWithdrawn {
+    escrow_state: offer.escrow_state,
+    offer: offer.key(),
+    partner: offer.partner,
+    value: offer.value,
+  });
+  Ok(())
+}
@@ -1,9 +1,13 @@
 use anchor_lang::prelude::*;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::spl_token::native_mint;
 
 use crate::error::EscrowError;
+use crate::market::market_mint;
+use crate::oracle::{load_price, OraclePrice};
 
 // Trading type between users
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
 pub enum TradeType {
   TokenToken,
   SolToken,
@@ -22,6 +26,16 @@ impl TradeType {
       }
     }
   }
+  // trade type between a send and a receive mint, `None` standing for SOL
+  pub fn from_mints(send_mint: Option<Pubkey>, receive_mint: Option<Pubkey>) -> Result<TradeType> {
+    match (send_mint, receive_mint) {
+      (Some(_), Some(_)) => Ok(TradeType::TokenToken),
+      (Some(_), None) => Ok(TradeType::TokenSol),
+      (None, Some(_)) => Ok(TradeType::SolToken),
+      (None, None) => Err(EscrowError::InvalidTradeType.into()),
+    }
+  }
+
   pub fn to_code(&self) -> u8 {
     match self {
       TradeType::TokenToken => 1,
@@ -52,6 +66,33 @@ pub struct EscrowAccount {
   pub vault_bump: u8,
   pub trade_type: u8,
   pub stage: u8,
+  // merkle root of partners allowed to exchange, see `utils::partner_leaf`
+  pub partner_root: Option<[u8; 32]>,
+  // commitment to a claim key that must co-sign the exchange, see `utils::claim_hash`
+  pub claim_hash: Option<[u8; 32]>,
+  // page of the pair's market the order is listed on, see `MarketPage`
+  pub market_page: Option<u16>,
+  // `receive_value` is the start price of a dutch auction, see `current_receive_value`
+  pub dutch_auction: Option<DutchAuction>,
+  // `receive_value` is the reserve price of an english auction, settled with `settle_auction`
+  pub english_auction: Option<EnglishAuction>,
+  // `receive_value` is the reserve price of a sealed-bid auction, settled with `settle_auction`
+  pub sealed_auction: Option<SealedAuction>,
+  // highest `BidAccount` of an auction, revealed for a sealed-bid one, and the
+  // number of bids placed or committed so far
+  pub best_bid: Option<Pubkey>,
+  pub bid_count: u32,
+  // request for quote: no `receive_value`, takers quote with offers, see `Rfq`
+  pub rfq: Option<Rfq>,
+  // highest quote of a best-price request for quote, see `settle_rfq`
+  pub best_quote: Option<Pubkey>,
+  // `receive_value` follows a price feed at each exchange, see `OraclePeg`
+  pub oracle_peg: Option<OraclePeg>,
+  // stop-loss or take-profit order, `AwaitingTrigger` until `trigger` sees its
+  // oracle cross the trigger price, see `TriggerCondition`
+  pub trigger: Option<TriggerCondition>,
+  // other mints the order may be paid in, each at its own price, see `PaymentOptions`
+  pub payment_options: Option<PaymentOptions>,
 }
 
 impl EscrowAccount {
@@ -59,15 +100,304 @@ impl EscrowAccount {
     + 32 * 8 // PubKey
     + 33 * 3 // Option pubkey
     + 8 * 4 // u64
-    + 1 * 4; // u8
+    + 4 // u8
+    + 33 * 2 // Option [u8; 32]
+    + 3 // Option u16
+    + 1 + DutchAuction::LEN // Option DutchAuction
+    + 1 + EnglishAuction::LEN // Option EnglishAuction
+    + 1 + SealedAuction::LEN // Option SealedAuction
+    + 33 // Option pubkey
+    + 4 // u32
+    + 1 + Rfq::LEN // Option Rfq
+    + 33 // Option pubkey
+    + 1 + OraclePeg::LEN // Option OraclePeg
+    + 1 + TriggerCondition::LEN // Option TriggerCondition
+    + 1 + PaymentOptions::LEN; // Option PaymentOptions
+
+  // size of the deals of the first release, before the optional fields from
+  // `partner_root` on were appended, see `migrate_escrow`
+  pub const FIRST_RELEASE_LEN: usize = 8 + 32 * 8 + 33 * 3 + 8 * 4 + 4;
+
+  // price the order is filled at, at unix time `now`
+  pub fn current_receive_value(&self, now: i64) -> u64 {
+    match &self.dutch_auction {
+      Some(auction) => auction.price_at(self.receive_value, now),
+      None => self.receive_value,
+    }
+  }
+
+  // auction orders are only filled by `settle_auction`
+  pub fn is_auction(&self) -> bool {
+    self.english_auction.is_some() || self.sealed_auction.is_some()
+  }
+
+  // account the creator is paid the receive side into: the creator wallet for
+  // SOL, as `exchange` pays it, `creator_receive_account` otherwise
+  pub fn creator_payout(&self) -> Pubkey {
+    match self.creator_receive_token_mint {
+      Some(_) => self.creator_receive_account,
+      None => self.creator,
+    }
+  }
+
+  // account the creator is paid an offer in `offer_mint` into: `creator_payout`
+  // in the order's receive mint, the creator's associated token account in
+  // another mint of a request for quote, the creator wallet for SOL
+  pub fn offer_payout(&self, offer_mint: Option<Pubkey>) -> Pubkey {
+    match offer_mint {
+      _ if offer_mint == self.creator_receive_token_mint => self.creator_payout(),
+      Some(mint) => get_associated_token_address(&self.creator, &mint),
+      None => self.creator,
+    }
+  }
+
+  // orders the creator can still cancel, decline or replace
+  pub fn is_open(&self) -> bool {
+    self.stage == Stage::ReadyExchange.to_code() || self.stage == Stage::AwaitingTrigger.to_code()
+  }
+
+  // whether the partner may pay the creator into `receive_account`, the order's
+  // own receive account or that of one of its payment options
+  pub fn accepts_payment_to(&self, receive_account: &Pubkey) -> bool {
+    *receive_account == self.creator_receive_account
+      || self
+        .payment_options
+        .is_some_and(|options| options.find(receive_account).is_some())
+  }
+
+  // switch the receive side of the order to the payment option paid into
+  // `receive_account`, so the order settles in its mint at its price
+  pub fn pay_with(&mut self, receive_account: &Pubkey) -> Result<PaymentOption> {
+    let option = self
+      .payment_options
+      .and_then(|options| options.find(receive_account).copied())
+      .ok_or(EscrowError::InvalidAccount)?;
+    let receive_mint = Some(option.mint).filter(|mint| *mint != native_mint::ID);
+    self.trade_type = TradeType::from_mints(self.creator_send_token_mint, receive_mint)?.to_code();
+    self.creator_receive_token_mint = receive_mint;
+    self.creator_receive_account = option.receive_account;
+    self.receive_value = option.receive_value;
+    Ok(option)
+  }
+
+  // unix time bids stop changing the outcome of an auction order
+  pub fn auction_end(&self) -> Option<i64> {
+    match (&self.english_auction, &self.sealed_auction) {
+      (Some(auction), _) => Some(auction.end_time),
+      (_, Some(auction)) => Some(auction.reveal_end),
+      _ => None,
+    }
+  }
+}
+
+// price of a dutch auction order, falling linearly from the order's `receive_value`
+// at `start_time` to `floor_value` at `end_time` (unix timestamps), then staying there
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DutchAuction {
+  pub floor_value: u64,
+  pub start_time: i64,
+  pub end_time: i64,
+}
+
+impl DutchAuction {
+  pub const LEN: usize = 8 * 3;
+
+  pub fn price_at(&self, start_value: u64, now: i64) -> u64 {
+    if now <= self.start_time || start_value <= self.floor_value {
+      return start_value;
+    }
+    if now >= self.end_time {
+      return self.floor_value;
+    }
+    // the decay is rounded down, in favor of the creator
+    let decay = (start_value - self.floor_value) as u128 * (now - self.start_time) as u128
+      / (self.end_time - self.start_time) as u128;
+    start_value - decay as u64
+  }
+}
+
+// bidding of an english auction order: bids are taken until `end_time` (unix
+// timestamp), each at least `min_increment` above the best one
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EnglishAuction {
+  pub end_time: i64,
+  pub min_increment: u64,
+}
+
+impl EnglishAuction {
+  pub const LEN: usize = 8 * 2;
+}
+
+// bidding of a sealed-bid auction order: bids are committed until `commit_end`
+// with a deposit covering them, then revealed until `reveal_end` (unix
+// timestamps). Deposits of bids never revealed go to the creator with
+// `forfeit_unrevealed`, back to their bidder otherwise
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SealedAuction {
+  pub commit_end: i64,
+  pub reveal_end: i64,
+  pub forfeit_unrevealed: bool,
+}
+
+impl SealedAuction {
+  pub const LEN: usize = 8 * 2 + 1;
+}
+
+// quoting of a request-for-quote order: quotes are offers made until `deadline`
+// (unix timestamp) in the order's receive mint or one of the first
+// `extra_mint_count` of `extra_mints` (`market::market_mint` of each). The
+// creator then accepts one of them, or with `best_price` anyone can settle the
+// highest, all quotes being in the receive mint
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rfq {
+  pub deadline: i64,
+  pub extra_mints: [Pubkey; 3],
+  pub extra_mint_count: u8,
+  pub best_price: bool,
+}
+
+impl Rfq {
+  pub const LEN: usize = 8 + 32 * 3 + 1 + 1;
+
+  // whether quotes may be made in `mint`, `None` standing for SOL
+  pub fn approves(&self, receive_mint: Option<Pubkey>, mint: Option<Pubkey>) -> bool {
+    mint == receive_mint || self.extra_mints[..self.extra_mint_count as usize].contains(&market_mint(mint))
+  }
+}
+
+// one bid on an auction, its deposit locked in the bid vault. Stage is
+// `ReadyExchange` while it is the best bid or not revealed yet, `CancelTrade`
+// once outbid or reclaimed and refunded, `Declined` when its unrevealed deposit
+// was forfeited, `Exchanged` when it won the auction
+#[account]
+pub struct BidAccount {
+  pub escrow_state: Pubkey,
+  pub bidder: Pubkey,
+  // the bid is paid from and refunded to this account (Token can be SOL or SPL Token)
+  pub bidder_send_account: Pubkey,
+  // receives the order's vault when the bid wins (Token can be SOL or SPL Token)
+  pub bidder_receive_account: Pubkey,
+  pub bid_vault: Pubkey,
+  // the bid, zero until a sealed bid is revealed
+  pub value: u64,
+  // held in the bid vault, the value of an english bid
+  pub deposit: u64,
+  // `utils::bid_commitment` of a sealed bid, cleared once revealed
+  pub commitment: Option<[u8; 32]>,
+  pub bid_index: u32,
+  pub bump: u8,
+  pub vault_bump: u8,
+  pub stage: u8,
+}
+
+impl BidAccount {
+  pub const LEN: usize = 8
+    + 32 * 5 // PubKey
+    + 8 * 2 // u64
+    + 33 // Option [u8; 32]
+    + 4 // u32
+    + 3; // u8
+}
+
+// counter-offer of a partner against an order, `value` of the partner's own mint
+// locked in the offer vault until the creator accepts it with `accept_offer` or
+// the partner withdraws it. Closed either way, one open offer per partner and order
+#[account]
+pub struct OfferAccount {
+  pub escrow_state: Pubkey,
+  pub partner: Pubkey,
+  // the offer is paid from and refunded to this account (Token can be SOL or SPL Token)
+  pub partner_send_account: Pubkey,
+  // receives the order's vault when the offer is accepted (Token can be SOL or SPL Token)
+  pub partner_receive_account: Pubkey,
+  pub offer_vault: Pubkey,
+  // mint of the offer, `None` for SOL
+  pub offer_mint: Option<Pubkey>,
+  pub value: u64,
+  pub bump: u8,
+  pub vault_bump: u8,
+}
+
+impl OfferAccount {
+  pub const LEN: usize = 8
+    + 32 * 5 // PubKey
+    + 33 // Option pubkey
+    + 8 // u64
+    + 2; // u8
+}
+
+// per creator counter, `create_trade` gives each order its next order id so
+// ids are never reused
+#[account]
+pub struct UserAccount {
+  pub creator: Pubkey,
+  pub next_order_id: u64,
+  pub bump: u8,
+}
+
+impl UserAccount {
+  pub const LEN: usize = 8
+    + 32 // PubKey
+    + 8 // u64
+    + 1; // u8
+}
+
+// open orders addressed to a partner through `specify_partner`, so wallets
+// can list incoming offers with a single account fetch
+#[account]
+pub struct PartnerInbox {
+  pub partner: Pubkey,
+  pub bump: u8,
+  // escrow state addresses
+  pub orders: Vec<Pubkey>,
+}
+
+impl PartnerInbox {
+  pub const MAX_ORDERS: usize = 32;
+  pub const LEN: usize = 8
+    + 32 // PubKey
+    + 1 // u8
+    + 4 + 32 * PartnerInbox::MAX_ORDERS; // Vec<Pubkey>
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MarketEntry {
+  // escrow state address
+  pub order: Pubkey,
+  pub trade_value: u64,
+  pub receive_value: u64,
+}
+
+// one page of the open orders of a pair, an on chain order book for takers.
+// SOL sides use the native mint as their key, see `market::market_mint`
+#[account]
+pub struct MarketPage {
+  pub send_mint: Pubkey,
+  pub receive_mint: Pubkey,
+  pub page: u16,
+  pub bump: u8,
+  pub orders: Vec<MarketEntry>,
+}
+
+impl MarketPage {
+  pub const MAX_ORDERS: usize = 32;
+  pub const LEN: usize = 8
+    + 32 * 2 // PubKey
+    + 2 // u16
+    + 1 // u8
+    + 4 + (32 + 8 * 2) * MarketPage::MAX_ORDERS; // Vec<MarketEntry>
 }
 
 // define stage of deal
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Stage {
   ReadyExchange,
   Exchanged,
   CancelTrade,
+  // refused by the specify partner, vault refunded to the creator
+  Declined,
+  // conditional order whose trigger has not fired yet, see `TriggerCondition`
+  AwaitingTrigger,
 }
 
 impl Stage {
@@ -77,6 +407,8 @@ impl Stage {
       1 => Ok(Stage::ReadyExchange),
       2 => Ok(Stage::Exchanged),
       3 => Ok(Stage::CancelTrade),
+      4 => Ok(Stage::Declined),
+      5 => Ok(Stage::AwaitingTrigger),
       unknown_code => {
         msg!("Unknow state: {}", unknown_code);
         Err(EscrowError::InvalidStage.into())
@@ -89,6 +421,210 @@ impl Stage {
       Stage::ReadyExchange => 1,
       Stage::Exchanged => 2,
       Stage::CancelTrade => 3,
+      Stage::Declined => 4,
+      Stage::AwaitingTrigger => 5,
     }
   }
 }
+
+// price of an oracle-pegged order: `trade_value` at the `oracle` price of one
+// send token in receive tokens, times `spread_bps` / 10_000, then bounded by
+// `min_value` and `max_value`. Prices older than `max_age` seconds, or with a
+// confidence interval wider than `max_confidence_bps` of the price, are refused
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OraclePeg {
+  // Pyth price account, see `oracle::load_price`
+  pub oracle: Pubkey,
+  pub spread_bps: u16,
+  pub min_value: u64,
+  pub max_value: u64,
+  pub max_age: u32,
+  pub max_confidence_bps: u16,
+  // decimals of the receive mint minus those of the send mint, set by
+  // `create_trade` from the mints
+  pub decimal_shift: i8,
+}
+
+impl OraclePeg {
+  pub const LEN: usize = 32 + 2 + 8 + 8 + 4 + 2 + 1;
+
+  // receive value of the order from the price in `oracle` at unix time `now`
+  pub fn receive_value(&self, trade_value: u64, oracle: &AccountInfo, now: i64) -> Result<u64> {
+    require_keys_eq!(oracle.key(), self.oracle, EscrowError::InvalidOracle);
+    let price = load_price(oracle)?;
+    self.price_at(trade_value, &price, now)
+  }
+
+  // rounded up in favor of the creator, out of range values ending at the bounds
+  pub fn price_at(&self, trade_value: u64, price: &OraclePrice, now: i64) -> Result<u64> {
+    price.check_age(self.max_age, now)?;
+    require!(
+      price.confidence as u128 * 10_000 <= price.price as u128 * self.max_confidence_bps as u128,
+      EscrowError::OracleConfidence
+    );
+    let value = price.value_of(trade_value, self.decimal_shift, self.spread_bps);
+    Ok(value.clamp(self.min_value, self.max_value))
+  }
+}
+
+// side of the trigger price an oracle price must reach to fire a conditional order
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TriggerDirection {
+  // take-profit, fires once the price rises to the trigger price
+  AtOrAbove,
+  // stop-loss, fires once the price falls to the trigger price
+  AtOrBelow,
+}
+
+// condition of a stop-loss or take-profit order: it is only filled once the
+// `oracle` price, in the units of the feed, has reached `trigger_price` from the
+// `direction` side. Prices older than `max_age` seconds do not fire it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TriggerCondition {
+  // Pyth price account, see `oracle::load_price`
+  pub oracle: Pubkey,
+  pub trigger_price: i64,
+  pub direction: TriggerDirection,
+  pub max_age: u32,
+}
+
+impl TriggerCondition {
+  pub const LEN: usize = 32 + 8 + 1 + 4;
+
+  // price in `oracle` at unix time `now` that fires the condition
+  pub fn fired_price(&self, oracle: &AccountInfo, now: i64) -> Result<OraclePrice> {
+    require_keys_eq!(oracle.key(), self.oracle, EscrowError::InvalidOracle);
+    let price = load_price(oracle)?;
+    price.check_age(self.max_age, now)?;
+    require!(self.is_reached(price.price), EscrowError::TriggerNotReached);
+    Ok(price)
+  }
+
+  pub fn is_reached(&self, price: i64) -> bool {
+    match self.direction {
+      TriggerDirection::AtOrAbove => price >= self.trigger_price,
+      TriggerDirection::AtOrBelow => price <= self.trigger_price,
+    }
+  }
+}
+
+// one more mint an order can be paid in: `receive_value` of `mint`
+// (`market::market_mint`, so `native_mint::ID` for SOL) paid into the creator's
+// `receive_account`, a token account of the mint or the creator wallet for SOL
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PaymentOption {
+  pub mint: Pubkey,
+  pub receive_value: u64,
+  pub receive_account: Pubkey,
+}
+
+impl PaymentOption {
+  pub const LEN: usize = 32 + 8 + 32;
+}
+
+// the first `count` of `options` are accepted besides the order's own receive
+// mint and value. `exchange` settles with the option whose receive account the
+// partner pays into
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PaymentOptions {
+  pub options: [PaymentOption; 3],
+  pub count: u8,
+}
+
+impl PaymentOptions {
+  pub const LEN: usize = PaymentOption::LEN * 3 + 1;
+
+  pub fn accepted(&self) -> &[PaymentOption] {
+    &self.options[..(self.count as usize).min(self.options.len())]
+  }
+
+  pub fn find(&self, receive_account: &Pubkey) -> Option<&PaymentOption> {
+    self
+      .accepted()
+      .iter()
+      .find(|option| option.receive_account == *receive_account)
+  }
+}
+
+// fat-finger check of a new order against the reference price account `oracle`,
+// in the layout of `OraclePeg::oracle`: the order's receive value must be within
+// `max_deviation_bps` of `trade_value` at the reference price, unless
+// `override_deviation` is set. Only checked when the order is created
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PriceGuard {
+  pub oracle: Pubkey,
+  pub max_deviation_bps: u16,
+  pub max_age: u32,
+  pub override_deviation: bool,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn dutch_auction_price_falls_to_the_floor() {
+    let auction = DutchAuction {
+      floor_value: 100,
+      start_time: 1_000,
+      end_time: 1_010,
+    };
+    assert_eq!(auction.price_at(200, 900), 200);
+    assert_eq!(auction.price_at(200, 1_000), 200);
+    assert_eq!(auction.price_at(200, 1_001), 190);
+    assert_eq!(auction.price_at(200, 1_005), 150);
+    assert_eq!(auction.price_at(200, 1_010), 100);
+    assert_eq!(auction.price_at(200, 2_000), 100);
+    // a start below the floor never decays
+    assert_eq!(auction.price_at(50, 1_005), 50);
+    // the decay is rounded down
+    assert_eq!(auction.price_at(103, 1_005), 102);
+  }
+
+  fn peg() -> OraclePeg {
+    OraclePeg {
+      oracle: Pubkey::default(),
+      spread_bps: 10_000,
+      min_value: 10,
+      max_value: 1_000,
+      max_age: 60,
+      max_confidence_bps: 100,
+      decimal_shift: 0,
+    }
+  }
+
+  fn price(price: i64, confidence: u64) -> OraclePrice {
+    OraclePrice {
+      price,
+      confidence,
+      exponent: -2,
+      publish_time: 1_000,
+    }
+  }
+
+  #[test]
+  fn oracle_peg_prices_within_its_bounds() {
+    let peg = peg();
+    assert_eq!(peg.price_at(100, &price(250, 0), 1_000).unwrap(), 250);
+    // rounded up
+    assert_eq!(peg.price_at(3, &price(250, 0), 1_000).unwrap(), 10);
+    assert_eq!(peg.price_at(7, &price(250, 0), 1_060).unwrap(), 18);
+    assert_eq!(peg.price_at(1_000, &price(250, 0), 1_000).unwrap(), 1_000);
+    let spread = OraclePeg { spread_bps: 10_200, ..peg };
+    assert_eq!(spread.price_at(100, &price(250, 0), 1_000).unwrap(), 255);
+  }
+
+  #[test]
+  fn oracle_peg_refuses_stale_or_uncertain_prices() {
+    let peg = peg();
+    assert_eq!(
+      peg.price_at(100, &price(250, 0), 1_061),
+      Err(EscrowError::StaleOracle.into())
+    );
+    assert!(peg.price_at(100, &price(250, 2), 1_000).is_ok());
+    assert_eq!(
+      peg.price_at(100, &price(250, 3), 1_000),
+      Err(EscrowError::OracleConfidence.into())
+    );
+  }
+}
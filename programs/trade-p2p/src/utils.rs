@@ -1,6 +1,7 @@
 use crate::error::EscrowError;
 
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{keccak, program::invoke, program::invoke_signed, system_instruction};
 use anchor_spl::token::{InitializeAccount, Transfer};
 
 pub fn transfer_native_pda_to_account<'info>(
@@ -41,7 +42,7 @@ pub fn transfer_native_to_account<'info>(
       anchor_lang::system_program::transfer(cpi_ctx_sol, amount)?;
     }
   }
-  return Ok(());
+  Ok(())
 }
 
 // transfer fungible token & nft token
@@ -58,22 +59,17 @@ pub fn transfer_token_to_account<'info>(
     to: receiver.to_account_info(),
     authority: authority.to_account_info(),
   };
-  let cpi_ctx;
-  match seeds {
-    Some(seeds) => {
-      cpi_ctx = CpiContext::new_with_signer(
-        token_program.to_account_info(),
-        transfer_instruction_account,
-        seeds,
-      );
-    }
-    None => {
-      cpi_ctx = CpiContext::new(
-        token_program.to_account_info(),
-        transfer_instruction_account,
-      );
-    }
-  }
+  let cpi_ctx = match seeds {
+    Some(seeds) => CpiContext::new_with_signer(
+      token_program.to_account_info(),
+      transfer_instruction_account,
+      seeds,
+    ),
+    None => CpiContext::new(
+      token_program.to_account_info(),
+      transfer_instruction_account,
+    ),
+  };
   anchor_spl::token::transfer(cpi_ctx, amount)?;
   Ok(())
 }
@@ -101,6 +97,39 @@ pub fn create_account<'info>(
   Ok(())
 }
 
+// create an account owned by this program at one of its PDAs, also when
+// someone already sent lamports to the address, which makes `create_account` fail
+pub fn create_program_account<'info>(
+  payer: &AccountInfo<'info>,
+  account: &AccountInfo<'info>,
+  space: usize,
+  signers_seeds: &[&[&[u8]]],
+  system_program: &AccountInfo<'info>,
+  rent: &Rent,
+) -> Result<()> {
+  let accounts = &[payer.clone(), account.clone(), system_program.clone()];
+  let lamports = rent.minimum_balance(space);
+  if account.lamports() == 0 {
+    invoke_signed(
+      &system_instruction::create_account(payer.key, account.key, lamports, space as u64, &crate::ID),
+      accounts,
+      signers_seeds,
+    )?;
+  } else {
+    let top_up = lamports.saturating_sub(account.lamports());
+    if top_up > 0 {
+      invoke(&system_instruction::transfer(payer.key, account.key, top_up), accounts)?;
+    }
+    invoke_signed(
+      &system_instruction::allocate(account.key, space as u64),
+      accounts,
+      signers_seeds,
+    )?;
+    invoke_signed(&system_instruction::assign(account.key, &crate::ID), accounts, signers_seeds)?;
+  }
+  Ok(())
+}
+
 pub fn initialize_token_account<'info>(
   account: AccountInfo<'info>,
   mint: AccountInfo<'info>,
@@ -160,3 +189,58 @@ pub fn close_native_account<'info>(
   )?;
   Ok(())
 }
+
+// commitment of a sealed bid of `value`, `salt` keeps it from being guessed
+pub fn bid_commitment(bidder: &Pubkey, value: u64, salt: &[u8; 32]) -> [u8; 32] {
+  keccak::hashv(&[b"bid", bidder.as_ref(), value.to_le_bytes().as_ref(), salt]).to_bytes()
+}
+
+// commitment stored for a claim-code order: hash of the claim public key
+pub fn claim_hash(claim_key: &Pubkey) -> [u8; 32] {
+  keccak::hashv(&[b"claim", claim_key.as_ref()]).to_bytes()
+}
+
+// leaf of a partner allowlist merkle tree
+pub fn partner_leaf(partner: &Pubkey) -> [u8; 32] {
+  keccak::hashv(&[partner.as_ref()]).to_bytes()
+}
+
+// parent node, children are sorted so a proof needs no left/right flags
+pub fn merkle_parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+  if a <= b {
+    keccak::hashv(&[a, b]).to_bytes()
+  } else {
+    keccak::hashv(&[b, a]).to_bytes()
+  }
+}
+
+pub fn verify_merkle_proof(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+  let computed = proof
+    .iter()
+    .fold(leaf, |node, sibling| merkle_parent(&node, sibling));
+  computed == *root
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bid_commitment_binds_bidder_value_and_salt() {
+    let bidder = Pubkey::new_unique();
+    let commitment = bid_commitment(&bidder, 100, &[1; 32]);
+    assert_eq!(commitment, bid_commitment(&bidder, 100, &[1; 32]));
+    assert_ne!(commitment, bid_commitment(&bidder, 101, &[1; 32]));
+    assert_ne!(commitment, bid_commitment(&bidder, 100, &[2; 32]));
+    assert_ne!(commitment, bid_commitment(&Pubkey::new_unique(), 100, &[1; 32]));
+  }
+
+  #[test]
+  fn merkle_parent_ignores_child_order() {
+    let (a, b) = ([1; 32], [2; 32]);
+    assert_eq!(merkle_parent(&a, &b), merkle_parent(&b, &a));
+    let root = merkle_parent(&merkle_parent(&a, &b), &[3; 32]);
+    assert!(verify_merkle_proof(&[b, [3; 32]], &root, a));
+    assert!(!verify_merkle_proof(&[a, [3; 32]], &root, a));
+  }
+}
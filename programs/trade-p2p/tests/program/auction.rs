@@ -0,0 +1,387 @@
+use crate::runtime::*;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::{system_program, sysvar};
+use anchor_spl::token::spl_token;
+use trade_p2p::constant::{BID_PDA_SEED, BID_VAULT_PDA_SEED};
+use trade_p2p::error::EscrowError;
+use trade_p2p::event::BidPlaced;
+use trade_p2p::state::{BidAccount, DutchAuction, EnglishAuction, EscrowAccount, Rfq, SealedAuction, Stage};
+use trade_p2p::utils::bid_commitment;
+
+fn bid_address(order: &Pubkey, bid_index: u32) -> Pubkey {
+  Pubkey::find_program_address(&[BID_PDA_SEED, order.as_ref(), bid_index.to_le_bytes().as_ref()], &trade_p2p::ID).0
+}
+
+fn bid_vault_address(bid: &Pubkey) -> (Pubkey, u8) {
+  Pubkey::find_program_address(&[BID_VAULT_PDA_SEED, bid.as_ref()], &trade_p2p::ID)
+}
+
+// the bid, its vault, bidder and bidder_send_account, as the instructions
+// refunding a bid take them
+fn bid_metas(bid: &Pubkey, bidder: &Trader) -> Vec<AccountMeta> {
+  vec![
+    AccountMeta::new(*bid, false),
+    AccountMeta::new(bid_vault_address(bid).0, false),
+    AccountMeta::new(bidder.wallet, false),
+    AccountMeta::new(bidder.send_account, false),
+  ]
+}
+
+fn place_bid_accounts(order: Pubkey, bid: Pubkey, bidder: &Trader) -> trade_p2p::accounts::PlaceBid {
+  trade_p2p::accounts::PlaceBid {
+    escrow_state: order,
+    bid,
+    bid_vault: bid_vault_address(&bid).0,
+    bidder: bidder.wallet,
+    bidder_send_account: bidder.send_account,
+    bidder_receive_account: bidder.receive_account,
+    system_program: system_program::ID,
+    token_program: spl_token::ID,
+    rent: sysvar::rent::ID,
+  }
+}
+
+// bid number `bid_index` on `order`, outbidding `best` when there is one
+fn place_bid(order: Pubkey, bid_index: u32, bidder: &Trader, value: u64, best: Option<(Pubkey, &Trader)>) -> Instruction {
+  let bid = bid_address(&order, bid_index);
+  let mut remaining = bidder.mint_metas();
+  if let Some((best_bid, best_bidder)) = best {
+    remaining.extend(bid_metas(&best_bid, best_bidder));
+  }
+  instruction(
+    place_bid_accounts(order, bid, bidder),
+    &remaining,
+    trade_p2p::instruction::PlaceBid {
+      value,
+      vault_bump: bid_vault_address(&bid).1,
+    },
+  )
+}
+
+fn settle_auction(creator: &Trader, order_id: u64, best: Option<(Pubkey, &Trader)>) -> Instruction {
+  let mut remaining = Vec::new();
+  if let Some((bid, bidder)) = best {
+    remaining.push(AccountMeta::new(bid, false));
+    remaining.push(AccountMeta::new(bid_vault_address(&bid).0, false));
+    remaining.push(AccountMeta::new(bidder.wallet, false));
+    remaining.push(AccountMeta::new(bidder.receive_account, false));
+    remaining.push(AccountMeta::new(bidder.send_account, false));
+  }
+  instruction(
+    trade_p2p::accounts::SettleAuction {
+      escrow_state: creator.order(order_id),
+      escrow_vault: creator.vault(order_id),
+      creator: creator.wallet,
+      creator_send_account: creator.send_account,
+      creator_receive_account: creator.receive_account,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+    },
+    &remaining,
+    trade_p2p::instruction::SettleAuction {},
+  )
+}
+
+fn token_auction(env: &mut Env) -> (Trader, Trader, Trader) {
+  let (send_mint, receive_mint) = (env.mint(6), env.mint(9));
+  let creator = Trader::new(env, Some(send_mint), Some(receive_mint), 1_000);
+  let first = creator.counterparty(env, 1_000);
+  let second = creator.counterparty(env, 1_000);
+  (creator, first, second)
+}
+
+#[test]
+fn dutch_auction_fills_at_the_price_of_the_clock() {
+  let mut env = Env::new();
+  let (mut creator, partner, _) = token_auction(&mut env);
+  let mut auction = params(100, 400);
+  auction.dutch_auction = Some(DutchAuction {
+    floor_value: 200,
+    start_time: env.now,
+    end_time: env.now + 100,
+  });
+  let order_id = creator.create(&mut env, auction, &[]);
+
+  // halfway through, the price has fallen from 400 to 300
+  env.warp(50);
+  let capped = creator.capped_exchange_instruction(&partner, order_id, &[], Some(299));
+  assert_eq!(env.process(&capped), Err(error(EscrowError::ReceiveValueExceeded)));
+  env.process(&creator.capped_exchange_instruction(&partner, order_id, &[], Some(300))).unwrap();
+  assert_eq!(env.token_balance(&creator.receive_account), 1_300);
+  assert_eq!(env.token_balance(&partner.send_account), 700);
+  assert_eq!(env.token_balance(&partner.receive_account), 1_100);
+  assert_eq!(env.state::<EscrowAccount>(&creator.order(order_id)).receive_value, 300);
+}
+
+#[test]
+fn english_auction_refunds_the_outbid_and_pays_the_winner() {
+  let mut env = Env::new();
+  let (mut creator, first, second) = token_auction(&mut env);
+  let mut auction = params(100, 200);
+  auction.english_auction = Some(EnglishAuction {
+    end_time: env.now + 100,
+    min_increment: 10,
+  });
+  let order_id = creator.create(&mut env, auction, &[]);
+  let order = creator.order(order_id);
+
+  assert_eq!(env.process(&place_bid(order, 0, &first, 190, None)), Err(error(EscrowError::BidTooLow)));
+  env.process(&place_bid(order, 0, &first, 200, None)).unwrap();
+  let first_bid = bid_address(&order, 0);
+  assert_eq!(env.token_balance(&first.send_account), 800);
+  let too_low = place_bid(order, 1, &second, 205, Some((first_bid, &first)));
+  assert_eq!(env.process(&too_low), Err(error(EscrowError::BidTooLow)));
+  env.process(&place_bid(order, 1, &second, 210, Some((first_bid, &first)))).unwrap();
+  assert_eq!(env.emitted::<BidPlaced>()[0].outbid, Some(first_bid));
+  assert_eq!(env.token_balance(&first.send_account), 1_000);
+  assert_eq!(env.state::<BidAccount>(&first_bid).stage, Stage::CancelTrade.to_code());
+
+  let second_bid = bid_address(&order, 1);
+  let settle = settle_auction(&creator, order_id, Some((second_bid, &second)));
+  assert_eq!(env.process(&settle), Err(error(EscrowError::AuctionNotEnded)));
+  env.warp(100);
+  env.process(&settle).unwrap();
+  assert_eq!(env.token_balance(&creator.receive_account), 1_210);
+  assert_eq!(env.token_balance(&second.receive_account), 1_100);
+  assert!(!env.exists(&bid_vault_address(&second_bid).0));
+  let order: EscrowAccount = env.state(&order);
+  assert_eq!((order.stage, order.receive_value), (Stage::Exchanged.to_code(), 210));
+}
+
+#[test]
+fn an_order_is_priced_one_way_only() {
+  let mut env = Env::new();
+  let (creator, _, _) = token_auction(&mut env);
+  let english = EnglishAuction {
+    end_time: env.now + 100,
+    min_increment: 10,
+  };
+  let mut two_auctions = params(100, 200);
+  two_auctions.english_auction = Some(english);
+  two_auctions.dutch_auction = Some(DutchAuction {
+    floor_value: 100,
+    start_time: env.now,
+    end_time: env.now + 100,
+  });
+  let create = creator.create_instruction(two_auctions, &[]);
+  assert_eq!(env.process(&create), Err(error(EscrowError::InvalidAuction)));
+
+  let mut auctioned_request = params(100, 0);
+  auctioned_request.english_auction = Some(english);
+  auctioned_request.rfq = Some(Rfq {
+    deadline: env.now + 100,
+    extra_mints: [Pubkey::default(); 3],
+    extra_mint_count: 0,
+    best_price: false,
+  });
+  let create = creator.create_instruction(auctioned_request, &[]);
+  assert_eq!(env.process(&create), Err(error(EscrowError::InvalidRfq)));
+}
+
+#[test]
+fn settle_auction_without_bids_refunds_the_creator() {
+  let mut env = Env::new();
+  let (mut creator, _, _) = token_auction(&mut env);
+  let mut auction = params(100, 200);
+  auction.english_auction = Some(EnglishAuction {
+    end_time: env.now + 100,
+    min_increment: 10,
+  });
+  let order_id = creator.create(&mut env, auction, &[]);
+  env.warp(100);
+  env.process(&settle_auction(&creator, order_id, None)).unwrap();
+  assert_eq!(env.token_balance(&creator.send_account), 1_000);
+  assert_eq!(env.state::<EscrowAccount>(&creator.order(order_id)).stage, Stage::CancelTrade.to_code());
+}
+
+#[test]
+fn settle_auction_pays_a_sol_bid_to_the_creator_wallet() {
+  let mut env = Env::new();
+  let send_mint = env.mint(6);
+  // the order receives SOL but was created naming another account to receive it
+  let mut creator = Trader {
+    receive_account: Pubkey::new_unique(),
+    ..Trader::new(&mut env, Some(send_mint), None, 1_000)
+  };
+  let bidder = creator.counterparty(&mut env, 1_000);
+  let mut auction = params(100, 200);
+  auction.english_auction = Some(EnglishAuction {
+    end_time: env.now + 100,
+    min_increment: 10,
+  });
+  let order_id = creator.create(&mut env, auction, &[]);
+  let order = creator.order(order_id);
+  env.process(&place_bid(order, 0, &bidder, 200, None)).unwrap();
+  let bid = Some((bid_address(&order, 0), &bidder));
+  env.warp(100);
+
+  let elsewhere = settle_auction(&creator, order_id, bid);
+  assert_eq!(env.process(&elsewhere), Err(error(EscrowError::InvalidAccount)));
+  let stored = creator.receive_account;
+  let creator = Trader {
+    receive_account: creator.wallet,
+    ..creator
+  };
+  let wallet_lamports = env.lamports(&creator.wallet);
+  let vault_rent = env.lamports(&creator.vault(order_id));
+  env.process(&settle_auction(&creator, order_id, bid)).unwrap();
+  assert_eq!(env.lamports(&creator.wallet), wallet_lamports + 200 + vault_rent);
+  assert!(!env.exists(&stored));
+  assert_eq!(env.token_balance(&bidder.receive_account), 1_100);
+}
+
+fn commit_bid(order: Pubkey, bid_index: u32, bidder: &Trader, deposit: u64, value: u64) -> Instruction {
+  let bid = bid_address(&order, bid_index);
+  instruction(
+    place_bid_accounts(order, bid, bidder),
+    &bidder.mint_metas(),
+    trade_p2p::instruction::CommitBid {
+      deposit,
+      vault_bump: bid_vault_address(&bid).1,
+      commitment: bid_commitment(&bidder.wallet, value, &[7; 32]),
+    },
+  )
+}
+
+fn reveal_bid(order: Pubkey, bid_index: u32, bidder: &Trader, value: u64, best: Option<(Pubkey, &Trader)>) -> Instruction {
+  let bid = bid_address(&order, bid_index);
+  let remaining = best.map_or_else(Vec::new, |(best_bid, best_bidder)| bid_metas(&best_bid, best_bidder));
+  instruction(
+    trade_p2p::accounts::RevealBid {
+      escrow_state: order,
+      bid,
+      bid_vault: bid_vault_address(&bid).0,
+      bidder: bidder.wallet,
+      bidder_send_account: bidder.send_account,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+    },
+    &remaining,
+    trade_p2p::instruction::RevealBid { value, salt: [7; 32] },
+  )
+}
+
+fn reclaim_bid(creator: &Trader, order_id: u64, bid_index: u32, bidder: &Trader) -> Instruction {
+  let order = creator.order(order_id);
+  let bid = bid_address(&order, bid_index);
+  instruction(
+    trade_p2p::accounts::ReclaimBid {
+      escrow_state: order,
+      creator_receive_account: creator.receive_account,
+      bid,
+      bid_vault: bid_vault_address(&bid).0,
+      bidder: bidder.wallet,
+      bidder_send_account: bidder.send_account,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+    },
+    &[],
+    trade_p2p::instruction::ReclaimBid {},
+  )
+}
+
+#[test]
+fn reveal_compares_against_the_order_best_bid_only() {
+  let mut env = Env::new();
+  let (mut creator, first, second) = token_auction(&mut env);
+  let mut auction = params(100, 200);
+  auction.sealed_auction = Some(SealedAuction {
+    commit_end: env.now + 100,
+    reveal_end: env.now + 200,
+    forfeit_unrevealed: false,
+  });
+  let (order_id, other_id) = (creator.create(&mut env, auction, &[]), creator.create(&mut env, auction, &[]));
+  let (order, other) = (creator.order(order_id), creator.order(other_id));
+  env.process(&commit_bid(order, 0, &first, 300, 300)).unwrap();
+  env.process(&commit_bid(order, 1, &second, 400, 350)).unwrap();
+  env.process(&commit_bid(other, 0, &first, 500, 500)).unwrap();
+  env.warp(100);
+  env.process(&reveal_bid(other, 0, &first, 500, None)).unwrap();
+  env.process(&reveal_bid(order, 0, &first, 300, None)).unwrap();
+
+  // the higher best bid of another auction cannot stand in for this one's
+  let foreign = reveal_bid(order, 1, &second, 350, Some((bid_address(&other, 0), &first)));
+  assert_eq!(env.process(&foreign), Err(error(EscrowError::InvalidAccount)));
+  env.process(&reveal_bid(order, 1, &second, 350, Some((bid_address(&order, 0), &first)))).unwrap();
+  assert_eq!(env.state::<EscrowAccount>(&order).best_bid, Some(bid_address(&order, 1)));
+  assert_eq!(env.token_balance(&first.send_account), 500);
+}
+
+#[test]
+fn sealed_auction_settles_revealed_bids_and_forfeits_the_rest() {
+  let mut env = Env::new();
+  let (mut creator, revealer, hider) = token_auction(&mut env);
+  let mut auction = params(100, 200);
+  auction.sealed_auction = Some(SealedAuction {
+    commit_end: env.now + 100,
+    reveal_end: env.now + 200,
+    forfeit_unrevealed: true,
+  });
+  let order_id = creator.create(&mut env, auction, &[]);
+  let order = creator.order(order_id);
+
+  assert_eq!(
+    env.process(&commit_bid(order, 0, &revealer, 150, 150)),
+    Err(error(EscrowError::DepositTooLow))
+  );
+  env.process(&commit_bid(order, 0, &revealer, 300, 250)).unwrap();
+  env.process(&commit_bid(order, 1, &hider, 300, 280)).unwrap();
+  assert_eq!(
+    env.process(&reveal_bid(order, 0, &revealer, 250, None)),
+    Err(error(EscrowError::OutsideRevealWindow))
+  );
+  env.warp(100);
+  assert_eq!(
+    env.process(&reveal_bid(order, 0, &revealer, 260, None)),
+    Err(error(EscrowError::InvalidCommitment))
+  );
+  env.process(&reveal_bid(order, 0, &revealer, 250, None)).unwrap();
+  assert_eq!(env.state::<EscrowAccount>(&order).best_bid, Some(bid_address(&order, 0)));
+
+  let reclaim = reclaim_bid(&creator, order_id, 1, &hider);
+  assert_eq!(env.process(&reclaim), Err(error(EscrowError::AuctionNotEnded)));
+  env.warp(100);
+  env.process(&reclaim).unwrap();
+  assert_eq!(env.token_balance(&creator.receive_account), 1_300);
+  assert_eq!(env.token_balance(&hider.send_account), 700);
+
+  env.process(&settle_auction(&creator, order_id, Some((bid_address(&order, 0), &revealer)))).unwrap();
+  assert_eq!(env.token_balance(&creator.receive_account), 1_550);
+  assert_eq!(env.token_balance(&revealer.send_account), 750);
+  assert_eq!(env.token_balance(&revealer.receive_account), 1_100);
+}
+
+#[test]
+fn reclaim_bid_forfeits_a_sol_deposit_to_the_creator_wallet() {
+  let mut env = Env::new();
+  let send_mint = env.mint(6);
+  // the order receives SOL but was created naming another account to receive it
+  let mut creator = Trader {
+    receive_account: Pubkey::new_unique(),
+    ..Trader::new(&mut env, Some(send_mint), None, 1_000)
+  };
+  let hider = creator.counterparty(&mut env, 1_000);
+  let mut auction = params(100, 200);
+  auction.sealed_auction = Some(SealedAuction {
+    commit_end: env.now + 100,
+    reveal_end: env.now + 200,
+    forfeit_unrevealed: true,
+  });
+  let order_id = creator.create(&mut env, auction, &[]);
+  env.process(&commit_bid(creator.order(order_id), 0, &hider, 300, 250)).unwrap();
+  env.warp(200);
+
+  let elsewhere = reclaim_bid(&creator, order_id, 0, &hider);
+  assert_eq!(env.process(&elsewhere), Err(error(EscrowError::InvalidAccount)));
+  let stored = creator.receive_account;
+  let creator = Trader {
+    receive_account: creator.wallet,
+    ..creator
+  };
+  let wallet_lamports = env.lamports(&creator.wallet);
+  env.process(&reclaim_bid(&creator, order_id, 0, &hider)).unwrap();
+  assert_eq!(env.lamports(&creator.wallet), wallet_lamports + 300);
+  assert!(!env.exists(&stored));
+}
@@ -0,0 +1,7 @@
+// program-level tests, each instruction run against the in-process runtime of `runtime`
+mod runtime;
+
+mod auction;
+mod offer;
+mod oracle;
+mod trade;
@@ -0,0 +1,173 @@
+use crate::runtime::*;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::{system_program, sysvar};
+use anchor_spl::token::spl_token;
+use trade_p2p::constant::{OFFER_PDA_SEED, OFFER_VAULT_PDA_SEED};
+use trade_p2p::error::EscrowError;
+use trade_p2p::event::OfferAccepted;
+use trade_p2p::state::{EscrowAccount, Rfq, Stage};
+
+fn offer_address(order: &Pubkey, partner: &Pubkey) -> Pubkey {
+  Pubkey::find_program_address(&[OFFER_PDA_SEED, order.as_ref(), partner.as_ref()], &trade_p2p::ID).0
+}
+
+fn offer_vault_address(offer: &Pubkey) -> (Pubkey, u8) {
+  Pubkey::find_program_address(&[OFFER_VAULT_PDA_SEED, offer.as_ref()], &trade_p2p::ID)
+}
+
+// `partner` offers `value` of its send mint, `best` being the best quote of a
+// best-price request for quote
+fn make_offer(order: Pubkey, partner: &Trader, value: u64, best: Option<Pubkey>) -> Instruction {
+  let offer = offer_address(&order, &partner.wallet);
+  let mut remaining = partner.mint_metas();
+  remaining.extend(best.map(|best| AccountMeta::new_readonly(best, false)));
+  instruction(
+    trade_p2p::accounts::MakeOffer {
+      escrow_state: order,
+      offer,
+      offer_vault: offer_vault_address(&offer).0,
+      partner: partner.wallet,
+      partner_send_account: partner.send_account,
+      partner_receive_account: partner.receive_account,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+      rent: sysvar::rent::ID,
+    },
+    &remaining,
+    trade_p2p::instruction::MakeOffer {
+      value,
+      vault_bump: offer_vault_address(&offer).1,
+    },
+  )
+}
+
+fn accept_offer(creator: &Trader, order_id: u64, partner: &Trader) -> Instruction {
+  let offer = offer_address(&creator.order(order_id), &partner.wallet);
+  instruction(
+    trade_p2p::accounts::AcceptOffer {
+      escrow_state: creator.order(order_id),
+      escrow_vault: creator.vault(order_id),
+      creator: creator.wallet,
+      creator_offer_account: creator.receive_account,
+      offer,
+      offer_vault: offer_vault_address(&offer).0,
+      partner: partner.wallet,
+      partner_receive_account: partner.receive_account,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+    },
+    &[],
+    trade_p2p::instruction::AcceptOffer {},
+  )
+}
+
+// signed by the partner, as it must be while the order is open
+fn withdraw_offer(order: Pubkey, partner: &Trader) -> Instruction {
+  let offer = offer_address(&order, &partner.wallet);
+  let mut withdraw = instruction(
+    trade_p2p::accounts::WithdrawOffer {
+      escrow_state: order,
+      offer,
+      offer_vault: offer_vault_address(&offer).0,
+      partner: partner.wallet,
+      partner_send_account: partner.send_account,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+    },
+    &[],
+    trade_p2p::instruction::WithdrawOffer {},
+  );
+  withdraw.accounts[3].is_signer = true;
+  withdraw
+}
+
+fn settle_rfq(creator: &Trader, order_id: u64, partner: &Trader) -> Instruction {
+  let offer = offer_address(&creator.order(order_id), &partner.wallet);
+  instruction(
+    trade_p2p::accounts::SettleRfq {
+      escrow_state: creator.order(order_id),
+      escrow_vault: creator.vault(order_id),
+      creator: creator.wallet,
+      creator_offer_account: creator.receive_account,
+      offer,
+      offer_vault: offer_vault_address(&offer).0,
+      partner: partner.wallet,
+      partner_receive_account: partner.receive_account,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+    },
+    &[],
+    trade_p2p::instruction::SettleRfq {},
+  )
+}
+
+fn token_order(env: &mut Env) -> (Trader, Trader, Trader) {
+  let (send_mint, receive_mint) = (env.mint(6), env.mint(9));
+  let creator = Trader::new(env, Some(send_mint), Some(receive_mint), 1_000);
+  let first = creator.counterparty(env, 1_000);
+  let second = creator.counterparty(env, 1_000);
+  (creator, first, second)
+}
+
+#[test]
+fn accept_offer_swaps_the_order_and_withdraw_refunds_the_others() {
+  let mut env = Env::new();
+  let (mut creator, taker, other) = token_order(&mut env);
+  let order_id = creator.create(&mut env, params(100, 250), &[]);
+  let order = creator.order(order_id);
+  env.process(&make_offer(order, &taker, 200, None)).unwrap();
+  env.process(&make_offer(order, &other, 150, None)).unwrap();
+  assert_eq!(env.token_balance(&taker.send_account), 800);
+
+  // the offer is paid where the order is, not into any account of its mint
+  let mut elsewhere = accept_offer(&creator, order_id, &taker);
+  elsewhere.accounts[3].pubkey = other.send_account;
+  assert_eq!(env.process(&elsewhere), Err(error(EscrowError::InvalidAccount)));
+  env.process(&accept_offer(&creator, order_id, &taker)).unwrap();
+  assert_eq!(env.token_balance(&creator.receive_account), 1_200);
+  assert_eq!(env.token_balance(&taker.receive_account), 1_100);
+  assert!(!env.exists(&offer_address(&order, &taker.wallet)));
+  assert_eq!(env.emitted::<OfferAccepted>()[0].value, 200);
+  assert_eq!(env.state::<EscrowAccount>(&order).stage, Stage::Exchanged.to_code());
+
+  env.process(&withdraw_offer(order, &other)).unwrap();
+  assert_eq!(env.token_balance(&other.send_account), 1_000);
+  assert!(!env.exists(&offer_vault_address(&offer_address(&order, &other.wallet)).0));
+}
+
+#[test]
+fn settle_rfq_swaps_the_order_with_its_best_quote() {
+  let mut env = Env::new();
+  let (mut creator, low, high) = token_order(&mut env);
+  let mut request = params(100, 0);
+  request.rfq = Some(Rfq {
+    deadline: env.now + 100,
+    extra_mints: [Pubkey::default(); 3],
+    extra_mint_count: 0,
+    best_price: true,
+  });
+  let order_id = creator.create(&mut env, request, &[]);
+  let order = creator.order(order_id);
+  env.process(&make_offer(order, &low, 200, None)).unwrap();
+  let low_offer = offer_address(&order, &low.wallet);
+  env.process(&make_offer(order, &high, 230, Some(low_offer))).unwrap();
+  let high_offer = offer_address(&order, &high.wallet);
+  assert_eq!(env.state::<EscrowAccount>(&order).best_quote, Some(high_offer));
+
+  assert_eq!(env.process(&withdraw_offer(order, &low)), Err(error(EscrowError::QuotingOpen)));
+  let settle = settle_rfq(&creator, order_id, &high);
+  assert_eq!(env.process(&settle), Err(error(EscrowError::QuotingOpen)));
+  env.warp(100);
+  assert_eq!(env.process(&withdraw_offer(order, &high)), Err(error(EscrowError::BestQuoteLocked)));
+  assert_eq!(
+    env.process(&settle_rfq(&creator, order_id, &low)),
+    Err(error(EscrowError::InvalidAccount))
+  );
+  env.process(&settle).unwrap();
+  assert_eq!(env.token_balance(&creator.receive_account), 1_230);
+  assert_eq!(env.token_balance(&high.receive_account), 1_100);
+  env.process(&withdraw_offer(order, &low)).unwrap();
+  assert_eq!(env.token_balance(&low.send_account), 1_000);
+}
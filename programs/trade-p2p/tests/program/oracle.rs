@@ -0,0 +1,148 @@
+use crate::runtime::*;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::spl_token::native_mint;
+use trade_p2p::error::EscrowError;
+use trade_p2p::event::OrderTriggered;
+use trade_p2p::oracle::pyth;
+use trade_p2p::state::{
+  EscrowAccount, OraclePeg, PaymentOption, PaymentOptions, PriceGuard, Stage, TriggerCondition,
+  TriggerDirection,
+};
+
+// both mints with 6 decimals, so feed prices apply to raw amounts
+fn token_pair(env: &mut Env) -> (Trader, Trader) {
+  let (send_mint, receive_mint) = (env.mint(6), env.mint(6));
+  let creator = Trader::new(env, Some(send_mint), Some(receive_mint), 1_000);
+  let partner = creator.counterparty(env, 1_000);
+  (creator, partner)
+}
+
+// 2 receive tokens per send token
+const PRICE: i64 = 200_000_000;
+
+#[test]
+fn pegged_order_is_exchanged_at_the_oracle_price() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let feed = env.price_feed(&pyth::ID, PRICE);
+  let mut pegged = params(100, 1);
+  pegged.oracle_peg = Some(OraclePeg {
+    oracle: feed,
+    spread_bps: 10_100,
+    min_value: 1,
+    max_value: u64::MAX,
+    max_age: 60,
+    max_confidence_bps: 100,
+    decimal_shift: 0,
+  });
+  let order_id = creator.create(&mut env, pegged, &[]);
+
+  let other_feed = env.price_feed(&pyth::ID, PRICE);
+  let wrong_feed = creator.exchange_instruction(&partner, order_id, &[AccountMeta::new_readonly(other_feed, false)]);
+  assert_eq!(env.process(&wrong_feed), Err(error(EscrowError::InvalidOracle)));
+  let oracle = [AccountMeta::new_readonly(feed, false)];
+  let exchange = creator.exchange_instruction(&partner, order_id, &oracle);
+  // anyone can write an account in the layout of a feed
+  env.set_price(&feed, &Pubkey::new_unique(), PRICE);
+  assert_eq!(env.process(&exchange), Err(error(EscrowError::InvalidOracle)));
+  env.set_price(&feed, &pyth::ID, PRICE);
+  env.warp(61);
+  assert_eq!(env.process(&exchange), Err(error(EscrowError::StaleOracle)));
+  env.set_price(&feed, &pyth::ID, PRICE / 2);
+  let slipped = creator.capped_exchange_instruction(&partner, order_id, &oracle, Some(100));
+  assert_eq!(env.process(&slipped), Err(error(EscrowError::ReceiveValueExceeded)));
+  env.process(&creator.capped_exchange_instruction(&partner, order_id, &oracle, Some(101))).unwrap();
+  assert_eq!(env.token_balance(&creator.receive_account), 1_101);
+  assert_eq!(env.state::<EscrowAccount>(&creator.order(order_id)).receive_value, 101);
+}
+
+#[test]
+fn triggered_order_opens_once_the_price_is_reached() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let feed = env.price_feed(&pyth::ID, PRICE);
+  let mut stop_loss = params(100, 150);
+  stop_loss.trigger = Some(TriggerCondition {
+    oracle: feed,
+    trigger_price: PRICE * 3 / 4,
+    direction: TriggerDirection::AtOrBelow,
+    max_age: 60,
+  });
+  let order_id = creator.create(&mut env, stop_loss, &[]);
+  let order = creator.order(order_id);
+  assert_eq!(env.state::<EscrowAccount>(&order).stage, Stage::AwaitingTrigger.to_code());
+  let exchange = creator.exchange_instruction(&partner, order_id, &[]);
+  assert_eq!(env.process(&exchange), Err(error(EscrowError::NotTriggered)));
+
+  let trigger = instruction(
+    trade_p2p::accounts::Trigger { escrow_state: order, oracle: feed },
+    &[],
+    trade_p2p::instruction::Trigger {},
+  );
+  assert_eq!(env.process(&trigger), Err(error(EscrowError::TriggerNotReached)));
+  env.set_price(&feed, &pyth::ID, PRICE / 2);
+  env.process(&trigger).unwrap();
+  assert_eq!(env.emitted::<OrderTriggered>()[0].price, PRICE / 2);
+  assert_eq!(env.state::<EscrowAccount>(&order).stage, Stage::ReadyExchange.to_code());
+  env.process(&exchange).unwrap();
+  assert_eq!(env.token_balance(&creator.receive_account), 1_150);
+}
+
+#[test]
+fn price_guard_refuses_values_off_the_reference_price() {
+  let mut env = Env::new();
+  let (mut creator, _) = token_pair(&mut env);
+  let feed = env.price_feed(&pyth::ID, PRICE);
+  let guard = PriceGuard {
+    oracle: feed,
+    max_deviation_bps: 1_000,
+    max_age: 60,
+    override_deviation: false,
+  };
+  let reference = [AccountMeta::new_readonly(feed, false)];
+  let mut fat_finger = params(100, 2_000);
+  fat_finger.price_guard = Some(guard);
+  let create = creator.create_instruction(fat_finger, &reference);
+  assert_eq!(env.process(&create), Err(error(EscrowError::PriceDeviation)));
+
+  let mut overridden = fat_finger;
+  overridden.price_guard = Some(PriceGuard {
+    override_deviation: true,
+    ..guard
+  });
+  creator.create(&mut env, overridden, &reference);
+  let mut close = params(100, 210);
+  close.price_guard = Some(guard);
+  creator.create(&mut env, close, &reference);
+}
+
+#[test]
+fn price_guard_is_not_combined_with_payment_options() {
+  let mut env = Env::new();
+  let (creator, _) = token_pair(&mut env);
+  let feed = env.price_feed(&pyth::ID, PRICE);
+  let mut guarded = params(100, 200);
+  guarded.price_guard = Some(PriceGuard {
+    oracle: feed,
+    max_deviation_bps: 1_000,
+    max_age: 60,
+    override_deviation: false,
+  });
+  // also payable in SOL, into the creator wallet
+  let option = PaymentOption {
+    mint: native_mint::ID,
+    receive_value: 200,
+    receive_account: creator.wallet,
+  };
+  guarded.payment_options = Some(PaymentOptions {
+    options: [option, PaymentOption::default(), PaymentOption::default()],
+    count: 1,
+  });
+  let extra = [
+    AccountMeta::new_readonly(creator.wallet, false),
+    AccountMeta::new_readonly(feed, false),
+  ];
+  let create = creator.create_instruction(guarded, &extra);
+  assert_eq!(env.process(&create), Err(error(EscrowError::InvalidPaymentOptions)));
+}
@@ -0,0 +1,595 @@
+// In-process runtime for the program tests. Accounts are laid out in the
+// loader's input format so the program can `realloc` and `assign` them, and the
+// cross-program calls to the system and token programs are served through the
+// syscall stubs, signatures of program addresses included.
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::{deserialize, BPF_ALIGN_OF_U128, MAX_PERMITTED_DATA_INCREASE};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_error::ProgramError;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+use anchor_lang::solana_program::program_utils::limited_deserialize;
+use anchor_lang::solana_program::system_instruction::SystemInstruction;
+use anchor_lang::solana_program::{bpf_loader, system_program, sysvar};
+use anchor_lang::{AccountDeserialize, Discriminator, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::spl_token;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::Once;
+use trade_p2p::constant::{INBOX_PDA_SEED, MARKET_PDA_SEED, STATE_PDA_SEED, USER_PDA_SEED, VAULT_PDA_SEED};
+use trade_p2p::error::EscrowError;
+use trade_p2p::market::market_mint;
+use trade_p2p::oracle::{encode_price, OraclePrice};
+use trade_p2p::processor::CreateParams;
+
+pub const SOL: u64 = 1_000_000_000;
+
+thread_local! {
+  static NOW: Cell<i64> = const { Cell::new(0) };
+  static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+  fn sol_log(&self, _message: &str) {}
+
+  fn sol_log_data(&self, fields: &[&[u8]]) {
+    EVENTS.with(|events| events.borrow_mut().extend(fields.iter().map(|field| field.to_vec())));
+  }
+
+  fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+    let clock = Clock {
+      unix_timestamp: NOW.with(Cell::get),
+      ..Clock::default()
+    };
+    unsafe { *(var_addr as *mut Clock) = clock };
+    0
+  }
+
+  fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+    unsafe { *(var_addr as *mut Rent) = Rent::default() };
+    0
+  }
+
+  fn sol_invoke_signed(
+    &self,
+    instruction: &Instruction,
+    account_infos: &[AccountInfo],
+    signers_seeds: &[&[&[u8]]],
+  ) -> std::result::Result<(), ProgramError> {
+    // the callee sees the accounts in instruction order, signed when the
+    // transaction signed them or the program signed for its address
+    let accounts = instruction
+      .accounts
+      .iter()
+      .map(|meta| {
+        let mut info = account_infos
+          .iter()
+          .find(|info| *info.key == meta.pubkey)
+          .cloned()
+          .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        info.is_signer = meta.is_signer && (info.is_signer || is_program_signer(info.key, signers_seeds));
+        if meta.is_signer && !info.is_signer {
+          return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(info)
+      })
+      .collect::<std::result::Result<Vec<_>, _>>()?;
+    if instruction.program_id == system_program::ID {
+      process_system(&accounts, &instruction.data)
+    } else if instruction.program_id == spl_token::ID {
+      spl_token::processor::Processor::process(&spl_token::ID, &accounts, &instruction.data)
+    } else {
+      Err(ProgramError::IncorrectProgramId)
+    }
+  }
+}
+
+fn is_program_signer(key: &Pubkey, signers_seeds: &[&[&[u8]]]) -> bool {
+  signers_seeds
+    .iter()
+    .any(|seeds| Pubkey::create_program_address(seeds, &trade_p2p::ID).ok() == Some(*key))
+}
+
+fn move_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> std::result::Result<(), ProgramError> {
+  let balance = from.lamports();
+  if balance < lamports {
+    return Err(ProgramError::InsufficientFunds);
+  }
+  **from.try_borrow_mut_lamports()? = balance - lamports;
+  **to.try_borrow_mut_lamports()? += lamports;
+  Ok(())
+}
+
+fn process_system(accounts: &[AccountInfo], data: &[u8]) -> std::result::Result<(), ProgramError> {
+  let instruction: SystemInstruction =
+    limited_deserialize(data, 1232).map_err(|_| ProgramError::InvalidInstructionData)?;
+  match instruction {
+    SystemInstruction::CreateAccount { lamports, space, owner } => {
+      let (from, to) = (&accounts[0], &accounts[1]);
+      if to.lamports() > 0 || !to.data_is_empty() || *to.owner != system_program::ID {
+        return Err(ProgramError::AccountAlreadyInitialized);
+      }
+      move_lamports(from, to, lamports)?;
+      to.realloc(space as usize, true)?;
+      to.assign(&owner);
+      Ok(())
+    }
+    SystemInstruction::Transfer { lamports } => {
+      let (from, to) = (&accounts[0], &accounts[1]);
+      if !from.data_is_empty() {
+        return Err(ProgramError::InvalidArgument);
+      }
+      move_lamports(from, to, lamports)
+    }
+    SystemInstruction::Allocate { space } => accounts[0].realloc(space as usize, true),
+    SystemInstruction::Assign { owner } => {
+      accounts[0].assign(&owner);
+      Ok(())
+    }
+    _ => Err(ProgramError::InvalidInstructionData),
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct AccountState {
+  pub lamports: u64,
+  pub data: Vec<u8>,
+  pub owner: Pubkey,
+  pub executable: bool,
+}
+
+// where an account sits in the serialized input
+struct Layout {
+  key: Pubkey,
+  owner: usize,
+  lamports: usize,
+  data_len: usize,
+}
+
+pub struct Env {
+  pub accounts: HashMap<Pubkey, AccountState>,
+  pub now: i64,
+  // events emitted by the last successful instruction
+  pub events: Vec<Vec<u8>>,
+}
+
+impl Env {
+  pub fn new() -> Env {
+    static STUBS: Once = Once::new();
+    STUBS.call_once(|| {
+      set_syscall_stubs(Box::new(Stubs));
+    });
+    let mut env = Env {
+      accounts: HashMap::new(),
+      now: 1_700_000_000,
+      events: Vec::new(),
+    };
+    env.set_account(system_program::ID, 1, Vec::new(), bpf_loader::ID, true);
+    env.set_account(spl_token::ID, 1, Vec::new(), bpf_loader::ID, true);
+    // bincode layout of `Rent`
+    let rent = Rent::default();
+    let mut rent_data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+    rent_data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+    rent_data.push(rent.burn_percent);
+    env.set_account(sysvar::rent::ID, 1, rent_data, sysvar::ID, false);
+    env
+  }
+
+  pub fn set_account(&mut self, key: Pubkey, lamports: u64, data: Vec<u8>, owner: Pubkey, executable: bool) {
+    self.accounts.insert(
+      key,
+      AccountState {
+        lamports,
+        data,
+        owner,
+        executable,
+      },
+    );
+  }
+
+  pub fn wallet(&mut self, lamports: u64) -> Pubkey {
+    let key = Pubkey::new_unique();
+    self.set_account(key, lamports, Vec::new(), system_program::ID, false);
+    key
+  }
+
+  pub fn mint(&mut self, decimals: u8) -> Pubkey {
+    let key = Pubkey::new_unique();
+    let mint = spl_token::state::Mint {
+      mint_authority: Some(Pubkey::new_unique()).into(),
+      supply: u64::MAX / 2,
+      decimals,
+      is_initialized: true,
+      freeze_authority: None.into(),
+    };
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    mint.pack_into_slice(&mut data);
+    let lamports = Rent::default().minimum_balance(data.len());
+    self.set_account(key, lamports, data, spl_token::ID, false);
+    key
+  }
+
+  // the associated token account of `owner`
+  pub fn token_account(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let key = get_associated_token_address(owner, mint);
+    let account = spl_token::state::Account {
+      mint: *mint,
+      owner: *owner,
+      amount,
+      state: spl_token::state::AccountState::Initialized,
+      ..spl_token::state::Account::default()
+    };
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    account.pack_into_slice(&mut data);
+    let lamports = Rent::default().minimum_balance(data.len());
+    self.set_account(key, lamports, data, spl_token::ID, false);
+    key
+  }
+
+  // a Pyth-format feed at `price` with exponent -8, published now
+  pub fn price_feed(&mut self, owner: &Pubkey, price: i64) -> Pubkey {
+    let key = Pubkey::new_unique();
+    self.set_price(&key, owner, price);
+    key
+  }
+
+  pub fn set_price(&mut self, feed: &Pubkey, owner: &Pubkey, price: i64) {
+    let data = encode_price(&OraclePrice {
+      price,
+      confidence: 0,
+      exponent: -8,
+      publish_time: self.now,
+    });
+    self.set_account(*feed, SOL, data, *owner, false);
+  }
+
+  pub fn lamports(&self, key: &Pubkey) -> u64 {
+    self.accounts.get(key).map_or(0, |account| account.lamports)
+  }
+
+  pub fn exists(&self, key: &Pubkey) -> bool {
+    self.lamports(key) > 0
+  }
+
+  pub fn token_balance(&self, key: &Pubkey) -> u64 {
+    spl_token::state::Account::unpack(&self.accounts[key].data).unwrap().amount
+  }
+
+  pub fn state<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+    T::try_deserialize(&mut self.accounts[key].data.as_slice()).unwrap()
+  }
+
+  pub fn emitted<E: Discriminator + AnchorDeserialize>(&self) -> Vec<E> {
+    self
+      .events
+      .iter()
+      .filter(|data| data.starts_with(&E::discriminator()))
+      .map(|data| E::deserialize(&mut &data[8..]).unwrap())
+      .collect()
+  }
+
+  pub fn warp(&mut self, seconds: i64) {
+    self.now += seconds;
+  }
+
+  // run `instruction` as a transaction of its own, signed by the accounts it
+  // marks as signers. Nothing is written back when it fails
+  pub fn process(&mut self, instruction: &Instruction) -> std::result::Result<(), ProgramError> {
+    let mut keys: Vec<(Pubkey, bool, bool)> = Vec::new();
+    for meta in &instruction.accounts {
+      match keys.iter_mut().find(|(key, _, _)| *key == meta.pubkey) {
+        Some(entry) => {
+          entry.1 |= meta.is_signer;
+          entry.2 |= meta.is_writable;
+        }
+        None => keys.push((meta.pubkey, meta.is_signer, meta.is_writable)),
+      }
+    }
+    let empty = AccountState {
+      lamports: 0,
+      data: Vec::new(),
+      owner: system_program::ID,
+      executable: false,
+    };
+
+    let mut input: Vec<u8> = Vec::new();
+    let mut layouts: Vec<Layout> = Vec::new();
+    input.extend_from_slice(&(instruction.accounts.len() as u64).to_le_bytes());
+    for meta in &instruction.accounts {
+      if let Some(index) = layouts.iter().position(|layout| layout.key == meta.pubkey) {
+        let position = instruction.accounts.iter().position(|other| other.pubkey == layouts[index].key).unwrap();
+        input.push(position as u8);
+        input.extend_from_slice(&[0; 7]);
+        continue;
+      }
+      let (key, is_signer, is_writable) = *keys.iter().find(|(key, _, _)| *key == meta.pubkey).unwrap();
+      let account = self.accounts.get(&key).unwrap_or(&empty);
+      input.extend_from_slice(&[0xff, is_signer as u8, is_writable as u8, account.executable as u8]);
+      input.extend_from_slice(&[0; 4]);
+      input.extend_from_slice(key.as_ref());
+      let owner = input.len();
+      input.extend_from_slice(account.owner.as_ref());
+      let lamports = input.len();
+      input.extend_from_slice(&account.lamports.to_le_bytes());
+      let data_len = input.len();
+      input.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+      input.extend_from_slice(&account.data);
+      input.resize(input.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+      input.resize(input.len() + (BPF_ALIGN_OF_U128 - input.len() % BPF_ALIGN_OF_U128) % BPF_ALIGN_OF_U128, 0);
+      input.extend_from_slice(&0u64.to_le_bytes());
+      layouts.push(Layout {
+        key,
+        owner,
+        lamports,
+        data_len,
+      });
+    }
+    input.extend_from_slice(&(instruction.data.len() as u64).to_le_bytes());
+    input.extend_from_slice(&instruction.data);
+    input.extend_from_slice(instruction.program_id.as_ref());
+
+    // the loader hands the program 8 byte aligned input
+    let mut aligned = vec![0u64; input.len() / 8 + 1];
+    let buffer = unsafe { std::slice::from_raw_parts_mut(aligned.as_mut_ptr() as *mut u8, input.len()) };
+    buffer.copy_from_slice(&input);
+
+    NOW.with(|now| now.set(self.now));
+    EVENTS.with(|events| events.borrow_mut().clear());
+    let result = {
+      let (program_id, accounts, data) = unsafe { deserialize(buffer.as_mut_ptr()) };
+      trade_p2p::entry(program_id, &accounts, data)
+    };
+    result?;
+
+    let read_u64 = |offset: usize| u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap());
+    let before: u128 = layouts.iter().map(|layout| self.lamports(&layout.key) as u128).sum();
+    let after: u128 = layouts.iter().map(|layout| read_u64(layout.lamports) as u128).sum();
+    assert_eq!(before, after, "instruction created or destroyed lamports");
+    for layout in &layouts {
+      let lamports = read_u64(layout.lamports);
+      let data_len = read_u64(layout.data_len) as usize;
+      let data_start = layout.data_len + 8;
+      let state = AccountState {
+        lamports,
+        data: buffer[data_start..data_start + data_len].to_vec(),
+        owner: Pubkey::new(&buffer[layout.owner..layout.owner + 32]),
+        executable: self.accounts.get(&layout.key).is_some_and(|account| account.executable),
+      };
+      // accounts left without lamports are gone once the transaction ends
+      match lamports {
+        0 => self.accounts.remove(&layout.key),
+        _ => self.accounts.insert(layout.key, state),
+      };
+    }
+    self.events = EVENTS.with(|events| events.borrow_mut().split_off(0));
+    Ok(())
+  }
+}
+
+pub fn error(error: EscrowError) -> ProgramError {
+  anchor_lang::error::Error::from(error).into()
+}
+
+pub fn state_address(creator: &Pubkey, order_id: u64) -> (Pubkey, u8) {
+  Pubkey::find_program_address(
+    &[STATE_PDA_SEED, creator.as_ref(), order_id.to_le_bytes().as_ref()],
+    &trade_p2p::ID,
+  )
+}
+
+pub fn vault_address(creator: &Pubkey, order_id: u64) -> (Pubkey, u8) {
+  Pubkey::find_program_address(
+    &[VAULT_PDA_SEED, creator.as_ref(), order_id.to_le_bytes().as_ref()],
+    &trade_p2p::ID,
+  )
+}
+
+pub fn user_address(creator: &Pubkey) -> Pubkey {
+  Pubkey::find_program_address(&[USER_PDA_SEED, creator.as_ref()], &trade_p2p::ID).0
+}
+
+pub fn inbox_address(partner: &Pubkey) -> Pubkey {
+  Pubkey::find_program_address(&[INBOX_PDA_SEED, partner.as_ref()], &trade_p2p::ID).0
+}
+
+// page of the market of a pair, `None` standing for SOL
+pub fn market_address(send_mint: Option<Pubkey>, receive_mint: Option<Pubkey>, page: u16) -> Pubkey {
+  let (send_mint, receive_mint) = (market_mint(send_mint), market_mint(receive_mint));
+  Pubkey::find_program_address(
+    &[MARKET_PDA_SEED, send_mint.as_ref(), receive_mint.as_ref(), page.to_le_bytes().as_ref()],
+    &trade_p2p::ID,
+  )
+  .0
+}
+
+pub fn params(trade_value: u64, receive_value: u64) -> CreateParams {
+  CreateParams {
+    specify_partner: None,
+    trade_value,
+    receive_value,
+    timestamp: 0,
+    // set from the creator by `Trader::create_instruction`
+    vault_bump: 0,
+    partner_root: None,
+    claim_hash: None,
+    market_page: None,
+    dutch_auction: None,
+    english_auction: None,
+    sealed_auction: None,
+    rfq: None,
+    oracle_peg: None,
+    price_guard: None,
+    trigger: None,
+    payment_options: None,
+  }
+}
+
+pub fn instruction(accounts: impl ToAccountMetas, remaining: &[AccountMeta], data: impl InstructionData) -> Instruction {
+  let mut metas = accounts.to_account_metas(None);
+  metas.extend_from_slice(remaining);
+  Instruction {
+    program_id: trade_p2p::ID,
+    accounts: metas,
+    data: data.data(),
+  }
+}
+
+// a wallet with an account in each of two mints, `None` standing for SOL, which
+// is paid from and into the wallet itself
+pub struct Trader {
+  pub wallet: Pubkey,
+  pub send_mint: Option<Pubkey>,
+  pub receive_mint: Option<Pubkey>,
+  pub send_account: Pubkey,
+  pub receive_account: Pubkey,
+  pub next_order_id: u64,
+}
+
+impl Trader {
+  pub fn new(env: &mut Env, send_mint: Option<Pubkey>, receive_mint: Option<Pubkey>, balance: u64) -> Trader {
+    let wallet = env.wallet(100 * SOL);
+    let mut account = |mint: Option<Pubkey>, amount: u64| match mint {
+      Some(mint) => env.token_account(&mint, &wallet, amount),
+      None => wallet,
+    };
+    let send_account = account(send_mint, balance);
+    let receive_account = account(receive_mint, balance);
+    Trader {
+      wallet,
+      send_mint,
+      receive_mint,
+      send_account,
+      receive_account,
+      next_order_id: 0,
+    }
+  }
+
+  // the trader on the other side of the pair
+  pub fn counterparty(&self, env: &mut Env, balance: u64) -> Trader {
+    Trader::new(env, self.receive_mint, self.send_mint, balance)
+  }
+
+  pub fn order(&self, order_id: u64) -> Pubkey {
+    state_address(&self.wallet, order_id).0
+  }
+
+  pub fn vault(&self, order_id: u64) -> Pubkey {
+    vault_address(&self.wallet, order_id).0
+  }
+
+  pub fn balance(&self, env: &Env, account: &Pubkey) -> u64 {
+    match *account == self.wallet {
+      true => env.lamports(account),
+      false => env.token_balance(account),
+    }
+  }
+
+  // the mints passed to `create_trade` ahead of its other remaining accounts
+  pub fn mint_metas(&self) -> Vec<AccountMeta> {
+    [self.send_mint, self.receive_mint]
+      .into_iter()
+      .flatten()
+      .map(|mint| AccountMeta::new_readonly(mint, false))
+      .collect()
+  }
+
+  // create an order at the trader's next order id
+  pub fn create_instruction(&self, mut params: CreateParams, extra: &[AccountMeta]) -> Instruction {
+    params.vault_bump = vault_address(&self.wallet, self.next_order_id).1;
+    let mut remaining = self.mint_metas();
+    remaining.extend_from_slice(extra);
+    instruction(
+      trade_p2p::accounts::Create {
+        escrow_state: self.order(self.next_order_id),
+        escrow_vault: self.vault(self.next_order_id),
+        creator: self.wallet,
+        creator_send_account: self.send_account,
+        creator_receive_account: self.receive_account,
+        fee_account: self.wallet,
+        system_program: system_program::ID,
+        token_program: spl_token::ID,
+        rent: sysvar::rent::ID,
+        user_account: user_address(&self.wallet),
+      },
+      &remaining,
+      trade_p2p::instruction::CreateTrade { params },
+    )
+  }
+
+  // create an order with the trader's next order id, returning that id
+  pub fn create(&mut self, env: &mut Env, params: CreateParams, extra: &[AccountMeta]) -> u64 {
+    env.process(&self.create_instruction(params, extra)).unwrap();
+    self.next_order_id += 1;
+    self.next_order_id - 1
+  }
+
+  pub fn cancel_instruction(&self, order_id: u64, extra: &[AccountMeta]) -> Instruction {
+    instruction(
+      trade_p2p::accounts::Cancel {
+        escrow_state: self.order(order_id),
+        escrow_vault: self.vault(order_id),
+        creator_send_account: self.send_account,
+        creator: self.wallet,
+        system_program: system_program::ID,
+        token_program: spl_token::ID,
+      },
+      extra,
+      trade_p2p::instruction::Cancel {
+        _order_id: order_id,
+        _state_bump: state_address(&self.wallet, order_id).1,
+        _vault_bump: vault_address(&self.wallet, order_id).1,
+      },
+    )
+  }
+
+  // `partner` takes order `order_id` of `self`
+  pub fn exchange_instruction(&self, partner: &Trader, order_id: u64, extra: &[AccountMeta]) -> Instruction {
+    self.exchange_with(partner, order_id, extra, Vec::new(), None)
+  }
+
+  // `exchange_instruction` paying at most `max_receive_value`
+  pub fn capped_exchange_instruction(
+    &self,
+    partner: &Trader,
+    order_id: u64,
+    extra: &[AccountMeta],
+    max_receive_value: Option<u64>,
+  ) -> Instruction {
+    self.exchange_with(partner, order_id, extra, Vec::new(), max_receive_value)
+  }
+
+  // `exchange_instruction` with the allowlist `proof` of `partner`
+  pub fn exchange_with(
+    &self,
+    partner: &Trader,
+    order_id: u64,
+    extra: &[AccountMeta],
+    proof: Vec<[u8; 32]>,
+    max_receive_value: Option<u64>,
+  ) -> Instruction {
+    instruction(
+      trade_p2p::accounts::Exchange {
+        escrow_state: self.order(order_id),
+        escrow_vault: self.vault(order_id),
+        creator_receive_account: self.receive_account,
+        partner_send_account: partner.send_account,
+        partner_receive_account: partner.receive_account,
+        creator: self.wallet,
+        partner: partner.wallet,
+        system_program: system_program::ID,
+        token_program: spl_token::ID,
+      },
+      extra,
+      trade_p2p::instruction::Exchange {
+        _order_id: order_id,
+        _state_bump: state_address(&self.wallet, order_id).1,
+        _vault_bump: vault_address(&self.wallet, order_id).1,
+        proof,
+        max_receive_value,
+      },
+    )
+  }
+}
@@ -0,0 +1,638 @@
+use crate::runtime::*;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::{system_program, sysvar};
+use anchor_spl::token::spl_token;
+use trade_p2p::constant::STATE_PDA_SEED;
+use trade_p2p::error::EscrowError;
+use trade_p2p::event::{OrdersMatched, TradeDeclined};
+use trade_p2p::processor::{CreateManyParams, CreateParams, OrderLevel};
+use trade_p2p::state::{
+  EscrowAccount, MarketPage, PartnerInbox, PaymentOption, PaymentOptions, Stage, UserAccount,
+};
+use trade_p2p::utils::{claim_hash, merkle_parent, partner_leaf};
+
+fn token_pair(env: &mut Env) -> (Trader, Trader) {
+  let (send_mint, receive_mint) = (env.mint(6), env.mint(9));
+  let creator = Trader::new(env, Some(send_mint), Some(receive_mint), 1_000);
+  let partner = creator.counterparty(env, 1_000);
+  (creator, partner)
+}
+
+#[test]
+fn create_and_exchange_token_for_token() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let order_id = creator.create(&mut env, params(100, 250), &[]);
+  assert_eq!(env.token_balance(&creator.send_account), 900);
+  assert_eq!(env.token_balance(&creator.vault(order_id)), 100);
+  assert_eq!(env.state::<UserAccount>(&user_address(&creator.wallet)).next_order_id, 1);
+
+  env.process(&creator.exchange_instruction(&partner, order_id, &[])).unwrap();
+  assert_eq!(env.token_balance(&partner.receive_account), 1_100);
+  assert_eq!(env.token_balance(&creator.receive_account), 1_250);
+  assert!(!env.exists(&creator.vault(order_id)));
+  let order: EscrowAccount = env.state(&creator.order(order_id));
+  assert_eq!(order.stage, Stage::Exchanged.to_code());
+  assert_eq!(order.specify_partner, Some(partner.wallet));
+}
+
+#[test]
+fn create_and_exchange_sol_for_token() {
+  let mut env = Env::new();
+  let receive_mint = env.mint(6);
+  let mut creator = Trader::new(&mut env, None, Some(receive_mint), 1_000);
+  let partner = creator.counterparty(&mut env, 1_000);
+  let order_id = creator.create(&mut env, params(SOL, 400), &[]);
+  assert_eq!(env.lamports(&creator.vault(order_id)), SOL + Rent::default().minimum_balance(0));
+
+  let partner_lamports = partner.balance(&env, &partner.receive_account);
+  env.process(&creator.exchange_instruction(&partner, order_id, &[])).unwrap();
+  assert_eq!(partner.balance(&env, &partner.receive_account), partner_lamports + SOL);
+  assert_eq!(env.token_balance(&creator.receive_account), 1_400);
+}
+
+#[test]
+fn order_ids_come_from_the_creator_counter() {
+  let mut env = Env::new();
+  let (mut creator, _) = token_pair(&mut env);
+  assert_eq!(creator.create(&mut env, params(10, 10), &[]), 0);
+  assert_eq!(creator.create(&mut env, params(10, 10), &[]), 1);
+  // an order can only be created at the address of the next id
+  for chosen in [0, 7] {
+    creator.next_order_id = chosen;
+    assert!(env.process(&creator.create_instruction(params(10, 10), &[])).is_err());
+  }
+  assert_eq!(env.state::<UserAccount>(&user_address(&creator.wallet)).next_order_id, 2);
+}
+
+#[test]
+fn allowlisted_orders_are_exchanged_with_a_proof() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let (listed, outsider) = (creator.counterparty(&mut env, 1_000), creator.counterparty(&mut env, 1_000));
+  let listed_leaf = partner_leaf(&listed.wallet);
+  let mut allowlisted = params(100, 250);
+  allowlisted.partner_root = Some(merkle_parent(&partner_leaf(&partner.wallet), &listed_leaf));
+  let order_id = creator.create(&mut env, allowlisted, &[]);
+
+  let unproven = creator.exchange_instruction(&partner, order_id, &[]);
+  assert_eq!(env.process(&unproven), Err(error(EscrowError::PartnerNotAllowed)));
+  let borrowed = creator.exchange_with(&outsider, order_id, &[], vec![listed_leaf], None);
+  assert_eq!(env.process(&borrowed), Err(error(EscrowError::PartnerNotAllowed)));
+  env.process(&creator.exchange_with(&partner, order_id, &[], vec![listed_leaf], None)).unwrap();
+  assert_eq!(env.token_balance(&partner.receive_account), 1_100);
+  assert_eq!(env.state::<EscrowAccount>(&creator.order(order_id)).specify_partner, Some(partner.wallet));
+}
+
+#[test]
+fn claim_code_orders_need_the_claim_key_to_sign() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let claim_key = Pubkey::new_unique();
+  let mut claimed = params(100, 250);
+  claimed.claim_hash = Some(claim_hash(&claim_key));
+  let order_id = creator.create(&mut env, claimed, &[]);
+
+  let unclaimed = creator.exchange_instruction(&partner, order_id, &[]);
+  assert_eq!(env.process(&unclaimed), Err(error(EscrowError::InvalidClaimCode)));
+  let unsigned = creator.exchange_instruction(&partner, order_id, &[AccountMeta::new_readonly(claim_key, false)]);
+  assert_eq!(env.process(&unsigned), Err(error(EscrowError::InvalidClaimCode)));
+  let wrong_key = AccountMeta::new_readonly(Pubkey::new_unique(), true);
+  let guessed = creator.exchange_instruction(&partner, order_id, &[wrong_key]);
+  assert_eq!(env.process(&guessed), Err(error(EscrowError::InvalidClaimCode)));
+  let claim_signer = AccountMeta::new_readonly(claim_key, true);
+  env.process(&creator.exchange_instruction(&partner, order_id, &[claim_signer])).unwrap();
+  assert_eq!(env.token_balance(&partner.receive_account), 1_100);
+  assert_eq!(env.token_balance(&creator.receive_account), 1_250);
+}
+
+#[test]
+fn exchange_is_paid_through_a_payment_option() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let option_mint = env.mint(6);
+  let option_account = env.token_account(&option_mint, &creator.wallet, 0);
+  let option = PaymentOption {
+    mint: option_mint,
+    receive_value: 40,
+    receive_account: option_account,
+  };
+  let mut payable = params(100, 250);
+  payable.payment_options = Some(PaymentOptions {
+    options: [option, PaymentOption::default(), PaymentOption::default()],
+    count: 1,
+  });
+  let order_id = creator.create(&mut env, payable, &[AccountMeta::new_readonly(option_account, false)]);
+
+  // the partner pays from an account of the option's mint into the option's account
+  let payer = Trader {
+    send_mint: Some(option_mint),
+    send_account: env.token_account(&option_mint, &partner.wallet, 1_000),
+    ..partner
+  };
+  let own_receive_account = creator.receive_account;
+  let paid_in_option = Trader {
+    receive_mint: Some(option_mint),
+    receive_account: option_account,
+    ..creator
+  };
+  let capped = paid_in_option.capped_exchange_instruction(&payer, order_id, &[], Some(39));
+  assert_eq!(env.process(&capped), Err(error(EscrowError::ReceiveValueExceeded)));
+  // capped below the order's own receive value, but not the option's
+  env.process(&paid_in_option.capped_exchange_instruction(&payer, order_id, &[], Some(40))).unwrap();
+  assert_eq!(env.token_balance(&option_account), 40);
+  assert_eq!(env.token_balance(&own_receive_account), 1_000);
+  assert_eq!(env.token_balance(&payer.send_account), 960);
+  assert_eq!(env.token_balance(&payer.receive_account), 1_100);
+  let order: EscrowAccount = env.state(&paid_in_option.order(order_id));
+  assert_eq!((order.creator_receive_token_mint, order.receive_value), (Some(option_mint), 40));
+}
+
+#[test]
+fn exchange_takes_the_order_off_its_market_page() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let market = market_address(creator.send_mint, creator.receive_mint, 0);
+  let mut listed = params(100, 250);
+  listed.market_page = Some(0);
+  let order_id = creator.create(&mut env, listed, &[AccountMeta::new(market, false)]);
+  let page: MarketPage = env.state(&market);
+  assert_eq!(page.orders.iter().map(|entry| entry.order).collect::<Vec<_>>(), vec![creator.order(order_id)]);
+
+  let unlisted = creator.exchange_instruction(&partner, order_id, &[]);
+  assert!(env.process(&unlisted).is_err());
+  env.process(&creator.exchange_instruction(&partner, order_id, &[AccountMeta::new(market, false)])).unwrap();
+  assert!(env.state::<MarketPage>(&market).orders.is_empty());
+}
+
+#[test]
+fn cancel_refunds_the_creator() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let order_id = creator.create(&mut env, params(100, 250), &[]);
+  env.process(&creator.cancel_instruction(order_id, &[])).unwrap();
+  assert_eq!(env.token_balance(&creator.send_account), 1_000);
+  assert!(!env.exists(&creator.vault(order_id)));
+  let exchange = creator.exchange_instruction(&partner, order_id, &[]);
+  assert_eq!(env.process(&exchange), Err(error(EscrowError::InvalidStage)));
+}
+
+// `partner` declines order `order_id` of `creator`, addressed to it
+fn decline_instruction(creator: &Trader, partner: &Trader, order_id: u64) -> Instruction {
+  instruction(
+    trade_p2p::accounts::Decline {
+      escrow_state: creator.order(order_id),
+      escrow_vault: creator.vault(order_id),
+      creator_send_account: creator.send_account,
+      creator: creator.wallet,
+      partner: partner.wallet,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+    },
+    &[AccountMeta::new(inbox_address(&partner.wallet), false)],
+    trade_p2p::instruction::Decline {
+      _order_id: order_id,
+      _state_bump: state_address(&creator.wallet, order_id).1,
+      _vault_bump: vault_address(&creator.wallet, order_id).1,
+    },
+  )
+}
+
+#[test]
+fn decline_refunds_and_unlists_the_order() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let inbox = inbox_address(&partner.wallet);
+  let mut order = params(100, 250);
+  order.specify_partner = Some(partner.wallet);
+  let order_id = creator.create(&mut env, order, &[AccountMeta::new(inbox, false)]);
+  assert_eq!(env.state::<PartnerInbox>(&inbox).orders, vec![creator.order(order_id)]);
+
+  let decline = decline_instruction(&creator, &partner, order_id);
+  env.process(&decline).unwrap();
+  assert_eq!(env.token_balance(&creator.send_account), 1_000);
+  assert!(!env.exists(&creator.vault(order_id)));
+  assert!(env.state::<PartnerInbox>(&inbox).orders.is_empty());
+  assert_eq!(env.state::<EscrowAccount>(&creator.order(order_id)).stage, Stage::Declined.to_code());
+  let declined = env.emitted::<TradeDeclined>();
+  assert_eq!(declined.len(), 1);
+  assert_eq!(declined[0].refund_value, 100);
+}
+
+#[test]
+fn orders_beyond_a_full_inbox_are_created_unlisted() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let inbox = inbox_address(&partner.wallet);
+  let addressed = || {
+    let mut order = params(1, 1);
+    order.specify_partner = Some(partner.wallet);
+    order
+  };
+  for _ in 0..PartnerInbox::MAX_ORDERS {
+    creator.create(&mut env, addressed(), &[AccountMeta::new(inbox, false)]);
+  }
+  let unlisted = creator.create(&mut env, addressed(), &[AccountMeta::new(inbox, false)]);
+  let orders = env.state::<PartnerInbox>(&inbox).orders;
+  assert_eq!(orders.len(), PartnerInbox::MAX_ORDERS);
+  assert!(!orders.contains(&creator.order(unlisted)));
+
+  // the unlisted order can still be declined, and frees no entry
+  env.process(&decline_instruction(&creator, &partner, unlisted)).unwrap();
+  assert_eq!(env.state::<PartnerInbox>(&inbox).orders, orders);
+}
+
+#[test]
+fn decline_refunds_sol_to_the_creator_wallet() {
+  let mut env = Env::new();
+  let receive_mint = env.mint(6);
+  let mut creator = Trader::new(&mut env, None, Some(receive_mint), 1_000);
+  let partner = creator.counterparty(&mut env, 1_000);
+  let mut order = params(SOL, 400);
+  order.specify_partner = Some(partner.wallet);
+  let wallet_lamports = env.lamports(&creator.wallet);
+  let order_id = creator.create(&mut env, order, &[AccountMeta::new(inbox_address(&partner.wallet), false)]);
+  let inbox_rent = env.lamports(&inbox_address(&partner.wallet));
+  let state_rent = env.lamports(&creator.order(order_id)) + env.lamports(&user_address(&creator.wallet));
+
+  env.process(&decline_instruction(&creator, &partner, order_id)).unwrap();
+  assert!(!env.exists(&creator.vault(order_id)));
+  assert_eq!(env.lamports(&creator.wallet), wallet_lamports - inbox_rent - state_rent);
+}
+
+#[test]
+fn create_many_funds_one_vault_per_level() {
+  let mut env = Env::new();
+  let (creator, _) = token_pair(&mut env);
+  let levels = [(100, 200), (150, 330)];
+  let mut remaining = creator.mint_metas();
+  for order_id in 0..levels.len() as u64 {
+    remaining.push(AccountMeta::new(creator.order(order_id), false));
+    remaining.push(AccountMeta::new(creator.vault(order_id), false));
+  }
+  let orders = levels
+    .iter()
+    .enumerate()
+    .map(|(index, &(trade_value, receive_value))| OrderLevel {
+      trade_value,
+      receive_value,
+      vault_bump: vault_address(&creator.wallet, index as u64).1,
+    })
+    .collect();
+  let create_many = instruction(
+    trade_p2p::accounts::CreateMany {
+      creator: creator.wallet,
+      creator_send_account: creator.send_account,
+      creator_receive_account: creator.receive_account,
+      fee_account: creator.wallet,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+      rent: sysvar::rent::ID,
+      user_account: user_address(&creator.wallet),
+    },
+    &remaining,
+    trade_p2p::instruction::CreateMany {
+      params: CreateManyParams {
+        orders,
+        timestamp: 0,
+        market_page: None,
+      },
+    },
+  );
+  // the state of an order must sit at its canonical address
+  let (first, canonical_bump) = state_address(&creator.wallet, 0);
+  let off_curve = (0..canonical_bump)
+    .rev()
+    .find_map(|bump| {
+      Pubkey::create_program_address(
+        &[STATE_PDA_SEED, creator.wallet.as_ref(), &0u64.to_le_bytes(), &[bump]],
+        &trade_p2p::ID,
+      )
+      .ok()
+    })
+    .unwrap();
+  let mut non_canonical = create_many.clone();
+  for meta in non_canonical.accounts.iter_mut().filter(|meta| meta.pubkey == first) {
+    meta.pubkey = off_curve;
+  }
+  assert_eq!(env.process(&non_canonical), Err(error(EscrowError::InvalidAccount)));
+  env.process(&create_many).unwrap();
+  assert_eq!(env.token_balance(&creator.send_account), 750);
+  for (order_id, (trade_value, receive_value)) in (0..).zip(levels) {
+    assert_eq!(env.token_balance(&creator.vault(order_id)), trade_value);
+    let order: EscrowAccount = env.state(&creator.order(order_id));
+    assert_eq!((order.trade_value, order.receive_value), (trade_value, receive_value));
+  }
+  assert_eq!(env.state::<UserAccount>(&user_address(&creator.wallet)).next_order_id, 2);
+}
+
+// the groups as `sdk::instruction::cancel_many` builds them, taken orders naming
+// their partner like addressed ones
+fn cancel_many_instruction(env: &Env, creator: &Trader, order_ids: &[u64], atomic: bool) -> Instruction {
+  let mut remaining = Vec::new();
+  for &order_id in order_ids {
+    remaining.push(AccountMeta::new(creator.order(order_id), false));
+    remaining.push(AccountMeta::new(creator.vault(order_id), false));
+    remaining.push(AccountMeta::new(creator.send_account, false));
+    let order: EscrowAccount = env.state(&creator.order(order_id));
+    if let Some(partner) = order.specify_partner {
+      remaining.push(AccountMeta::new(inbox_address(&partner), false));
+    }
+  }
+  instruction(
+    trade_p2p::accounts::CancelMany {
+      creator: creator.wallet,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+    },
+    &remaining,
+    trade_p2p::instruction::CancelMany { atomic },
+  )
+}
+
+#[test]
+fn cancel_many_skips_closed_orders_unless_atomic() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let taken = creator.create(&mut env, params(100, 250), &[]);
+  let open = creator.create(&mut env, params(200, 500), &[]);
+  env.process(&creator.exchange_instruction(&partner, taken, &[])).unwrap();
+
+  let atomic = cancel_many_instruction(&env, &creator, &[taken, open], true);
+  assert_eq!(env.process(&atomic), Err(error(EscrowError::InvalidStage)));
+  env.process(&cancel_many_instruction(&env, &creator, &[taken, open], false)).unwrap();
+  assert_eq!(env.token_balance(&creator.send_account), 900);
+  assert!(!env.exists(&creator.vault(open)));
+  assert_eq!(env.state::<EscrowAccount>(&creator.order(taken)).stage, Stage::Exchanged.to_code());
+  assert_eq!(env.state::<EscrowAccount>(&creator.order(open)).stage, Stage::CancelTrade.to_code());
+}
+
+// replaces order `old_order_id` of `creator` by one funded from the accounts of
+// `replacement`, a trader with the same wallet
+fn replace_instruction(creator: &Trader, old_order_id: u64, replacement: &Trader, mut params: CreateParams) -> Instruction {
+  params.vault_bump = vault_address(&creator.wallet, creator.next_order_id).1;
+  instruction(
+    trade_p2p::accounts::Replace {
+      escrow_state: creator.order(old_order_id),
+      escrow_vault: creator.vault(old_order_id),
+      refund_account: creator.send_account,
+      new_escrow_state: creator.order(creator.next_order_id),
+      new_escrow_vault: creator.vault(creator.next_order_id),
+      creator: creator.wallet,
+      creator_send_account: replacement.send_account,
+      creator_receive_account: replacement.receive_account,
+      fee_account: creator.wallet,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+      rent: sysvar::rent::ID,
+      user_account: user_address(&creator.wallet),
+    },
+    &replacement.mint_metas(),
+    trade_p2p::instruction::ReplaceTrade {
+      _old_order_id: old_order_id,
+      params,
+    },
+  )
+}
+
+#[test]
+fn replace_trade_moves_the_escrowed_value() {
+  let mut env = Env::new();
+  let (mut creator, _) = token_pair(&mut env);
+  let old_order_id = creator.create(&mut env, params(100, 250), &[]);
+  let new_order_id = creator.next_order_id;
+  let replace = replace_instruction(&creator, old_order_id, &creator, params(150, 300));
+  env.process(&replace).unwrap();
+  assert_eq!(env.token_balance(&creator.send_account), 850);
+  assert_eq!(env.token_balance(&creator.vault(new_order_id)), 150);
+  assert!(!env.exists(&creator.vault(old_order_id)));
+  let old_order: EscrowAccount = env.state(&creator.order(old_order_id));
+  assert_eq!(old_order.stage, Stage::CancelTrade.to_code());
+  assert_eq!(env.state::<EscrowAccount>(&creator.order(new_order_id)).receive_value, 300);
+}
+
+#[test]
+fn replace_trade_to_another_send_mint_refunds_the_old_vault() {
+  let mut env = Env::new();
+  let (mut creator, _) = token_pair(&mut env);
+  let old_order_id = creator.create(&mut env, params(100, 250), &[]);
+  let other_mint = env.mint(6);
+  let replacement = Trader {
+    wallet: creator.wallet,
+    send_mint: Some(other_mint),
+    receive_mint: creator.receive_mint,
+    send_account: env.token_account(&other_mint, &creator.wallet, 1_000),
+    receive_account: creator.receive_account,
+    next_order_id: creator.next_order_id,
+  };
+  let new_order_id = creator.next_order_id;
+  let replace = replace_instruction(&creator, old_order_id, &replacement, params(150, 300));
+  env.process(&replace).unwrap();
+  assert_eq!(env.token_balance(&creator.send_account), 1_000);
+  assert_eq!(env.token_balance(&replacement.send_account), 850);
+  assert_eq!(env.token_balance(&creator.vault(new_order_id)), 150);
+  assert!(!env.exists(&creator.vault(old_order_id)));
+  let new_order: EscrowAccount = env.state(&creator.order(new_order_id));
+  assert_eq!(new_order.creator_send_token_mint, Some(other_mint));
+}
+
+fn exchange_many_instruction(creator: &Trader, partner: &Trader, order_ids: &[u64], budget: u64, atomic: bool) -> Instruction {
+  let remaining: Vec<_> = order_ids
+    .iter()
+    .flat_map(|&order_id| {
+      [
+        AccountMeta::new(creator.order(order_id), false),
+        AccountMeta::new(creator.vault(order_id), false),
+        AccountMeta::new(creator.wallet, false),
+        AccountMeta::new(creator.receive_account, false),
+      ]
+    })
+    .collect();
+  instruction(
+    trade_p2p::accounts::ExchangeMany {
+      partner_send_account: partner.send_account,
+      partner_receive_account: partner.receive_account,
+      partner: partner.wallet,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+    },
+    &remaining,
+    trade_p2p::instruction::ExchangeMany {
+      budget,
+      max_price: None,
+      atomic,
+    },
+  )
+}
+
+#[test]
+fn exchange_many_fills_orders_within_the_budget() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let cheap = creator.create(&mut env, params(100, 250), &[]);
+  let dear = creator.create(&mut env, params(100, 300), &[]);
+
+  let atomic = exchange_many_instruction(&creator, &partner, &[cheap, dear], 400, true);
+  assert_eq!(env.process(&atomic), Err(error(EscrowError::BudgetExceeded)));
+  env.process(&exchange_many_instruction(&creator, &partner, &[cheap, dear], 400, false)).unwrap();
+  assert_eq!(env.token_balance(&partner.send_account), 750);
+  assert_eq!(env.token_balance(&partner.receive_account), 1_100);
+  assert_eq!(env.state::<EscrowAccount>(&creator.order(cheap)).stage, Stage::Exchanged.to_code());
+  assert_eq!(env.state::<EscrowAccount>(&creator.order(dear)).stage, Stage::ReadyExchange.to_code());
+}
+
+#[test]
+fn exchange_many_skips_claim_code_orders_and_bad_listings() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let mut claimed = params(100, 250);
+  claimed.claim_hash = Some(claim_hash(&Pubkey::new_unique()));
+  let claimed = creator.create(&mut env, claimed, &[]);
+  let plain = creator.create(&mut env, params(100, 250), &[]);
+  let market = market_address(creator.send_mint, creator.receive_mint, 0);
+  let mut listed = params(100, 250);
+  listed.market_page = Some(0);
+  let listed = creator.create(&mut env, listed, &[AccountMeta::new(market, false)]);
+
+  // the listed order is passed with another page than its own
+  let wrong_page = AccountMeta::new(market_address(creator.send_mint, creator.receive_mint, 1), false);
+  let mut atomic = exchange_many_instruction(&creator, &partner, &[claimed, plain, listed], 1_000, true);
+  atomic.accounts.push(wrong_page.clone());
+  assert_eq!(env.process(&atomic), Err(error(EscrowError::InvalidClaimCode)));
+  let mut best_effort = exchange_many_instruction(&creator, &partner, &[claimed, plain, listed], 1_000, false);
+  best_effort.accounts.push(wrong_page);
+  env.process(&best_effort).unwrap();
+  assert_eq!(env.token_balance(&partner.receive_account), 1_100);
+  for (order_id, stage) in [(claimed, Stage::ReadyExchange), (plain, Stage::Exchanged), (listed, Stage::ReadyExchange)] {
+    assert_eq!(env.state::<EscrowAccount>(&creator.order(order_id)).stage, stage.to_code());
+  }
+  assert_eq!(env.state::<MarketPage>(&market).orders.len(), 1);
+}
+
+// `a_receive_account` is where order a is paid, each matcher account getting the
+// fee in the asset of the order it is named after
+fn match_orders_instruction(
+  creator_a: &Trader,
+  order_a: u64,
+  a_receive_account: Pubkey,
+  creator_b: &Trader,
+  order_b: u64,
+  matcher: &Trader,
+) -> Instruction {
+  instruction(
+    trade_p2p::accounts::MatchOrders {
+      escrow_state_a: creator_a.order(order_a),
+      escrow_vault_a: creator_a.vault(order_a),
+      creator_a: creator_a.wallet,
+      creator_a_receive_account: a_receive_account,
+      escrow_state_b: creator_b.order(order_b),
+      escrow_vault_b: creator_b.vault(order_b),
+      creator_b: creator_b.wallet,
+      creator_b_receive_account: creator_b.receive_account,
+      matcher: matcher.wallet,
+      matcher_a_account: matcher.send_account,
+      matcher_b_account: matcher.receive_account,
+      system_program: system_program::ID,
+      token_program: spl_token::ID,
+    },
+    &[],
+    trade_p2p::instruction::MatchOrders {},
+  )
+}
+
+#[test]
+fn match_orders_pays_each_creator_and_the_matcher() {
+  let mut env = Env::new();
+  let (mut creator_a, mut creator_b) = token_pair(&mut env);
+  let matcher = Trader::new(&mut env, creator_a.send_mint, creator_a.receive_mint, 0);
+  let order_a = creator_a.create(&mut env, params(100, 200), &[]);
+  let order_b = creator_b.create(&mut env, params(210, 90), &[]);
+  let match_orders =
+    match_orders_instruction(&creator_a, order_a, creator_a.receive_account, &creator_b, order_b, &matcher);
+  env.process(&match_orders).unwrap();
+  assert_eq!(env.token_balance(&creator_a.receive_account), 1_200);
+  assert_eq!(env.token_balance(&creator_b.receive_account), 1_090);
+  assert_eq!(env.token_balance(&matcher.send_account), 10);
+  assert_eq!(env.token_balance(&matcher.receive_account), 10);
+  assert!(!env.exists(&creator_a.vault(order_a)) && !env.exists(&creator_b.vault(order_b)));
+  assert_eq!(env.emitted::<OrdersMatched>()[0].fee_a, 10);
+}
+
+#[test]
+fn match_orders_pays_sol_to_the_creator_wallet() {
+  let mut env = Env::new();
+  let send_mint = env.mint(6);
+  // order a receives SOL but was created naming another account to receive it
+  let mut creator_a = Trader {
+    receive_account: Pubkey::new_unique(),
+    ..Trader::new(&mut env, Some(send_mint), None, 1_000)
+  };
+  let mut creator_b = Trader::new(&mut env, None, Some(send_mint), 1_000);
+  let matcher = Trader::new(&mut env, Some(send_mint), None, 0);
+  let order_a = creator_a.create(&mut env, params(100, 200), &[]);
+  let order_b = creator_b.create(&mut env, params(210, 90), &[]);
+  let stored = env.state::<EscrowAccount>(&creator_a.order(order_a)).creator_receive_account;
+  assert_eq!(stored, creator_a.receive_account);
+
+  let elsewhere = match_orders_instruction(&creator_a, order_a, stored, &creator_b, order_b, &matcher);
+  assert_eq!(env.process(&elsewhere), Err(error(EscrowError::InvalidAccount)));
+  let wallet_lamports = env.lamports(&creator_a.wallet);
+  let vault_rent = env.lamports(&creator_a.vault(order_a));
+  let match_orders = match_orders_instruction(&creator_a, order_a, creator_a.wallet, &creator_b, order_b, &matcher);
+  env.process(&match_orders).unwrap();
+  assert_eq!(env.lamports(&creator_a.wallet), wallet_lamports + 200 + vault_rent);
+  assert!(!env.exists(&stored));
+  assert_eq!(env.token_balance(&creator_b.receive_account), 1_090);
+  assert_eq!(env.token_balance(&matcher.send_account), 10);
+}
+
+fn migrate_instruction(order: Pubkey, creator: &Pubkey, payer: &Pubkey) -> Instruction {
+  instruction(
+    trade_p2p::accounts::MigrateEscrow {
+      escrow_state: order,
+      creator: *creator,
+      user_account: user_address(creator),
+      payer: *payer,
+      system_program: system_program::ID,
+    },
+    &[],
+    trade_p2p::instruction::MigrateEscrow {},
+  )
+}
+
+#[test]
+fn first_release_deals_are_migrated_then_cancelled() {
+  let mut env = Env::new();
+  let (mut creator, partner) = token_pair(&mut env);
+  let mut addressed = params(100, 250);
+  addressed.specify_partner = Some(partner.wallet);
+  let inbox = [AccountMeta::new(inbox_address(&partner.wallet), false)];
+  let order_id = creator.create(&mut env, addressed, &inbox);
+  let order = creator.order(order_id);
+  // the deal as the first release stored it, its options all set so no padding
+  // is left after its fields, and no order id counter for its creator
+  env.accounts.remove(&user_address(&creator.wallet));
+  let legacy = env.accounts.get_mut(&order).unwrap();
+  legacy.data.truncate(EscrowAccount::FIRST_RELEASE_LEN);
+  legacy.lamports = Rent::default().minimum_balance(EscrowAccount::FIRST_RELEASE_LEN);
+  let cancel = creator.cancel_instruction(order_id, &inbox);
+  let undecodable = anchor_lang::error::Error::from(anchor_lang::error::ErrorCode::AccountDidNotDeserialize);
+  assert_eq!(env.process(&cancel), Err(undecodable.into()));
+
+  let not_a_deal = migrate_instruction(creator.send_account, &creator.wallet, &partner.wallet);
+  assert_eq!(env.process(&not_a_deal), Err(error(EscrowError::InvalidAccount)));
+  let other_creator = migrate_instruction(order, &partner.wallet, &partner.wallet);
+  assert_eq!(env.process(&other_creator), Err(error(EscrowError::InvalidAccount)));
+  env.process(&migrate_instruction(order, &creator.wallet, &partner.wallet)).unwrap();
+  assert_eq!(env.lamports(&order), Rent::default().minimum_balance(EscrowAccount::LEN));
+  // new orders of the creator take ids after the deal's
+  assert_eq!(env.state::<UserAccount>(&user_address(&creator.wallet)).next_order_id, order_id + 1);
+  let migrated: EscrowAccount = env.state(&order);
+  assert_eq!((migrated.specify_partner, migrated.partner_root), (Some(partner.wallet), None));
+  let again = migrate_instruction(order, &creator.wallet, &partner.wallet);
+  assert_eq!(env.process(&again), Err(error(EscrowError::AlreadyMigrated)));
+  env.process(&cancel).unwrap();
+  assert_eq!(env.token_balance(&creator.send_account), 1_000);
+}